@@ -42,6 +42,16 @@ impl Cache<str> {
         self.0.borrow_mut().insert(s.clone().into_rc());
         s
     }
+
+    /// Same as `get`, but an `Str::Rc` is cached as is (not copied) if no equal string is cached yet.
+    pub fn intern(&self, s: Str) -> Str {
+        if let Some(cached) = self.0.borrow().get(&s[..]) {
+            return cached.clone().into();
+        } // &self.0 is dropped
+        let s = s.into_rc();
+        self.0.borrow_mut().insert(s.clone());
+        s.into()
+    }
 }
 
 impl<T: Hash + Eq + Clone> Cache<[T]> {
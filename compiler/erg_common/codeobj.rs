@@ -1,19 +1,21 @@
 use std::fmt;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::{BufReader, Read, Write};
 use std::path::Path;
 
-use crate::deserialize::{DeserializeResult, Deserializer};
+use crate::deserialize::{DeserializeError, DeserializeResult, Deserializer};
 use crate::impl_display_from_debug;
-use crate::opcode::Opcode;
-use crate::python_util::detect_magic_number;
+use crate::opcode::{Opcode, OpcodeTable, HAVE_ARGUMENT};
+use crate::python_util::{detect_magic_number, magic_number};
 use crate::serialize::*;
+use crate::set::Set;
 use crate::traits::HasType;
 use crate::ty::{Type, TypePair};
 use crate::value::ValueObj;
 use crate::Str;
 
-pub fn consts_into_bytes(consts: Vec<ValueObj>) -> Vec<u8> {
+pub fn consts_into_bytes(consts: Vec<ValueObj>, refs: &mut RefTable, python_ver: u32) -> Vec<u8> {
     let mut tuple = vec![];
     if consts.len() > u8::MAX as usize {
         tuple.push(DataTypePrefix::Tuple as u8);
@@ -23,11 +25,553 @@ pub fn consts_into_bytes(consts: Vec<ValueObj>) -> Vec<u8> {
         tuple.push(consts.len() as u8);
     }
     for obj in consts {
-        tuple.append(&mut obj.into_bytes());
+        tuple.append(&mut obj.into_bytes_with(refs, python_ver));
     }
     tuple
 }
 
+/// `co_localspluskinds` (3.11~)
+const CO_FAST_LOCAL: u8 = 0x20;
+const CO_FAST_CELL: u8 = 0x40;
+const CO_FAST_FREE: u8 = 0x80;
+
+/// A handler range of the exception table (`co_exceptiontable`, 3.11~).
+/// The offsets are in bytes, as in `dis._ExceptionTableEntry`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionTableEntry {
+    pub start: usize,
+    pub end: usize,
+    pub target: usize,
+    /// the stack depth to pop down to before jumping to `target`
+    pub depth: usize,
+    /// whether the offset of the raising instruction is pushed (for `RERAISE`)
+    pub lasti: bool,
+}
+
+/// Appends `value` as 6-bit chunks (from the most significant one) with the continuation bit (`0x40`).
+/// `msb` (`0x80`) marks the beginning of an entry.
+fn push_except_varint(table: &mut Vec<u8>, value: usize, mut msb: u8) {
+    for shift in [24, 18, 12, 6] {
+        if value >= 1 << shift {
+            table.push(((value >> shift) & 0x3f) as u8 | 0x40 | msb);
+            msb = 0;
+        }
+    }
+    table.push((value & 0x3f) as u8 | msb);
+}
+
+fn read_except_varint(table: &mut impl Iterator<Item = u8>) -> Option<usize> {
+    let mut byte = table.next()?;
+    let mut value = (byte & 0x3f) as usize;
+    while byte & 0x40 != 0 {
+        byte = table.next()?;
+        value = (value << 6) | (byte & 0x3f) as usize;
+    }
+    Some(value)
+}
+
+/// An error found while analyzing the bytecode of a code object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeObjError {
+    /// the byte at the offset is not a known opcode
+    InvalidOpcode { offset: usize, byte: u8 },
+    /// the stack effect of the opcode is not known
+    UnknownStackEffect { offset: usize, op: Opcode },
+    /// `LOAD_CONST` refers to a nonexistent constant
+    ConstOutOfRange { offset: usize, idx: usize },
+    /// a jump target is out of the code
+    JumpOutOfRange { offset: usize, target: usize },
+    /// the instruction pops more objects than the stack has
+    StackUnderflow { offset: usize },
+    /// the instruction is reachable with different stack depths
+    InconsistentStackDepth {
+        offset: usize,
+        expected: u32,
+        found: u32,
+    },
+}
+
+impl fmt::Display for CodeObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidOpcode { offset, byte } => {
+                write!(f, "{offset}: invalid opcode {byte}")
+            }
+            Self::UnknownStackEffect { offset, op } => {
+                write!(f, "{offset}: the stack effect of {op} is unknown")
+            }
+            Self::ConstOutOfRange { offset, idx } => {
+                write!(f, "{offset}: constant #{idx} does not exist")
+            }
+            Self::JumpOutOfRange { offset, target } => {
+                write!(f, "{offset}: jump target {target} is out of range")
+            }
+            Self::StackUnderflow { offset } => write!(f, "{offset}: stack underflow"),
+            Self::InconsistentStackDepth {
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{offset}: inconsistent stack depth (expected {expected}, found {found})"
+            ),
+        }
+    }
+}
+
+/// The first difference between two code objects (see `CodeObj::diff`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeDiff {
+    /// the instructions (opcode, arg) at the byte offset differ
+    /// `None` if the code ends before the offset
+    Instr {
+        offset: usize,
+        lhs: Option<(u8, u8)>,
+        rhs: Option<(u8, u8)>,
+    },
+    /// the constants at the index differ (`None` if it does not exist)
+    Const {
+        idx: usize,
+        lhs: Option<ValueObj>,
+        rhs: Option<ValueObj>,
+    },
+    /// the code objects at the constant index differ
+    Nested { idx: usize, diff: Box<CodeDiff> },
+    /// the other attribute (e.g. `names`, `stacksize`) differs
+    Field {
+        name: &'static str,
+        lhs: String,
+        rhs: String,
+    },
+}
+
+impl fmt::Display for CodeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn fmt_instr(instr: &Option<(u8, u8)>) -> String {
+            match instr {
+                Some((op, arg)) => match Opcode::try_from_byte(*op) {
+                    Some(op) => format!("{op} {arg}"),
+                    None => format!("<invalid opcode {op}> {arg}"),
+                },
+                None => "<end of code>".to_string(),
+            }
+        }
+        fn fmt_const(obj: &Option<ValueObj>) -> String {
+            match obj {
+                Some(obj) => format!("{obj}"),
+                None => "<nothing>".to_string(),
+            }
+        }
+        match self {
+            Self::Instr { offset, lhs, rhs } => write!(
+                f,
+                "instruction at offset {offset}: {} != {}",
+                fmt_instr(lhs),
+                fmt_instr(rhs)
+            ),
+            Self::Const { idx, lhs, rhs } => write!(
+                f,
+                "constant #{idx}: {} != {}",
+                fmt_const(lhs),
+                fmt_const(rhs)
+            ),
+            Self::Nested { idx, diff } => {
+                write!(f, "in the code object of constant #{idx}: {diff}")
+            }
+            Self::Field { name, lhs, rhs } => write!(f, "{name}: {lhs} != {rhs}"),
+        }
+    }
+}
+
+/// Decodes the instruction at `offset` including its `EXTENDED_ARG` prefixes.
+/// returns the offset of the opcode and the full arg
+fn decode_instr(code: &[u8], offset: usize) -> (usize, u32) {
+    let mut instr = offset;
+    let mut arg = 0u32;
+    while code[instr] == Opcode::EXTENDED_ARG as u8 && instr + 3 < code.len() {
+        arg = (arg | code[instr + 1] as u32) << 8;
+        instr += 2;
+    }
+    (instr, arg | code[instr + 1] as u32)
+}
+
+/// Overwrites the arg of the instruction at `instr` (whose `EXTENDED_ARG` prefixes start at `offset`).
+/// returns `false` (and leaves the code as it is) if `arg` does not fit in the existing prefixes
+fn patch_arg(code: &mut [u8], offset: usize, instr: usize, arg: usize) -> bool {
+    // the number of bytes available for the arg
+    let width = (instr - offset) / 2 + 1;
+    if width < 4 && arg >> (8 * width) != 0 {
+        return false;
+    }
+    for i in 0..width {
+        code[instr + 1 - i * 2] = (arg >> (8 * i)) as u8;
+    }
+    true
+}
+
+/// An instruction decoded by `decode_instrs`
+struct RawInstr {
+    /// the offset including the `EXTENDED_ARG` prefixes
+    offset: usize,
+    /// the offset of the opcode
+    op_offset: usize,
+    op: u8,
+    arg: u32,
+    removed: bool,
+}
+
+fn decode_instrs(code: &[u8]) -> Vec<RawInstr> {
+    let mut instrs = vec![];
+    let mut offset = 0;
+    while offset + 1 < code.len() {
+        let (instr, arg) = decode_instr(code, offset);
+        instrs.push(RawInstr {
+            offset,
+            op_offset: instr,
+            op: code[instr],
+            arg,
+            removed: false,
+        });
+        offset = instr + 2;
+    }
+    instrs
+}
+
+/// Evaluates `lhs <op> rhs` for constant folding.
+/// returns `None` if the result is not known at compile time (or may differ from Python's one)
+fn fold_binary(op: Opcode, lhs: &ValueObj, rhs: &ValueObj) -> Option<ValueObj> {
+    // `Inf` follows Erg's semantics (e.g. `Inf * 0 == Inf`)
+    if [lhs, rhs]
+        .iter()
+        .any(|obj| matches!(obj, ValueObj::Inf | ValueObj::NegInf))
+    {
+        return None;
+    }
+    // same limit as CPython, so as not to bloat the code object
+    const MAX_STR_SIZE: u64 = 4096;
+    let (lhs, rhs) = (lhs.clone(), rhs.clone());
+    match op {
+        Opcode::BINARY_ADD => lhs.try_add(rhs),
+        Opcode::BINARY_SUBTRACT => lhs.try_sub(rhs),
+        Opcode::BINARY_MULTIPLY => match (&lhs, &rhs) {
+            (ValueObj::Str(s), ValueObj::Nat(n))
+                if (s.chars().count() as u64).saturating_mul(*n) > MAX_STR_SIZE =>
+            {
+                None
+            }
+            _ => lhs.try_mul(rhs),
+        },
+        Opcode::BINARY_TRUE_DIVIDE => lhs.try_div(rhs),
+        _ => None,
+    }
+}
+
+/// The truthiness of a constant (`None` if unknown)
+fn truthiness(obj: &ValueObj) -> Option<bool> {
+    match obj {
+        ValueObj::True => Some(true),
+        ValueObj::False | ValueObj::None => Some(false),
+        ValueObj::Nat(n) => Some(*n != 0),
+        ValueObj::Int(i) => Some(*i != 0),
+        ValueObj::Float(f) => Some(*f != 0.0),
+        ValueObj::Str(s) => Some(!s.is_empty()),
+        ValueObj::Array(arr) => Some(!arr.is_empty()),
+        _ => None,
+    }
+}
+
+/// Appends an entry to `lnotab`: the bytecode offset advances by `sd` and the line number by `ld`.
+/// Deltas that do not fit in a byte (`ld` is a signed byte) are split into multiple entries,
+/// and an entry that does not advance the offset is merged into the previous one if possible.
+fn push_lnotab_entry(lnotab: &mut Vec<u8>, mut sd: usize, mut ld: isize) {
+    if sd == 0 && lnotab.len() >= 2 {
+        let last_ld = lnotab.last_mut().unwrap();
+        if let Ok(merged) = i8::try_from(*last_ld as i8 as isize + ld) {
+            *last_ld = merged as u8;
+            return;
+        }
+    }
+    while sd > 0xff {
+        lnotab.extend([0xff, 0]);
+        sd -= 0xff;
+    }
+    while ld > i8::MAX as isize {
+        lnotab.extend([sd as u8, i8::MAX as u8]);
+        ld -= i8::MAX as isize;
+        sd = 0;
+    }
+    while ld < i8::MIN as isize {
+        lnotab.extend([sd as u8, i8::MIN as u8]);
+        ld -= i8::MIN as isize;
+        sd = 0;
+    }
+    lnotab.extend([sd as u8, ld as i8 as u8]);
+}
+
+/// Encodes line ranges (`(start, end, line)`, contiguous from offset 0) as `co_linetable` of Python 3.10:
+/// `(bytecode delta, line delta)` pairs, where the line delta applies to the range it precedes.
+/// See Objects/lnotab_notes.txt of CPython 3.10 for details.
+fn linetable_310(ranges: &[(usize, usize, isize)], firstlineno: isize) -> Vec<u8> {
+    let mut table = vec![];
+    let mut prev_line = firstlineno;
+    for &(start, end, line) in ranges {
+        let (mut sd, mut ld) = (end - start, line - prev_line);
+        prev_line = line;
+        // -128 means "no line number"
+        while ld > i8::MAX as isize {
+            table.extend([0, i8::MAX as u8]);
+            ld -= i8::MAX as isize;
+        }
+        while ld < -(i8::MAX as isize) {
+            table.extend([0, -i8::MAX as u8]);
+            ld += i8::MAX as isize;
+        }
+        while sd > 254 {
+            table.extend([254, ld as i8 as u8]);
+            sd -= 254;
+            ld = 0;
+        }
+        table.extend([sd as u8, ld as i8 as u8]);
+    }
+    table
+}
+
+/// Appends `value` as 6-bit chunks (from the least significant one) with the continuation bit (`0x40`).
+fn push_location_varint(table: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x40 {
+        table.push(0x40 | (value & 0x3f) as u8);
+        value >>= 6;
+    }
+    table.push(value as u8);
+}
+
+fn read_location_varint(table: &mut impl Iterator<Item = u8>) -> Option<usize> {
+    let mut byte = table.next()?;
+    let mut value = (byte & 0x3f) as usize;
+    let mut shift = 6;
+    while byte & 0x40 != 0 {
+        byte = table.next()?;
+        value |= ((byte & 0x3f) as usize) << shift;
+        shift += 6;
+    }
+    Some(value)
+}
+
+/// the form of a `co_linetable` entry of Python 3.11 (`_PyCodeLocationInfoKind`)
+const LOCATION_NO_COLUMNS: u8 = 13;
+const LOCATION_LONG: u8 = 14;
+const LOCATION_NONE: u8 = 15;
+
+/// Encodes line ranges (`(start, end, line)`, contiguous from offset 0) as `co_linetable` of Python 3.11.
+/// Erg has no column information, so every entry has the "no columns" form.
+/// See Objects/locations.md of CPython 3.11 for details.
+fn location_table_311(ranges: &[(usize, usize, isize)], firstlineno: isize) -> Vec<u8> {
+    let mut table = vec![];
+    let mut prev_line = firstlineno;
+    for &(start, end, line) in ranges {
+        // an entry covers up to 8 code units
+        let mut units = (end - start) / 2;
+        let mut ld = line - prev_line;
+        prev_line = line;
+        while units > 0 {
+            let len = units.min(8);
+            table.push(0x80 | LOCATION_NO_COLUMNS << 3 | (len - 1) as u8);
+            let signed = if ld < 0 {
+                (-ld as usize) << 1 | 1
+            } else {
+                (ld as usize) << 1
+            };
+            push_location_varint(&mut table, signed);
+            units -= len;
+            ld = 0;
+        }
+    }
+    table
+}
+
+/// Decodes `co_linetable` of Python 3.10/3.11 into line ranges (`(start, end, line)`).
+/// The ranges without a line number are omitted.
+fn linetable_ranges(
+    table: &[u8],
+    firstlineno: isize,
+    python_ver: u32,
+) -> Vec<(usize, usize, isize)> {
+    let mut ranges = vec![];
+    let (mut addr, mut line) = (0, firstlineno);
+    if python_ver >= 3495 {
+        let mut table = table.iter().copied();
+        while let Some(first) = table.next() {
+            let len = ((first & 7) as usize + 1) * 2;
+            let has_line = match (first >> 3) & 0xf {
+                LOCATION_NONE => false,
+                kind @ (LOCATION_NO_COLUMNS | LOCATION_LONG) => {
+                    let Some(signed) = read_location_varint(&mut table) else {
+                        break;
+                    };
+                    if signed & 1 != 0 {
+                        line -= (signed >> 1) as isize;
+                    } else {
+                        line += (signed >> 1) as isize;
+                    }
+                    if kind == LOCATION_LONG {
+                        // end line delta, start column, end column
+                        for _ in 0..3 {
+                            read_location_varint(&mut table);
+                        }
+                    }
+                    true
+                }
+                // one line forms (the line delta is 0, 1 or 2), followed by the columns
+                kind @ 10..=12 => {
+                    line += kind as isize - 10;
+                    table.next();
+                    table.next();
+                    true
+                }
+                // short forms (the same line), followed by the column
+                _ => {
+                    table.next();
+                    true
+                }
+            };
+            if has_line {
+                ranges.push((addr, addr + len, line));
+            }
+            addr += len;
+        }
+    } else {
+        for pair in table.chunks(2) {
+            let sd = pair[0] as usize;
+            let ld = pair.get(1).map_or(0, |ld| *ld as i8);
+            let has_line = ld != i8::MIN;
+            if has_line {
+                line += ld as isize;
+            }
+            if sd != 0 && has_line {
+                ranges.push((addr, addr + sd, line));
+            }
+            addr += sd;
+        }
+    }
+    ranges
+}
+
+/// Converts `co_linetable` of Python 3.10/3.11 into `lnotab` (the format of 3.9)
+fn lnotab_of_linetable(table: &[u8], firstlineno: u32, python_ver: u32) -> Vec<u8> {
+    let mut lnotab = vec![];
+    let (mut addr, mut line) = (0, firstlineno as isize);
+    for (start, _end, range_line) in linetable_ranges(table, line, python_ver) {
+        if range_line != line {
+            push_lnotab_entry(&mut lnotab, start - addr, range_line - line);
+            addr = start;
+            line = range_line;
+        }
+    }
+    lnotab
+}
+
+/// Computes the maximum stack depth of the bytecode, following all branches.
+/// This is independent of the stack size tracked during code generation, so it can be used to verify it.
+///
+/// Jump targets are interpreted in the same way as the code generator emits them
+/// (absolute: `arg * 2`, relative: `next instruction + arg * 2`;
+/// `compute_stacksize_with` counts the args in bytes for Python 3.9 and earlier, see `OpcodeTable::jump_bytes`).
+pub fn compute_stacksize(code: &[u8], consts: &[ValueObj]) -> Result<u32, CodeObjError> {
+    compute_stacksize_with(code, consts, &OpcodeTable::new())
+}
+
+/// Same as `compute_stacksize`, but the code is decoded with `table`
+/// (the bytes of the target Python version and the custom opcodes).
+pub fn compute_stacksize_with(
+    code: &[u8],
+    consts: &[ValueObj],
+    table: &OpcodeTable,
+) -> Result<u32, CodeObjError> {
+    // the stack depth before executing each instruction (indexed by offset / 2)
+    let mut depths: Vec<Option<u32>> = vec![None; code.len() / 2];
+    let mut unvisited = vec![(0usize, 0u32)];
+    let mut max_depth = 0;
+    while let Some((mut offset, mut depth)) = unvisited.pop() {
+        while offset + 1 < code.len() {
+            match depths[offset / 2] {
+                Some(expected) if expected == depth => break,
+                Some(expected) => {
+                    return Err(CodeObjError::InconsistentStackDepth {
+                        offset,
+                        expected,
+                        found: depth,
+                    });
+                }
+                None => {
+                    depths[offset / 2] = Some(depth);
+                }
+            }
+            let (instr, arg) = decode_instr(code, offset);
+            let next = instr + 2;
+            let op = match table.decode(code[instr]) {
+                Some(op) => op,
+                None => {
+                    let custom = table.get(code[instr]).ok_or(CodeObjError::InvalidOpcode {
+                        offset: instr,
+                        byte: code[instr],
+                    })?;
+                    // custom opcodes never jump
+                    depth = u32::try_from(depth as i32 + custom.stack_effect_with(arg))
+                        .map_err(|_| CodeObjError::StackUnderflow { offset: instr })?;
+                    max_depth = max_depth.max(depth);
+                    offset = next;
+                    continue;
+                }
+            };
+            if op == Opcode::LOAD_CONST && arg as usize >= consts.len() {
+                return Err(CodeObjError::ConstOutOfRange {
+                    offset: instr,
+                    idx: arg as usize,
+                });
+            }
+            let target = match op {
+                _ if op.is_absolute_jump() => Some(table.jump_bytes(arg as usize)),
+                Opcode::JUMP_FORWARD | Opcode::FOR_ITER | Opcode::SETUP_WITH => {
+                    Some(next + table.jump_bytes(arg as usize))
+                }
+                _ => None,
+            };
+            let apply = |jump: bool| {
+                let effect = op
+                    .stack_effect(arg, jump)
+                    .ok_or(CodeObjError::UnknownStackEffect { offset: instr, op })?;
+                u32::try_from(depth as i32 + effect)
+                    .map_err(|_| CodeObjError::StackUnderflow { offset: instr })
+            };
+            if let Some(target) = target {
+                if target >= code.len() {
+                    return Err(CodeObjError::JumpOutOfRange {
+                        offset: instr,
+                        target,
+                    });
+                }
+                let jump_depth = apply(true)?;
+                max_depth = max_depth.max(jump_depth);
+                unvisited.push((target, jump_depth));
+            }
+            depth = apply(false)?;
+            max_depth = max_depth.max(depth);
+            if matches!(
+                op,
+                Opcode::RETURN_VALUE
+                    | Opcode::RAISE_VARARGS
+                    | Opcode::RERAISE
+                    | Opcode::JUMP_FORWARD
+                    | Opcode::JUMP_ABSOLUTE
+            ) {
+                break;
+            }
+            offset = next;
+        }
+    }
+    Ok(max_depth)
+}
+
 /// Bit masks for CodeObj.flags
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
@@ -95,10 +639,22 @@ impl CodeObjFlags {
     }
 }
 
+/// The parameters of a code object, classified by kind (see `CodeObj::arg_spec`)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ArgSpec {
+    pub posonly: Vec<Str>,
+    pub pos_or_kw: Vec<Str>,
+    /// `*args`
+    pub varargs: Option<Str>,
+    pub kwonly: Vec<Str>,
+    /// `**kwargs`
+    pub kwargs: Option<Str>,
+}
+
 /// Implementation of `PyCodeObject`, see Include/cpython/code.h in CPython for details.
 ///
 /// 各属性をErg側のObjに変換すると遅くなりそうなので、アクサスされたときのみ変換して提供する
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone)]
 pub struct CodeObj {
     pub argcount: u32,
     pub posonlyargcount: u32,
@@ -114,12 +670,74 @@ pub struct CodeObj {
     pub cellvars: Vec<Str>,    // names used in the inner function (closure)
     pub filename: Str,
     pub name: Str,
+    /// the qualified name (e.g. `f.<locals>.g`), `co_qualname` since 3.11
+    pub qualname: Str,
     pub firstlineno: u32,
     // lnotab (line number table): see Object/lnotab_notes.txt in CPython for details
     // e.g. +12bytes, +3line -> [.., 0x1C, 0x03, ..]
     // ([sdelta, ldelta, sdelta, ldelta, ..])
     // if delta > 255 -> [255, 0, 255-delta, ...]
+    // always in the format of 3.9 (converted to/from `co_linetable` when (de)serialized for 3.10~)
     pub lnotab: Vec<u8>,
+    // exception table (3.11~): the handler ranges encoded with varints (see `ExceptionTableEntry`)
+    pub exceptiontable: Vec<u8>,
+}
+
+/// Structural equality: the constants are compared with `ValueObj::marshal_eq`
+/// (recursively for nested code objects), so that two code objects are equal only if they are marshalled identically.
+/// This is consistent with `Hash`, so identical code objects can be shared in the constant pool.
+impl PartialEq for CodeObj {
+    fn eq(&self, other: &Self) -> bool {
+        self.argcount == other.argcount
+            && self.posonlyargcount == other.posonlyargcount
+            && self.kwonlyargcount == other.kwonlyargcount
+            && self.nlocals == other.nlocals
+            && self.stacksize == other.stacksize
+            && self.flags == other.flags
+            && self.code == other.code
+            && self.consts.len() == other.consts.len()
+            && self
+                .consts
+                .iter()
+                .zip(other.consts.iter())
+                .all(|(l, r)| l.marshal_eq(r))
+            && self.names == other.names
+            && self.varnames == other.varnames
+            && self.freevars == other.freevars
+            && self.cellvars == other.cellvars
+            && self.filename == other.filename
+            && self.name == other.name
+            && self.qualname == other.qualname
+            && self.firstlineno == other.firstlineno
+            && self.lnotab == other.lnotab
+            && self.exceptiontable == other.exceptiontable
+    }
+}
+
+impl Eq for CodeObj {}
+
+impl Hash for CodeObj {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // `ValueObj::hash` distinguishes the types and hashes floats bitwise, so this is consistent with `eq`
+        self.argcount.hash(state);
+        self.posonlyargcount.hash(state);
+        self.kwonlyargcount.hash(state);
+        self.nlocals.hash(state);
+        self.stacksize.hash(state);
+        self.flags.hash(state);
+        self.code.hash(state);
+        self.consts.hash(state);
+        self.names.hash(state);
+        self.varnames.hash(state);
+        self.freevars.hash(state);
+        self.cellvars.hash(state);
+        self.filename.hash(state);
+        self.name.hash(state);
+        self.qualname.hash(state);
+        self.firstlineno.hash(state);
+        self.lnotab.hash(state);
+        self.exceptiontable.hash(state);
+    }
 }
 
 impl HasType for CodeObj {
@@ -160,8 +778,10 @@ impl Default for CodeObj {
             cellvars: Vec::new(),
             filename: "<dummy>".into(),
             name: "<dummy>".into(),
+            qualname: "<dummy>".into(),
             firstlineno: 1,
             lnotab: Vec::new(),
+            exceptiontable: Vec::new(),
         }
     }
 }
@@ -185,6 +805,7 @@ impl CodeObj {
         firstlineno: u32,
         lnotab: Vec<u8>,
     ) -> Self {
+        let name = name.into();
         Self {
             argcount,
             posonlyargcount,
@@ -199,9 +820,11 @@ impl CodeObj {
             freevars,
             cellvars,
             filename,
-            name: name.into(),
+            qualname: name.clone(),
+            name,
             firstlineno,
             lnotab,
+            exceptiontable: Vec::new(),
         }
     }
 
@@ -211,6 +834,7 @@ impl CodeObj {
         name: T,
         firstlineno: u32,
     ) -> Self {
+        let name = name.into();
         Self {
             argcount: params.len() as u32,
             posonlyargcount: 0,
@@ -225,26 +849,71 @@ impl CodeObj {
             freevars: Vec::new(),
             cellvars: Vec::new(),
             filename: filename.into(),
-            name: name.into(),
+            qualname: name.clone(),
+            name,
             firstlineno,
             lnotab: Vec::with_capacity(4),
+            exceptiontable: Vec::new(),
         }
     }
 
     pub fn from_pyc<P: AsRef<Path>>(path: P) -> DeserializeResult<Self> {
+        Self::from_pyc_with_version(path).map(|(code, _)| code)
+    }
+
+    /// Same as `from_pyc`, but also returns the Python version of the file (e.g. 3425),
+    /// which is needed to decode `code` (see `OpcodeTable::for_version`).
+    pub fn from_pyc_with_version<P: AsRef<Path>>(path: P) -> DeserializeResult<(Self, u32)> {
         let mut f = BufReader::new(File::open(path)?);
         let v = &mut Vec::with_capacity(16);
         f.read_to_end(v)?;
-        let python_ver = get_magic_num_from_bytes(&Deserializer::consume::<4>(v));
-        let _padding = Deserializer::deserialize_u32(v);
-        let _timestamp = Deserializer::deserialize_u32(v);
-        let _padding = Deserializer::deserialize_u32(v);
-        let code = Self::from_bytes(v, python_ver)?;
-        Ok(code)
+        let python_ver = Self::read_pyc_header(v)?;
+        // the top-level code object is also registered in the reference table (`FLAG_REF`)
+        match Deserializer::new().deserialize_const(v, python_ver)? {
+            ValueObj::Code(code) => Ok((*code, python_ver)),
+            _ => Err(DeserializeError::file_broken_error()),
+        }
+    }
+
+    /// Consumes the header of a .pyc file and returns the Python version (e.g. 3425).
+    ///
+    /// * ~3.2: magic number, timestamp
+    /// * 3.3~3.6: magic number, timestamp, source size
+    /// * 3.7~: magic number, bit field, timestamp and source size (or source hash if the bit field is not 0)
+    fn read_pyc_header(v: &mut Vec<u8>) -> DeserializeResult<u32> {
+        if v.len() < 4 {
+            return Err(DeserializeError::file_broken_error());
+        }
+        let magic = Deserializer::consume::<4>(v);
+        // the last 2 bytes of a magic number are always "\r\n"
+        if magic[2..] != [b'\r', b'\n'] {
+            return Err(DeserializeError::invalid_magic_number_error(&magic));
+        }
+        let python_ver = get_magic_num_from_bytes(&magic);
+        let rest_len = if python_ver >= 3392 {
+            12
+        } else if python_ver >= 3210 {
+            8
+        } else {
+            4
+        };
+        if v.len() < rest_len {
+            return Err(DeserializeError::file_broken_error());
+        }
+        v.drain(..rest_len);
+        Ok(python_ver)
     }
 
     pub fn from_bytes(v: &mut Vec<u8>, python_ver: u32) -> DeserializeResult<Self> {
-        let mut des = Deserializer::new();
+        Self::from_bytes_with(&mut Deserializer::new(), v, python_ver)
+    }
+
+    /// `des` keeps the objects referred to by `Ref`s across the nested code objects
+    pub fn from_bytes_with(
+        des: &mut Deserializer,
+        v: &mut Vec<u8>,
+        python_ver: u32,
+    ) -> DeserializeResult<Self> {
         let argcount = Deserializer::deserialize_u32(v);
         let posonlyargcount = if python_ver >= 3413 {
             Deserializer::deserialize_u32(v)
@@ -252,19 +921,72 @@ impl CodeObj {
             0
         };
         let kwonlyargcount = Deserializer::deserialize_u32(v);
-        let nlocals = Deserializer::deserialize_u32(v);
+        let nlocals = if python_ver >= 3495 {
+            0 // recalculated from `co_localspluskinds`
+        } else {
+            Deserializer::deserialize_u32(v)
+        };
         let stacksize = Deserializer::deserialize_u32(v);
         let flags = Deserializer::deserialize_u32(v);
-        let code = des.deserialize_bytes(v)?;
+        let code = des.deserialize_bytes(v, python_ver)?;
         let consts = des.deserialize_const_vec(v, python_ver)?;
         let names = des.deserialize_str_vec(v, python_ver)?;
+        if python_ver >= 3495 {
+            let localsplusnames = des.deserialize_str_vec(v, python_ver)?;
+            let localspluskinds = des.deserialize_bytes(v, python_ver)?;
+            let filename = des.deserialize_str(v, python_ver)?;
+            let name = des.deserialize_str(v, python_ver)?;
+            let qualname = des.deserialize_str(v, python_ver)?;
+            let firstlineno = Deserializer::deserialize_u32(v);
+            let linetable = des.deserialize_bytes(v, python_ver)?;
+            let lnotab = lnotab_of_linetable(&linetable, firstlineno, python_ver);
+            let exceptiontable = des.deserialize_bytes(v, python_ver)?;
+            let (mut varnames, mut cellvars, mut freevars) = (vec![], vec![], vec![]);
+            for (name, kind) in localsplusnames.into_iter().zip(localspluskinds) {
+                if kind & CO_FAST_LOCAL != 0 {
+                    varnames.push(name.clone());
+                }
+                if kind & CO_FAST_CELL != 0 {
+                    cellvars.push(name.clone());
+                }
+                if kind & CO_FAST_FREE != 0 {
+                    freevars.push(name);
+                }
+            }
+            let mut code = CodeObj::new(
+                argcount,
+                posonlyargcount,
+                kwonlyargcount,
+                varnames.len() as u32,
+                stacksize,
+                flags,
+                code,
+                consts,
+                names,
+                varnames,
+                freevars,
+                cellvars,
+                filename,
+                name,
+                firstlineno,
+                lnotab,
+            );
+            code.qualname = qualname;
+            code.exceptiontable = exceptiontable;
+            return Ok(code);
+        }
         let varnames = des.deserialize_str_vec(v, python_ver)?;
         let freevars = des.deserialize_str_vec(v, python_ver)?;
         let cellvars = des.deserialize_str_vec(v, python_ver)?;
         let filename = des.deserialize_str(v, python_ver)?;
         let name = des.deserialize_str(v, python_ver)?;
         let firstlineno = Deserializer::deserialize_u32(v);
-        let lnotab = des.deserialize_bytes(v)?;
+        let lnotab = des.deserialize_bytes(v, python_ver)?;
+        let lnotab = if python_ver >= 3439 {
+            lnotab_of_linetable(&lnotab, firstlineno, python_ver)
+        } else {
+            lnotab
+        };
         Ok(CodeObj::new(
             argcount,
             posonlyargcount,
@@ -285,55 +1007,839 @@ impl CodeObj {
         ))
     }
 
+    /// Since 3.11, `varnames`, `cellvars` and `freevars` are merged into `co_localsplusnames`
+    /// (a cell variable that is also a parameter appears only once)
+    fn localsplus(&self) -> (Vec<Str>, Vec<u8>) {
+        let mut names = vec![];
+        let mut kinds = vec![];
+        for varname in self.varnames.iter() {
+            names.push(varname.clone());
+            if self.cellvars.contains(varname) {
+                kinds.push(CO_FAST_LOCAL | CO_FAST_CELL);
+            } else {
+                kinds.push(CO_FAST_LOCAL);
+            }
+        }
+        for cellvar in self.cellvars.iter() {
+            if !self.varnames.contains(cellvar) {
+                names.push(cellvar.clone());
+                kinds.push(CO_FAST_CELL);
+            }
+        }
+        for freevar in self.freevars.iter() {
+            names.push(freevar.clone());
+            kinds.push(CO_FAST_FREE);
+        }
+        (names, kinds)
+    }
+
     pub fn into_bytes(self, python_ver: u32) -> Vec<u8> {
+        self.into_bytes_with(&mut RefTable::new(), python_ver)
+    }
+
+    /// the interned strings written before are replaced with `Ref`s
+    pub fn into_bytes_with(self, refs: &mut RefTable, python_ver: u32) -> Vec<u8> {
+        let linetable = self.linetable(python_ver);
         let mut bytes = vec![DataTypePrefix::Code as u8];
         bytes.append(&mut self.argcount.to_le_bytes().to_vec());
         if python_ver >= 3413 {
             bytes.append(&mut self.posonlyargcount.to_le_bytes().to_vec());
         }
         bytes.append(&mut self.kwonlyargcount.to_le_bytes().to_vec());
-        bytes.append(&mut self.nlocals.to_le_bytes().to_vec());
+        if python_ver < 3495 {
+            bytes.append(&mut self.nlocals.to_le_bytes().to_vec());
+        }
         bytes.append(&mut self.stacksize.to_le_bytes().to_vec());
         bytes.append(&mut self.flags.to_le_bytes().to_vec());
+        let (localsplusnames, localspluskinds) = self.localsplus();
         // co_code is represented as PyStrObject (Not Ascii, Unicode)
         bytes.append(&mut raw_string_into_bytes(self.code));
-        bytes.append(&mut consts_into_bytes(self.consts)); // write as PyTupleObject
-        bytes.append(&mut strs_into_bytes(self.names));
-        bytes.append(&mut strs_into_bytes(self.varnames));
-        bytes.append(&mut strs_into_bytes(self.freevars));
-        bytes.append(&mut strs_into_bytes(self.cellvars));
+        bytes.append(&mut consts_into_bytes(self.consts, refs, python_ver)); // write as PyTupleObject
+        bytes.append(&mut refs.strs_into_bytes(self.names));
+        if python_ver >= 3495 {
+            bytes.append(&mut refs.strs_into_bytes(localsplusnames));
+            bytes.append(&mut raw_string_into_bytes(localspluskinds));
+        } else {
+            bytes.append(&mut refs.strs_into_bytes(self.varnames));
+            bytes.append(&mut refs.strs_into_bytes(self.freevars));
+            bytes.append(&mut refs.strs_into_bytes(self.cellvars));
+        }
         bytes.append(&mut str_into_bytes(self.filename, false));
-        bytes.append(&mut str_into_bytes(self.name, true));
+        bytes.append(&mut refs.interned_str_into_bytes(self.name));
+        if python_ver >= 3495 {
+            bytes.append(&mut refs.interned_str_into_bytes(self.qualname));
+        }
         bytes.append(&mut self.firstlineno.to_le_bytes().to_vec());
-        // lnotab is represented as PyStrObject
-        bytes.append(&mut raw_string_into_bytes(self.lnotab));
+        // lnotab (co_linetable since 3.10) is represented as PyStrObject
+        bytes.append(&mut raw_string_into_bytes(linetable));
+        if python_ver >= 3495 {
+            bytes.append(&mut raw_string_into_bytes(self.exceptiontable));
+        }
         bytes
     }
 
+    /// `python_ver` is the magic number of the target Python (if `None`, that of the installed one).
+    /// Fails without creating the file if the version is not supported (3.7~3.11),
+    /// or if it is not given and no supported Python is installed.
     pub fn dump_as_pyc<P: AsRef<Path>>(
         self,
         path: P,
         python_ver: Option<u32>,
     ) -> std::io::Result<()> {
-        let mut file = File::create(path)?;
+        let python_ver = python_ver.or_else(detect_magic_number).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no supported Python is installed (specify the target version)",
+            )
+        })?;
+        // a pre-release magic number is replaced with that of the final release
+        let magic = get_python_version(python_ver)
+            .and_then(magic_number)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("unsupported Python version (magic number: {python_ver})"),
+                )
+            })?;
+        let python_ver = get_magic_num_from_bytes(&magic);
         let mut bytes = Vec::with_capacity(16);
-        let python_ver = python_ver.unwrap_or_else(detect_magic_number);
-        bytes.append(&mut get_magic_num_bytes(python_ver).to_vec());
-        bytes.append(&mut vec![0; 4]); // padding
+        bytes.append(&mut magic.to_vec());
+        bytes.append(&mut vec![0; 4]); // bit field
         bytes.append(&mut get_timestamp_bytes().to_vec());
-        bytes.append(&mut vec![0; 4]); // padding
+        bytes.append(&mut vec![0; 4]); // source size
         bytes.append(&mut self.into_bytes(python_ver));
+        let mut file = File::create(path)?;
         file.write_all(&bytes[..])?;
         Ok(())
     }
 
+    /// `varnames` are laid out as: positional-only, positional-or-keyword, keyword-only, `*args`, `**kwargs`, (locals...)
+    pub fn arg_spec(&self) -> ArgSpec {
+        let posonly = self.posonlyargcount as usize;
+        let argcount = self.argcount as usize;
+        let kwonly_end = argcount + self.kwonlyargcount as usize;
+        let mut idx = kwonly_end;
+        let mut next_name = |flag: CodeObjFlags| {
+            if flag.is_in(self.flags) {
+                idx += 1;
+                self.varnames.get(idx - 1).cloned()
+            } else {
+                None
+            }
+        };
+        let varargs = next_name(CodeObjFlags::VarArgs);
+        let kwargs = next_name(CodeObjFlags::VarKeywords);
+        ArgSpec {
+            posonly: self.varnames[..posonly].to_vec(),
+            pos_or_kw: self.varnames[posonly..argcount].to_vec(),
+            varargs,
+            kwonly: self.varnames[argcount..kwonly_end].to_vec(),
+            kwargs,
+        }
+    }
+
+    /// code objects directly stored in `consts`
+    fn child_codes(&self) -> impl DoubleEndedIterator<Item = &CodeObj> {
+        self.consts.iter().filter_map(|obj| match obj {
+            ValueObj::Code(code) => Some(code.as_ref()),
+            _ => None,
+        })
+    }
+
+    /// All code objects nested in this code object (recursively, pre-order). `self` is not included.
+    pub fn nested_codes(&self) -> impl Iterator<Item = &CodeObj> {
+        let mut stack = self.child_codes().rev().collect::<Vec<_>>();
+        std::iter::from_fn(move || {
+            let code = stack.pop()?;
+            stack.extend(code.child_codes().rev());
+            Some(code)
+        })
+    }
+
+    /// Applies `f` to all nested code objects (in the same order as `nested_codes`).
+    /// This takes a closure instead of returning an iterator,
+    /// because a parent and its children cannot be borrowed mutably at the same time.
+    pub fn nested_codes_mut<F: FnMut(&mut CodeObj)>(&mut self, mut f: F) {
+        fn visit<F: FnMut(&mut CodeObj)>(code: &mut CodeObj, f: &mut F) {
+            for obj in code.consts.iter_mut() {
+                if let ValueObj::Code(child) = obj {
+                    f(child);
+                    visit(child, f);
+                }
+            }
+        }
+        visit(self, &mut f);
+    }
+
+    /// Shifts the targets of all absolute jumps by `byte_offset`,
+    /// so that the code can be embedded at `byte_offset` of another code object.
+    /// Relative jumps are left as they are.
+    ///
+    /// # Panics
+    /// if `byte_offset` is odd, or a new target does not fit in the existing `EXTENDED_ARG` prefixes
+    pub fn rebase_jumps(&mut self, byte_offset: usize) {
+        self.rebase_jumps_with(byte_offset, &OpcodeTable::new());
+    }
+
+    /// Same as `rebase_jumps`, but the code is decoded (and the jumps are encoded) with `table`.
+    pub fn rebase_jumps_with(&mut self, byte_offset: usize, table: &OpcodeTable) {
+        assert!(byte_offset & 1 == 0, "unaligned offset: {byte_offset}");
+        let mut offset = 0;
+        while offset + 1 < self.code.len() {
+            let (instr, arg) = decode_instr(&self.code, offset);
+            let is_absolute_jump = table
+                .decode(self.code[instr])
+                .map(|op| op.is_absolute_jump())
+                .unwrap_or(false);
+            if is_absolute_jump {
+                let new_arg = arg as usize + table.jump_arg(byte_offset);
+                if !patch_arg(&mut self.code, offset, instr, new_arg) {
+                    panic!(
+                        "the jump target at {instr} overflows: {}",
+                        table.jump_bytes(new_arg)
+                    );
+                }
+            }
+            offset = instr + 2;
+        }
+    }
+
+    /// Appends an entry to `lnotab`: the bytecode offset advances by `sd` and the line number by `ld`
+    /// (`ld` may be negative, i.e. the line number decreases).
+    /// Deltas that do not fit in a byte are split into multiple entries.
+    pub fn push_lnotab(&mut self, sd: usize, ld: isize) {
+        push_lnotab_entry(&mut self.lnotab, sd, ld);
+    }
+
+    /// Registers a handler range in `exceptiontable`.
+    /// The entries must be pushed in the order of `start` (and must not overlap).
+    pub fn push_exception_handler(&mut self, entry: ExceptionTableEntry) {
+        // encoded in instruction units
+        push_except_varint(&mut self.exceptiontable, entry.start / 2, 0x80);
+        push_except_varint(&mut self.exceptiontable, (entry.end - entry.start) / 2, 0);
+        push_except_varint(&mut self.exceptiontable, entry.target / 2, 0);
+        let depth_lasti = entry.depth << 1 | entry.lasti as usize;
+        push_except_varint(&mut self.exceptiontable, depth_lasti, 0);
+    }
+
+    /// Decodes `exceptiontable` (same as `dis._parse_exception_table`)
+    pub fn exception_handlers(&self) -> Vec<ExceptionTableEntry> {
+        let mut table = self.exceptiontable.iter().copied();
+        let mut entries = vec![];
+        while let Some(start) = read_except_varint(&mut table) {
+            let (Some(len), Some(target), Some(depth_lasti)) = (
+                read_except_varint(&mut table),
+                read_except_varint(&mut table),
+                read_except_varint(&mut table),
+            ) else {
+                break;
+            };
+            entries.push(ExceptionTableEntry {
+                start: start * 2,
+                end: (start + len) * 2,
+                target: target * 2,
+                depth: depth_lasti >> 1,
+                lasti: depth_lasti & 1 != 0,
+            });
+        }
+        entries
+    }
+
+    /// The offsets where the lines start and the line numbers (same as `dis.findlinestarts` of Python 3.9)
+    pub fn line_starts(&self) -> Vec<(usize, isize)> {
+        let mut starts = vec![];
+        let (mut addr, mut lineno) = (0, self.firstlineno as isize);
+        let mut last_lineno = None;
+        for pair in self.lnotab.chunks(2) {
+            if pair[0] != 0 {
+                if last_lineno != Some(lineno) {
+                    starts.push((addr, lineno));
+                    last_lineno = Some(lineno);
+                }
+                addr += pair[0] as usize;
+            }
+            lineno += pair[1] as i8 as isize;
+        }
+        if last_lineno != Some(lineno) {
+            starts.push((addr, lineno));
+        }
+        starts
+    }
+
+    /// The runs of bytecode on the same line: `(start, end, line)`, from offset 0 to the end of the code
+    fn line_ranges(&self) -> Vec<(usize, usize, isize)> {
+        // the first line always starts at 0
+        let starts = self.line_starts();
+        let ends = starts
+            .iter()
+            .skip(1)
+            .map(|(addr, _)| *addr)
+            .chain([self.code.len()]);
+        starts
+            .iter()
+            .zip(ends)
+            .map(|(&(start, line), end)| (start, end.min(self.code.len()), line))
+            .filter(|(start, end, _)| start < end)
+            .collect()
+    }
+
+    /// `lnotab` in the format of the target version (`co_linetable` since 3.10)
+    pub fn linetable(&self, python_ver: u32) -> Vec<u8> {
+        let firstlineno = self.firstlineno as isize;
+        if python_ver >= 3495 {
+            location_table_311(&self.line_ranges(), firstlineno)
+        } else if python_ver >= 3439 {
+            linetable_310(&self.line_ranges(), firstlineno)
+        } else {
+            self.lnotab.clone()
+        }
+    }
+
+    /// Inserts `EXTENDED_ARG`s for the jumps in `long_jumps` (the offset of the jump instruction and its full arg),
+    /// whose args were truncated to the lower byte,
+    /// and moves the other jump targets and `lnotab` to the new offsets.
+    pub fn relax_jumps(&mut self, long_jumps: &[(usize, u32)]) {
+        self.relax_jumps_with(long_jumps, &OpcodeTable::new());
+    }
+
+    /// Same as `relax_jumps`, but the code is decoded with `table`.
+    pub fn relax_jumps_with(&mut self, long_jumps: &[(usize, u32)], table: &OpcodeTable) {
+        if long_jumps.is_empty() {
+            return;
+        }
+        let mut instrs = decode_instrs(&self.code);
+        for instr in instrs.iter_mut() {
+            if let Some((_, arg)) = long_jumps.iter().find(|(idx, _)| *idx == instr.op_offset) {
+                instr.arg = *arg;
+            }
+        }
+        self.reassemble(&instrs, table);
+    }
+
+    /// A peephole optimizer for finished code objects (nested code objects are not visited):
+    ///
+    /// * `LOAD_CONST a; LOAD_CONST b; BINARY_ADD` (also `BINARY_SUBTRACT`/`BINARY_MULTIPLY`) is folded into `LOAD_CONST (a + b)`
+    /// * `LOAD_CONST c; POP_JUMP_IF_FALSE/TRUE` is replaced with a `JUMP_ABSOLUTE` or removed, depending on the truthiness of `c`
+    /// * `NOP`s are removed, except those needed to keep a line in `lnotab` (see `ErgConfig::coverage`)
+    ///
+    /// Instructions that are jump targets (including exception handlers) are not folded into the preceding ones.
+    /// The jumps, `lnotab` and `exceptiontable` are moved to the new offsets.
+    pub fn peephole_optimize(&mut self) {
+        self.peephole_optimize_with(&OpcodeTable::new());
+    }
+
+    /// Same as `peephole_optimize`, but the code is decoded with `table`.
+    pub fn peephole_optimize_with(&mut self, table: &OpcodeTable) {
+        let mut instrs = decode_instrs(&self.code);
+        let code_len = self.code.len();
+        let mut jump_targets = self
+            .exception_handlers()
+            .into_iter()
+            .map(|entry| entry.target)
+            .collect::<Set<_>>();
+        for (i, instr) in instrs.iter().enumerate() {
+            match table.decode(instr.op) {
+                Some(op) if op.is_absolute_jump() => {
+                    jump_targets.insert(table.jump_bytes(instr.arg as usize));
+                }
+                Some(op) if op.is_relative_jump() => {
+                    let end = instrs.get(i + 1).map_or(code_len, |next| next.offset);
+                    jump_targets.insert(end + table.jump_bytes(instr.arg as usize));
+                }
+                _ => {}
+            }
+        }
+        let is_load_const = |instr: &RawInstr| table.decode(instr.op) == Some(Opcode::LOAD_CONST);
+        // constant folding (the result can be folded again, e.g. `1 + 2 + 3`)
+        let mut stack = Vec::<usize>::new();
+        for i in 0..instrs.len() {
+            if let [.., lhs, rhs] = stack[..] {
+                let folded = if is_load_const(&instrs[lhs])
+                    && is_load_const(&instrs[rhs])
+                    && !jump_targets.contains(&instrs[rhs].offset)
+                    && !jump_targets.contains(&instrs[i].offset)
+                {
+                    let l = self.consts.get(instrs[lhs].arg as usize);
+                    let r = self.consts.get(instrs[rhs].arg as usize);
+                    l.zip(r)
+                        .and_then(|(l, r)| fold_binary(table.decode(instrs[i].op)?, l, r))
+                } else {
+                    None
+                };
+                if let Some(folded) = folded {
+                    instrs[lhs].arg = self.const_index(folded);
+                    instrs[rhs].removed = true;
+                    instrs[i].removed = true;
+                    stack.pop();
+                    continue;
+                }
+            }
+            stack.push(i);
+        }
+        // constant conditions
+        let mut prev = None::<usize>;
+        for i in 0..instrs.len() {
+            if instrs[i].removed {
+                continue;
+            }
+            let cond = prev.filter(|p| {
+                is_load_const(&instrs[*p]) && !jump_targets.contains(&instrs[i].offset)
+            });
+            let jump_if = match table.decode(instrs[i].op) {
+                Some(Opcode::POP_JUMP_IF_FALSE) => Some(false),
+                Some(Opcode::POP_JUMP_IF_TRUE) => Some(true),
+                _ => None,
+            };
+            let truthy =
+                cond.and_then(|p| self.consts.get(instrs[p].arg as usize).and_then(truthiness));
+            if let (Some(p), Some(jump_if), Some(truthy)) = (cond, jump_if, truthy) {
+                if truthy == jump_if {
+                    instrs[p].op = table.encode(Opcode::JUMP_ABSOLUTE);
+                    instrs[p].arg = instrs[i].arg;
+                } else {
+                    instrs[p].removed = true;
+                }
+                instrs[i].removed = true;
+                prev = None;
+                continue;
+            }
+            prev = Some(i);
+        }
+        // `NOP`s
+        let line_starts = self
+            .line_starts()
+            .into_iter()
+            .map(|(offset, _)| offset)
+            .collect::<Set<_>>();
+        for i in 0..instrs.len() {
+            if instrs[i].removed || table.decode(instrs[i].op) != Some(Opcode::NOP) {
+                continue;
+            }
+            let next = instrs[i + 1..]
+                .iter()
+                .find(|instr| !instr.removed)
+                .map(|instr| instr.offset);
+            // the line of this `NOP` would be lost
+            // (`Option::is_none_or` is not used, since it requires Rust 1.82)
+            let next_starts_line = match next {
+                Some(next) => line_starts.contains(&next),
+                None => true,
+            };
+            let is_line_marker = line_starts.contains(&instrs[i].offset) && next_starts_line;
+            if !is_line_marker {
+                instrs[i].removed = true;
+            }
+        }
+        if instrs.iter().any(|instr| instr.removed) {
+            self.reassemble(&instrs, table);
+        }
+    }
+
+    /// Removes the instructions after an unconditional transfer (`RETURN_VALUE`, `JUMP_ABSOLUTE`, etc.)
+    /// up to the next jump target, which are never executed.
+    /// Jumps in removed instructions do not keep their targets alive.
+    /// The handlers in `exceptiontable` are always reachable.
+    /// The jumps, `lnotab` and `exceptiontable` are moved to the new offsets.
+    pub fn eliminate_dead_code(&mut self) {
+        self.eliminate_dead_code_with(&OpcodeTable::new());
+    }
+
+    /// Same as `eliminate_dead_code`, but the code is decoded with `table`.
+    pub fn eliminate_dead_code_with(&mut self, table: &OpcodeTable) {
+        let mut instrs = decode_instrs(&self.code);
+        let code_len = self.code.len();
+        let handlers = self
+            .exception_handlers()
+            .into_iter()
+            .map(|entry| entry.target)
+            .collect::<Set<_>>();
+        // removing a jump may make more instructions unreachable
+        loop {
+            let mut jump_targets = handlers.clone();
+            for (i, instr) in instrs.iter().enumerate() {
+                if instr.removed {
+                    continue;
+                }
+                match table.decode(instr.op) {
+                    Some(op) if op.is_absolute_jump() => {
+                        jump_targets.insert(table.jump_bytes(instr.arg as usize));
+                    }
+                    Some(op) if op.is_relative_jump() => {
+                        let end = instrs.get(i + 1).map_or(code_len, |next| next.offset);
+                        jump_targets.insert(end + table.jump_bytes(instr.arg as usize));
+                    }
+                    _ => {}
+                }
+            }
+            let mut reachable = true;
+            let mut changed = false;
+            for instr in instrs.iter_mut() {
+                if jump_targets.contains(&instr.offset) {
+                    reachable = true;
+                }
+                if instr.removed {
+                    continue;
+                }
+                if !reachable {
+                    instr.removed = true;
+                    changed = true;
+                    continue;
+                }
+                reachable = !table
+                    .decode(instr.op)
+                    .is_some_and(|op| op.is_unconditional_transfer());
+            }
+            if !changed {
+                break;
+            }
+        }
+        if instrs.iter().any(|instr| instr.removed) {
+            self.reassemble(&instrs, table);
+        }
+    }
+
+    /// The index of `obj` in `consts` (appended if not found).
+    fn const_index(&mut self, obj: ValueObj) -> u32 {
+        if let Some(idx) = self.consts.iter().position(|c| c.marshal_eq(&obj)) {
+            idx as u32
+        } else {
+            self.consts.push(obj);
+            self.consts.len() as u32 - 1
+        }
+    }
+
+    /// Re-emits `instrs` (decoded from `self.code`) without the removed ones,
+    /// with as few `EXTENDED_ARG`s as possible,
+    /// and moves the jump targets, `lnotab` and `exceptiontable` to the new offsets.
+    /// A jump to a removed instruction lands on the next remaining one.
+    /// A handler range with no instructions left is dropped.
+    fn reassemble(&mut self, instrs: &[RawInstr], table: &OpcodeTable) {
+        let code_len = self.code.len();
+        // maps an offset in the current code to the offset in the new code
+        let new_offset_of = |new_offsets: &[usize], sizes: &[usize], offset: usize| {
+            if offset >= code_len {
+                return new_offsets[instrs.len()] + offset - code_len;
+            }
+            // the instruction containing the byte at `offset`
+            let i = instrs.partition_point(|instr| instr.offset <= offset) - 1;
+            new_offsets[i] + (offset - instrs[i].offset).min(sizes[i])
+        };
+        let jump_of = |op: u8| {
+            table.decode(op).map_or((false, false), |op| {
+                (op.is_absolute_jump(), op.is_relative_jump())
+            })
+        };
+        let width = |arg: u32| {
+            2 * (1 + (arg > 0xff) as usize + (arg > 0xffff) as usize + (arg > 0xff_ffff) as usize)
+        };
+        let mut sizes = instrs
+            .iter()
+            .map(|instr| match (instr.removed, jump_of(instr.op)) {
+                (true, _) => 0,
+                (_, (false, false)) => width(instr.arg),
+                _ => 2,
+            })
+            .collect::<Vec<_>>();
+        // a jump may need more `EXTENDED_ARG`s as the preceding instructions grow,
+        // so repeat until the sizes converge (they never shrink)
+        let (new_offsets, args) = loop {
+            let mut new_offsets = Vec::with_capacity(instrs.len() + 1);
+            let mut new_offset = 0;
+            for size in sizes.iter() {
+                new_offsets.push(new_offset);
+                new_offset += size;
+            }
+            new_offsets.push(new_offset);
+            let args = instrs
+                .iter()
+                .enumerate()
+                .map(|(i, instr)| match jump_of(instr.op) {
+                    (true, _) => {
+                        let target = table.jump_bytes(instr.arg as usize);
+                        table.jump_arg(new_offset_of(&new_offsets, &sizes, target)) as u32
+                    }
+                    (_, true) => {
+                        let end = instrs.get(i + 1).map_or(code_len, |next| next.offset);
+                        let target = end + table.jump_bytes(instr.arg as usize);
+                        let new_target = new_offset_of(&new_offsets, &sizes, target);
+                        table.jump_arg(new_target - new_offsets[i + 1]) as u32
+                    }
+                    _ => instr.arg,
+                })
+                .collect::<Vec<_>>();
+            let mut converged = true;
+            for ((size, arg), instr) in sizes.iter_mut().zip(args.iter()).zip(instrs) {
+                if !instr.removed && width(*arg) > *size {
+                    *size = width(*arg);
+                    converged = false;
+                }
+            }
+            if converged {
+                break (new_offsets, args);
+            }
+        };
+        let mut code = Vec::with_capacity(new_offsets[instrs.len()]);
+        for ((instr, arg), size) in instrs.iter().zip(args).zip(sizes.iter()) {
+            if instr.removed {
+                continue;
+            }
+            for i in (1..size / 2).rev() {
+                code.push(table.encode(Opcode::EXTENDED_ARG));
+                code.push((arg >> (8 * i)) as u8);
+            }
+            code.push(instr.op);
+            code.push(arg as u8);
+        }
+        // `lnotab`: (bytecode delta, line delta)
+        let mut lnotab = Vec::with_capacity(self.lnotab.len());
+        let (mut addr, mut new_addr) = (0, 0);
+        let len = self.lnotab.len();
+        for (i, pair) in self.lnotab.chunks(2).enumerate() {
+            addr += pair[0] as usize;
+            // the fillers for long deltas are rebuilt below
+            if pair[1] == 0 && i * 2 + 2 < len {
+                continue;
+            }
+            let target = new_offset_of(&new_offsets, &sizes, addr);
+            // the instructions of the remaining lines were all removed
+            if addr < code_len && target == new_offsets[instrs.len()] {
+                break;
+            }
+            push_lnotab_entry(&mut lnotab, target - new_addr, pair[1] as i8 as isize);
+            new_addr = target;
+        }
+        let handlers = self.exception_handlers();
+        self.exceptiontable.clear();
+        for entry in handlers {
+            let start = new_offset_of(&new_offsets, &sizes, entry.start);
+            let end = new_offset_of(&new_offsets, &sizes, entry.end);
+            if start < end {
+                self.push_exception_handler(ExceptionTableEntry {
+                    start,
+                    end,
+                    target: new_offset_of(&new_offsets, &sizes, entry.target),
+                    ..entry
+                });
+            }
+        }
+        self.code = code;
+        self.lnotab = lnotab;
+    }
+
+    /// Sorts `consts` by `ValueObj::canonical_cmp` and rewrites the args of `LOAD_CONST`s accordingly,
+    /// so that the constant pool does not depend on the order in which the constants were added.
+    /// `consts[0]` is left in place, since CPython reads it as the docstring of a function.
+    /// Nested code objects are canonicalized first.
+    ///
+    /// # Panics
+    /// if a new index does not fit in the existing `EXTENDED_ARG` prefixes
+    /// (only possible with more than 256 constants)
+    pub fn canonicalize_consts(&mut self) {
+        for obj in self.consts.iter_mut() {
+            if let ValueObj::Code(code) = obj {
+                code.canonicalize_consts();
+            }
+        }
+        let mut order = (0..self.consts.len()).collect::<Vec<_>>();
+        if let Some(rest) = order.get_mut(1..) {
+            rest.sort_by(|l, r| self.consts[*l].canonical_cmp(&self.consts[*r]));
+        }
+        let mut new_idx = vec![0; order.len()];
+        for (new, old) in order.iter().enumerate() {
+            new_idx[*old] = new;
+        }
+        let mut offset = 0;
+        while offset + 1 < self.code.len() {
+            let (instr, arg) = decode_instr(&self.code, offset);
+            if self.code[instr] == Opcode::LOAD_CONST as u8 {
+                let new_arg = new_idx[arg as usize];
+                if !patch_arg(&mut self.code, offset, instr, new_arg) {
+                    panic!("the const index at {instr} overflows: {new_arg}");
+                }
+            }
+            offset = instr + 2;
+        }
+        let mut consts = std::mem::take(&mut self.consts)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.consts = order
+            .into_iter()
+            .map(|i| consts[i].take().unwrap())
+            .collect();
+    }
+
+    /// The names of the exceptions raised by `RAISE_VARARGS` in this code object (best-effort).
+    ///
+    /// The raised object is traced back to the instruction that pushed it,
+    /// and its name is recorded if it is a `LOAD_GLOBAL`/`LOAD_NAME`
+    /// (for `raise ValueError(...)`, this is the load of the callee).
+    /// Re-raises, values computed across jumps and nested code objects are not considered.
+    pub fn raised_types(&self) -> Set<Str> {
+        let mut instrs = vec![];
+        let mut offset = 0;
+        while offset + 1 < self.code.len() {
+            let (instr, arg) = decode_instr(&self.code, offset);
+            instrs.push((Opcode::try_from_byte(self.code[instr]), arg));
+            offset = instr + 2;
+        }
+        let mut raised = Set::new();
+        for (i, (op, argc)) in instrs.iter().enumerate() {
+            if *op != Some(Opcode::RAISE_VARARGS) || *argc == 0 {
+                continue;
+            }
+            // the exception is pushed by the instruction at which `pushed` reaches `argc` (exc, [cause])
+            let mut pushed = 0;
+            for (op, arg) in instrs[..i].iter().rev() {
+                let effect = match op {
+                    Some(Opcode::JUMP_FORWARD | Opcode::FOR_ITER) => None,
+                    Some(op) if !op.is_absolute_jump() => op.stack_effect(*arg, false),
+                    _ => None,
+                };
+                match effect {
+                    Some(effect) => pushed += effect,
+                    None => break,
+                }
+                if pushed == *argc as i32 {
+                    if let Some(Opcode::LOAD_GLOBAL | Opcode::LOAD_NAME) = op {
+                        if let Some(name) = self.names.get(*arg as usize) {
+                            raised.insert(name.clone());
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+        raised
+    }
+
+    /// Compares the code, the constants and the other attributes in this order,
+    /// and reports the first difference (`None` if the code objects are identical).
+    /// Constants are compared in the same way as they are marshalled (e.g. `1` and `True` differ).
+    pub fn diff(&self, other: &CodeObj) -> Option<CodeDiff> {
+        let instr = |code: &[u8], offset: usize| match code.get(offset..offset + 2) {
+            Some(instr) => Some((instr[0], instr[1])),
+            None => code.get(offset).map(|op| (*op, 0)),
+        };
+        let code_len = self.code.len().max(other.code.len());
+        for offset in (0..code_len).step_by(2) {
+            let (lhs, rhs) = (instr(&self.code, offset), instr(&other.code, offset));
+            if lhs != rhs {
+                return Some(CodeDiff::Instr { offset, lhs, rhs });
+            }
+        }
+        let consts_len = self.consts.len().max(other.consts.len());
+        for idx in 0..consts_len {
+            match (self.consts.get(idx), other.consts.get(idx)) {
+                (Some(ValueObj::Code(l)), Some(ValueObj::Code(r))) => {
+                    if let Some(diff) = l.diff(r) {
+                        return Some(CodeDiff::Nested {
+                            idx,
+                            diff: Box::new(diff),
+                        });
+                    }
+                }
+                (Some(l), Some(r)) if l.marshal_eq(r) => {}
+                (lhs, rhs) => {
+                    return Some(CodeDiff::Const {
+                        idx,
+                        lhs: lhs.cloned(),
+                        rhs: rhs.cloned(),
+                    });
+                }
+            }
+        }
+        let fields = [
+            (
+                "argcount",
+                format!("{}", self.argcount),
+                format!("{}", other.argcount),
+            ),
+            (
+                "posonlyargcount",
+                format!("{}", self.posonlyargcount),
+                format!("{}", other.posonlyargcount),
+            ),
+            (
+                "kwonlyargcount",
+                format!("{}", self.kwonlyargcount),
+                format!("{}", other.kwonlyargcount),
+            ),
+            (
+                "nlocals",
+                format!("{}", self.nlocals),
+                format!("{}", other.nlocals),
+            ),
+            (
+                "stacksize",
+                format!("{}", self.stacksize),
+                format!("{}", other.stacksize),
+            ),
+            (
+                "flags",
+                format!("{:#x}", self.flags),
+                format!("{:#x}", other.flags),
+            ),
+            (
+                "names",
+                format!("{:?}", self.names),
+                format!("{:?}", other.names),
+            ),
+            (
+                "varnames",
+                format!("{:?}", self.varnames),
+                format!("{:?}", other.varnames),
+            ),
+            (
+                "freevars",
+                format!("{:?}", self.freevars),
+                format!("{:?}", other.freevars),
+            ),
+            (
+                "cellvars",
+                format!("{:?}", self.cellvars),
+                format!("{:?}", other.cellvars),
+            ),
+            (
+                "filename",
+                format!("{}", self.filename),
+                format!("{}", other.filename),
+            ),
+            ("name", format!("{}", self.name), format!("{}", other.name)),
+            (
+                "qualname",
+                format!("{}", self.qualname),
+                format!("{}", other.qualname),
+            ),
+            (
+                "firstlineno",
+                format!("{}", self.firstlineno),
+                format!("{}", other.firstlineno),
+            ),
+            (
+                "lnotab",
+                format!("{:?}", self.lnotab),
+                format!("{:?}", other.lnotab),
+            ),
+            (
+                "exceptiontable",
+                format!("{:?}", self.exceptiontable),
+                format!("{:?}", other.exceptiontable),
+            ),
+        ];
+        fields
+            .into_iter()
+            .find(|(_, lhs, rhs)| lhs != rhs)
+            .map(|(name, lhs, rhs)| CodeDiff::Field { name, lhs, rhs })
+    }
+
     fn tables_info(&self) -> String {
         let mut tables = "".to_string();
         if !self.consts.is_empty() {
             tables += &format!("Constants:\n");
         }
         for (i, obj) in self.consts.iter().enumerate() {
-            tables += &format!("   {}: {}\n", i, obj);
+            tables += &format!("   {}: {}\n", i, obj.repr());
         }
         if !self.names.is_empty() {
             tables += &format!("Names:\n");
@@ -384,29 +1890,39 @@ impl CodeObj {
         attrs
     }
 
-    fn instr_info(&self) -> String {
-        let mut lnotab_iter = self.lnotab.iter();
+    fn instr_info(&self, table: &OpcodeTable) -> String {
+        // the args of the instructions in a .pyc of another version may be out of range
+        let or_unknown = |s: Option<&Str>| s.map_or("?".to_string(), |s| s.to_string());
+        let line_starts = self.line_starts();
+        let mut line_starts = line_starts.iter().peekable();
         let mut code_iter = self.code.iter();
-        let mut idx = 0;
-        let mut line_offset = 0;
-        let mut lineno = self.firstlineno as u8;
-        let mut sdelta = lnotab_iter.next().unwrap_or(&0);
-        let mut ldelta = lnotab_iter.next().unwrap_or(&0);
+        let mut idx = 0usize;
+        // the higher bytes given by `EXTENDED_ARG`s
+        let mut ext = 0;
         let mut instrs = "".to_string();
         instrs += &format!("lnotab: {:?}\n", self.lnotab);
-        if *sdelta != 0 {
-            instrs += &format!("{}:\n", lineno);
-        }
         loop {
-            if *sdelta == line_offset {
-                line_offset = 0;
-                lineno += ldelta;
+            while let Some((_, lineno)) = line_starts.next_if(|(addr, _)| *addr <= idx) {
                 instrs += &format!("{}:\n", lineno);
-                sdelta = lnotab_iter.next().unwrap_or(&0);
-                ldelta = lnotab_iter.next().unwrap_or(&0);
             }
             if let (Some(op), Some(arg)) = (code_iter.next(), code_iter.next()) {
-                let op = Opcode::from(*op);
+                let op = match table.decode(*op) {
+                    Some(op) => op,
+                    None => {
+                        let name = table
+                            .get(*op)
+                            .map_or(format!("<{op}>"), |custom| custom.name.to_string());
+                        instrs += &format!("{:>15} {:<25}{}\n", idx, name, arg);
+                        idx += 2;
+                        continue;
+                    }
+                };
+                let arg = ext | *arg as usize;
+                ext = if op == Opcode::EXTENDED_ARG {
+                    arg << 8
+                } else {
+                    0
+                };
                 let s_op = op.to_string();
                 instrs += &format!("{:>15} {:<25}", idx, s_op);
                 match op {
@@ -428,31 +1944,35 @@ impl CodeObj {
                     | Opcode::LOAD_GLOBAL
                     | Opcode::STORE_ATTR
                     | Opcode::LOAD_ATTR
-                    | Opcode::LOAD_METHOD => {
-                        instrs += &format!("{} ({})", arg, self.names.get(*arg as usize).unwrap());
+                    | Opcode::LOAD_METHOD
+                    | Opcode::IMPORT_NAME
+                    | Opcode::IMPORT_FROM => {
+                        instrs += &format!("{} ({})", arg, or_unknown(self.names.get(arg)));
                     }
                     Opcode::STORE_DEREF | Opcode::LOAD_DEREF => {
-                        instrs +=
-                            &format!("{} ({})", arg, self.freevars.get(*arg as usize).unwrap());
+                        instrs += &format!("{} ({})", arg, or_unknown(self.freevars.get(arg)));
                     }
                     Opcode::STORE_FAST | Opcode::LOAD_FAST => {
-                        instrs +=
-                            &format!("{} ({})", arg, self.varnames.get(*arg as usize).unwrap());
+                        instrs += &format!("{} ({})", arg, or_unknown(self.varnames.get(arg)));
                     }
                     Opcode::LOAD_CONST => {
-                        instrs += &format!("{} ({})", arg, self.consts.get(*arg as usize).unwrap());
+                        let c = self
+                            .consts
+                            .get(arg)
+                            .map_or("?".to_string(), |c| c.to_string());
+                        instrs += &format!("{} ({})", arg, c);
                     }
                     Opcode::FOR_ITER => {
-                        instrs += &format!("{} (to {})", arg, idx + arg * 2 + 2);
+                        instrs += &format!("{} (to {})", arg, idx + table.jump_bytes(arg) + 2);
                     }
-                    Opcode::JUMP_FORWARD => {
-                        instrs += &format!("{} (to {})", arg, idx + arg * 2 + 2);
+                    Opcode::JUMP_FORWARD | Opcode::SETUP_WITH => {
+                        instrs += &format!("{} (to {})", arg, idx + table.jump_bytes(arg) + 2);
                     }
                     Opcode::JUMP_ABSOLUTE => {
-                        instrs += &format!("{} (to {})", arg, arg * 2);
+                        instrs += &format!("{} (to {})", arg, table.jump_bytes(arg));
                     }
                     Opcode::POP_JUMP_IF_FALSE | Opcode::POP_JUMP_IF_TRUE => {
-                        instrs += &format!("{} (to {})", arg, arg * 2);
+                        instrs += &format!("{} (to {})", arg, table.jump_bytes(arg));
                     }
                     Opcode::MAKE_FUNCTION => {
                         let flag = match arg {
@@ -467,16 +1987,19 @@ impl CodeObj {
                     | Opcode::BINARY_SUBTRACT
                     | Opcode::BINARY_MULTIPLY
                     | Opcode::BINARY_TRUE_DIVIDE => {
-                        instrs += &format!("{} ({:?})", arg, TypePair::from(*arg));
+                        // 0 (and the args of CPython) is not a type pair
+                        match u8::try_from(arg).ok().and_then(TypePair::from_u8) {
+                            Some(pair) => instrs += &format!("{} ({:?})", arg, pair),
+                            None => instrs += &format!("{}", arg),
+                        }
                     }
-                    other if other.take_arg() => {
+                    other if other.has_arg() => {
                         instrs += &format!("{}", arg);
                     }
                     _ => {}
                 }
                 instrs.push('\n');
                 idx += 2;
-                line_offset += 2;
             } else {
                 break;
             }
@@ -485,17 +2008,252 @@ impl CodeObj {
     }
 
     pub fn code_info(&self) -> String {
+        self.code_info_with(&OpcodeTable::new())
+    }
+
+    /// Same as `code_info`, but the custom opcodes in `table` are also disassembled.
+    pub fn code_info_with(&self, table: &OpcodeTable) -> String {
         let mut info = "".to_string();
         info += &format!("Disassembly of {:?}:\n", self);
         info += &self.attrs_info();
         info += &self.tables_info();
-        info += &self.instr_info();
+        info += &self.instr_info(table);
         info.push('\n');
         for cons in self.consts.iter() {
             if let ValueObj::Code(c) = cons {
-                info += &c.code_info();
+                info += &c.code_info_with(table);
             }
         }
         info
     }
+
+    /// `dis.dis`-style listing: line number, `>>` for jump targets, offset, opname, arg and what the arg refers to.
+    /// The code objects in `consts` follow, indented by their depth.
+    pub fn disassemble(&self) -> String {
+        self.disassemble_with(&OpcodeTable::new())
+    }
+
+    /// Same as `disassemble`, but the code is decoded with `table`.
+    pub fn disassemble_with(&self, table: &OpcodeTable) -> String {
+        self.disassemble_with_depth(0, table)
+    }
+
+    fn disassemble_with_depth(&self, depth: usize, table: &OpcodeTable) -> String {
+        let indent = "    ".repeat(depth);
+        let mut dis = "".to_string();
+        for line in self.listing(table).lines() {
+            if !line.is_empty() {
+                dis += &indent;
+            }
+            dis += line;
+            dis.push('\n');
+        }
+        for cons in self.consts.iter() {
+            if let ValueObj::Code(c) = cons {
+                dis += &format!("\n{indent}Disassembly of {:?}:\n", c);
+                dis += &c.disassemble_with_depth(depth + 1, table);
+            }
+        }
+        dis
+    }
+
+    /// Same format as `dis.Instruction._disassemble` of CPython 3.10
+    fn listing(&self, table: &OpcodeTable) -> String {
+        // (offset, opcode byte, arg including the `EXTENDED_ARG`s)
+        let mut instrs = vec![];
+        let mut ext = 0;
+        for (i, pair) in self.code.chunks_exact(2).enumerate() {
+            let arg = ext | pair[1] as usize;
+            ext = if pair[0] == Opcode::EXTENDED_ARG as u8 {
+                arg << 8
+            } else {
+                0
+            };
+            instrs.push((i * 2, pair[0], arg));
+        }
+        let jump_target = |offset: usize, op: Opcode, arg: usize| {
+            if op.is_absolute_jump() {
+                Some(table.jump_bytes(arg))
+            } else if op.is_relative_jump() {
+                Some(offset + 2 + table.jump_bytes(arg))
+            } else {
+                None
+            }
+        };
+        let labels = instrs
+            .iter()
+            .filter_map(|(offset, byte, arg)| jump_target(*offset, table.decode(*byte)?, *arg))
+            .collect::<Set<_>>();
+        let line_starts = self.line_starts();
+        let max_lineno = line_starts.iter().map(|(_, l)| *l).max().unwrap_or(0);
+        let lineno_width = if max_lineno >= 1000 {
+            max_lineno.to_string().len()
+        } else {
+            3
+        };
+        let mut line_starts = line_starts.iter().peekable();
+        let mut listing = "".to_string();
+        for (offset, byte, arg) in instrs {
+            let mut lineno = None;
+            while let Some((_, l)) = line_starts.next_if(|(addr, _)| *addr <= offset) {
+                lineno = Some(*l);
+            }
+            let lineno = if let Some(lineno) = lineno {
+                if offset > 0 {
+                    listing.push('\n');
+                }
+                format!("{lineno:>lineno_width$}")
+            } else {
+                " ".repeat(lineno_width)
+            };
+            let label = if labels.contains(&offset) { ">>" } else { "  " };
+            let op = table.decode(byte);
+            let name = match (op, table.get(byte)) {
+                (Some(op), _) => op.to_string(),
+                (None, Some(custom)) => custom.name.to_string(),
+                (None, None) => format!("<{byte}>"),
+            };
+            let mut line = format!("{lineno}     {label} {offset:>4} {name:<20}");
+            if op.map_or(byte >= HAVE_ARGUMENT, |op| op.has_arg()) {
+                line += &format!(" {arg:>5}");
+                let repr = op.map_or("".to_string(), |op| self.arg_repr(op, offset, arg, table));
+                if !repr.is_empty() {
+                    line += &format!(" ({repr})");
+                }
+            }
+            listing += line.trim_end();
+            listing.push('\n');
+        }
+        listing
+    }
+
+    /// what the arg of the instruction refers to (e.g. the name of the variable)
+    fn arg_repr(&self, op: Opcode, offset: usize, arg: usize, table: &OpcodeTable) -> String {
+        let or_unknown = |s: Option<&Str>| s.map_or("?".to_string(), |s| s.to_string());
+        match op {
+            Opcode::LOAD_CONST => self.consts.get(arg).map_or("?".to_string(), |c| c.repr()),
+            Opcode::STORE_NAME
+            | Opcode::DELETE_NAME
+            | Opcode::STORE_ATTR
+            | Opcode::STORE_GLOBAL
+            | Opcode::LOAD_NAME
+            | Opcode::LOAD_ATTR
+            | Opcode::IMPORT_NAME
+            | Opcode::IMPORT_FROM
+            | Opcode::LOAD_GLOBAL
+            | Opcode::LOAD_METHOD => or_unknown(self.names.get(arg)),
+            Opcode::LOAD_FAST | Opcode::STORE_FAST | Opcode::DELETE_FAST => {
+                or_unknown(self.varnames.get(arg))
+            }
+            Opcode::LOAD_CLOSURE | Opcode::LOAD_DEREF | Opcode::STORE_DEREF => {
+                or_unknown(self.cellvars.iter().chain(self.freevars.iter()).nth(arg))
+            }
+            Opcode::COMPARE_OP => ["<", "<=", "==", "!=", ">", ">="]
+                .get(arg)
+                .unwrap_or(&"?")
+                .to_string(),
+            Opcode::MAKE_FUNCTION => ["defaults", "kwdefaults", "annotations", "closure"]
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| arg & (1 << i) != 0)
+                .map(|(_, flag)| *flag)
+                .collect::<Vec<_>>()
+                .join(", "),
+            Opcode::FORMAT_VALUE => {
+                let conv = ["", "str", "repr", "ascii"][arg & 3];
+                match (conv, arg & 4 != 0) {
+                    ("", true) => "with format".to_string(),
+                    (conv, true) => format!("{conv}, with format"),
+                    (conv, false) => conv.to_string(),
+                }
+            }
+            _ if op.is_absolute_jump() => format!("to {}", table.jump_bytes(arg)),
+            _ if op.is_relative_jump() => format!("to {}", offset + 2 + table.jump_bytes(arg)),
+            _ => "".to_string(),
+        }
+    }
+}
+
+/// Builds a `CodeObj` instruction by instruction, without going through the compiler (e.g. in tests).
+///
+/// ```
+/// use erg_common::codeobj::CodeObjBuilder;
+/// use erg_common::opcode::Opcode;
+/// use erg_common::value::ValueObj;
+///
+/// // x = 1
+/// let code = CodeObjBuilder::new("<module>")
+///     .const_(1)
+///     .const_(ValueObj::None)
+///     .name("x")
+///     .instr(Opcode::LOAD_CONST, 0)
+///     .instr(Opcode::STORE_NAME, 0)
+///     .instr(Opcode::LOAD_CONST, 1)
+///     .instr(Opcode::RETURN_VALUE, 0)
+///     .build();
+/// assert_eq!(code.stacksize, 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CodeObjBuilder {
+    code: CodeObj,
+}
+
+impl CodeObjBuilder {
+    pub fn new<S: Into<Str>>(name: S) -> Self {
+        Self {
+            code: CodeObj::empty(vec![], "<string>", name, 1),
+        }
+    }
+
+    /// `arg`s larger than 255 are prefixed with `EXTENDED_ARG`s
+    pub fn instr(mut self, op: Opcode, arg: u32) -> Self {
+        for shift in [24, 16, 8] {
+            if arg >> shift != 0 {
+                self.code.code.push(Opcode::EXTENDED_ARG as u8);
+                self.code.code.push((arg >> shift) as u8);
+            }
+        }
+        self.code.code.push(op as u8);
+        self.code.code.push(arg as u8);
+        self
+    }
+
+    /// appends to `consts` (the index is the number of constants added before)
+    pub fn const_<C: Into<ValueObj>>(mut self, obj: C) -> Self {
+        self.code.consts.push(obj.into());
+        self
+    }
+
+    /// appends to `names`
+    pub fn name<S: Into<Str>>(mut self, name: S) -> Self {
+        self.code.names.push(name.into());
+        self
+    }
+
+    /// appends to `varnames` (parameters should be given by `params`)
+    pub fn varname<S: Into<Str>>(mut self, name: S) -> Self {
+        self.code.varnames.push(name.into());
+        self
+    }
+
+    pub fn params<S: Into<Str>>(mut self, params: Vec<S>) -> Self {
+        let params = params.into_iter().map(Into::into).collect::<Vec<_>>();
+        self.code.argcount = params.len() as u32;
+        self.code.varnames.splice(0..0, params);
+        self
+    }
+
+    pub fn filename<S: Into<Str>>(mut self, filename: S) -> Self {
+        self.code.filename = filename.into();
+        self
+    }
+
+    /// Sets `nlocals` and `stacksize` (if it can be computed) from the tables and the code.
+    pub fn build(mut self) -> CodeObj {
+        self.code.nlocals = self.code.varnames.len() as u32;
+        if let Ok(stacksize) = compute_stacksize(&self.code.code, &self.code.consts) {
+            self.code.stacksize = stacksize;
+        }
+        self.code
+    }
 }
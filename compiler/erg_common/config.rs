@@ -4,13 +4,16 @@
 use std::env;
 use std::env::consts::{ARCH, OS};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{stdin as std_stdin, BufRead, BufReader, IsTerminal};
+use std::path::Path;
 use std::process;
+use std::time::SystemTime;
 
 use crate::lazy::Lazy;
+use crate::serialize::get_python_version;
 use crate::stdin;
 use crate::Str;
-use crate::{power_assert, read_file};
+use crate::{get_hash, power_assert, read_file};
 
 pub const SEMVER: &str = env!("CARGO_PKG_VERSION");
 pub const GIT_HASH_SHORT: &str = env!("GIT_HASH_SHORT");
@@ -38,6 +41,8 @@ impl Input {
         matches!(self, Input::REPL)
     }
 
+    /// The name embedded in code objects and error messages.
+    /// Inputs that are not files get a pseudo name like `<stdin>`.
     pub fn enclosed_name(&self) -> &str {
         match self {
             Self::File(filename) => &filename[..],
@@ -47,6 +52,30 @@ impl Input {
         }
     }
 
+    /// The actual path of the input file.
+    /// Unlike `enclosed_name`, this returns `None` for inputs not backed by a file (REPL, pipe, string, dummy).
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::File(filename) => Some(Path::new(&filename[..])),
+            Self::REPL | Self::Pipe(_) | Self::Str(_) | Self::Dummy => None,
+        }
+    }
+
+    /// The last modification time of the input file.
+    /// returns `None` if the input is not a file or its metadata cannot be read.
+    pub fn mtime(&self) -> Option<SystemTime> {
+        let metadata = std::fs::metadata(self.path()?).ok()?;
+        metadata.modified().ok()
+    }
+
+    /// The hash of the content of the input file (for detecting changes, not cryptographically secure).
+    /// returns `None` if the input is not a file or it cannot be read.
+    pub fn source_hash(&self) -> Option<u64> {
+        let file = File::open(self.path()?).ok()?;
+        let src = read_file(file).ok()?;
+        Some(get_hash(&src) as u64)
+    }
+
     /// ファイルに書き出すとき使う
     pub fn filename(&self) -> &str {
         match self {
@@ -101,7 +130,8 @@ impl Input {
             Self::Pipe(s) | Self::Str(s) => s.split('\n').collect::<Vec<_>>()
                 [ln_begin - 1..=ln_end - 1]
                 .iter()
-                .map(|s| Str::rc(*s))
+                .copied()
+                .map(Str::rc)
                 .collect(),
             Self::REPL => stdin::reread_lines(ln_begin, ln_end),
             Self::Dummy => panic!("cannot read lines from a dummy file"),
@@ -118,31 +148,56 @@ impl Input {
     }
 }
 
+/// The fields left `None` are unset and take the default values (see the accessors of the same names),
+/// so that `merge` can tell them from the fields set explicitly.
 #[derive(Debug, Clone)]
 pub struct ErgConfig {
-    /// options: lex | parse | compile | exec
-    pub mode: &'static str,
+    /// options: lex | parse | compile | exec (default)
+    pub mode: Option<&'static str>,
     /// optimization level.
     /// * 0: no optimization
     /// * 1 (default): e.g. constant folding, dead code elimination
     /// * 2: e.g. static dispatching, inlining, peephole
     /// * 3: e.g. JIT compiling
-    pub opt_level: u8,
-    pub dump_as_pyc: bool,
+    pub opt_level: Option<u8>,
+    pub dump_as_pyc: Option<bool>,
+    /// the magic number of the target Python version (e.g. 3425 for 3.9)
     pub python_ver: Option<u32>,
+    /// generate bytecode for the Erg VM instead of CPython
+    /// (the Erg-specific instructions such as `ERG_BINARY_ADD_INT` are used)
+    pub erg_vm: Option<bool>,
+    /// `Input::REPL` if no input is given
     pub input: Input,
-    pub module: &'static str,
+    pub module: Option<&'static str>,
     /// verbosity level for system messages.
     /// * 0: display errors
     /// * 1: display errors and warns
     /// * 2 (default): display errors, warnings and hints
-    pub verbose: u8,
+    pub verbose: Option<u8>,
+    /// emit a `NOP` for each source line that has no instruction (for coverage tools)
+    pub coverage: Option<bool>,
+    /// wrap top-level statements other than definitions in `if __name__ == "__main__"`
+    pub main_guard: Option<bool>,
+    /// remove `assert` calls (like Python's `-O`)
+    pub optimize_asserts: Option<bool>,
 }
 
 impl Default for ErgConfig {
     #[inline]
     fn default() -> Self {
-        Self::new("exec", 1, false, None, Input::REPL, "<module>", 2)
+        Self {
+            mode: None,
+            opt_level: None,
+            dump_as_pyc: None,
+            python_ver: None,
+            erg_vm: None,
+            input: Input::REPL,
+            module: None,
+            verbose: None,
+            coverage: None,
+            main_guard: None,
+            optimize_asserts: None,
+        }
     }
 }
 
@@ -157,13 +212,17 @@ impl ErgConfig {
         verbose: u8,
     ) -> Self {
         Self {
-            mode,
-            opt_level,
-            dump_as_pyc,
+            mode: Some(mode),
+            opt_level: Some(opt_level),
+            dump_as_pyc: Some(dump_as_pyc),
             python_ver,
+            erg_vm: None,
             input,
-            module,
-            verbose,
+            module: Some(module),
+            verbose: Some(verbose),
+            coverage: None,
+            main_guard: None,
+            optimize_asserts: None,
         }
     }
 
@@ -173,6 +232,85 @@ impl ErgConfig {
         self.clone()
     }
 
+    #[inline]
+    pub fn mode(&self) -> &'static str {
+        self.mode.unwrap_or("exec")
+    }
+
+    #[inline]
+    pub fn opt_level(&self) -> u8 {
+        self.opt_level.unwrap_or(1)
+    }
+
+    #[inline]
+    pub fn dump_as_pyc(&self) -> bool {
+        self.dump_as_pyc.unwrap_or(false)
+    }
+
+    #[inline]
+    pub fn erg_vm(&self) -> bool {
+        self.erg_vm.unwrap_or(false)
+    }
+
+    #[inline]
+    pub fn module(&self) -> &'static str {
+        self.module.unwrap_or("<module>")
+    }
+
+    #[inline]
+    pub fn verbose(&self) -> u8 {
+        self.verbose.unwrap_or(2)
+    }
+
+    #[inline]
+    pub fn coverage(&self) -> bool {
+        self.coverage.unwrap_or(false)
+    }
+
+    #[inline]
+    pub fn main_guard(&self) -> bool {
+        self.main_guard.unwrap_or(false)
+    }
+
+    #[inline]
+    pub fn optimize_asserts(&self) -> bool {
+        self.optimize_asserts.unwrap_or(false)
+    }
+
+    /// Combines two configs. The fields set in `self` take precedence,
+    /// and the unset fields (`None`, or `Input::REPL` for `input`) are taken from `lower_priority`.
+    pub fn merge(self, lower_priority: ErgConfig) -> ErgConfig {
+        Self {
+            mode: self.mode.or(lower_priority.mode),
+            opt_level: self.opt_level.or(lower_priority.opt_level),
+            dump_as_pyc: self.dump_as_pyc.or(lower_priority.dump_as_pyc),
+            python_ver: self.python_ver.or(lower_priority.python_ver),
+            erg_vm: self.erg_vm.or(lower_priority.erg_vm),
+            input: if self.input.is_repl() {
+                lower_priority.input
+            } else {
+                self.input
+            },
+            module: self.module.or(lower_priority.module),
+            verbose: self.verbose.or(lower_priority.verbose),
+            coverage: self.coverage.or(lower_priority.coverage),
+            main_guard: self.main_guard.or(lower_priority.main_guard),
+            optimize_asserts: self.optimize_asserts.or(lower_priority.optimize_asserts),
+        }
+    }
+
+    /// reads all of stdin as the input (the process exits if it cannot be read)
+    fn read_pipe() -> Input {
+        match stdin::read_all() {
+            Ok(src) => Input::Pipe(src),
+            Err(e) => {
+                let code = e.raw_os_error().unwrap_or(1);
+                println!("cannot read '<stdin>': [Errno {code}] {e}");
+                process::exit(code);
+            }
+        }
+    }
+
     pub fn parse() -> Self {
         let mut args = env::args();
         args.next(); // "ergc"
@@ -183,28 +321,49 @@ impl ErgConfig {
                 "-c" => {
                     cfg.input = Input::Str(Str::from(args.next().unwrap()));
                 }
+                "--coverage" => {
+                    cfg.coverage = Some(true);
+                }
                 "--dump-as-pyc" => {
-                    cfg.dump_as_pyc = true;
+                    cfg.dump_as_pyc = Some(true);
+                }
+                "--erg-vm" => {
+                    cfg.erg_vm = Some(true);
+                }
+                "-" => {
+                    cfg.input = Self::read_pipe();
+                    break;
                 }
                 "-?" | "-h" | "--help" => {
                     println!("erg [option] ... [-c cmd | -m mod | file | -] [arg] ...");
                     // TODO:
                     process::exit(0);
                 }
+                "--main-guard" => {
+                    cfg.main_guard = Some(true);
+                }
                 "-m" => {
-                    cfg.module = Box::leak(args.next().unwrap().into_boxed_str());
+                    cfg.module = Some(Box::leak(args.next().unwrap().into_boxed_str()));
                 }
                 "--mode" => {
-                    cfg.mode = Box::leak(args.next().unwrap().into_boxed_str());
+                    cfg.mode = Some(Box::leak(args.next().unwrap().into_boxed_str()));
+                }
+                "-O" => {
+                    cfg.optimize_asserts = Some(true);
                 }
                 "-o" | "--opt-level" | "--optimization-level" => {
-                    cfg.opt_level = args.next().unwrap().parse::<u8>().unwrap();
+                    cfg.opt_level = Some(args.next().unwrap().parse::<u8>().unwrap());
                 }
                 "-p" | "--py-ver" | "--python-version" => {
-                    cfg.python_ver = Some(args.next().unwrap().parse::<u32>().unwrap());
+                    let python_ver = args.next().unwrap().parse::<u32>().unwrap();
+                    if get_python_version(python_ver).is_none() {
+                        println!("unsupported Python version (magic number: {python_ver})");
+                        process::exit(1);
+                    }
+                    cfg.python_ver = Some(python_ver);
                 }
                 "--verbose\n" => {
-                    cfg.verbose = args.next().unwrap().parse::<u8>().unwrap();
+                    cfg.verbose = Some(args.next().unwrap().parse::<u8>().unwrap());
                 }
                 "-V" | "--version" => {
                     println!("Erg {}", env!("CARGO_PKG_VERSION"));
@@ -219,6 +378,10 @@ impl ErgConfig {
                 }
             }
         }
+        // `cat foo.er | erg` is compiled as a module, not a REPL session
+        if cfg.input.is_repl() && !std_stdin().is_terminal() {
+            cfg.input = Self::read_pipe();
+        }
         cfg
     }
 }
@@ -6,7 +6,10 @@ use crate::cache::Cache;
 use crate::codeobj::CodeObj;
 use crate::config::{ErgConfig, Input};
 use crate::error::{ErrorCore, ErrorKind, Location};
-use crate::serialize::DataTypePrefix;
+use crate::long::Long;
+use crate::opcode::OpcodeTable;
+use crate::serialize::{get_python_version, DataTypePrefix, FLAG_REF};
+use crate::set::Set;
 use crate::traits::HasType;
 use crate::ty::{TyParam, Type};
 use crate::value::ValueObj;
@@ -64,6 +67,17 @@ impl DeserializeError {
         )
     }
 
+    pub fn invalid_magic_number_error(bytes: &[u8; 4]) -> Self {
+        Self::new(
+            0,
+            fn_name!(),
+            switch_lang!(
+                format!("invalid magic number: {bytes:02X?} (not a .pyc file?)"),
+                format!("不正なマジックナンバーです: {bytes:02X?} (.pycファイルではありません?)")
+            ),
+        )
+    }
+
     pub fn type_error(expect: &Type, found: &Type) -> Self {
         Self::new(
             0,
@@ -89,6 +103,8 @@ pub struct Deserializer {
     str_cache: Cache<str>,
     arr_cache: Cache<[ValueObj]>,
     dict_cache: Cache<[(ValueObj, ValueObj)]>,
+    /// the objects read with `FLAG_REF` (referred to by `Ref`)
+    refs: Vec<ValueObj>,
 }
 
 impl Deserializer {
@@ -97,6 +113,7 @@ impl Deserializer {
             str_cache: Cache::new(),
             arr_cache: Cache::new(),
             dict_cache: Cache::new(),
+            refs: vec![],
         }
     }
 
@@ -107,9 +124,11 @@ impl Deserializer {
             eprintln!("{:?} is not a filename", cfg.input);
             process::exit(1);
         };
-        let codeobj = CodeObj::from_pyc(&filename[..])
+        let (codeobj, python_ver) = CodeObj::from_pyc_with_version(&filename[..])
             .unwrap_or_else(|_| panic!("failed to deserialize {filename}"));
-        println!("{}", codeobj.code_info());
+        let table =
+            get_python_version(python_ver).map_or_else(OpcodeTable::new, OpcodeTable::for_version);
+        println!("{}", codeobj.code_info_with(&table));
     }
 
     fn get_cached_str(&mut self, s: &str) -> ValueObj {
@@ -120,7 +139,6 @@ impl Deserializer {
         ValueObj::Array(self.arr_cache.get(arr))
     }
 
-    /// TODO: 使わない？
     pub fn get_cached_dict(&mut self, dict: &[(ValueObj, ValueObj)]) -> ValueObj {
         ValueObj::Dict(self.dict_cache.get(dict))
     }
@@ -141,33 +159,140 @@ impl Deserializer {
         u32::from_le_bytes(Self::consume::<4>(v))
     }
 
+    pub fn deserialize_f64(v: &mut Vec<u8>) -> DeserializeResult<f64> {
+        if v.len() < 8 {
+            return Err(DeserializeError::file_broken_error());
+        }
+        Ok(f64::from_le_bytes(Self::consume::<8>(v)))
+    }
+
+    /// the real part and the imaginary part of a BinComplex (without the prefix)
+    pub fn deserialize_complex(v: &mut Vec<u8>) -> DeserializeResult<(f64, f64)> {
+        let re = Self::deserialize_f64(v)?;
+        let im = Self::deserialize_f64(v)?;
+        Ok((re, im))
+    }
+
     pub fn deserialize_const(
         &mut self,
         v: &mut Vec<u8>,
         python_ver: u32,
     ) -> DeserializeResult<ValueObj> {
-        match DataTypePrefix::from(v.remove(0)) {
+        if v.is_empty() {
+            return Err(DeserializeError::file_broken_error());
+        }
+        let byte = v.remove(0);
+        if byte & FLAG_REF == 0 {
+            return self.deserialize_obj(DataTypePrefix::from(byte), v, python_ver);
+        }
+        // the index is reserved before the contents are read (same as CPython)
+        let idx = self.refs.len();
+        self.refs.push(ValueObj::None);
+        let obj = self.deserialize_obj(DataTypePrefix::from(byte & !FLAG_REF), v, python_ver)?;
+        self.refs[idx] = obj.clone();
+        Ok(obj)
+    }
+
+    fn deserialize_obj(
+        &mut self,
+        prefix: DataTypePrefix,
+        v: &mut Vec<u8>,
+        python_ver: u32,
+    ) -> DeserializeResult<ValueObj> {
+        match prefix {
+            DataTypePrefix::Ref => {
+                if v.len() < 4 {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                let idx = Self::deserialize_u32(v) as usize;
+                self.refs
+                    .get(idx)
+                    .cloned()
+                    .ok_or_else(DeserializeError::file_broken_error)
+            }
             DataTypePrefix::Int32 => {
+                if v.len() < 4 {
+                    return Err(DeserializeError::file_broken_error());
+                }
                 let bytes = Self::consume::<4>(v);
                 Ok(ValueObj::Int(i32::from_le_bytes(bytes)))
             }
-            DataTypePrefix::BinFloat => {
-                let bytes = Self::consume::<8>(v);
-                Ok(ValueObj::Float(f64::from_le_bytes(bytes)))
+            DataTypePrefix::Long => {
+                // the sign of the length is that of the value
+                if v.len() < 4 {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                let len = i32::from_le_bytes(Self::consume::<4>(v));
+                let n_digits = len.unsigned_abs() as usize;
+                if v.len() < n_digits * 2 {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                let digits = v
+                    .drain(..n_digits * 2)
+                    .collect::<Vec<_>>()
+                    .chunks(2)
+                    .map(|digit| u16::from_le_bytes([digit[0], digit[1]]))
+                    .collect();
+                Ok(ValueObj::from(Long::from_digits(len < 0, digits)))
+            }
+            DataTypePrefix::BinFloat => Ok(ValueObj::Float(Self::deserialize_f64(v)?)),
+            DataTypePrefix::BinComplex => {
+                let (re, im) = Self::deserialize_complex(v)?;
+                Ok(ValueObj::complex(re, im))
+            }
+            // written by marshal version 0 (as the `repr` of the float)
+            DataTypePrefix::Float => {
+                if v.is_empty() {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                let len = v.remove(0) as usize;
+                if v.len() < len {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                let repr = String::from_utf8(v.drain(..len).collect())?;
+                repr.parse::<f64>()
+                    .map(ValueObj::Float)
+                    .map_err(|_| DeserializeError::file_broken_error())
             }
             DataTypePrefix::ShortAscii | DataTypePrefix::ShortAsciiInterned => {
-                let len = v.remove(0);
-                let bytes = v.drain(..len as usize).collect();
+                if v.is_empty() {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                let len = v.remove(0) as usize;
+                if v.len() < len {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                let bytes = v.drain(..len).collect();
                 Ok(self.get_cached_str(&String::from_utf8(bytes)?))
             }
-            DataTypePrefix::Str | DataTypePrefix::Unicode => {
-                let len = Self::deserialize_u32(v);
-                let bytes = v.drain(..len as usize).collect();
+            // `bytes` (TYPE_STRING)
+            DataTypePrefix::Str => {
+                if v.len() < 4 {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                let len = Self::deserialize_u32(v) as usize;
+                if v.len() < len {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                Ok(ValueObj::Bytes(v.drain(..len).collect()))
+            }
+            DataTypePrefix::Ascii | DataTypePrefix::AsciiInterned | DataTypePrefix::Unicode => {
+                if v.len() < 4 {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                let len = Self::deserialize_u32(v) as usize;
+                if v.len() < len {
+                    return Err(DeserializeError::file_broken_error());
+                }
+                let bytes = v.drain(..len).collect();
                 Ok(self.get_cached_str(&String::from_utf8(bytes)?))
             }
             DataTypePrefix::True => Ok(ValueObj::True),
             DataTypePrefix::False => Ok(ValueObj::False),
             DataTypePrefix::SmallTuple => {
+                if v.is_empty() {
+                    return Err(DeserializeError::file_broken_error());
+                }
                 let len = v.remove(0);
                 let mut arr = Vec::with_capacity(len as usize);
                 for _ in 0..len {
@@ -175,55 +300,37 @@ impl Deserializer {
                 }
                 Ok(self.get_cached_arr(&arr))
             }
-            DataTypePrefix::Tuple => {
-                let len = Self::deserialize_u32(v);
-                let mut arr = Vec::with_capacity(len as usize);
-                for _ in 0..len {
-                    arr.push(self.deserialize_const(v, python_ver)?);
-                }
+            DataTypePrefix::Tuple | DataTypePrefix::List => {
+                let arr = self.deserialize_elems(v, python_ver)?;
                 Ok(self.get_cached_arr(&arr))
             }
-            DataTypePrefix::Code => {
-                let argcount = Self::deserialize_u32(v);
-                let posonlyargcount = if python_ver >= 3413 {
-                    Self::deserialize_u32(v)
-                } else {
-                    0
-                };
-                let kwonlyargcount = Self::deserialize_u32(v);
-                let nlocals = Self::deserialize_u32(v);
-                let stacksize = Self::deserialize_u32(v);
-                let flags = Self::deserialize_u32(v);
-                let code = self.deserialize_bytes(v)?;
-                let consts = self.deserialize_const_vec(v, python_ver)?;
-                let names = self.deserialize_str_vec(v, python_ver)?;
-                let varnames = self.deserialize_str_vec(v, python_ver)?;
-                let freevars = self.deserialize_str_vec(v, python_ver)?;
-                let cellvars = self.deserialize_str_vec(v, python_ver)?;
-                let filename = self.deserialize_str(v, python_ver)?;
-                let name = self.deserialize_str(v, python_ver)?;
-                let firstlineno = Self::deserialize_u32(v);
-                let lnotab = self.deserialize_bytes(v)?;
-                Ok(ValueObj::from(CodeObj::new(
-                    argcount,
-                    posonlyargcount,
-                    kwonlyargcount,
-                    nlocals,
-                    stacksize,
-                    flags,
-                    code,
-                    consts,
-                    names,
-                    varnames,
-                    freevars,
-                    cellvars,
-                    filename,
-                    name,
-                    firstlineno,
-                    lnotab,
-                )))
+            DataTypePrefix::Dict => {
+                let mut dict = vec![];
+                loop {
+                    match v.first().copied().map(DataTypePrefix::from) {
+                        Some(DataTypePrefix::Null) => {
+                            v.remove(0);
+                            break;
+                        }
+                        Some(_) => {
+                            let key = self.deserialize_const(v, python_ver)?;
+                            let value = self.deserialize_const(v, python_ver)?;
+                            dict.push((key, value));
+                        }
+                        None => return Err(DeserializeError::file_broken_error()),
+                    }
+                }
+                Ok(self.get_cached_dict(&dict))
+            }
+            DataTypePrefix::Set => Ok(ValueObj::Set(self.deserialize_set(v, python_ver)?)),
+            DataTypePrefix::FrozenSet => {
+                Ok(ValueObj::FrozenSet(self.deserialize_set(v, python_ver)?))
             }
+            DataTypePrefix::Code => Ok(ValueObj::from(CodeObj::from_bytes_with(
+                self, v, python_ver,
+            )?)),
             DataTypePrefix::None => Ok(ValueObj::None),
+            DataTypePrefix::Ellipsis => Ok(ValueObj::Ellipsis),
             other => Err(DeserializeError::new(
                 0,
                 fn_name!(),
@@ -235,6 +342,32 @@ impl Deserializer {
         }
     }
 
+    /// the length (u32) and the elements of a Tuple, List, Set or FrozenSet (without the prefix)
+    pub fn deserialize_elems(
+        &mut self,
+        v: &mut Vec<u8>,
+        python_ver: u32,
+    ) -> DeserializeResult<Vec<ValueObj>> {
+        if v.len() < 4 {
+            return Err(DeserializeError::file_broken_error());
+        }
+        let len = Self::deserialize_u32(v);
+        let mut elems = vec![];
+        for _ in 0..len {
+            elems.push(self.deserialize_const(v, python_ver)?);
+        }
+        Ok(elems)
+    }
+
+    /// the elements of a Set or FrozenSet (without the prefix)
+    pub fn deserialize_set(
+        &mut self,
+        v: &mut Vec<u8>,
+        python_ver: u32,
+    ) -> DeserializeResult<Set<ValueObj>> {
+        Ok(self.deserialize_elems(v, python_ver)?.into())
+    }
+
     pub fn deserialize_const_vec(
         &mut self,
         v: &mut Vec<u8>,
@@ -295,15 +428,19 @@ impl Deserializer {
         }
     }
 
-    pub fn deserialize_bytes(&self, v: &mut Vec<u8>) -> DeserializeResult<Vec<u8>> {
-        if DataTypePrefix::from(v.remove(0)) != DataTypePrefix::Str {
-            return Err(DeserializeError::new(
+    /// 3.11~ marshals the bytes of a code object with `FLAG_REF` (and may refer to them with `Ref`)
+    pub fn deserialize_bytes(
+        &mut self,
+        v: &mut Vec<u8>,
+        python_ver: u32,
+    ) -> DeserializeResult<Vec<u8>> {
+        match self.deserialize_const(v, python_ver)? {
+            ValueObj::Bytes(bytes) => Ok(bytes),
+            _ => Err(DeserializeError::new(
                 0,
                 fn_name!(),
                 switch_lang!("failed to load bytes", "バイト列の読み込みに失敗しました"),
-            ));
+            )),
         }
-        let len = Self::deserialize_u32(v);
-        Ok(v.drain(0..len as usize).collect())
     }
 }
@@ -14,6 +14,7 @@ pub mod fxhash;
 pub mod lazy;
 pub mod lazy_buffer;
 pub mod levenshtein;
+pub mod long;
 pub mod macros;
 pub mod opcode;
 pub mod python_util;
@@ -0,0 +1,217 @@
+//! defines `Long` (an arbitrary-precision integer).
+//!
+//! 多倍長整数`Long`を定義する
+use std::cmp::Ordering;
+use std::fmt;
+
+/// An arbitrary-precision integer, represented in the same way as `PyLongObject` of CPython
+/// (the sign and the absolute value in 15-bit digits, from the least significant one).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Long {
+    negative: bool,
+    /// no trailing zeros (zero has no digits)
+    digits: Vec<u16>,
+}
+
+impl fmt::Display for Long {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.digits.is_empty() {
+            return write!(f, "0");
+        }
+        // divides by 10^4 repeatedly
+        let mut rest = self.digits.clone();
+        let mut chunks = vec![];
+        while !rest.is_empty() {
+            chunks.push(Self::divmod_small(&mut rest, 10000));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.into_iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{chunk:04}")?;
+        }
+        Ok(())
+    }
+}
+
+/// numerical order
+impl Ord for Long {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let abs_cmp = || {
+            self.digits
+                .len()
+                .cmp(&other.digits.len())
+                .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+        };
+        match (self.negative, other.negative) {
+            (false, false) => abs_cmp(),
+            (true, true) => abs_cmp().reverse(),
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for Long {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<u128> for Long {
+    fn from(n: u128) -> Self {
+        let mut digits = vec![];
+        let mut rest = n;
+        while rest > 0 {
+            digits.push((rest & Self::MASK as u128) as u16);
+            rest >>= Self::SHIFT;
+        }
+        Self {
+            negative: false,
+            digits,
+        }
+    }
+}
+
+impl From<u64> for Long {
+    fn from(n: u64) -> Self {
+        Self::from(n as u128)
+    }
+}
+
+impl From<i128> for Long {
+    fn from(i: i128) -> Self {
+        let mut long = Self::from(i.unsigned_abs());
+        long.negative = i < 0;
+        long
+    }
+}
+
+impl From<i64> for Long {
+    fn from(i: i64) -> Self {
+        Self::from(i as i128)
+    }
+}
+
+impl Long {
+    /// the number of bits of a digit
+    pub const SHIFT: u32 = 15;
+    const MASK: u64 = (1 << Self::SHIFT) - 1;
+
+    /// `digits` are 15-bit digits from the least significant one
+    pub fn from_digits(negative: bool, mut digits: Vec<u16>) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        Self {
+            negative: negative && !digits.is_empty(),
+            digits,
+        }
+    }
+
+    /// the integral part of `f` (`None` if `f` is NaN or infinite)
+    pub fn from_f64(f: f64) -> Option<Self> {
+        if !f.is_finite() {
+            return None;
+        }
+        let abs = f.trunc().abs();
+        if abs < u64::MAX as f64 {
+            let long = Self::from(abs as u64);
+            return Some(Self::from_digits(f < 0.0, long.digits));
+        }
+        // abs = mantissa * 2^exp (a float this large has no fractional part)
+        let bits = abs.to_bits();
+        let exp = (bits >> 52) as usize - 1075;
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let mut digits = vec![0; exp / Self::SHIFT as usize];
+        let mut rest = (mantissa as u128) << (exp % Self::SHIFT as usize);
+        while rest > 0 {
+            digits.push((rest & Self::MASK as u128) as u16);
+            rest >>= Self::SHIFT;
+        }
+        Some(Self::from_digits(f < 0.0, digits))
+    }
+
+    /// parses a decimal literal (`_` is allowed as a separator)
+    pub fn parse(s: &str) -> Option<Self> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        if s.is_empty() || s.starts_with('_') {
+            return None;
+        }
+        let mut digits = vec![];
+        for c in s.chars().filter(|c| *c != '_') {
+            let d = c.to_digit(10)?;
+            // digits = digits * 10 + d
+            let mut carry = d;
+            for digit in digits.iter_mut() {
+                let n = *digit as u32 * 10 + carry;
+                *digit = (n & Self::MASK as u32) as u16;
+                carry = n >> Self::SHIFT;
+            }
+            if carry > 0 {
+                digits.push(carry as u16);
+            }
+        }
+        Some(Self::from_digits(negative, digits))
+    }
+
+    /// divides `digits` by `divisor` in place, returns the remainder
+    fn divmod_small(digits: &mut Vec<u16>, divisor: u32) -> u32 {
+        let mut rem = 0;
+        for digit in digits.iter_mut().rev() {
+            let n = (rem << Self::SHIFT) | *digit as u32;
+            *digit = (n / divisor) as u16;
+            rem = n % divisor;
+        }
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        rem
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn digits(&self) -> &[u16] {
+        &self.digits
+    }
+
+    /// returns `None` if the absolute value does not fit in `u128`
+    fn abs_to_u128(&self) -> Option<u128> {
+        let mut n = 0u128;
+        for digit in self.digits.iter().rev() {
+            if n.leading_zeros() < Self::SHIFT {
+                return None;
+            }
+            n = (n << Self::SHIFT) | *digit as u128;
+        }
+        Some(n)
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.negative {
+            None
+        } else {
+            u64::try_from(self.abs_to_u128()?).ok()
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        i64::try_from(self.to_i128()?).ok()
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        let abs = self.abs_to_u128()?;
+        if self.negative {
+            0i128.checked_sub_unsigned(abs)
+        } else {
+            i128::try_from(abs).ok()
+        }
+    }
+}
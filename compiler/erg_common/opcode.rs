@@ -5,7 +5,26 @@
 #![allow(dead_code)]
 #![allow(non_camel_case_types)]
 
+use std::fmt;
+
+use crate::dict::Dict;
 use crate::impl_display_from_debug;
+use crate::python_util::PythonVersion;
+use crate::ty::TypePair;
+use crate::Str;
+
+/// Opcodes greater than or equal to this take an arg (Erg-specific opcodes from 220 do not).
+pub const HAVE_ARGUMENT: u8 = 90;
+
+/// `BYTE_TABLES[minor - 7][byte]`: the opcode of `byte` in CPython 3.`minor`
+const BYTE_TABLES: [[Option<Opcode>; 256]; 5] = Opcode::byte_tables();
+
+thread_local! {
+    static OPCODES_BY_NAME: Dict<&'static str, Opcode> = (0..=u8::MAX)
+        .filter_map(Opcode::try_from_byte)
+        .map(|op| (op.name(), op))
+        .collect();
+}
 
 /// Based on Python opcodes.
 /// This is represented by u8.
@@ -43,6 +62,8 @@ pub enum Opcode {
     PUSH_EXC_INFO = 35,
     CHECK_EXC_MATCH = 36,
     CHECK_EG_MATCH = 37,
+    /// 119 (with an arg) since 3.10
+    RERAISE = 48,
     WITH_EXCEPT_START = 49,
     GET_AITER = 50,
     GET_ANEXT = 51,
@@ -55,6 +76,7 @@ pub enum Opcode {
     INPLACE_MULTIPLY = 57,
     INPLACE_MODULO = 59,
     STORE_SUBSCR = 60,
+    DELETE_SUBSCR = 61,
     BINARY_AND = 64,
     BINARY_XOR = 65,
     BINARY_OR = 66,
@@ -63,7 +85,12 @@ pub enum Opcode {
     PRINT_EXPR = 70,
     LOAD_BUILD_CLASS = 71,
     LOAD_ASSERTION_ERROR = 74,
+    /// since 3.9
+    LIST_TO_TUPLE = 82,
     RETURN_VALUE = 83,
+    YIELD_VALUE = 86,
+    POP_BLOCK = 87,
+    POP_EXCEPT = 89,
     /* ↓ These opcodes take an arg */
     STORE_NAME = 90,
     DELETE_NAME = 91,
@@ -97,12 +124,31 @@ pub enum Opcode {
     RAISE_VARARGS = 130,
     CALL_FUNCTION = 131,
     MAKE_FUNCTION = 132,
+    BUILD_SLICE = 133,
     LOAD_CLOSURE = 135,
     LOAD_DEREF = 136,
     STORE_DEREF = 137,
     CALL_FUNCTION_KW = 141,
+    CALL_FUNCTION_EX = 142,
+    SETUP_WITH = 143,
+    EXTENDED_ARG = 144,
+    LIST_APPEND = 145,
+    /// until 3.8
+    BUILD_MAP_UNPACK_WITH_CALL = 151,
+    MATCH_CLASS = 152,
+    FORMAT_VALUE = 155,
+    BUILD_CONST_KEY_MAP = 156,
+    BUILD_STRING = 157,
+    /// until 3.8
+    BUILD_TUPLE_UNPACK_WITH_CALL = 158,
     LOAD_METHOD = 160,
     CALL_METHOD = 161,
+    /// since 3.9
+    LIST_EXTEND = 162,
+    /// since 3.9
+    SET_UPDATE = 163,
+    /// since 3.9
+    DICT_MERGE = 164,
     // Erg-specific opcodes (must have a unary `ERG_`)
     // Define in descending order from 219, 255
     ERG_POP_NTH = 196,
@@ -132,6 +178,8 @@ pub enum Opcode {
     /* ↑ These opcodes take an arg ↑ */
     /* ↓ These opcodes take no arg ↓ */
     // ... = 220,
+    /// `+` of `Int`s (the operands are statically typed, so no type check is needed)
+    ERG_BINARY_ADD_INT = 241,
     ERG_LOAD_EMPTY_SLOT = 242,
     ERG_LOAD_EMPTY_STR = 243,
     ERG_LOAD_1_NAT = 244,
@@ -154,11 +202,22 @@ pub enum Opcode {
 
 use Opcode::*;
 
-impl_display_from_debug!(Opcode);
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 
 impl From<u8> for Opcode {
     fn from(byte: u8) -> Self {
-        match byte {
+        Self::try_from_byte(byte).unwrap_or_else(|| panic!("not implemented opcode: {byte}"))
+    }
+}
+
+impl Opcode {
+    /// returns `None` if `byte` is not a known opcode
+    pub const fn try_from_byte(byte: u8) -> Option<Self> {
+        let op = match byte {
             1 => POP_TOP,
             2 => ROT_TWO,
             3 => ROT_THREE,
@@ -170,6 +229,8 @@ impl From<u8> for Opcode {
             11 => UNARY_NEGATIVE,
             12 => UNARY_NOT,
             15 => UNARY_INVERT,
+            16 => BINARY_MATRIX_MULTIPLY,
+            17 => INPLACE_MATRIX_MULTIPLY,
             19 => BINARY_POWER,
             20 => BINARY_MULTIPLY,
             22 => BINARY_MODULO,
@@ -186,6 +247,7 @@ impl From<u8> for Opcode {
             35 => PUSH_EXC_INFO,
             36 => CHECK_EXC_MATCH,
             37 => CHECK_EG_MATCH,
+            48 => RERAISE,
             49 => WITH_EXCEPT_START,
             50 => GET_AITER,
             51 => GET_ANEXT,
@@ -197,6 +259,7 @@ impl From<u8> for Opcode {
             57 => INPLACE_MULTIPLY,
             59 => INPLACE_MODULO,
             60 => STORE_SUBSCR,
+            61 => DELETE_SUBSCR,
             64 => BINARY_AND,
             65 => BINARY_XOR,
             66 => BINARY_OR,
@@ -205,7 +268,11 @@ impl From<u8> for Opcode {
             70 => PRINT_EXPR,
             71 => LOAD_BUILD_CLASS,
             74 => LOAD_ASSERTION_ERROR,
+            82 => LIST_TO_TUPLE,
             83 => RETURN_VALUE,
+            86 => YIELD_VALUE,
+            87 => POP_BLOCK,
+            89 => POP_EXCEPT,
             /* ↓ These opcodes take an arg */
             90 => STORE_NAME,
             91 => DELETE_NAME,
@@ -233,18 +300,34 @@ impl From<u8> for Opcode {
             116 => LOAD_GLOBAL,
             117 => IS_OP,
             118 => CONTAINS_OP,
+            // `RERAISE` of 3.10+
+            119 => RERAISE,
             124 => LOAD_FAST,
             125 => STORE_FAST,
             126 => DELETE_FAST,
             130 => RAISE_VARARGS,
             131 => CALL_FUNCTION,
             132 => MAKE_FUNCTION,
+            133 => BUILD_SLICE,
             135 => LOAD_CLOSURE,
             136 => LOAD_DEREF,
             137 => STORE_DEREF,
             141 => CALL_FUNCTION_KW,
+            142 => CALL_FUNCTION_EX,
+            143 => SETUP_WITH,
+            144 => EXTENDED_ARG,
+            145 => LIST_APPEND,
+            151 => BUILD_MAP_UNPACK_WITH_CALL,
+            152 => MATCH_CLASS,
+            155 => FORMAT_VALUE,
+            156 => BUILD_CONST_KEY_MAP,
+            157 => BUILD_STRING,
+            158 => BUILD_TUPLE_UNPACK_WITH_CALL,
             160 => LOAD_METHOD,
             161 => CALL_METHOD,
+            162 => LIST_EXTEND,
+            163 => SET_UPDATE,
+            164 => DICT_MERGE,
             // Erg-specific opcodes
             196 => ERG_POP_NTH,
             197 => ERG_PEEK_NTH,
@@ -274,6 +357,7 @@ impl From<u8> for Opcode {
             /* ↑ These opcodes take an arg ↑ */
             /* ↓ These opcodes take no arg ↓ */
             // ... = 220,
+            241 => ERG_BINARY_ADD_INT,
             242 => ERG_LOAD_EMPTY_SLOT,
             243 => ERG_LOAD_EMPTY_STR,
             244 => ERG_LOAD_1_NAT,
@@ -288,13 +372,773 @@ impl From<u8> for Opcode {
             253 => ERG_TRY_BINARY_DIVIDE,
             254 => ERG_BINARY_TRUE_DIVIDE,
             255 => NOT_IMPLEMENTED,
-            other => panic!("not implemented opcode: {other}"),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// whether the arg of the instruction is meaningful
+    /// (an instruction without it still has a zero arg in wordcode)
+    pub const fn has_arg(&self) -> bool {
+        HAVE_ARGUMENT <= (*self as u8) && (*self as u8) < 220
+    }
+
+    /// returns `None` if `byte` is not the opcode of any instruction in the Python `version`
+    /// (only Erg-specific opcodes are decoded if the version is not supported)
+    pub fn from_u8(byte: u8, version: PythonVersion) -> Option<Self> {
+        if version.major == 3 && (7..=11).contains(&version.minor) {
+            BYTE_TABLES[(version.minor - 7) as usize][byte as usize]
+        } else if byte >= ERG_POP_NTH as u8 {
+            Self::try_from_byte(byte)
+        } else {
+            None
+        }
+    }
+
+    /// the tables of `from_u8` for CPython 3.7 ~ 3.11
+    const fn byte_tables() -> [[Option<Self>; 256]; 5] {
+        let mut tables = [[None; 256]; 5];
+        let mut byte = 0;
+        while byte <= u8::MAX as usize {
+            if let Some(op) = Self::try_from_byte(byte as u8) {
+                let bytes = op.bytes_by_version();
+                let mut i = 0;
+                while i < bytes.len() {
+                    if let Some(b) = bytes[i] {
+                        tables[i][b as usize] = Some(op);
+                    }
+                    i += 1;
+                }
+            }
+            byte += 1;
+        }
+        tables
+    }
+
+    /// the mnemonic of the opcode (same as `opcode.opname` of CPython)
+    pub const fn name(&self) -> &'static str {
+        match self {
+            POP_TOP => "POP_TOP",
+            ROT_TWO => "ROT_TWO",
+            ROT_THREE => "ROT_THREE",
+            DUP_TOP => "DUP_TOP",
+            DUP_TOP2 => "DUP_TOP_TWO",
+            ROT_FOUR => "ROT_FOUR",
+            NOP => "NOP",
+            UNARY_POSITIVE => "UNARY_POSITIVE",
+            UNARY_NEGATIVE => "UNARY_NEGATIVE",
+            UNARY_NOT => "UNARY_NOT",
+            UNARY_INVERT => "UNARY_INVERT",
+            BINARY_MATRIX_MULTIPLY => "BINARY_MATRIX_MULTIPLY",
+            INPLACE_MATRIX_MULTIPLY => "INPLACE_MATRIX_MULTIPLY",
+            BINARY_POWER => "BINARY_POWER",
+            BINARY_MULTIPLY => "BINARY_MULTIPLY",
+            BINARY_MODULO => "BINARY_MODULO",
+            BINARY_ADD => "BINARY_ADD",
+            BINARY_SUBTRACT => "BINARY_SUBTRACT",
+            BINARY_SUBSCR => "BINARY_SUBSCR",
+            BINARY_TRUE_DIVIDE => "BINARY_TRUE_DIVIDE",
+            INPLACE_FLOOR_DIVIDE => "INPLACE_FLOOR_DIVIDE",
+            INPLACE_TRUE_DIVIDE => "INPLACE_TRUE_DIVIDE",
+            GET_LEN => "GET_LEN",
+            MATCH_MAPPING => "MATCH_MAPPING",
+            MATCH_SEQUENCE => "MATCH_SEQUENCE",
+            MATCH_KEYS => "MATCH_KEYS",
+            PUSH_EXC_INFO => "PUSH_EXC_INFO",
+            CHECK_EXC_MATCH => "CHECK_EXC_MATCH",
+            CHECK_EG_MATCH => "CHECK_EG_MATCH",
+            RERAISE => "RERAISE",
+            WITH_EXCEPT_START => "WITH_EXCEPT_START",
+            GET_AITER => "GET_AITER",
+            GET_ANEXT => "GET_ANEXT",
+            BEFORE_ASYNC_WITH => "BEFORE_ASYNC_WITH",
+            BEFORE_WITH => "BEFORE_WITH",
+            END_ASYNC_FOR => "END_ASYNC_FOR",
+            INPLACE_ADD => "INPLACE_ADD",
+            INPLACE_SUBTRACT => "INPLACE_SUBTRACT",
+            INPLACE_MULTIPLY => "INPLACE_MULTIPLY",
+            INPLACE_MODULO => "INPLACE_MODULO",
+            STORE_SUBSCR => "STORE_SUBSCR",
+            DELETE_SUBSCR => "DELETE_SUBSCR",
+            BINARY_AND => "BINARY_AND",
+            BINARY_XOR => "BINARY_XOR",
+            BINARY_OR => "BINARY_OR",
+            GET_ITER => "GET_ITER",
+            GET_YIELD_FROM_ITER => "GET_YIELD_FROM_ITER",
+            PRINT_EXPR => "PRINT_EXPR",
+            LOAD_BUILD_CLASS => "LOAD_BUILD_CLASS",
+            LOAD_ASSERTION_ERROR => "LOAD_ASSERTION_ERROR",
+            LIST_TO_TUPLE => "LIST_TO_TUPLE",
+            RETURN_VALUE => "RETURN_VALUE",
+            YIELD_VALUE => "YIELD_VALUE",
+            POP_BLOCK => "POP_BLOCK",
+            POP_EXCEPT => "POP_EXCEPT",
+            STORE_NAME => "STORE_NAME",
+            DELETE_NAME => "DELETE_NAME",
+            UNPACK_SEQUENCE => "UNPACK_SEQUENCE",
+            FOR_ITER => "FOR_ITER",
+            UNPACK_EX => "UNPACK_EX",
+            STORE_ATTR => "STORE_ATTR",
+            STORE_GLOBAL => "STORE_GLOBAL",
+            LOAD_CONST => "LOAD_CONST",
+            LOAD_NAME => "LOAD_NAME",
+            BUILD_TUPLE => "BUILD_TUPLE",
+            BUILD_LIST => "BUILD_LIST",
+            BUILD_SET => "BUILD_SET",
+            BUILD_MAP => "BUILD_MAP",
+            LOAD_ATTR => "LOAD_ATTR",
+            COMPARE_OP => "COMPARE_OP",
+            IMPORT_NAME => "IMPORT_NAME",
+            IMPORT_FROM => "IMPORT_FROM",
+            JUMP_FORWARD => "JUMP_FORWARD",
+            JUMP_IF_FALSE_OR_POP => "JUMP_IF_FALSE_OR_POP",
+            JUMP_IF_TRUE_OR_POP => "JUMP_IF_TRUE_OR_POP",
+            JUMP_ABSOLUTE => "JUMP_ABSOLUTE",
+            POP_JUMP_IF_FALSE => "POP_JUMP_IF_FALSE",
+            POP_JUMP_IF_TRUE => "POP_JUMP_IF_TRUE",
+            LOAD_GLOBAL => "LOAD_GLOBAL",
+            IS_OP => "IS_OP",
+            CONTAINS_OP => "CONTAINS_OP",
+            LOAD_FAST => "LOAD_FAST",
+            STORE_FAST => "STORE_FAST",
+            DELETE_FAST => "DELETE_FAST",
+            RAISE_VARARGS => "RAISE_VARARGS",
+            CALL_FUNCTION => "CALL_FUNCTION",
+            MAKE_FUNCTION => "MAKE_FUNCTION",
+            BUILD_SLICE => "BUILD_SLICE",
+            LOAD_CLOSURE => "LOAD_CLOSURE",
+            LOAD_DEREF => "LOAD_DEREF",
+            STORE_DEREF => "STORE_DEREF",
+            CALL_FUNCTION_KW => "CALL_FUNCTION_KW",
+            CALL_FUNCTION_EX => "CALL_FUNCTION_EX",
+            SETUP_WITH => "SETUP_WITH",
+            EXTENDED_ARG => "EXTENDED_ARG",
+            LIST_APPEND => "LIST_APPEND",
+            BUILD_MAP_UNPACK_WITH_CALL => "BUILD_MAP_UNPACK_WITH_CALL",
+            MATCH_CLASS => "MATCH_CLASS",
+            FORMAT_VALUE => "FORMAT_VALUE",
+            BUILD_CONST_KEY_MAP => "BUILD_CONST_KEY_MAP",
+            BUILD_STRING => "BUILD_STRING",
+            BUILD_TUPLE_UNPACK_WITH_CALL => "BUILD_TUPLE_UNPACK_WITH_CALL",
+            LOAD_METHOD => "LOAD_METHOD",
+            CALL_METHOD => "CALL_METHOD",
+            LIST_EXTEND => "LIST_EXTEND",
+            SET_UPDATE => "SET_UPDATE",
+            DICT_MERGE => "DICT_MERGE",
+            ERG_POP_NTH => "ERG_POP_NTH",
+            ERG_PEEK_NTH => "ERG_PEEK_NTH",
+            ERG_INC => "ERG_INC",
+            ERG_DEC => "ERG_DEC",
+            ERG_LOAD_FAST_IMMUT => "ERG_LOAD_FAST_IMMUT",
+            ERG_STORE_FAST_IMMUT => "ERG_STORE_FAST_IMMUT",
+            ERG_MOVE_FAST => "ERG_MOVE_FAST",
+            ERG_CLONE_FAST => "ERG_CLONE_FAST",
+            ERG_COPY_FAST => "ERG_COPY_FAST",
+            ERG_REF_FAST => "ERG_REF_FAST",
+            ERG_REF_MUT_FAST => "ERG_REF_MUT_FAST",
+            ERG_MOVE_OUTER => "ERG_MOVE_OUTER",
+            ERG_CLONE_OUTER => "ERG_CLONE_OUTER",
+            ERG_COPY_OUTER => "ERG_COPY_OUTER",
+            ERG_REF_OUTER => "ERG_REF_OUTER",
+            ERG_REF_MUT_OUTER => "ERG_REF_MUT_OUTER",
+            ERG_LESS_THAN => "ERG_LESS_THAN",
+            ERG_LESS_EQUAL => "ERG_LESS_EQUAL",
+            ERG_EQUAL => "ERG_EQUAL",
+            ERG_NOT_EQUAL => "ERG_NOT_EQUAL",
+            ERG_MAKE_SLOT => "ERG_MAKE_SLOT",
+            ERG_MAKE_TYPE => "ERG_MAKE_TYPE",
+            ERG_MAKE_PURE_FUNCTION => "ERG_MAKE_PURE_FUNCTION",
+            ERG_CALL_PURE_FUNCTION => "ERG_CALL_PURE_FUNCTION",
+            ERG_BINARY_ADD_INT => "ERG_BINARY_ADD_INT",
+            ERG_LOAD_EMPTY_SLOT => "ERG_LOAD_EMPTY_SLOT",
+            ERG_LOAD_EMPTY_STR => "ERG_LOAD_EMPTY_STR",
+            ERG_LOAD_1_NAT => "ERG_LOAD_1_NAT",
+            ERG_LOAD_1_INT => "ERG_LOAD_1_INT",
+            ERG_LOAD_1_REAL => "ERG_LOAD_1_REAL",
+            ERG_LOAD_NONE => "ERG_LOAD_NONE",
+            ERG_MUTATE => "ERG_MUTATE",
+            ERG_STORE_SUBSCR => "ERG_STORE_SUBSCR",
+            ERG_BINARY_SUBSCR => "ERG_BINARY_SUBSCR",
+            ERG_BINARY_RANGE => "ERG_BINARY_RANGE",
+            ERG_TRY_BINARY_DIVIDE => "ERG_TRY_BINARY_DIVIDE",
+            ERG_BINARY_TRUE_DIVIDE => "ERG_BINARY_TRUE_DIVIDE",
+            NOT_IMPLEMENTED => "NOT_IMPLEMENTED",
+        }
+    }
+
+    /// the inverse of `Opcode::name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        OPCODES_BY_NAME.with(|ops| ops.get(name).copied())
+    }
+
+    /// the bytes of the opcode in CPython 3.7 ~ 3.11 (`None` if the opcode is absent in the version)
+    const fn bytes_by_version(&self) -> [Option<u8>; 5] {
+        const NONE: Option<u8> = None;
+        let byte = Some(*self as u8);
+        match self {
+            ROT_FOUR => [NONE, byte, byte, byte, NONE],
+            GET_LEN | MATCH_MAPPING | MATCH_SEQUENCE | MATCH_KEYS | MATCH_CLASS => {
+                [NONE, NONE, NONE, byte, byte]
+            }
+            PUSH_EXC_INFO | CHECK_EXC_MATCH | CHECK_EG_MATCH | BEFORE_WITH => {
+                [NONE, NONE, NONE, NONE, byte]
+            }
+            RERAISE => [NONE, NONE, byte, Some(119), Some(119)],
+            WITH_EXCEPT_START | LOAD_ASSERTION_ERROR | LIST_TO_TUPLE | IS_OP | CONTAINS_OP
+            | LIST_EXTEND | SET_UPDATE | DICT_MERGE => [NONE, NONE, byte, byte, byte],
+            END_ASYNC_FOR => [NONE, byte, byte, byte, byte],
+            BUILD_MAP_UNPACK_WITH_CALL | BUILD_TUPLE_UNPACK_WITH_CALL => {
+                [byte, byte, NONE, NONE, NONE]
+            }
+            // replaced by `BINARY_OP`, `SWAP`, `COPY`, `CALL`, etc. in 3.11
+            ROT_TWO
+            | ROT_THREE
+            | DUP_TOP
+            | DUP_TOP2
+            | BINARY_MATRIX_MULTIPLY
+            | INPLACE_MATRIX_MULTIPLY
+            | BINARY_POWER
+            | BINARY_MULTIPLY
+            | BINARY_MODULO
+            | BINARY_ADD
+            | BINARY_SUBTRACT
+            | BINARY_TRUE_DIVIDE
+            | INPLACE_FLOOR_DIVIDE
+            | INPLACE_TRUE_DIVIDE
+            | INPLACE_ADD
+            | INPLACE_SUBTRACT
+            | INPLACE_MULTIPLY
+            | INPLACE_MODULO
+            | BINARY_AND
+            | BINARY_XOR
+            | BINARY_OR
+            | POP_BLOCK
+            | JUMP_ABSOLUTE
+            | POP_JUMP_IF_FALSE
+            | POP_JUMP_IF_TRUE
+            | CALL_FUNCTION
+            | CALL_FUNCTION_KW
+            | SETUP_WITH
+            | CALL_METHOD => [byte, byte, byte, byte, NONE],
+            // `MAKE_CELL` (135) is inserted before them in 3.11
+            LOAD_CLOSURE => [byte, byte, byte, byte, Some(136)],
+            LOAD_DEREF => [byte, byte, byte, byte, Some(137)],
+            STORE_DEREF => [byte, byte, byte, byte, Some(138)],
+            _ => [byte; 5],
+        }
+    }
+
+    /// the byte of the opcode in the Python `version`
+    /// (Erg-specific opcodes are the same in all versions)
+    pub fn to_byte(&self, version: PythonVersion) -> Result<u8, UnavailableOpcodeError> {
+        let byte = *self as u8;
+        if byte >= ERG_POP_NTH as u8 {
+            return Ok(byte);
+        }
+        let err = UnavailableOpcodeError { op: *self, version };
+        if version.major != 3 || !(7..=11).contains(&version.minor) {
+            return Err(err);
+        }
+        self.bytes_by_version()[(version.minor - 7) as usize].ok_or(err)
+    }
+
+    /// The stack effect of the instruction (follows `stack_effect` of CPython 3.9).
+    /// `jump` selects the effect when the jump is taken.
+    /// returns `None` if unknown (where `dis.stack_effect` raises `ValueError`)
+    pub const fn stack_effect(&self, arg: u32, jump: bool) -> Option<i32> {
+        let arg = arg as i32;
+        let effect = match self {
+            NOP | ROT_TWO | ROT_THREE | ROT_FOUR | UNARY_POSITIVE | UNARY_NEGATIVE | UNARY_NOT
+            | UNARY_INVERT | GET_ITER | GET_YIELD_FROM_ITER | YIELD_VALUE | DELETE_NAME
+            | DELETE_FAST | LOAD_ATTR | JUMP_FORWARD | JUMP_ABSOLUTE | POP_BLOCK
+            | LIST_TO_TUPLE | EXTENDED_ARG => 0,
+            DUP_TOP | LOAD_BUILD_CLASS | LOAD_ASSERTION_ERROR | GET_LEN | MATCH_MAPPING
+            | MATCH_SEQUENCE | LOAD_CONST | LOAD_NAME | LOAD_GLOBAL | LOAD_FAST | LOAD_CLOSURE
+            | LOAD_DEREF | LOAD_METHOD | IMPORT_FROM | WITH_EXCEPT_START => 1,
+            DUP_TOP2 => 2,
+            POP_TOP
+            | PRINT_EXPR
+            | RETURN_VALUE
+            | STORE_NAME
+            | STORE_GLOBAL
+            | STORE_FAST
+            | STORE_DEREF
+            | LIST_APPEND
+            | LIST_EXTEND
+            | SET_UPDATE
+            | DICT_MERGE
+            | COMPARE_OP
+            | IS_OP
+            | CONTAINS_OP
+            | IMPORT_NAME
+            | POP_JUMP_IF_FALSE
+            | POP_JUMP_IF_TRUE
+            | BINARY_MATRIX_MULTIPLY
+            | INPLACE_MATRIX_MULTIPLY
+            | BINARY_POWER
+            | BINARY_MULTIPLY
+            | BINARY_MODULO
+            | BINARY_ADD
+            | BINARY_SUBTRACT
+            | BINARY_SUBSCR
+            | BINARY_TRUE_DIVIDE
+            | BINARY_AND
+            | BINARY_XOR
+            | BINARY_OR
+            | INPLACE_FLOOR_DIVIDE
+            | INPLACE_TRUE_DIVIDE
+            | INPLACE_ADD
+            | INPLACE_SUBTRACT
+            | INPLACE_MULTIPLY
+            | INPLACE_MODULO
+            | ERG_BINARY_ADD_INT => -1,
+            STORE_ATTR => -2,
+            // subject, class, attribute names -> attributes, success (3.10)
+            MATCH_CLASS => -1,
+            // the exception (type, value, traceback)
+            RERAISE | POP_EXCEPT => -3,
+            STORE_SUBSCR => -3,
+            DELETE_SUBSCR => -2,
+            JUMP_IF_FALSE_OR_POP | JUMP_IF_TRUE_OR_POP => {
+                if jump {
+                    0
+                } else {
+                    -1
+                }
+            }
+            // `__exit__` + the result of `__enter__`,
+            // or `__exit__` + the previous and the raised exception (3 + 3 values) in the handler
+            SETUP_WITH => {
+                if jump {
+                    6
+                } else {
+                    1
+                }
+            }
+            // the iterator is popped when exhausted
+            FOR_ITER => {
+                if jump {
+                    -1
+                } else {
+                    1
+                }
+            }
+            UNPACK_SEQUENCE => arg - 1,
+            UNPACK_EX => (arg & 0xff) + (arg >> 8),
+            BUILD_TUPLE | BUILD_LIST | BUILD_SET | BUILD_SLICE => 1 - arg,
+            BUILD_MAP => 1 - 2 * arg,
+            BUILD_TUPLE_UNPACK_WITH_CALL | BUILD_MAP_UNPACK_WITH_CALL => 1 - arg,
+            BUILD_STRING => 1 - arg,
+            // the format spec is popped if exists
+            FORMAT_VALUE => {
+                if arg & 0x04 != 0 {
+                    -1
+                } else {
+                    0
+                }
+            }
+            // arg values + the key tuple
+            BUILD_CONST_KEY_MAP => -arg,
+            RAISE_VARARGS => -arg,
+            CALL_FUNCTION => -arg,
+            CALL_FUNCTION_KW | CALL_METHOD => -arg - 1,
+            // the callable + the argument tuple (+ the keyword argument dict)
+            CALL_FUNCTION_EX => -1 - (arg & 0x01),
+            // code object + qualified name + (defaults, kwdefaults, annotations, closure)
+            MAKE_FUNCTION => -1 - (arg & 0x0f).count_ones() as i32,
+            _ => return None,
+        };
+        Some(effect)
+    }
+
+    /// whether the arg of the instruction is an absolute jump target
+    pub const fn is_absolute_jump(&self) -> bool {
+        matches!(
+            self,
+            JUMP_ABSOLUTE
+                | POP_JUMP_IF_FALSE
+                | POP_JUMP_IF_TRUE
+                | JUMP_IF_FALSE_OR_POP
+                | JUMP_IF_TRUE_OR_POP
+        )
+    }
+
+    /// whether the arg of the instruction is a jump delta from the next instruction
+    pub const fn is_relative_jump(&self) -> bool {
+        matches!(self, JUMP_FORWARD | FOR_ITER | SETUP_WITH)
+    }
+
+    /// whether the instruction never falls through to the next one
+    pub const fn is_unconditional_transfer(&self) -> bool {
+        matches!(
+            self,
+            RETURN_VALUE | RAISE_VARARGS | RERAISE | JUMP_ABSOLUTE | JUMP_FORWARD
+        )
+    }
+
+    /// the Erg-specific fused instruction that can replace `self` when the operand types are `pair`
+    /// (`None` if there is no fast path for the pair)
+    pub const fn specialize(&self, pair: TypePair) -> Option<Self> {
+        match (self, pair) {
+            (BINARY_ADD, TypePair::IntInt) => Some(ERG_BINARY_ADD_INT),
+            _ => None,
+        }
+    }
+
+    /// one-line summary of the instruction (for disassemblers, editor tooltips, etc.)
+    pub const fn description(&self) -> &'static str {
+        match self {
+            POP_TOP => "Removes TOS",
+            ROT_TWO => "Swaps the two topmost stack items",
+            ROT_THREE => "Lifts the second and third stack items one position up, moves TOS down to position three",
+            DUP_TOP => "Duplicates TOS",
+            DUP_TOP2 => "Duplicates the two topmost stack items",
+            ROT_FOUR => "Lifts the second, third and fourth stack items one position up, moves TOS down to position four",
+            NOP => "Does nothing",
+            UNARY_POSITIVE => "Implements TOS = +TOS",
+            UNARY_NEGATIVE => "Implements TOS = -TOS",
+            UNARY_NOT => "Implements TOS = not TOS",
+            UNARY_INVERT => "Implements TOS = ~TOS",
+            BINARY_MATRIX_MULTIPLY => "Implements TOS = TOS1 @ TOS",
+            INPLACE_MATRIX_MULTIPLY => "Implements in-place TOS = TOS1 @ TOS",
+            BINARY_POWER => "Implements TOS = TOS1 ** TOS",
+            BINARY_MULTIPLY => "Implements TOS = TOS1 * TOS",
+            BINARY_MODULO => "Implements TOS = TOS1 % TOS",
+            BINARY_ADD => "Implements TOS = TOS1 + TOS",
+            BINARY_SUBTRACT => "Implements TOS = TOS1 - TOS",
+            BINARY_SUBSCR => "Implements TOS = TOS1[TOS]",
+            BINARY_TRUE_DIVIDE => "Implements TOS = TOS1 / TOS",
+            INPLACE_FLOOR_DIVIDE => "Implements in-place TOS = TOS1 // TOS",
+            INPLACE_TRUE_DIVIDE => "Implements in-place TOS = TOS1 / TOS",
+            GET_LEN => "Pushes len(TOS) onto the stack",
+            MATCH_MAPPING => "Pushes True if TOS is a mapping, otherwise False",
+            MATCH_SEQUENCE => "Pushes True if TOS is a sequence, otherwise False",
+            MATCH_KEYS => "Pushes a tuple of the values of TOS1 for the keys in TOS (or None)",
+            PUSH_EXC_INFO => "Pushes the current exception and sets TOS as the new one",
+            CHECK_EXC_MATCH => "Pushes whether the exception TOS1 matches the type TOS",
+            CHECK_EG_MATCH => "Splits the exception group TOS1 by the type TOS",
+            RERAISE => "Re-raises the exception on the top of the stack",
+            WITH_EXCEPT_START => "Calls the __exit__ function with the current exception",
+            GET_AITER => "Implements TOS = TOS.__aiter__()",
+            GET_ANEXT => "Pushes get_awaitable(TOS.__anext__())",
+            BEFORE_ASYNC_WITH => "Resolves __aenter__ and __aexit__ of TOS",
+            BEFORE_WITH => "Pushes __exit__ of TOS and the result of TOS.__enter__()",
+            END_ASYNC_FOR => "Terminates an async for loop",
+            INPLACE_ADD => "Implements in-place TOS = TOS1 + TOS",
+            INPLACE_SUBTRACT => "Implements in-place TOS = TOS1 - TOS",
+            INPLACE_MULTIPLY => "Implements in-place TOS = TOS1 * TOS",
+            INPLACE_MODULO => "Implements in-place TOS = TOS1 % TOS",
+            STORE_SUBSCR => "Implements TOS1[TOS] = TOS2",
+            DELETE_SUBSCR => "Implements del TOS1[TOS]",
+            BINARY_AND => "Implements TOS = TOS1 & TOS",
+            BINARY_XOR => "Implements TOS = TOS1 ^ TOS",
+            BINARY_OR => "Implements TOS = TOS1 | TOS",
+            GET_ITER => "Implements TOS = iter(TOS)",
+            GET_YIELD_FROM_ITER => "Implements TOS = iter(TOS) unless TOS is a generator or coroutine",
+            PRINT_EXPR => "Prints TOS (used in the interactive mode)",
+            LOAD_BUILD_CLASS => "Pushes builtins.__build_class__ onto the stack",
+            LOAD_ASSERTION_ERROR => "Pushes AssertionError onto the stack",
+            LIST_TO_TUPLE => "Replaces the list on TOS with a tuple",
+            RETURN_VALUE => "Returns TOS to the caller",
+            YIELD_VALUE => "Pops TOS and yields it from a generator",
+            POP_BLOCK => "Removes a block from the block stack",
+            POP_EXCEPT => "Removes an exception handler block and restores the previous exception state",
+            STORE_NAME => "Implements co_names[arg] = TOS",
+            DELETE_NAME => "Implements del co_names[arg]",
+            UNPACK_SEQUENCE => "Unpacks TOS into arg individual values",
+            FOR_ITER => "Pushes next(TOS), or pops TOS and jumps forward by arg when exhausted",
+            UNPACK_EX => "Unpacks TOS into values with a starred target",
+            STORE_ATTR => "Implements TOS.co_names[arg] = TOS1",
+            STORE_GLOBAL => "Stores TOS into the global co_names[arg]",
+            LOAD_CONST => "Pushes co_consts[arg] onto the stack",
+            LOAD_NAME => "Pushes the value of co_names[arg] onto the stack",
+            BUILD_TUPLE => "Creates a tuple from arg stack items",
+            BUILD_LIST => "Creates a list from arg stack items",
+            BUILD_SET => "Creates a set from arg stack items",
+            BUILD_MAP => "Creates a dict from arg key-value pairs",
+            LOAD_ATTR => "Replaces TOS with getattr(TOS, co_names[arg])",
+            COMPARE_OP => "Performs the comparison operation selected by arg",
+            IMPORT_NAME => "Imports the module co_names[arg]",
+            IMPORT_FROM => "Loads the attribute co_names[arg] from the module TOS",
+            JUMP_FORWARD => "Increments the bytecode counter by arg",
+            JUMP_IF_FALSE_OR_POP => "Jumps to arg if TOS is false (keeping TOS), otherwise pops TOS",
+            JUMP_IF_TRUE_OR_POP => "Jumps to arg if TOS is true (keeping TOS), otherwise pops TOS",
+            JUMP_ABSOLUTE => "Sets the bytecode counter to arg",
+            POP_JUMP_IF_FALSE => "Pops TOS and jumps to arg if it is false",
+            POP_JUMP_IF_TRUE => "Pops TOS and jumps to arg if it is true",
+            LOAD_GLOBAL => "Pushes the global co_names[arg] onto the stack",
+            IS_OP => "Performs `is` (or `is not` if arg is 1)",
+            CONTAINS_OP => "Performs `in` (or `not in` if arg is 1)",
+            LOAD_FAST => "Pushes the local co_varnames[arg] onto the stack",
+            STORE_FAST => "Stores TOS into the local co_varnames[arg]",
+            DELETE_FAST => "Deletes the local co_varnames[arg]",
+            RAISE_VARARGS => "Raises an exception (arg: the number of parameters of the raise statement)",
+            CALL_FUNCTION => "Calls a callable object with arg positional arguments",
+            MAKE_FUNCTION => "Creates a function object from the code object and qualified name on the stack",
+            BUILD_SLICE => "Creates a slice object from arg (2 or 3) stack items",
+            LOAD_CLOSURE => "Pushes a reference to the cell arg onto the stack",
+            LOAD_DEREF => "Pushes the object referenced by the cell arg onto the stack",
+            STORE_DEREF => "Stores TOS into the cell arg",
+            CALL_FUNCTION_KW => "Calls a callable object with positional and keyword arguments (names in TOS)",
+            CALL_FUNCTION_EX => "Calls a callable object with the argument tuple (and the keyword argument dict if arg & 0x01)",
+            SETUP_WITH => "Calls TOS.__enter__() keeping __exit__, and sets up a handler at arg (relative)",
+            EXTENDED_ARG => "Prefixes the arg of the next instruction with arg as its higher byte",
+            LIST_APPEND => "Appends TOS to the list at the arg-th position from TOS (for list comprehensions)",
+            BUILD_MAP_UNPACK_WITH_CALL => "Merges arg mappings into a dict of keyword arguments (duplicate keys are errors)",
+            MATCH_CLASS => "Extracts the attributes named by TOS from TOS2 if it is an instance of TOS1",
+            FORMAT_VALUE => "Formats TOS (arg: conversion | 0x04 if a format spec is on TOS)",
+            BUILD_CONST_KEY_MAP => "Creates a dict from arg values and the tuple of keys in TOS",
+            BUILD_STRING => "Concatenates arg strings into a new string",
+            BUILD_TUPLE_UNPACK_WITH_CALL => "Concatenates arg iterables into a tuple of positional arguments",
+            LOAD_METHOD => "Loads the method co_names[arg] of TOS",
+            CALL_METHOD => "Calls a method with arg positional arguments",
+            LIST_EXTEND => "Extends the list at the arg-th position from TOS with the iterable TOS",
+            SET_UPDATE => "Adds the elements of the iterable TOS to the set at the arg-th position from TOS",
+            DICT_MERGE => "Merges the mapping TOS into the dict at the arg-th position from TOS (duplicate keys are errors)",
+            ERG_POP_NTH => "Removes the arg-th element from TOS",
+            ERG_PEEK_NTH => "Pushes a reference to the arg-th element from TOS",
+            ERG_INC => "Increments the variable by 1 (arg: type code)",
+            ERG_DEC => "Decrements the variable by 1 (arg: type code)",
+            ERG_LOAD_FAST_IMMUT => "Pushes the immutable local co_varnames[arg] onto the stack",
+            ERG_STORE_FAST_IMMUT => "Stores TOS into the immutable local co_varnames[arg]",
+            ERG_MOVE_FAST => "Moves the local co_varnames[arg] onto the stack",
+            ERG_CLONE_FAST => "Pushes a clone of the local co_varnames[arg]",
+            ERG_COPY_FAST => "Pushes a copy of the local co_varnames[arg]",
+            ERG_REF_FAST => "Pushes a reference to the local co_varnames[arg]",
+            ERG_REF_MUT_FAST => "Pushes a mutable reference to the local co_varnames[arg]",
+            ERG_MOVE_OUTER => "Moves the outer variable arg onto the stack",
+            ERG_CLONE_OUTER => "Pushes a clone of the outer variable arg",
+            ERG_COPY_OUTER => "Pushes a copy of the outer variable arg",
+            ERG_REF_OUTER => "Pushes a reference to the outer variable arg",
+            ERG_REF_MUT_OUTER => "Pushes a mutable reference to the outer variable arg",
+            ERG_LESS_THAN => "Implements TOS = TOS1 < TOS (arg: type pair)",
+            ERG_LESS_EQUAL => "Implements TOS = TOS1 <= TOS (arg: type pair)",
+            ERG_EQUAL => "Implements TOS = TOS1 == TOS (arg: type pair)",
+            ERG_NOT_EQUAL => "Implements TOS = TOS1 != TOS (arg: type pair)",
+            ERG_MAKE_SLOT => "Creates a slot",
+            ERG_MAKE_TYPE => "Creates a type object",
+            ERG_MAKE_PURE_FUNCTION => "Creates a pure function object",
+            ERG_CALL_PURE_FUNCTION => "Calls a pure function with arg positional arguments",
+            ERG_BINARY_ADD_INT => "Implements TOS = TOS1 + TOS (TOS1 and TOS are Ints)",
+            ERG_LOAD_EMPTY_SLOT => "Pushes an empty slot onto the stack",
+            ERG_LOAD_EMPTY_STR => "Pushes an empty string onto the stack",
+            ERG_LOAD_1_NAT => "Pushes 1 (Nat) onto the stack",
+            ERG_LOAD_1_INT => "Pushes 1 (Int) onto the stack",
+            ERG_LOAD_1_REAL => "Pushes 1.0 onto the stack",
+            ERG_LOAD_NONE => "Pushes None onto the stack",
+            ERG_MUTATE => "Converts TOS into a mutable object (`!x`)",
+            ERG_STORE_SUBSCR => "Implements TOS1[TOS] = TOS2 (never fails)",
+            ERG_BINARY_SUBSCR => "Implements TOS = TOS1[TOS] (never fails)",
+            ERG_BINARY_RANGE => "Creates a range from TOS1 and TOS",
+            ERG_TRY_BINARY_DIVIDE => "Implements TOS = TOS1 /? TOS (TOS may be 0)",
+            ERG_BINARY_TRUE_DIVIDE => "Implements TOS = TOS1 / TOS (TOS is never 0)",
+            NOT_IMPLEMENTED => "Not implemented",
         }
     }
 }
 
-impl Opcode {
-    pub const fn take_arg(&self) -> bool {
-        90 <= (*self as u8) && (*self as u8) < 220
+/// The opcode does not exist in the Python version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnavailableOpcodeError {
+    pub op: Opcode,
+    pub version: PythonVersion,
+}
+
+impl fmt::Display for UnavailableOpcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not available in Python {}", self.op, self.version)
+    }
+}
+
+impl std::error::Error for UnavailableOpcodeError {}
+
+/// Comparison operators (the operand of `COMPARE_OP`), same order as `cmp_op` of CPython
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CompareOp {
+    LT = 0,
+    LE = 1,
+    EQ = 2,
+    NE = 3,
+    GT = 4,
+    GE = 5,
+}
+
+impl_display_from_debug!(CompareOp);
+
+impl CompareOp {
+    /// `COMPARISON_*` masks of CPython 3.12+
+    const fn mask(&self) -> u8 {
+        const UNORDERED: u8 = 1;
+        const LESS_THAN: u8 = 2;
+        const GREATER_THAN: u8 = 4;
+        const EQUALS: u8 = 8;
+        match self {
+            Self::LT => LESS_THAN,
+            Self::LE => LESS_THAN | EQUALS,
+            Self::EQ => EQUALS,
+            Self::NE => UNORDERED | LESS_THAN | GREATER_THAN,
+            Self::GT => GREATER_THAN,
+            Self::GE => GREATER_THAN | EQUALS,
+        }
+    }
+
+    /// the arg of `COMPARE_OP` for the Python version (magic number)
+    /// * ~ 3.11: `cmp_op` index
+    /// * 3.12: `cmp_op` index << 4 | mask
+    /// * 3.13 ~: `cmp_op` index << 5 | mask
+    pub const fn arg(&self, python_ver: u32) -> u8 {
+        if python_ver >= 3550 {
+            ((*self as u8) << 5) | self.mask()
+        } else if python_ver >= 3531 {
+            ((*self as u8) << 4) | self.mask()
+        } else {
+            *self as u8
+        }
+    }
+}
+
+/// An opcode that is not defined in `Opcode` (e.g. an instruction of a custom VM).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CustomOpcode {
+    pub byte: u8,
+    /// the name shown by the disassembler
+    pub name: Str,
+    /// the stack effect of the instruction,
+    /// not counting the arguments popped by an intrinsic (i.e. the number of the results for an intrinsic)
+    pub stack_effect: i32,
+    /// calls of the function with this name are compiled into this instruction
+    /// (the arguments are pushed onto the stack, and the arg is the number of them)
+    pub intrinsic: Option<Str>,
+}
+
+impl CustomOpcode {
+    pub fn new(byte: u8, name: &str, stack_effect: i32, intrinsic: Option<&str>) -> Self {
+        Self {
+            byte,
+            name: Str::rc(name),
+            stack_effect,
+            intrinsic: intrinsic.map(Str::rc),
+        }
+    }
+
+    /// The stack effect of the instruction with `arg`.
+    /// An intrinsic pops `arg` arguments in addition to `stack_effect`.
+    pub fn stack_effect_with(&self, arg: u32) -> i32 {
+        if self.intrinsic.is_some() {
+            self.stack_effect - arg as i32
+        } else {
+            self.stack_effect
+        }
+    }
+}
+
+/// The byte of a custom opcode is already used by another opcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeConflictError {
+    pub byte: u8,
+    /// the name of the opcode using the byte
+    pub used_by: Str,
+}
+
+impl fmt::Display for OpcodeConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is already used by {}", self.byte, self.used_by)
+    }
+}
+
+impl std::error::Error for OpcodeConflictError {}
+
+/// The custom opcodes registered by the user, and the Python version of the bytecode.
+/// The code generator, the disassembler and `compute_stacksize` refer to this for bytes unknown to `Opcode`.
+#[derive(Debug, Clone, Default)]
+pub struct OpcodeTable {
+    ops: Dict<u8, CustomOpcode>,
+    /// `None` for the Erg VM (the bytes are the discriminants of `Opcode`)
+    version: Option<PythonVersion>,
+}
+
+impl OpcodeTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// a table for decoding the bytecode of the Python `version`
+    pub fn for_version(version: PythonVersion) -> Self {
+        Self {
+            ops: Dict::new(),
+            version: Some(version),
+        }
+    }
+
+    pub fn version(&self) -> Option<PythonVersion> {
+        self.version
+    }
+
+    pub fn set_version(&mut self, version: Option<PythonVersion>) {
+        self.version = version;
+    }
+
+    /// the opcode of `byte` in the target version (custom opcodes are not included)
+    pub fn decode(&self, byte: u8) -> Option<Opcode> {
+        match self.version {
+            Some(version) => Opcode::from_u8(byte, version),
+            None => Opcode::try_from_byte(byte),
+        }
+    }
+
+    /// the byte of `op` in the target version
+    /// (the discriminant if `op` is not available in the version)
+    pub fn encode(&self, op: Opcode) -> u8 {
+        self.version
+            .and_then(|version| op.to_byte(version).ok())
+            .unwrap_or(op as u8)
+    }
+
+    /// the number of bytes a jump arg counts:
+    /// jumps are byte offsets before Python 3.10, and instruction offsets since then (and in the Erg VM)
+    fn jump_unit(&self) -> usize {
+        match self.version {
+            Some(version) if (version.major, version.minor) < (3, 10) => 1,
+            _ => 2,
+        }
+    }
+
+    /// the arg of a jump to the offset `bytes` (absolute) or over `bytes` bytes (relative)
+    pub fn jump_arg(&self, bytes: usize) -> usize {
+        bytes / self.jump_unit()
+    }
+
+    /// the offset (absolute) or the distance (relative) in bytes of the jump arg `arg`
+    pub fn jump_bytes(&self, arg: usize) -> usize {
+        arg * self.jump_unit()
+    }
+
+    /// returns an error if `op.byte` is already used by `Opcode` or another custom opcode
+    pub fn register(&mut self, op: CustomOpcode) -> Result<(), OpcodeConflictError> {
+        let used_by = if let Some(builtin) = Opcode::try_from_byte(op.byte) {
+            Some(Str::from(builtin.to_string()))
+        } else {
+            self.ops.get(&op.byte).map(|other| other.name.clone())
+        };
+        if let Some(used_by) = used_by {
+            return Err(OpcodeConflictError {
+                byte: op.byte,
+                used_by,
+            });
+        }
+        self.ops.insert(op.byte, op);
+        Ok(())
+    }
+
+    pub fn get(&self, byte: u8) -> Option<&CustomOpcode> {
+        self.ops.get(&byte)
+    }
+
+    /// the custom opcode that calls of `name` are compiled into
+    pub fn get_intrinsic(&self, name: &str) -> Option<&CustomOpcode> {
+        self.ops
+            .values()
+            .find(|op| op.intrinsic.as_ref().map(|i| &i[..]) == Some(name))
     }
 }
@@ -1,52 +1,240 @@
 //! utilities for calling CPython.
 //!
 //! CPythonを呼び出すためのユーティリティー
-use std::process::Command;
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::serialize::get_magic_num_from_bytes;
+use crate::serialize::{get_magic_num_bytes, get_magic_num_from_bytes, get_python_version};
 
-pub fn which_python() -> String {
-    let (cmd, python) = if cfg!(windows) {
-        ("where", "python")
-    } else {
-        ("which", "python3")
-    };
-    let out = Command::new(cmd)
-        .arg(python)
-        .output()
-        .expect("python not found");
-    let res = String::from_utf8(out.stdout).unwrap();
-    let res = res.split('\n').next().unwrap_or("").replace('\r', "");
-    if res.is_empty() {
-        panic!("python not found");
+/// A release version of CPython, e.g. `3.9.13`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PythonVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub micro: u8,
+}
+
+impl fmt::Display for PythonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.micro)
     }
-    res.to_string()
 }
 
-pub fn detect_magic_number() -> u32 {
-    let out = if cfg!(windows) {
-        Command::new("cmd")
-            .arg("/C")
-            .arg(which_python())
+impl PythonVersion {
+    pub const fn new(major: u8, minor: u8, micro: u8) -> Self {
+        Self {
+            major,
+            minor,
+            micro,
+        }
+    }
+
+    /// e.g. `3.9.13` and `3.9.0` are the same minor version
+    pub const fn is_same_minor(&self, other: &Self) -> bool {
+        self.major == other.major && self.minor == other.minor
+    }
+
+    /// parses the output of `python --version`, e.g. `Python 3.9.13`
+    /// (the suffix of a pre-release like `3.12.0rc1` is ignored)
+    pub fn parse(s: &str) -> Option<Self> {
+        let ver = s.trim().strip_prefix("Python ")?;
+        let mut nums = ver.split('.').map(|n| {
+            let end = n.find(|c: char| !c.is_ascii_digit()).unwrap_or(n.len());
+            n[..end].parse::<u8>().ok()
+        });
+        let major = nums.next()??;
+        let minor = nums.next()??;
+        let micro = nums.next().flatten().unwrap_or(0);
+        Some(Self::new(major, minor, micro))
+    }
+}
+
+/// (Python version, the range of the magic numbers from the first alpha to the final release)
+///
+/// cf. `Lib/importlib/_bootstrap_external.py` of CPython
+pub(crate) const MAGIC_NUMBERS: [(PythonVersion, u32, u32); 5] = [
+    (PythonVersion::new(3, 7, 0), 3390, 3394),
+    (PythonVersion::new(3, 8, 0), 3400, 3413),
+    (PythonVersion::new(3, 9, 0), 3420, 3425),
+    (PythonVersion::new(3, 10, 0), 3430, 3439),
+    (PythonVersion::new(3, 11, 0), 3450, 3495),
+];
+
+/// Returns the magic number (as written at the head of `.pyc` files) of the final release of `version`.
+/// The micro version is ignored, and `None` is returned if the version is not supported.
+pub fn magic_number(version: PythonVersion) -> Option<[u8; 4]> {
+    MAGIC_NUMBERS
+        .iter()
+        .find(|(ver, _, _)| ver.is_same_minor(&version))
+        .map(|(_, _, magic)| get_magic_num_bytes(*magic))
+}
+
+/// how long to wait for `python --version`
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// returns `None` if the command fails to start or does not finish within `VERSION_TIMEOUT`
+fn output_with_timeout(cmd: &mut Command) -> Option<Output> {
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let deadline = Instant::now() + VERSION_TIMEOUT;
+    while child.try_wait().ok()?.is_none() {
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    child.wait_with_output().ok()
+}
+
+/// returns `None` if `python --version` fails or does not report a version
+fn python_version_of<S: AsRef<OsStr>>(python: S) -> Option<PythonVersion> {
+    let out = output_with_timeout(Command::new(python).arg("--version"))?;
+    // Python 2 (and 3 ~ 3.3) prints the version to stderr
+    PythonVersion::parse(&String::from_utf8_lossy(&out.stdout))
+        .or_else(|| PythonVersion::parse(&String::from_utf8_lossy(&out.stderr)))
+}
+
+/// Detects the version of the installed Python.
+/// `$PYTHON` is used if set, otherwise `python3` (or `python` if it is not found).
+pub fn detect_python_version() -> Option<PythonVersion> {
+    if let Ok(python) = env::var("PYTHON") {
+        return python_version_of(&python);
+    }
+    ["python3", "python"]
+        .into_iter()
+        .find_map(python_version_of)
+}
+
+/// the files named `name` in the directories of `$PATH` (in order)
+fn search_path(name: &str) -> Vec<PathBuf> {
+    let Some(paths) = env::var_os("PATH") else {
+        return vec![];
+    };
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// the path of the interpreter that the Python launcher (`py -3`) selects
+fn py_launcher_executable() -> Option<PathBuf> {
+    let out = output_with_timeout(
+        Command::new("py")
+            .arg("-3")
             .arg("-c")
-            .arg("import importlib.util as util;print(util.MAGIC_NUMBER.hex())")
-            .output()
-            .expect("cannot get the magic number from python")
+            .arg("import sys;print(sys.executable)"),
+    )?;
+    let path = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (out.status.success() && !path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// the interpreters to try, in order of preference
+fn python_candidates(version: Option<PythonVersion>) -> Vec<PathBuf> {
+    let mut candidates = vec![];
+    if let Some(python) = env::var_os("PYTHON") {
+        let python = PathBuf::from(python);
+        // a bare command name is looked up in `$PATH`
+        if python.components().count() == 1 && !python.is_file() {
+            candidates.extend(search_path(&python.to_string_lossy()));
+        } else {
+            candidates.push(python);
+        }
+    }
+    if cfg!(windows) {
+        candidates.extend(py_launcher_executable());
+        candidates.extend(search_path("python.exe"));
     } else {
-        let python_command = format!(
-            "{} -c 'import importlib.util as util;print(util.MAGIC_NUMBER.hex())'",
-            which_python()
-        );
-        Command::new("sh")
-            .arg("-c")
-            .arg(python_command)
-            .output()
-            .expect("cannot get the magic number from python")
-    };
-    let s_hex_magic_num = String::from_utf8(out.stdout).unwrap();
-    let first_byte = u8::from_str_radix(&s_hex_magic_num[0..=1], 16).unwrap();
-    let second_byte = u8::from_str_radix(&s_hex_magic_num[2..=3], 16).unwrap();
-    get_magic_num_from_bytes(&[first_byte, second_byte, 0, 0])
+        // e.g. `python3.9`
+        if let Some(ver) = version {
+            candidates.extend(search_path(&format!("python{}.{}", ver.major, ver.minor)));
+        }
+        candidates.extend(search_path("python3"));
+        candidates.extend(search_path("python"));
+    }
+    candidates
+}
+
+/// Finds the first Python interpreter that actually runs and reports its version.
+/// `$PYTHON` is tried first, then `python3` and `python` on `$PATH` (`py -3` and `python.exe` on Windows).
+pub fn find_python_executable() -> Option<PathBuf> {
+    python_candidates(None)
+        .into_iter()
+        .find(|python| python_version_of(python).is_some())
+}
+
+/// Finds the first Python interpreter whose minor version is the same as `version`.
+/// `$PYTHON` is tried first, then `python3.X`, `python3` and `python` on `$PATH`.
+pub fn find_python_executable_for(version: PythonVersion) -> Option<PathBuf> {
+    python_candidates(Some(version))
+        .into_iter()
+        .find(|python| python_version_of(python).is_some_and(|ver| ver.is_same_minor(&version)))
+}
+
+/// Runs the `.pyc` file with the interpreter of the version that the magic number of the file indicates.
+/// The stdout/stderr of the interpreter are forwarded to those of the current process.
+pub fn run_pyc<P, I, S>(path: P, args: I) -> io::Result<ExitStatus>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let path = path.as_ref();
+    let mut magic = [0; 4];
+    File::open(path)?.read_exact(&mut magic)?;
+    let version = get_python_version(get_magic_num_from_bytes(&magic)).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} is not a .pyc file of a supported Python version",
+                path.display()
+            ),
+        )
+    })?;
+    let python = find_python_executable_for(version).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Python {}.{} not found", version.major, version.minor),
+        )
+    })?;
+    Command::new(&python)
+        .arg(path)
+        .args(args)
+        .status()
+        .map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("cannot execute {}: {err}", python.display()),
+            )
+        })
+}
+
+/// The magic number (of the final release) of the installed Python (see `detect_python_version`).
+/// `None` if Python is not found or its version is not supported.
+pub fn detect_magic_number() -> Option<u32> {
+    detect_python_version()
+        .and_then(magic_number)
+        .map(|magic| get_magic_num_from_bytes(&magic))
+}
+
+/// the interpreter that `exec_pyc` and the like run
+/// (if none is found, `python3` is spawned, which fails with a "not found" error)
+fn python_path() -> String {
+    find_python_executable().map_or_else(
+        || if cfg!(windows) { "python" } else { "python3" }.to_string(),
+        |python| python.to_string_lossy().to_string(),
+    )
 }
 
 /// executes over a shell, cause `python` may not exist as an executable file (like pyenv)
@@ -54,12 +242,12 @@ pub fn exec_pyc<S: Into<String>>(file: S) {
     let mut out = if cfg!(windows) {
         Command::new("cmd")
             .arg("/C")
-            .arg(which_python())
+            .arg(python_path())
             .arg(&file.into())
             .spawn()
             .expect("cannot execute python")
     } else {
-        let python_command = format!("{} {}", which_python(), file.into());
+        let python_command = format!("{} {}", python_path(), file.into());
         Command::new("sh")
             .arg("-c")
             .arg(python_command)
@@ -74,12 +262,12 @@ pub fn eval_pyc<S: Into<String>>(file: S) -> String {
     let out = if cfg!(windows) {
         Command::new("cmd")
             .arg("/C")
-            .arg(which_python())
+            .arg(python_path())
             .arg(&file.into())
             .spawn()
             .expect("cannot execute python")
     } else {
-        let python_command = format!("{} {}", which_python(), file.into());
+        let python_command = format!("{} {}", python_path(), file.into());
         Command::new("sh")
             .arg("-c")
             .arg(python_command)
@@ -92,13 +280,13 @@ pub fn eval_pyc<S: Into<String>>(file: S) -> String {
 
 pub fn exec_py(code: &str) {
     if cfg!(windows) {
-        Command::new(which_python())
+        Command::new(python_path())
             .arg("-c")
             .arg(code)
             .spawn()
             .expect("cannot execute python");
     } else {
-        let python_command = format!("{} -c \"{}\"", which_python(), code);
+        let python_command = format!("{} -c \"{}\"", python_path(), code);
         Command::new("sh")
             .arg("-c")
             .arg(python_command)
@@ -1,14 +1,18 @@
 //! オブジェクトのシリアライズ(バイナリ列化)のためのユーティリティーを定義・実装する
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::dict::Dict;
 use crate::impl_display_from_debug;
+use crate::long::Long;
+use crate::python_util::{magic_number, PythonVersion, MAGIC_NUMBERS};
+use crate::value::ValueObj;
 use crate::Str;
 
-/* Python bytecode specification */
+/* Python bytecode specification (3.7~) */
 // 0~3 byte: magic number
-// 4~7 byte: padding (0;4)
+// 4~7 byte: bit field (0 = timestamp-based)
 // 8~B byte: UNIX timestamp
-// C~F byte: padding (0;4)
+// C~F byte: source size (0;4)
 // 10~ byte: marshalled code objects
 // the unary magic number of Python bytecode
 // magic number = version number (2byte) + 168624128 (0x0A0D0000)
@@ -24,6 +28,20 @@ pub const fn get_magic_num_from_bytes(bytes: &[u8; 4]) -> u32 {
     u32::from_le_bytes([bytes[0], bytes[1], 0, 0])
 }
 
+/// Returns the magic number of the final release of `version` (the micro version is ignored).
+/// `None` if the version is not supported.
+pub fn get_magic_num(version: PythonVersion) -> Option<u32> {
+    magic_number(version).map(|bytes| get_magic_num_from_bytes(&bytes))
+}
+
+/// Returns the Python version that `python_ver` (a magic number, including those of pre-releases) belongs to.
+pub fn get_python_version(python_ver: u32) -> Option<PythonVersion> {
+    MAGIC_NUMBERS
+        .iter()
+        .find(|(_, first, last)| (*first..=*last).contains(&python_ver))
+        .map(|(ver, _, _)| *ver)
+}
+
 pub fn get_timestamp_bytes() -> [u8; 4] {
     let secs = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -47,16 +65,25 @@ pub enum DataTypePrefix {
     False = 'F' as u8,      // 0x46
     None = 'N' as u8,       // 0x4E
     StopIter = 'S' as u8,   // 0x53
+    Ellipsis = b'.',        // 0x2E
     Ref = 'r' as u8,
     /* unsized objects (ref counted) */
     Long = 'l' as u8, // 0x6C + len: u32 + payload: 2*len+3byte (~ -2^31-1 && 2^31 ~)
     Str = 's' as u8,  // 0x73 + len: u32 + payload
     ShortAscii = 'z' as u8, // 0x7A + len: u8 + payload
     ShortAsciiInterned = 'Z' as u8, //  0x5A + len: u8 + payload
+    Ascii = b'a',     // 0x61 + len: u32 + payload
+    AsciiInterned = b'A', // 0x41 + len: u32 + payload
     Unicode = 'u' as u8, // 0x75 + len: u32 + payload
     Interned = 't' as u8, // 0x74 + len + payload
     SmallTuple = ')' as u8, // 0x29 + len: u8 + payload
     Tuple = '(' as u8, // 0x28 + len: u32 + payload
+    List = b'[',      // 0x5B + len: u32 + payload
+    Dict = b'{',      // 0x7B + (key, value)... + Null
+    Set = b'<',       // 0x3C + len: u32 + payload
+    FrozenSet = b'>', // 0x3E + len: u32 + payload
+    /// the terminator of Dict
+    Null = b'0', // 0x30
     Code = 'c' as u8, // 0x63
     /* Erg specific prefix */
     Builtin = 'b' as u8, // 0x62 + str
@@ -72,22 +99,31 @@ impl From<u8> for DataTypePrefix {
             'I' => Self::Int64,
             'l' => Self::Long,
             'f' => Self::Float,
-            'g' => Self::BinFloat,
+            'g' | '\u{00E7}' => Self::BinFloat,
             'x' => Self::Complex,
-            'y' => Self::BinComplex,
+            'y' | '\u{00F9}' => Self::BinComplex,
             'T' => Self::True,
             'F' => Self::False,
             'N' => Self::None,
             'S' => Self::StopIter,
+            '.' => Self::Ellipsis,
             's' | '\u{00F3}' => Self::Str,
             'Z' | '\u{00DA}' => Self::ShortAsciiInterned,
             'z' | '\u{00FA}' => Self::ShortAscii,
+            'a' | '\u{00E1}' => Self::Ascii,
+            'A' | '\u{00C1}' => Self::AsciiInterned,
             'u' => Self::Unicode,
             't' => Self::Interned,
             '(' | '\u{00A8}' => Self::Tuple,
             ')' | '\u{00A9}' => Self::SmallTuple,
+            '[' | '\u{00DB}' => Self::List,
+            '{' | '\u{00FB}' => Self::Dict,
+            '<' | '\u{00BC}' => Self::Set,
+            '>' | '\u{00BE}' => Self::FrozenSet,
+            '0' => Self::Null,
             'c' | '\u{00E3}' => Self::Code,
             'b' => Self::Builtin,
+            'r' => Self::Ref,
             'n' => Self::Nat,
             /*'\u{00F9}' => DataTypeUnaryOp::ErgInt8,
             '\u{00FA}' => DataTypeUnaryOp::ErgInt32,
@@ -108,10 +144,16 @@ impl DataTypePrefix {
             | Self::Str
             | Self::ShortAscii
             | Self::ShortAsciiInterned
+            | Self::Ascii
+            | Self::AsciiInterned
             | Self::Unicode
             | Self::Interned
             | Self::SmallTuple
             | Self::Tuple
+            | Self::List
+            | Self::Dict
+            | Self::Set
+            | Self::FrozenSet
             | Self::Code
             | Self::Builtin => false,
             _ => true,
@@ -119,30 +161,66 @@ impl DataTypePrefix {
     }
 }
 
-pub fn strs_into_bytes(names: Vec<Str>) -> Vec<u8> {
-    let mut tuple = vec![];
-    if names.len() > u8::MAX as usize {
-        tuple.push(DataTypePrefix::Tuple as u8);
-        tuple.append(&mut (names.len() as u32).to_le_bytes().to_vec());
-    } else {
-        tuple.push(DataTypePrefix::SmallTuple as u8);
-        tuple.push(names.len() as u8);
+/// The high bit of a type code: the object is registered to the reference table
+pub const FLAG_REF: u8 = 0x80;
+
+/// The interned strings written so far.
+/// The later occurrences are written as `Ref`s to the first one, as CPython does for names like `self` or `print`.
+/// Only interned strings are written with `FLAG_REF`, so the index of a string is the order in which it first appeared.
+#[derive(Debug, Default)]
+pub struct RefTable {
+    strs: Dict<Str, u32>,
+}
+
+impl RefTable {
+    pub fn new() -> Self {
+        Self::default()
     }
-    for name in names.into_iter() {
-        tuple.append(&mut str_into_bytes(name, true));
+
+    pub fn interned_str_into_bytes(&mut self, s: Str) -> Vec<u8> {
+        if let Some(idx) = self.strs.get(&s) {
+            let mut bytes = vec![DataTypePrefix::Ref as u8];
+            bytes.append(&mut idx.to_le_bytes().to_vec());
+            return bytes;
+        }
+        self.strs.insert(s.clone(), self.strs.len() as u32);
+        let mut bytes = str_into_bytes(s, true);
+        bytes[0] |= FLAG_REF;
+        bytes
+    }
+
+    pub fn strs_into_bytes(&mut self, names: Vec<Str>) -> Vec<u8> {
+        let mut tuple = vec![];
+        if names.len() > u8::MAX as usize {
+            tuple.push(DataTypePrefix::Tuple as u8);
+            tuple.append(&mut (names.len() as u32).to_le_bytes().to_vec());
+        } else {
+            tuple.push(DataTypePrefix::SmallTuple as u8);
+            tuple.push(names.len() as u8);
+        }
+        for name in names.into_iter() {
+            tuple.append(&mut self.interned_str_into_bytes(name));
+        }
+        tuple
     }
-    tuple
 }
 
 pub fn str_into_bytes(cont: Str, is_interned: bool) -> Vec<u8> {
     let mut bytes = vec![];
-    if cont.is_ascii() {
+    if cont.is_ascii() && cont.len() <= u8::MAX as usize {
         if is_interned {
             bytes.push(DataTypePrefix::ShortAsciiInterned as u8);
         } else {
             bytes.push(DataTypePrefix::ShortAscii as u8);
         }
         bytes.push(cont.len() as u8);
+    } else if cont.is_ascii() {
+        if is_interned {
+            bytes.push(DataTypePrefix::AsciiInterned as u8);
+        } else {
+            bytes.push(DataTypePrefix::Ascii as u8);
+        }
+        bytes.append(&mut (cont.len() as u32).to_le_bytes().to_vec());
     } else {
         bytes.push(DataTypePrefix::Unicode as u8);
         bytes.append(&mut (cont.len() as u32).to_le_bytes().to_vec());
@@ -151,6 +229,97 @@ pub fn str_into_bytes(cont: Str, is_interned: bool) -> Vec<u8> {
     bytes
 }
 
+/// Python has no Nat type, so a Nat is marshaled as a plain int
+/// (as a `Long` if it does not fit in `Int32`)
+pub fn nat_into_bytes(n: u64) -> Vec<u8> {
+    if n <= i32::MAX as u64 {
+        return [
+            vec![DataTypePrefix::Int32 as u8],
+            (n as i32).to_le_bytes().to_vec(),
+        ]
+        .concat();
+    }
+    long_into_bytes(&Long::from(n))
+}
+
+/// Long: the number of digits (negative if the value is negative) + 15-bit digits (little endian)
+pub fn long_into_bytes(long: &Long) -> Vec<u8> {
+    let len = long.digits().len() as i32;
+    let len = if long.is_negative() { -len } else { len };
+    let mut bytes = vec![DataTypePrefix::Long as u8];
+    bytes.append(&mut len.to_le_bytes().to_vec());
+    for digit in long.digits() {
+        bytes.append(&mut digit.to_le_bytes().to_vec());
+    }
+    bytes
+}
+
+/// BinFloat: 8-byte little endian IEEE 754 (so NaN, ±inf and -0.0 are kept bit-for-bit)
+pub fn float_into_bytes(f: f64) -> Vec<u8> {
+    let mut bytes = vec![DataTypePrefix::BinFloat as u8];
+    bytes.append(&mut f.to_le_bytes().to_vec());
+    bytes
+}
+
+/// BinComplex: the real part and the imaginary part (in the same format as BinFloat)
+pub fn complex_into_bytes(re: f64, im: f64) -> Vec<u8> {
+    let mut bytes = vec![DataTypePrefix::BinComplex as u8];
+    bytes.append(&mut re.to_le_bytes().to_vec());
+    bytes.append(&mut im.to_le_bytes().to_vec());
+    bytes
+}
+
+fn seq_into_bytes(
+    prefix: DataTypePrefix,
+    elems: Vec<ValueObj>,
+    refs: &mut RefTable,
+    python_ver: u32,
+) -> Vec<u8> {
+    let mut bytes = vec![prefix as u8];
+    bytes.append(&mut (elems.len() as u32).to_le_bytes().to_vec());
+    for elem in elems.into_iter() {
+        bytes.append(&mut elem.into_bytes_with(refs, python_ver));
+    }
+    bytes
+}
+
+pub fn tuple_into_bytes(elems: Vec<ValueObj>, refs: &mut RefTable, python_ver: u32) -> Vec<u8> {
+    seq_into_bytes(DataTypePrefix::Tuple, elems, refs, python_ver)
+}
+
+pub fn list_into_bytes(elems: Vec<ValueObj>, refs: &mut RefTable, python_ver: u32) -> Vec<u8> {
+    seq_into_bytes(DataTypePrefix::List, elems, refs, python_ver)
+}
+
+pub fn set_into_bytes(
+    elems: Vec<ValueObj>,
+    frozen: bool,
+    refs: &mut RefTable,
+    python_ver: u32,
+) -> Vec<u8> {
+    let prefix = if frozen {
+        DataTypePrefix::FrozenSet
+    } else {
+        DataTypePrefix::Set
+    };
+    seq_into_bytes(prefix, elems, refs, python_ver)
+}
+
+/// Dict has no length, the entries are terminated by Null
+pub fn dict_into_bytes(
+    entries: Vec<(ValueObj, ValueObj)>,
+    refs: &mut RefTable,
+    python_ver: u32,
+) -> Vec<u8> {
+    let mut bytes = vec![DataTypePrefix::Dict as u8];
+    for (key, value) in entries.into_iter() {
+        bytes.append(&mut key.into_bytes_with(refs, python_ver));
+        bytes.append(&mut value.into_bytes_with(refs, python_ver));
+    }
+    bytes.push(DataTypePrefix::Null as u8);
+    bytes
+}
+
 pub fn raw_string_into_bytes(mut cont: Vec<u8>) -> Vec<u8> {
     let mut tuple = vec![DataTypePrefix::Str as u8];
     tuple.append(&mut (cont.len() as u32).to_le_bytes().to_vec());
@@ -1,5 +1,5 @@
 use std::borrow::Borrow;
-use std::collections::hash_set::{IntoIter, Iter};
+use std::collections::hash_set::{Drain, IntoIter, Iter};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
@@ -106,6 +106,18 @@ impl<T: Hash> Set<T> {
     pub fn into_iter(self) -> IntoIter<T> {
         self.elems.into_iter()
     }
+
+    /// Removes all elements that do not satisfy `f`
+    #[inline]
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        self.elems.retain(f);
+    }
+
+    /// Removes all elements and returns them as an iterator (the set is empty afterwards)
+    #[inline]
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        self.elems.drain()
+    }
 }
 
 impl<T: Hash + Eq> Set<T> {
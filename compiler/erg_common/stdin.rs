@@ -1,5 +1,5 @@
 use std::cell::RefCell;
-use std::io::{stdin, BufRead, BufReader};
+use std::io::{self, stdin, BufRead, BufReader, Read};
 
 use crate::Str;
 
@@ -19,6 +19,18 @@ impl StdinReader {
         self.buf.last().unwrap().clone()
     }
 
+    /// reads all of stdin at once (e.g. `cat foo.er | erg`)
+    /// returns an error if stdin cannot be read or is not valid UTF-8
+    pub fn read_all(&mut self) -> io::Result<Str> {
+        let mut buf = "".to_string();
+        stdin().lock().read_to_string(&mut buf)?;
+        for line in buf.lines() {
+            self.lineno += 1;
+            self.buf.push(Str::rc(line));
+        }
+        Ok(buf.into())
+    }
+
     pub fn reread(&self) -> Str {
         self.buf.last().unwrap().clone()
     }
@@ -36,6 +48,10 @@ pub fn read() -> Str {
     READER.with(|s| s.borrow_mut().read())
 }
 
+pub fn read_all() -> io::Result<Str> {
+    READER.with(|s| s.borrow_mut().read_all())
+}
+
 pub fn reread() -> Str {
     READER.with(|s| s.borrow().reread())
 }
@@ -3,6 +3,8 @@ use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::ops::{Add, Deref};
 
+use crate::cache::Cache;
+
 pub type RcStr = std::rc::Rc<str>;
 
 /// Used to hold an immutable string.
@@ -131,6 +133,35 @@ impl Str {
         }
     }
 
+    /// Same as `str::split`, but the pieces are `Str`s interned in `cache`
+    /// (`self` itself is interned if `pat` is not found).
+    /// Renamed from `split` so as not to shadow `str::split` (`Str` derefs to `str`).
+    pub fn split_str<'a>(
+        &'a self,
+        pat: &'a str,
+        cache: &'a Cache<str>,
+    ) -> impl Iterator<Item = Str> + 'a {
+        let whole: &'a str = self;
+        whole.split(pat).map(move |piece| {
+            if piece.len() == whole.len() {
+                cache.intern(self.clone())
+            } else {
+                cache.get(piece)
+            }
+        })
+    }
+
+    /// Same as `str::replace`, but the result is a `Str` interned in `cache`
+    /// (`self` itself is interned if `from` is not found).
+    /// Renamed from `replace` so as not to shadow `str::replace`.
+    pub fn replace_str(&self, from: &str, to: &str, cache: &Cache<str>) -> Str {
+        if self.contains(from) {
+            cache.intern(Str::from(self.as_ref().replace(from, to)))
+        } else {
+            cache.intern(self.clone())
+        }
+    }
+
     pub fn is_uppercase(&self) -> bool {
         self.chars()
             .next()
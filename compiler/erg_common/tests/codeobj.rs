@@ -0,0 +1,1308 @@
+extern crate erg_common;
+
+mod tests {
+    use std::fs;
+    use std::process::Command;
+
+    use erg_common::codeobj::{
+        compute_stacksize, compute_stacksize_with, ArgSpec, CodeDiff, CodeObj, CodeObjBuilder,
+        CodeObjError, CodeObjFlags, ExceptionTableEntry,
+    };
+    use erg_common::deserialize::Deserializer;
+    use erg_common::opcode::Opcode::{self, *};
+    use erg_common::opcode::OpcodeTable;
+    use erg_common::python_util::PythonVersion;
+    use erg_common::serialize::{
+        get_magic_num_bytes, get_python_version, DataTypePrefix, FLAG_REF,
+    };
+    use erg_common::value::ValueObj;
+    use erg_common::Str;
+
+    #[test]
+    fn test_arg_spec() {
+        // def f(a, /, b, *args, c, **kwargs): d = 1
+        let varnames = ["a", "b", "c", "args", "kwargs", "d"]
+            .into_iter()
+            .map(Str::ever)
+            .collect::<Vec<_>>();
+        let mut code = CodeObj::empty(varnames, "<string>", "f", 1);
+        code.argcount = 2;
+        code.posonlyargcount = 1;
+        code.kwonlyargcount = 1;
+        code.flags |= CodeObjFlags::VarArgs as u32 | CodeObjFlags::VarKeywords as u32;
+        assert_eq!(
+            code.arg_spec(),
+            ArgSpec {
+                posonly: vec![Str::ever("a")],
+                pos_or_kw: vec![Str::ever("b")],
+                varargs: Some(Str::ever("args")),
+                kwonly: vec![Str::ever("c")],
+                kwargs: Some(Str::ever("kwargs")),
+            }
+        );
+        code.flags = CodeObjFlags::VarKeywords as u32;
+        assert_eq!(code.arg_spec().varargs, None);
+        assert_eq!(code.arg_spec().kwargs, Some(Str::ever("args")));
+    }
+
+    fn code(name: &'static str, children: Vec<CodeObj>) -> CodeObj {
+        let mut code = CodeObj::empty(vec![], "<string>", name, 1);
+        code.consts.push(ValueObj::None);
+        for child in children {
+            code.consts.push(ValueObj::from(child));
+        }
+        code
+    }
+
+    #[test]
+    fn test_nested_codes() {
+        // module -> (f -> (g, h), k)
+        let mut module = code(
+            "<module>",
+            vec![
+                code("f", vec![code("g", vec![]), code("h", vec![])]),
+                code("k", vec![]),
+            ],
+        );
+        let names = module
+            .nested_codes()
+            .map(|c| c.name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["f", "g", "h", "k"]);
+        let mut visited = vec![];
+        module.nested_codes_mut(|c| {
+            visited.push(c.name.to_string());
+            c.firstlineno = 2;
+        });
+        assert_eq!(visited, names);
+        assert!(module.nested_codes().all(|c| c.firstlineno == 2));
+        assert_eq!(module.firstlineno, 1);
+    }
+
+    fn assemble(instrs: &[(Opcode, u8)]) -> Vec<u8> {
+        instrs
+            .iter()
+            .flat_map(|(op, arg)| [*op as u8, *arg])
+            .collect()
+    }
+
+    #[test]
+    fn test_compute_stacksize() {
+        // x = (1, 1, 1) if True else 2
+        let code = assemble(&[
+            (LOAD_CONST, 0),        // 0: True
+            (POP_JUMP_IF_FALSE, 7), // 2: (to 14)
+            (LOAD_CONST, 1),        // 4
+            (LOAD_CONST, 1),        // 6
+            (LOAD_CONST, 1),        // 8
+            (BUILD_TUPLE, 3),       // 10
+            (JUMP_FORWARD, 1),      // 12: (to 16)
+            (LOAD_CONST, 2),        // 14
+            (RETURN_VALUE, 0),      // 16
+        ]);
+        let consts = [ValueObj::True, ValueObj::Nat(1), ValueObj::Nat(2)];
+        assert_eq!(compute_stacksize(&code, &consts), Ok(3));
+        // the else branch is deeper
+        let code = assemble(&[
+            (LOAD_CONST, 0),        // 0: True
+            (POP_JUMP_IF_FALSE, 4), // 2: (to 8)
+            (LOAD_CONST, 1),        // 4
+            (JUMP_FORWARD, 4),      // 6: (to 16)
+            (LOAD_CONST, 2),        // 8
+            (DUP_TOP, 0),           // 10
+            (DUP_TOP, 0),           // 12
+            (BUILD_TUPLE, 3),       // 14
+            (RETURN_VALUE, 0),      // 16
+        ]);
+        assert_eq!(compute_stacksize(&code, &consts), Ok(3));
+        assert_eq!(
+            compute_stacksize(&assemble(&[(POP_TOP, 0)]), &[]),
+            Err(CodeObjError::StackUnderflow { offset: 0 })
+        );
+        let code = assemble(&[(LOAD_CONST, 3), (RETURN_VALUE, 0)]);
+        assert_eq!(
+            compute_stacksize(&code, &consts),
+            Err(CodeObjError::ConstOutOfRange { offset: 0, idx: 3 })
+        );
+    }
+
+    #[test]
+    fn test_compute_stacksize_with_version() {
+        // `LOAD_DEREF` is 137 in Python 3.11, which is `STORE_DEREF` in the older versions
+        let code = vec![137, 0, RETURN_VALUE as u8, 0];
+        let py311 = OpcodeTable::for_version(PythonVersion::new(3, 11, 0));
+        assert_eq!(compute_stacksize_with(&code, &[], &py311), Ok(1));
+        assert_eq!(
+            compute_stacksize(&code, &[]),
+            Err(CodeObjError::StackUnderflow { offset: 0 })
+        );
+        let mut codeobj = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        codeobj.code = code;
+        codeobj.freevars = vec![Str::ever("x")];
+        assert!(codeobj.disassemble_with(&py311).contains("LOAD_DEREF"));
+        assert!(codeobj.code_info_with(&py311).contains("LOAD_DEREF"));
+        assert!(codeobj.disassemble().contains("STORE_DEREF"));
+        // bytes unknown in the version are not decoded by the disassembler
+        codeobj.code = vec![BINARY_ADD as u8, 0, RETURN_VALUE as u8, 0];
+        assert!(codeobj.disassemble_with(&py311).contains("<23>"));
+        assert!(codeobj.code_info_with(&py311).contains("<23>"));
+    }
+
+    #[test]
+    fn test_rebase_jumps() {
+        // while x: discard y
+        let snippet = assemble(&[
+            (LOAD_NAME, 0),         // 0
+            (POP_JUMP_IF_FALSE, 5), // 2: (to 10)
+            (LOAD_NAME, 1),         // 4
+            (POP_TOP, 0),           // 6
+            (JUMP_ABSOLUTE, 0),     // 8: (to 0)
+            (JUMP_FORWARD, 0),      // 10: (to 12)
+            (LOAD_CONST, 0),        // 12
+            (RETURN_VALUE, 0),      // 14
+        ]);
+        let mut inlined = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        inlined.code = snippet;
+        // embed after 3 instructions
+        inlined.rebase_jumps(6);
+        let mut code = assemble(&[(NOP, 0), (NOP, 0), (NOP, 0)]);
+        code.extend(inlined.code);
+        assert_eq!(&code[8..10], &[POP_JUMP_IF_FALSE as u8, 8]); // (to 16)
+        assert_eq!(code[16], JUMP_FORWARD as u8);
+        assert_eq!(&code[14..16], &[JUMP_ABSOLUTE as u8, 3]); // (to 6)
+        assert_eq!(code[6], LOAD_NAME as u8);
+        // relative jumps are not changed
+        assert_eq!(&code[16..18], &[JUMP_FORWARD as u8, 0]);
+        // a jump with EXTENDED_ARG: 0x1FF + 0x02
+        let mut extended = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        extended.code = vec![144, 1, JUMP_ABSOLUTE as u8, 0xFF];
+        extended.rebase_jumps(4);
+        assert_eq!(extended.code, vec![144, 2, JUMP_ABSOLUTE as u8, 1]);
+    }
+
+    #[test]
+    fn test_jumps_in_bytes() {
+        // Python 3.9 counts the jump args in bytes
+        let py39 = OpcodeTable::for_version(PythonVersion::new(3, 9, 0));
+        let snippet = assemble(&[
+            (LOAD_NAME, 0),          // 0
+            (POP_JUMP_IF_FALSE, 10), // 2: (to 10)
+            (LOAD_NAME, 1),          // 4
+            (POP_TOP, 0),            // 6
+            (JUMP_ABSOLUTE, 0),      // 8: (to 0)
+            (JUMP_FORWARD, 2),       // 10: (to 14)
+            (NOP, 0),                // 12
+            (LOAD_CONST, 0),         // 14
+            (RETURN_VALUE, 0),       // 16
+        ]);
+        assert_eq!(
+            compute_stacksize_with(&snippet, &[ValueObj::None], &py39),
+            Ok(1)
+        );
+        // the jumps are out of range if they are read as instruction offsets
+        assert!(compute_stacksize(&snippet, &[ValueObj::None]).is_err());
+        let mut code = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        code.code = snippet.clone();
+        code.consts = vec![ValueObj::None];
+        let dis = code.disassemble_with(&py39);
+        assert!(dis.contains("POP_JUMP_IF_FALSE       10 (to 10)"));
+        assert!(dis.contains("JUMP_FORWARD             2 (to 14)"));
+        // the unreachable `NOP` is removed and the jumps are moved
+        code.eliminate_dead_code_with(&py39);
+        assert_eq!(
+            &code.code[10..14],
+            &[JUMP_FORWARD as u8, 0, LOAD_CONST as u8, 0]
+        );
+        assert_eq!(&code.code[2..4], &[POP_JUMP_IF_FALSE as u8, 10]);
+        code.rebase_jumps_with(6, &py39);
+        assert_eq!(&code.code[2..4], &[POP_JUMP_IF_FALSE as u8, 16]);
+        assert_eq!(&code.code[8..10], &[JUMP_ABSOLUTE as u8, 6]);
+    }
+
+    #[test]
+    fn test_relax_jumps() {
+        let mut instrs = vec![
+            (JUMP_FORWARD, 1),      // 0: (to 4)
+            (NOP, 0),               // 2
+            (POP_JUMP_IF_FALSE, 0), // 4: (to 600), truncated
+        ];
+        instrs.extend([(NOP, 0); 296]); // 6..598
+        instrs.push((JUMP_ABSOLUTE, 2)); // 598: (to 4)
+        instrs.extend([(LOAD_CONST, 0), (RETURN_VALUE, 0)]); // 600
+        let mut code = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        code.code = assemble(&instrs);
+        // line 2 at 4, line 3 at 600 (= 4 + 255 + 255 + 86)
+        code.lnotab = vec![4, 1, 255, 0, 255, 0, 86, 1];
+        code.relax_jumps(&[(4, 300)]);
+        assert_eq!(code.code.len(), 606);
+        // the jump targeting the relaxed jump lands on its `EXTENDED_ARG`
+        assert_eq!(&code.code[0..2], &[JUMP_FORWARD as u8, 1]);
+        assert_eq!(
+            &code.code[4..8],
+            &[EXTENDED_ARG as u8, 1, POP_JUMP_IF_FALSE as u8, 45] // (to 602)
+        );
+        assert_eq!(&code.code[600..602], &[JUMP_ABSOLUTE as u8, 2]);
+        assert_eq!(code.code[602], LOAD_CONST as u8);
+        assert_eq!(code.lnotab, vec![4, 1, 255, 0, 255, 0, 88, 1]);
+        // nothing to relax
+        let mut short = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        short.code = assemble(&[(JUMP_FORWARD, 0), (NOP, 0)]);
+        short.relax_jumps(&[]);
+        assert_eq!(short.code, assemble(&[(JUMP_FORWARD, 0), (NOP, 0)]));
+    }
+
+    /// the instructions of `code_info` (offset, opcode, arg and its description)
+    fn disassemble(code: &CodeObj) -> Vec<String> {
+        code.code_info()
+            .split_once("lnotab")
+            .unwrap()
+            .1
+            .lines()
+            .filter(|l| l.starts_with("  "))
+            .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect()
+    }
+
+    #[test]
+    fn test_peephole_optimize() {
+        // x = 1 + 2 * 3
+        //
+        // None
+        let mut code = CodeObjBuilder::new("<module>")
+            .const_(ValueObj::Nat(1))
+            .const_(ValueObj::Nat(2))
+            .const_(ValueObj::Nat(3))
+            .const_(ValueObj::None)
+            .name("x")
+            .instr(LOAD_CONST, 0)
+            .instr(LOAD_CONST, 1)
+            .instr(LOAD_CONST, 2)
+            .instr(BINARY_MULTIPLY, 0)
+            .instr(BINARY_ADD, 0)
+            .instr(STORE_NAME, 0)
+            .instr(NOP, 0) // 12: a marker of line 2
+            .instr(LOAD_CONST, 3)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        code.lnotab = vec![12, 1, 2, 1];
+        assert_eq!(
+            disassemble(&code)[..6],
+            [
+                "0 LOAD_CONST 0 (1)",
+                "2 LOAD_CONST 1 (2)",
+                "4 LOAD_CONST 2 (3)",
+                "6 BINARY_MULTIPLY 0",
+                "8 BINARY_ADD 0",
+                "10 STORE_NAME 0 (x)",
+            ]
+        );
+        code.peephole_optimize();
+        assert_eq!(
+            disassemble(&code),
+            [
+                "0 LOAD_CONST 5 (7)",
+                "2 STORE_NAME 0 (x)",
+                "4 NOP",
+                "6 LOAD_CONST 3 (None)",
+                "8 RETURN_VALUE",
+            ]
+        );
+        assert_eq!(code.consts[4], ValueObj::Nat(6));
+        assert_eq!(code.lnotab, vec![4, 1, 2, 1]);
+        // `NOP`s that do not start a line are removed
+        let mut code = CodeObjBuilder::new("<module>")
+            .const_(ValueObj::None)
+            .instr(NOP, 0)
+            .instr(LOAD_CONST, 0)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        code.peephole_optimize();
+        assert_eq!(code.code, assemble(&[(LOAD_CONST, 0), (RETURN_VALUE, 0)]));
+        // the results follow Python's semantics, or are left to runtime
+        let folded = |lhs: ValueObj, rhs: ValueObj, op: Opcode| {
+            let mut code = CodeObjBuilder::new("<module>")
+                .const_(lhs)
+                .const_(rhs)
+                .instr(LOAD_CONST, 0)
+                .instr(LOAD_CONST, 1)
+                .instr(op, 0)
+                .instr(RETURN_VALUE, 0)
+                .build();
+            code.peephole_optimize();
+            (code.code.len() == 4).then(|| code.consts[code.code[1] as usize].clone())
+        };
+        assert_eq!(
+            folded(ValueObj::Nat(1), ValueObj::Nat(2), BINARY_SUBTRACT),
+            Some(ValueObj::Int(-1))
+        );
+        assert_eq!(
+            folded(ValueObj::from("a"), ValueObj::from("b"), BINARY_ADD),
+            Some(ValueObj::from("ab"))
+        );
+        assert_eq!(
+            folded(ValueObj::Float(0.5), ValueObj::Nat(2), BINARY_MULTIPLY),
+            Some(ValueObj::Float(1.0))
+        );
+        assert_eq!(
+            folded(ValueObj::from("a"), ValueObj::Nat(1), BINARY_ADD),
+            None
+        );
+        assert_eq!(
+            folded(ValueObj::Nat(1), ValueObj::Nat(2), BINARY_TRUE_DIVIDE),
+            Some(ValueObj::Float(0.5))
+        );
+        assert_eq!(
+            folded(ValueObj::Nat(1), ValueObj::Nat(0), BINARY_TRUE_DIVIDE),
+            None
+        );
+        assert_eq!(
+            folded(ValueObj::Nat(u64::MAX), ValueObj::Nat(2), BINARY_MULTIPLY)
+                .map(|obj| obj.to_string()),
+            Some("36893488147419103230".to_string())
+        );
+        assert_eq!(
+            folded(ValueObj::from("ab"), ValueObj::Nat(2048), BINARY_MULTIPLY),
+            Some(ValueObj::from("ab".repeat(2048).as_str()))
+        );
+        assert_eq!(
+            folded(ValueObj::from("ab"), ValueObj::Nat(2049), BINARY_MULTIPLY),
+            None
+        );
+        assert_eq!(
+            folded(ValueObj::Inf, ValueObj::Nat(0), BINARY_MULTIPLY),
+            None
+        );
+    }
+
+    /// whether every jump of `code` targets the start of an instruction
+    fn jumps_are_valid(code: &[u8]) -> bool {
+        let starts = (0..code.len())
+            .step_by(2)
+            .filter(|i| *i == 0 || code[i - 2] != EXTENDED_ARG as u8)
+            .collect::<Vec<_>>();
+        code.chunks(2).enumerate().all(|(i, instr)| {
+            let op = Opcode::from(instr[0]);
+            let target = if op.is_absolute_jump() {
+                instr[1] as usize * 2
+            } else if op.is_relative_jump() {
+                i * 2 + 2 + instr[1] as usize * 2
+            } else {
+                return true;
+            };
+            starts.contains(&target) || target == code.len()
+        })
+    }
+
+    #[test]
+    fn test_peephole_jump_targets() {
+        let mut code = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        code.consts = vec![ValueObj::True, ValueObj::None];
+        code.names = vec![Str::ever("x")];
+        code.code = assemble(&[
+            (NOP, 0),                // 0
+            (LOAD_CONST, 0),         // 2: True
+            (POP_JUMP_IF_FALSE, 10), // 4: (to 20), never taken
+            (LOAD_NAME, 0),          // 6
+            (POP_JUMP_IF_TRUE, 10),  // 8: (to 20)
+            (JUMP_FORWARD, 2),       // 10: (to 16)
+            (NOP, 0),                // 12
+            (NOP, 0),                // 14
+            (JUMP_ABSOLUTE, 0),      // 16: (to 0)
+            (NOP, 0),                // 18
+            (LOAD_CONST, 1),         // 20
+            (RETURN_VALUE, 0),       // 22
+        ]);
+        assert!(jumps_are_valid(&code.code));
+        code.peephole_optimize();
+        assert_eq!(
+            code.code,
+            assemble(&[
+                (LOAD_NAME, 0),        // 0
+                (POP_JUMP_IF_TRUE, 4), // 2: (to 8)
+                (JUMP_FORWARD, 0),     // 4: (to 6)
+                (JUMP_ABSOLUTE, 0),    // 6: (to 0)
+                (LOAD_CONST, 1),       // 8
+                (RETURN_VALUE, 0),     // 10
+            ])
+        );
+        assert!(jumps_are_valid(&code.code));
+        assert_eq!(compute_stacksize(&code.code, &code.consts), Ok(1));
+        // always taken: replaced with an unconditional jump
+        let mut code = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        code.consts = vec![ValueObj::Nat(0), ValueObj::None];
+        code.names = vec![Str::ever("x")];
+        code.code = assemble(&[
+            (LOAD_CONST, 0),        // 0: 0
+            (POP_JUMP_IF_FALSE, 4), // 2: (to 8)
+            (LOAD_NAME, 0),         // 4
+            (POP_TOP, 0),           // 6
+            (LOAD_CONST, 1),        // 8
+            (RETURN_VALUE, 0),      // 10
+        ]);
+        code.peephole_optimize();
+        assert_eq!(&code.code[..2], &[JUMP_ABSOLUTE as u8, 3]); // (to 6)
+        assert_eq!(code.code[6], LOAD_CONST as u8);
+        assert!(jumps_are_valid(&code.code));
+        // a jump target is not folded into the preceding instruction
+        let mut code = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        code.consts = vec![ValueObj::Nat(1), ValueObj::Nat(2)];
+        let before = assemble(&[
+            (LOAD_CONST, 0),    // 0
+            (LOAD_CONST, 1),    // 2
+            (JUMP_ABSOLUTE, 3), // 4: (to 6)
+            (BINARY_ADD, 0),    // 6
+            (RETURN_VALUE, 0),  // 8
+        ]);
+        code.code = before.clone();
+        code.peephole_optimize();
+        assert_eq!(code.code, before);
+    }
+
+    #[test]
+    fn test_eliminate_dead_code() {
+        // def f(x):
+        //     if x: return 1
+        //     else: return 2
+        let mut code = CodeObjBuilder::new("f")
+            .params(vec!["x"])
+            .const_(ValueObj::None)
+            .const_(1)
+            .const_(2)
+            .instr(LOAD_FAST, 0)
+            .instr(POP_JUMP_IF_FALSE, 4) // (to 8)
+            .instr(LOAD_CONST, 1)
+            .instr(RETURN_VALUE, 0)
+            .instr(LOAD_CONST, 2) // 8: a jump target
+            .instr(RETURN_VALUE, 0)
+            .instr(LOAD_CONST, 0) // 12: the implicit `return None`
+            .instr(RETURN_VALUE, 0)
+            .build();
+        code.lnotab = vec![4, 1, 4, 2, 4, 1];
+        code.eliminate_dead_code();
+        assert_eq!(
+            code.code,
+            assemble(&[
+                (LOAD_FAST, 0),
+                (POP_JUMP_IF_FALSE, 4),
+                (LOAD_CONST, 1),
+                (RETURN_VALUE, 0),
+                (LOAD_CONST, 2),
+                (RETURN_VALUE, 0),
+            ])
+        );
+        // line 5 has no instructions left
+        assert_eq!(code.lnotab, vec![4, 1, 4, 2]);
+        assert_eq!(code.line_starts(), vec![(0, 1), (4, 2), (8, 4)]);
+        // a jump in unreachable code does not keep its target
+        let mut code = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        code.consts = vec![ValueObj::None];
+        code.code = assemble(&[
+            (JUMP_FORWARD, 2),  // 0: (to 6)
+            (JUMP_ABSOLUTE, 5), // 2: (to 10)
+            (NOP, 0),           // 4
+            (LOAD_CONST, 0),    // 6
+            (RETURN_VALUE, 0),  // 8
+            (LOAD_CONST, 0),    // 10
+            (RETURN_VALUE, 0),  // 12
+        ]);
+        code.eliminate_dead_code();
+        assert_eq!(
+            code.code,
+            assemble(&[(JUMP_FORWARD, 0), (LOAD_CONST, 0), (RETURN_VALUE, 0)])
+        );
+        // nothing is unreachable
+        let before = assemble(&[
+            (LOAD_CONST, 0),       // 0
+            (POP_JUMP_IF_TRUE, 4), // 2: (to 8)
+            (JUMP_ABSOLUTE, 0),    // 4: (to 0)
+            (RAISE_VARARGS, 0),    // 6: unreachable
+            (LOAD_CONST, 0),       // 8
+            (RETURN_VALUE, 0),     // 10
+        ]);
+        code.code = before.clone();
+        code.eliminate_dead_code();
+        assert_eq!(code.code.len(), before.len() - 2);
+        assert_eq!(&code.code[6..8], &[LOAD_CONST as u8, 0]);
+        assert_eq!(&code.code[2..4], &[POP_JUMP_IF_TRUE as u8, 3]); // (to 6)
+                                                                    // an exception handler is reached only through the exception table
+        let mut code = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        code.consts = vec![ValueObj::None];
+        code.code = assemble(&[
+            (NOP, 0),          // 0
+            (LOAD_CONST, 0),   // 2
+            (RETURN_VALUE, 0), // 4
+            (NOP, 0),          // 6: unreachable
+            (POP_TOP, 0),      // 8: the handler
+            (LOAD_CONST, 0),   // 10
+            (RETURN_VALUE, 0), // 12
+        ]);
+        code.push_exception_handler(ExceptionTableEntry {
+            start: 2,
+            end: 4,
+            target: 8,
+            depth: 0,
+            lasti: false,
+        });
+        // the range covers only removed instructions
+        code.push_exception_handler(ExceptionTableEntry {
+            start: 6,
+            end: 8,
+            target: 8,
+            depth: 0,
+            lasti: false,
+        });
+        code.eliminate_dead_code();
+        assert_eq!(code.code.len(), 12);
+        assert_eq!(&code.code[6..8], &[POP_TOP as u8, 0]);
+        assert_eq!(
+            code.exception_handlers(),
+            [ExceptionTableEntry {
+                start: 2,
+                end: 4,
+                target: 6,
+                depth: 0,
+                lasti: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_push_lnotab() {
+        let mut code = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        code.push_lnotab(6, 2); // line 3 at 6
+        code.push_lnotab(0, 1); // empty line: merged into the previous entry
+        code.push_lnotab(4, -3); // back to line 1 at 10
+        code.push_lnotab(600, 300); // line 301 at 610
+        code.push_lnotab(2, -200); // line 101 at 612
+        #[rustfmt::skip]
+        let expected = vec![
+            6, 3,
+            4, -3i8 as u8,
+            255, 0, 255, 0, 90, 127, 0, 127, 0, 46,
+            2, -128i8 as u8, 0, -72i8 as u8,
+        ];
+        assert_eq!(code.lnotab, expected);
+        // same as `dis.findlinestarts` of Python 3.9
+        assert_eq!(
+            code.line_starts(),
+            vec![(0, 1), (6, 4), (10, 1), (610, 301), (612, 101)]
+        );
+    }
+
+    #[test]
+    fn test_raised_types() {
+        // def f(x):
+        //     if x: raise ValueError("bad")
+        //     raise TypeError from x
+        //     raise x
+        //     raise
+        let code = CodeObjBuilder::new("f")
+            .params(vec!["x"])
+            .const_(ValueObj::None)
+            .const_("bad")
+            .name("ValueError")
+            .name("TypeError")
+            .instr(LOAD_FAST, 0)
+            .instr(POP_JUMP_IF_FALSE, 6)
+            .instr(LOAD_GLOBAL, 0)
+            .instr(LOAD_CONST, 1)
+            .instr(CALL_FUNCTION, 1)
+            .instr(RAISE_VARARGS, 1)
+            .instr(LOAD_GLOBAL, 1)
+            .instr(LOAD_FAST, 0)
+            .instr(RAISE_VARARGS, 2)
+            .instr(LOAD_FAST, 0)
+            .instr(RAISE_VARARGS, 1)
+            .instr(RAISE_VARARGS, 0)
+            .build();
+        let raised = code.raised_types();
+        assert_eq!(raised.len(), 2);
+        assert!(raised.contains(&Str::ever("ValueError")));
+        assert!(raised.contains(&Str::ever("TypeError")));
+    }
+
+    #[test]
+    fn test_code_obj_builder() {
+        // def f(a): b = a; return b.x
+        let code = CodeObjBuilder::new("f")
+            .params(vec!["a"])
+            .varname("b")
+            .name("x")
+            .instr(LOAD_FAST, 0)
+            .instr(STORE_FAST, 1)
+            .instr(LOAD_FAST, 1)
+            .instr(LOAD_ATTR, 0)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        assert_eq!(code.argcount, 1);
+        assert_eq!(code.nlocals, 2);
+        assert_eq!(code.stacksize, 1);
+        let dis = code.code_info();
+        let instrs = dis
+            .split_once("lnotab")
+            .unwrap()
+            .1
+            .lines()
+            .filter(|l| l.starts_with("  "))
+            .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            instrs,
+            [
+                "0 LOAD_FAST 0 (a)",
+                "2 STORE_FAST 1 (b)",
+                "4 LOAD_FAST 1 (b)",
+                "6 LOAD_ATTR 0 (x)",
+                "8 RETURN_VALUE",
+            ]
+        );
+        // large args are extended
+        let mut builder = CodeObjBuilder::new("<module>");
+        for i in 0..300 {
+            builder = builder.const_(i);
+        }
+        let code = builder
+            .instr(LOAD_CONST, 299)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        assert_eq!(
+            code.code,
+            [
+                EXTENDED_ARG as u8,
+                1,
+                LOAD_CONST as u8,
+                43,
+                RETURN_VALUE as u8,
+                0
+            ]
+        );
+        assert_eq!(code.stacksize, 1);
+    }
+
+    #[test]
+    fn test_diff() {
+        // x = 1
+        let code = |store: Opcode, one: ValueObj| {
+            CodeObjBuilder::new("<module>")
+                .const_(one)
+                .const_(ValueObj::None)
+                .name("x")
+                .instr(LOAD_CONST, 0)
+                .instr(store, 0)
+                .instr(LOAD_CONST, 1)
+                .instr(RETURN_VALUE, 0)
+                .build()
+        };
+        let lhs = code(STORE_NAME, ValueObj::Nat(1));
+        assert_eq!(lhs.diff(&lhs.clone()), None);
+        let rhs = code(STORE_GLOBAL, ValueObj::Nat(1));
+        let diff = lhs.diff(&rhs).unwrap();
+        assert_eq!(
+            diff,
+            CodeDiff::Instr {
+                offset: 2,
+                lhs: Some((STORE_NAME as u8, 0)),
+                rhs: Some((STORE_GLOBAL as u8, 0)),
+            }
+        );
+        assert_eq!(
+            diff.to_string(),
+            "instruction at offset 2: STORE_NAME 0 != STORE_GLOBAL 0"
+        );
+        // `1` and `True` are different constants
+        let rhs = code(STORE_NAME, ValueObj::True);
+        assert!(matches!(
+            lhs.diff(&rhs),
+            Some(CodeDiff::Const { idx: 0, .. })
+        ));
+        let mut rhs = lhs.clone();
+        rhs.names.push(Str::ever("y"));
+        assert!(matches!(
+            lhs.diff(&rhs),
+            Some(CodeDiff::Field { name: "names", .. })
+        ));
+    }
+
+    /// the values loaded by `LOAD_CONST`s, in order
+    fn loaded_consts(code: &CodeObj) -> Vec<ValueObj> {
+        let mut loaded = vec![];
+        let mut arg = 0;
+        for instr in code.code.chunks(2) {
+            arg = (arg << 8) | instr[1] as usize;
+            if instr[0] == EXTENDED_ARG as u8 {
+                continue;
+            }
+            if instr[0] == LOAD_CONST as u8 {
+                loaded.push(code.consts[arg].clone());
+            }
+            arg = 0;
+        }
+        loaded
+    }
+
+    fn assert_sorted(consts: &[ValueObj]) {
+        assert!(consts
+            .windows(2)
+            .all(|pair| pair[0].canonical_cmp(&pair[1]).is_le()));
+    }
+
+    #[test]
+    fn test_canonicalize_consts() {
+        let inner = CodeObjBuilder::new("f")
+            .const_("doc?")
+            .const_(ValueObj::Nat(1))
+            .const_(ValueObj::None)
+            .instr(LOAD_CONST, 1)
+            .instr(POP_TOP, 0)
+            .instr(LOAD_CONST, 2)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        let mut code = CodeObjBuilder::new("<module>")
+            .const_("b")
+            .const_(ValueObj::Nat(2))
+            .const_(inner)
+            .const_("a")
+            .const_(ValueObj::Int(-1))
+            .const_(ValueObj::True)
+            .const_(ValueObj::None)
+            .instr(LOAD_CONST, 0)
+            .instr(LOAD_CONST, 3)
+            .instr(LOAD_CONST, 1)
+            .instr(LOAD_CONST, 4)
+            .instr(LOAD_CONST, 5)
+            .instr(LOAD_CONST, 2)
+            .instr(POP_TOP, 0)
+            .instr(LOAD_CONST, 6)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        let before = loaded_consts(&code);
+        let inner_before = loaded_consts(code.nested_codes().next().unwrap());
+        code.canonicalize_consts();
+        // the first constant (the docstring of a function) stays in place
+        assert!(code.consts[0].marshal_eq(&ValueObj::from("b")));
+        assert_sorted(&code.consts[1..]);
+        assert!(code.consts[1].marshal_eq(&ValueObj::None));
+        let after = loaded_consts(&code);
+        assert_eq!(before.len(), after.len());
+        // the nested code object itself has been rewritten, so it is identified by its name
+        assert!(before.iter().zip(after.iter()).all(|(l, r)| match (l, r) {
+            (ValueObj::Code(l), ValueObj::Code(r)) => l.name == r.name,
+            (l, r) => l.marshal_eq(r),
+        }));
+        // nested code objects are canonicalized too
+        let inner = code.nested_codes().next().unwrap();
+        assert!(inner.consts[0].marshal_eq(&ValueObj::from("doc?")));
+        assert_sorted(&inner.consts[1..]);
+        assert!(inner.consts[1].marshal_eq(&ValueObj::None));
+        assert!(inner_before
+            .iter()
+            .zip(loaded_consts(inner).iter())
+            .all(|(l, r)| l.marshal_eq(r)));
+        // canonicalization is idempotent and independent of the insertion order
+        let mut again = code.clone();
+        again.canonicalize_consts();
+        assert_eq!(again.diff(&code), None);
+    }
+
+    #[test]
+    fn test_canonicalize_consts_extended_arg() {
+        // a docstring and 300 constants in descending order: the index `i` becomes `301 - i`
+        let mut builder = CodeObjBuilder::new("f").const_("doc");
+        for i in (0..300u64).rev() {
+            builder = builder.const_(ValueObj::Nat(i));
+        }
+        let mut code = builder
+            .instr(LOAD_CONST, 300)
+            .instr(LOAD_CONST, 257)
+            .instr(LOAD_CONST, 101)
+            .build();
+        let before = loaded_consts(&code);
+        code.canonicalize_consts();
+        assert_sorted(&code.consts[1..]);
+        assert_eq!(loaded_consts(&code), before);
+        assert_eq!(
+            code.code,
+            vec![
+                EXTENDED_ARG as u8,
+                0,
+                LOAD_CONST as u8,
+                1,
+                EXTENDED_ARG as u8,
+                0,
+                LOAD_CONST as u8,
+                44,
+                LOAD_CONST as u8,
+                200
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pyc_roundtrip() {
+        let f = CodeObjBuilder::new("f")
+            .params(vec!["a"])
+            .instr(LOAD_FAST, 0)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        let code = CodeObjBuilder::new("<module>")
+            .const_(ValueObj::from(f))
+            .const_("f")
+            .const_(ValueObj::None)
+            .name("f")
+            .instr(LOAD_CONST, 0)
+            .instr(LOAD_CONST, 1)
+            .instr(MAKE_FUNCTION, 0)
+            .instr(STORE_NAME, 0)
+            .instr(LOAD_CONST, 2)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        let path = std::env::temp_dir().join("erg_test_pyc_roundtrip.pyc");
+        for python_ver in [3413, 3425, 3439] {
+            code.clone().dump_as_pyc(&path, Some(python_ver)).unwrap();
+            assert_eq!(CodeObj::from_pyc(&path).unwrap(), code);
+        }
+        // a file without the magic number is rejected
+        fs::write(&path, b"print(1)\n\n\n\n\n\n\n\n").unwrap();
+        let err = CodeObj::from_pyc(&path).unwrap_err();
+        assert!(err.desc.contains("magic number"));
+        // so is a truncated header
+        fs::write(&path, [0x55, 0x0D, 0x0D, 0x0A, 0, 0]).unwrap();
+        assert!(CodeObj::from_pyc(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_cpython_pyc() {
+        // `tests/pyc/sample.py` compiled by CPython 3.7 ~ 3.11
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/pyc");
+        for ver in ["37", "38", "39", "310", "311"] {
+            let code = CodeObj::from_pyc(dir.join(format!("sample{ver}.pyc"))).unwrap();
+            assert_eq!(&code.name[..], "<module>", "{ver}");
+            assert_eq!(
+                &code.names[..],
+                ["f", "C", "print", "m", "frozenset"].map(Str::ever),
+                "{ver}"
+            );
+            // the second "abc" is a reference to the first one
+            let abc = ValueObj::from("abc");
+            assert!(code
+                .consts
+                .contains(&ValueObj::Array(vec![abc.clone(), abc].into())));
+            assert!(code.consts.contains(&ValueObj::Bytes(vec![0])));
+            let codes = code
+                .consts
+                .iter()
+                .filter_map(|c| match c {
+                    ValueObj::Code(code) => Some(code),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(codes.len(), 2, "{ver}");
+            assert_eq!(&codes[0].name[..], "f");
+            assert_eq!(&codes[0].varnames[..], ["x", "y"].map(Str::ever));
+            assert_eq!(codes[0].argcount, 2);
+            assert_eq!(&codes[1].name[..], "C");
+            assert!(codes[1]
+                .consts
+                .iter()
+                .any(|c| matches!(c, ValueObj::Code(m) if &m.name[..] == "m")));
+            // `dis.findlinestarts` of each version (`co_linetable` since 3.10)
+            let line_starts = match ver {
+                "37" | "38" | "39" | "310" => vec![(0, 1), (10, 5), (24, 10)],
+                _ => vec![(0, 0), (2, 1), (10, 5), (36, 10)],
+            };
+            assert_eq!(code.line_starts(), line_starts, "{ver}");
+        }
+        // `PUSH_NULL` of 3.11 has the byte of `ROT_TWO`
+        let (code, python_ver) = CodeObj::from_pyc_with_version(dir.join("sample311.pyc")).unwrap();
+        let version = get_python_version(python_ver).unwrap();
+        assert!(version.is_same_minor(&PythonVersion::new(3, 11, 0)));
+        assert!(code.disassemble().contains("ROT_TWO"));
+        let table = OpcodeTable::for_version(version);
+        let dis = code.disassemble_with(&table);
+        assert!(!dis.contains("ROT_TWO"));
+        assert!(dis.contains("STORE_NAME               0 (f)"), "{dis}");
+        // the args of `LOAD_GLOBAL` in 3.11 (`namei << 1`) are out of `names`
+        assert!(code
+            .code_info_with(&table)
+            .contains("STORE_NAME               0 (f)"));
+    }
+
+    #[test]
+    fn test_linetable() {
+        // a line going back, a gap over 127 lines and a line spanning over 254 bytes
+        let mut instrs = vec![(NOP, 0); 200];
+        instrs.extend([(LOAD_CONST, 0), (RETURN_VALUE, 0)]);
+        let mut code = CodeObj::empty(vec![], "<string>", "<module>", 1);
+        code.consts = vec![ValueObj::None];
+        code.code = assemble(&instrs);
+        code.push_lnotab(0, 2); // line 3 at 0
+        code.push_lnotab(4, -1); // line 2 at 4
+        code.push_lnotab(6, 300); // line 302 at 10
+        code.push_lnotab(390, 1); // line 303 at 400
+        let line_starts = vec![(0, 3), (4, 2), (10, 302), (400, 303)];
+        assert_eq!(code.line_starts(), line_starts);
+        // the format of 3.9 is kept in memory
+        assert_eq!(code.linetable(3425), code.lnotab);
+        for python_ver in [3439, 3495] {
+            let mut bytes = code.clone().into_bytes(python_ver);
+            bytes.remove(0);
+            let loaded = CodeObj::from_bytes(&mut bytes, python_ver).unwrap();
+            assert_eq!(loaded.line_starts(), line_starts, "{python_ver}");
+        }
+        // (bytecode delta, line delta) for 3.10, applied before each range
+        assert_eq!(
+            code.linetable(3439),
+            [4, 2, 6, -1i8 as u8, 0, 127, 0, 127, 254, 46, 136, 0, 4, 1]
+        );
+        // "no columns" entries of up to 8 code units for 3.11
+        let table = code.linetable(3495);
+        assert_eq!(table[..4], [0x80 | 13 << 3 | 1, 4, 0x80 | 13 << 3 | 2, 3]);
+        // the matching CPython (if installed) reads the same line numbers
+        let path = std::env::temp_dir().join("erg_test_linetable.pyc");
+        for (python_ver, minor) in [(3439, 10), (3495, 11)] {
+            let python = format!("python3.{minor}");
+            let installed = Command::new(&python)
+                .arg("--version")
+                .output()
+                .is_ok_and(|out| out.status.success());
+            if !installed {
+                continue;
+            }
+            code.clone().dump_as_pyc(&path, Some(python_ver)).unwrap();
+            let script = format!(
+                "import dis, marshal; \
+                 code = marshal.loads(open({path:?}, 'rb').read()[16:]); \
+                 print(list(dis.findlinestarts(code)))"
+            );
+            let out = Command::new(&python)
+                .arg("-c")
+                .arg(script)
+                .output()
+                .unwrap();
+            assert!(
+                out.status.success(),
+                "{}",
+                String::from_utf8_lossy(&out.stderr)
+            );
+            assert_eq!(
+                String::from_utf8_lossy(&out.stdout).trim(),
+                format!("{line_starts:?}"),
+                "{python_ver}"
+            );
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_marshal_refs() {
+        // def f(print): print(print)
+        let f = CodeObjBuilder::new("f")
+            .params(vec!["print"])
+            .instr(LOAD_FAST, 0)
+            .instr(LOAD_FAST, 0)
+            .instr(CALL_FUNCTION, 1)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        let code = CodeObjBuilder::new("<module>")
+            .const_(ValueObj::from(f))
+            .const_("f")
+            .const_(ValueObj::None)
+            .name("f")
+            .name("print")
+            .instr(LOAD_CONST, 0)
+            .instr(LOAD_CONST, 1)
+            .instr(MAKE_FUNCTION, 0)
+            .instr(STORE_NAME, 0)
+            .instr(LOAD_CONST, 2)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        let bytes = code.clone().into_bytes(3425);
+        // "print" (the varname of `f`) is written once with FLAG_REF, then referred to by `Ref`
+        let print = [DataTypePrefix::ShortAsciiInterned as u8 | FLAG_REF, 5];
+        let flagged = bytes
+            .windows(7)
+            .filter(|w| w[..2] == print && &w[2..] == b"print")
+            .count();
+        assert_eq!(flagged, 1);
+        assert!(bytes.contains(&(DataTypePrefix::Ref as u8)));
+        let mut v = bytes;
+        v.remove(0);
+        assert_eq!(CodeObj::from_bytes(&mut v, 3425).unwrap(), code);
+        // a reference to an unknown index is rejected
+        let mut broken = vec![DataTypePrefix::Ref as u8, 9, 0, 0, 0];
+        assert!(Deserializer::new()
+            .deserialize_const(&mut broken, 3425)
+            .is_err());
+    }
+
+    #[test]
+    fn test_dump_as_pyc() {
+        // print("ok")
+        let code = CodeObjBuilder::new("<module>")
+            .name("print")
+            .const_("ok")
+            .const_(ValueObj::None)
+            .instr(LOAD_NAME, 0)
+            .instr(LOAD_CONST, 0)
+            .instr(CALL_FUNCTION, 1)
+            .instr(POP_TOP, 0)
+            .instr(LOAD_CONST, 1)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        let dir = std::env::temp_dir();
+        for (minor, python_ver) in [(7, 3394), (8, 3413), (9, 3425), (10, 3439)] {
+            let module = format!("erg_test_dump_as_pyc_3_{minor}");
+            let path = dir.join(format!("{module}.pyc"));
+            code.clone().dump_as_pyc(&path, Some(python_ver)).unwrap();
+            let header = fs::read(&path).unwrap();
+            assert_eq!(header[..4], get_magic_num_bytes(python_ver));
+            assert_eq!(header[4..8], [0; 4]);
+            // the matching CPython (if installed) can import the file
+            let python = format!("python3.{minor}");
+            let installed = Command::new(&python)
+                .arg("--version")
+                .output()
+                .is_ok_and(|out| out.status.success());
+            if installed {
+                let out = Command::new(&python)
+                    .arg("-c")
+                    .arg(format!("import {module}"))
+                    .current_dir(&dir)
+                    .output()
+                    .unwrap();
+                assert!(
+                    out.status.success(),
+                    "{}",
+                    String::from_utf8_lossy(&out.stderr)
+                );
+                assert_eq!(out.stdout, b"ok\n");
+            }
+            fs::remove_file(&path).unwrap();
+        }
+        let path = dir.join("erg_test_dump_as_pyc.pyc");
+        // a pre-release magic number is replaced with that of the final release
+        code.clone().dump_as_pyc(&path, Some(3430)).unwrap();
+        assert_eq!(fs::read(&path).unwrap()[..4], get_magic_num_bytes(3439));
+        fs::remove_file(&path).unwrap();
+        // unsupported versions (3.6, 3.12) are rejected without writing anything
+        for python_ver in [3379, 3531] {
+            assert!(code.clone().dump_as_pyc(&path, Some(python_ver)).is_err());
+            assert!(!path.exists());
+        }
+    }
+
+    #[test]
+    fn test_disassemble() {
+        // def f(a, b):
+        //     if a < b:
+        //         return a + 1
+        //     for x in b:
+        //         a = -x
+        //     return g(a, b)
+        let mut code = CodeObjBuilder::new("f")
+            .params(vec!["a", "b"])
+            .varname("x")
+            .name("g")
+            .const_(ValueObj::None)
+            .const_(1)
+            .instr(LOAD_FAST, 0)
+            .instr(LOAD_FAST, 1)
+            .instr(COMPARE_OP, 0)
+            .instr(POP_JUMP_IF_FALSE, 8)
+            .instr(LOAD_FAST, 0)
+            .instr(LOAD_CONST, 1)
+            .instr(BINARY_ADD, 0)
+            .instr(RETURN_VALUE, 0)
+            .instr(LOAD_FAST, 1)
+            .instr(GET_ITER, 0)
+            .instr(FOR_ITER, 5)
+            .instr(STORE_FAST, 2)
+            .instr(LOAD_FAST, 2)
+            .instr(UNARY_NEGATIVE, 0)
+            .instr(STORE_FAST, 0)
+            .instr(JUMP_ABSOLUTE, 10)
+            .instr(LOAD_GLOBAL, 0)
+            .instr(LOAD_FAST, 0)
+            .instr(LOAD_FAST, 1)
+            .instr(CALL_FUNCTION, 2)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        code.firstlineno = 2;
+        code.lnotab = vec![0, 1, 8, 1, 8, 1, 8, 1, 8, 1];
+        // the output of `dis.dis(f)` (CPython 3.10)
+        let expect = "  3           0 LOAD_FAST                0 (a)
+              2 LOAD_FAST                1 (b)
+              4 COMPARE_OP               0 (<)
+              6 POP_JUMP_IF_FALSE        8 (to 16)
+
+  4           8 LOAD_FAST                0 (a)
+             10 LOAD_CONST               1 (1)
+             12 BINARY_ADD
+             14 RETURN_VALUE
+
+  5     >>   16 LOAD_FAST                1 (b)
+             18 GET_ITER
+        >>   20 FOR_ITER                 5 (to 32)
+             22 STORE_FAST               2 (x)
+
+  6          24 LOAD_FAST                2 (x)
+             26 UNARY_NEGATIVE
+             28 STORE_FAST               0 (a)
+             30 JUMP_ABSOLUTE           10 (to 20)
+
+  7     >>   32 LOAD_GLOBAL              0 (g)
+             34 LOAD_FAST                0 (a)
+             36 LOAD_FAST                1 (b)
+             38 CALL_FUNCTION            2
+             40 RETURN_VALUE
+";
+        assert_eq!(code.disassemble(), expect);
+        // nested code objects are listed after their parent, indented
+        let module = CodeObjBuilder::new("<module>")
+            .const_(ValueObj::from(code.clone()))
+            .const_(ValueObj::None)
+            .instr(LOAD_CONST, 0)
+            .instr(POP_TOP, 0)
+            .instr(LOAD_CONST, 1)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        let dis = module.disassemble();
+        let (outer, inner) = dis
+            .split_once("\nDisassembly of <code object f at ")
+            .unwrap();
+        assert!(outer.starts_with("  1           0 LOAD_CONST               0 (<code object f"));
+        let inner = inner.split_once(":\n").unwrap().1;
+        for (nested, line) in inner.lines().zip(expect.lines()) {
+            if line.is_empty() {
+                assert!(nested.is_empty());
+            } else {
+                assert_eq!(nested, format!("    {line}"));
+            }
+        }
+        // constants are shown with their `repr`
+        // x = "a\nb"
+        // y = (1,)
+        // z = 1.0
+        let mut code = CodeObjBuilder::new("<module>")
+            .const_("a\nb")
+            .const_(ValueObj::Array(vec![ValueObj::Nat(1)].into()))
+            .const_(1.0)
+            .const_(ValueObj::None)
+            .name("x")
+            .name("y")
+            .name("z")
+            .instr(LOAD_CONST, 0)
+            .instr(STORE_NAME, 0)
+            .instr(LOAD_CONST, 1)
+            .instr(STORE_NAME, 1)
+            .instr(LOAD_CONST, 2)
+            .instr(STORE_NAME, 2)
+            .instr(LOAD_CONST, 3)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        code.lnotab = vec![4, 1, 4, 1];
+        // the output of `dis.dis` and `dis.show_code` (CPython 3.10)
+        let expect = "  1           0 LOAD_CONST               0 ('a\\nb')
+              2 STORE_NAME               0 (x)
+
+  2           4 LOAD_CONST               1 ((1,))
+              6 STORE_NAME               1 (y)
+
+  3           8 LOAD_CONST               2 (1.0)
+             10 STORE_NAME               2 (z)
+             12 LOAD_CONST               3 (None)
+             14 RETURN_VALUE
+";
+        assert_eq!(code.disassemble(), expect);
+        assert!(code
+            .code_info()
+            .contains("Constants:\n   0: 'a\\nb'\n   1: (1,)\n   2: 1.0\n   3: None\n"));
+    }
+
+    #[test]
+    fn test_exception_table() {
+        // def f():
+        //     try:
+        //         g()
+        //     except:
+        //         pass
+        let entries = [
+            ExceptionTableEntry {
+                start: 4,
+                end: 32,
+                target: 36,
+                depth: 0,
+                lasti: false,
+            },
+            ExceptionTableEntry {
+                start: 36,
+                end: 40,
+                target: 46,
+                depth: 1,
+                lasti: true,
+            },
+        ];
+        let mut code = CodeObj::empty(vec![], "<string>", "f", 1);
+        for entry in entries {
+            code.push_exception_handler(entry);
+        }
+        // `f.__code__.co_exceptiontable` (CPython 3.11)
+        assert_eq!(code.exceptiontable, [130, 14, 18, 0, 146, 2, 23, 3]);
+        assert_eq!(code.exception_handlers(), entries);
+        // values over 6 bits are split into multiple bytes
+        let large = ExceptionTableEntry {
+            start: 2 * 100,
+            end: 2 * 5000,
+            target: 2 * 300000,
+            depth: 3,
+            lasti: false,
+        };
+        let mut code = CodeObj::empty(vec![], "<string>", "f", 1);
+        code.push_exception_handler(large);
+        assert_eq!(code.exceptiontable[..2], [0x80 | 0x40 | 1, 36]);
+        assert_eq!(code.exception_handlers(), [large]);
+    }
+
+    #[test]
+    fn test_pyc_roundtrip_3_11() {
+        // def h(a):
+        //     def k(): return a
+        //     return k
+        let mut code = CodeObjBuilder::new("h")
+            .params(vec!["a"])
+            .varname("k")
+            .const_(ValueObj::None)
+            .instr(LOAD_FAST, 1)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        code.cellvars.push(Str::ever("a"));
+        code.qualname = Str::ever("C.h");
+        code.push_exception_handler(ExceptionTableEntry {
+            start: 0,
+            end: 2,
+            target: 2,
+            depth: 0,
+            lasti: false,
+        });
+        let module = CodeObjBuilder::new("<module>")
+            .const_(ValueObj::from(code.clone()))
+            .const_(ValueObj::None)
+            .instr(LOAD_CONST, 1)
+            .instr(RETURN_VALUE, 0)
+            .build();
+        let path = std::env::temp_dir().join("erg_test_pyc_roundtrip_3_11.pyc");
+        module.clone().dump_as_pyc(&path, Some(3495)).unwrap();
+        assert_eq!(CodeObj::from_pyc(&path).unwrap(), module);
+        // the exception table and the qualified name are not written for older versions
+        module.clone().dump_as_pyc(&path, Some(3439)).unwrap();
+        let loaded = CodeObj::from_pyc(&path).unwrap();
+        let ValueObj::Code(h) = &loaded.consts[0] else {
+            panic!()
+        };
+        assert!(h.exceptiontable.is_empty());
+        assert_eq!(&h.qualname[..], "h");
+        assert_eq!(h.varnames, code.varnames);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+extern crate erg_common;
+
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use erg_common::config::{ErgConfig, Input};
+
+    #[test]
+    fn test_input_path() {
+        let file = Input::File("examples/fib.er".into());
+        assert_eq!(file.path(), Some(Path::new("examples/fib.er")));
+        assert_eq!(file.enclosed_name(), "examples/fib.er");
+
+        assert_eq!(Input::REPL.path(), None);
+        assert_eq!(Input::REPL.enclosed_name(), "<stdin>");
+
+        let pipe = Input::Pipe("print! 1".into());
+        assert_eq!(pipe.path(), None);
+        assert_eq!(pipe.enclosed_name(), "<stdin>");
+
+        let string = Input::Str("print! 1".into());
+        assert_eq!(string.path(), None);
+        assert_eq!(string.enclosed_name(), "<string>");
+
+        assert_eq!(Input::Dummy.path(), None);
+        assert_eq!(Input::Dummy.enclosed_name(), "<dummy>");
+    }
+
+    #[test]
+    fn test_input_mtime_and_source_hash() {
+        let path = std::env::temp_dir().join("erg_test_input_source_hash.er");
+        fs::write(&path, "x = 1\n").unwrap();
+        let file = Input::File(path.to_str().unwrap().to_string().into());
+        assert!(file.mtime().is_some());
+        let hash = file.source_hash().unwrap();
+        assert_eq!(file.source_hash(), Some(hash));
+        fs::write(&path, "x = 2\n").unwrap();
+        assert!(file.mtime().is_some());
+        assert_ne!(file.source_hash(), Some(hash));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(file.mtime(), None);
+        assert_eq!(file.source_hash(), None);
+
+        for input in [
+            Input::REPL,
+            Input::Pipe("x = 1".into()),
+            Input::Str("x = 1".into()),
+            Input::Dummy,
+        ] {
+            assert_eq!(input.mtime(), None);
+            assert_eq!(input.source_hash(), None);
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let cli = ErgConfig {
+            opt_level: Some(2),
+            ..ErgConfig::default()
+        };
+        let env = ErgConfig {
+            python_ver: Some(3531),
+            erg_vm: Some(true),
+            ..ErgConfig::default()
+        };
+        let cfg = cli.merge(env);
+        assert_eq!(cfg.opt_level, Some(2));
+        assert_eq!(cfg.python_ver, Some(3531));
+        assert!(cfg.erg_vm());
+        // fields set on both sides: `self` wins
+        let cfg = ErgConfig {
+            python_ver: Some(3425),
+            ..ErgConfig::default()
+        }
+        .merge(cfg);
+        assert_eq!(cfg.opt_level(), 2);
+        assert_eq!(cfg.python_ver, Some(3425));
+        // the default values and `false` can be set explicitly
+        let cfg = ErgConfig {
+            opt_level: Some(1),
+            coverage: Some(false),
+            ..ErgConfig::default()
+        }
+        .merge(ErgConfig {
+            coverage: Some(true),
+            ..cfg
+        });
+        assert_eq!(cfg.opt_level(), 1);
+        assert!(!cfg.coverage());
+        // unset on both sides
+        assert_eq!(cfg.verbose, None);
+        assert_eq!(cfg.verbose(), 2);
+        assert_eq!(cfg.mode(), "exec");
+        assert!(!ErgConfig::default().erg_vm());
+        // `Input::REPL` stands for no input
+        let cfg = ErgConfig::default().merge(ErgConfig {
+            input: Input::Str("x = 1".into()),
+            ..ErgConfig::default()
+        });
+        assert_eq!(cfg.input, Input::Str("x = 1".into()));
+    }
+}
@@ -0,0 +1,147 @@
+extern crate erg_common;
+
+mod tests {
+    use erg_common::codeobj::{CodeObj, CodeObjBuilder};
+    use erg_common::deserialize::Deserializer;
+    use erg_common::long::Long;
+    use erg_common::opcode::Opcode::*;
+    use erg_common::value::ValueObj;
+
+    /// xorshift64* (a fixed seed makes failures reproducible)
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    const CHARS: [char; 8] = ['a', 'Z', '0', '_', ' ', 'é', 'あ', '😀'];
+
+    fn gen_str(rng: &mut Rng) -> String {
+        let len = [0, 1, 8, 255, 256, 300][rng.below(6)];
+        // half of the strings are ASCII only
+        let chars = if rng.below(2) == 0 { 5 } else { CHARS.len() };
+        (0..len).map(|_| CHARS[rng.below(chars)]).collect()
+    }
+
+    /// generates values in the normal form of the deserializer
+    /// (e.g. `Nat`s that fit in `Int32` come back as `Int`s, so they are not generated)
+    fn gen_scalar(rng: &mut Rng) -> ValueObj {
+        match rng.below(10) {
+            0 => ValueObj::Int(rng.next() as i32),
+            1 => ValueObj::Int([0, -1, i32::MIN, i32::MAX][rng.below(4)]),
+            2 => ValueObj::Nat(rng.next().max(1 << 31)),
+            3 => {
+                let digits = (0..rng.below(10) + 5)
+                    .map(|_| (rng.next() & 0x7FFF) as u16)
+                    .chain([1])
+                    .collect();
+                ValueObj::from(Long::from_digits(rng.below(2) == 0, digits))
+            }
+            4 => {
+                let f = f64::from_bits(rng.next());
+                ValueObj::Float(if f.is_nan() { 0.0 } else { f })
+            }
+            5 => ValueObj::Float([0.0, -0.0, f64::INFINITY, f64::MIN_POSITIVE][rng.below(4)]),
+            6 => ValueObj::Str(gen_str(rng).into()),
+            7 => ValueObj::Bytes((0..rng.below(4)).map(|_| rng.next() as u8).collect()),
+            8 => [ValueObj::True, ValueObj::False][rng.below(2)].clone(),
+            _ => [ValueObj::None, ValueObj::Ellipsis][rng.below(2)].clone(),
+        }
+    }
+
+    fn gen_code(rng: &mut Rng, depth: usize) -> CodeObj {
+        let names = ["print", "x", "f", "self"];
+        let mut builder = CodeObjBuilder::new(names[rng.below(names.len())])
+            .params(vec!["self"; rng.below(2)])
+            .filename("<fuzz>");
+        for _ in 0..rng.below(4) {
+            builder = builder.const_(gen_value(rng, depth + 1));
+        }
+        for _ in 0..rng.below(4) {
+            builder = builder.name(names[rng.below(names.len())]);
+        }
+        builder
+            .const_(ValueObj::None)
+            .instr(LOAD_CONST, 0)
+            .instr(RETURN_VALUE, 0)
+            .build()
+    }
+
+    fn gen_value(rng: &mut Rng, depth: usize) -> ValueObj {
+        if depth > 3 {
+            return gen_scalar(rng);
+        }
+        match rng.below(10) {
+            0 => {
+                let elems = (0..rng.below(4))
+                    .map(|_| gen_value(rng, depth + 1))
+                    .collect();
+                ValueObj::Array(elems)
+            }
+            // over the limit of SmallTuple
+            1 if depth == 0 => {
+                let elems = (0..256).map(|_| gen_scalar(rng)).collect();
+                ValueObj::Array(elems)
+            }
+            2 => {
+                let entries = (0..rng.below(4))
+                    .map(|_| (gen_scalar(rng), gen_value(rng, depth + 1)))
+                    .collect();
+                ValueObj::Dict(entries)
+            }
+            3 => ValueObj::from(gen_code(rng, depth)),
+            _ => gen_scalar(rng),
+        }
+    }
+
+    fn marshal_roundtrip(obj: ValueObj) {
+        let mut bytes = obj.clone().into_bytes(3425);
+        let res = Deserializer::new().deserialize_const(&mut bytes, 3425);
+        assert_eq!(res.unwrap(), obj);
+        assert!(bytes.is_empty(), "{} bytes are left: {obj}", bytes.len());
+    }
+
+    #[test]
+    fn test_marshal_roundtrip_fuzz() {
+        let mut rng = Rng(0x5EED_0E59);
+        for _ in 0..500 {
+            marshal_roundtrip(gen_value(&mut rng, 0));
+        }
+    }
+
+    #[test]
+    fn test_marshal_roundtrip_edge_cases() {
+        marshal_roundtrip(ValueObj::Str("".into()));
+        marshal_roundtrip(ValueObj::Array(vec![].into()));
+        marshal_roundtrip(ValueObj::Dict(vec![].into()));
+        marshal_roundtrip(ValueObj::Nat(u64::MAX));
+        // too long for ShortAscii
+        marshal_roundtrip(ValueObj::Str("a".repeat(256).into()));
+        // a code object in a tuple shares the reference table with the enclosing one
+        let inner = CodeObjBuilder::new("f").params(vec!["x"]).build();
+        let outer = CodeObjBuilder::new("g")
+            .const_(ValueObj::Array(vec![ValueObj::from(inner)].into()))
+            .name("g")
+            .build();
+        marshal_roundtrip(ValueObj::from(outer));
+        // deeply nested containers
+        let mut nested = ValueObj::None;
+        for i in 0..200 {
+            nested = if i % 2 == 0 {
+                ValueObj::Array(vec![nested].into())
+            } else {
+                ValueObj::Dict(vec![(ValueObj::Int(i), nested)].into())
+            };
+        }
+        marshal_roundtrip(nested);
+    }
+}
@@ -0,0 +1,190 @@
+extern crate erg_common;
+
+mod tests {
+    use std::panic;
+
+    use erg_common::opcode::{CompareOp, Opcode, OpcodeTable, HAVE_ARGUMENT};
+    use erg_common::python_util::PythonVersion;
+
+    #[test]
+    fn test_opcode_description() {
+        assert_eq!(
+            Opcode::LOAD_CONST.description(),
+            "Pushes co_consts[arg] onto the stack"
+        );
+        // `Opcode::from` panics on unknown bytes
+        panic::set_hook(Box::new(|_| {}));
+        let ops = (0..=u8::MAX)
+            .filter_map(|byte| panic::catch_unwind(|| Opcode::from(byte)).ok())
+            .collect::<Vec<_>>();
+        let _ = panic::take_hook();
+        assert!(!ops.is_empty());
+        for op in ops {
+            assert!(!op.description().is_empty(), "{op}");
+        }
+    }
+
+    #[test]
+    fn test_compare_op_arg() {
+        use CompareOp::*;
+        let ops = [LT, LE, EQ, NE, GT, GE];
+        // taken from `dis` of each CPython version
+        let py39 = ops.map(|op| op.arg(3425));
+        assert_eq!(py39, [0, 1, 2, 3, 4, 5]);
+        let py311 = ops.map(|op| op.arg(3495));
+        assert_eq!(py311, [0, 1, 2, 3, 4, 5]);
+        let py312 = ops.map(|op| op.arg(3531));
+        assert_eq!(py312, [2, 26, 40, 55, 68, 92]);
+        let py313 = ops.map(|op| op.arg(3571));
+        assert_eq!(py313, [2, 42, 72, 103, 132, 172]);
+    }
+
+    #[test]
+    fn test_opcode_to_byte() {
+        let py37 = PythonVersion::new(3, 7, 0);
+        let py38 = PythonVersion::new(3, 8, 0);
+        let py39 = PythonVersion::new(3, 9, 0);
+        let py310 = PythonVersion::new(3, 10, 0);
+        // taken from `opcode.opmap` of each CPython version
+        assert_eq!(Opcode::LOAD_CONST.to_byte(py38), Ok(100));
+        assert_eq!(Opcode::LOAD_CONST.to_byte(py310), Ok(100));
+        assert!(Opcode::RERAISE.to_byte(py38).is_err());
+        assert_eq!(Opcode::RERAISE.to_byte(py39), Ok(48));
+        assert_eq!(Opcode::RERAISE.to_byte(py310), Ok(119));
+        assert!(Opcode::ROT_FOUR.to_byte(py37).is_err());
+        assert_eq!(Opcode::ROT_FOUR.to_byte(py38), Ok(6));
+        assert_eq!(Opcode::BUILD_TUPLE_UNPACK_WITH_CALL.to_byte(py38), Ok(158));
+        assert!(Opcode::BUILD_TUPLE_UNPACK_WITH_CALL.to_byte(py310).is_err());
+        assert!(Opcode::IS_OP.to_byte(py38).is_err());
+        assert_eq!(Opcode::IS_OP.to_byte(py310), Ok(117));
+        assert!(Opcode::MATCH_CLASS.to_byte(py39).is_err());
+        assert_eq!(Opcode::MATCH_CLASS.to_byte(py310), Ok(152));
+        let err = Opcode::GET_LEN.to_byte(py38).unwrap_err();
+        assert_eq!(err.to_string(), "GET_LEN is not available in Python 3.8.0");
+        // Erg-specific opcodes are not affected
+        assert_eq!(Opcode::ERG_POP_NTH.to_byte(py37), Ok(196));
+    }
+
+    #[test]
+    fn test_opcode_has_arg() {
+        assert_eq!(Opcode::STORE_NAME as u8, HAVE_ARGUMENT);
+        assert!(Opcode::STORE_NAME.has_arg());
+        assert!(!Opcode::POP_EXCEPT.has_arg());
+        assert!(!Opcode::POP_TOP.has_arg());
+        assert!(Opcode::DICT_MERGE.has_arg());
+        // Erg-specific opcodes take an arg until 219
+        assert!(Opcode::ERG_CALL_PURE_FUNCTION.has_arg());
+        assert!(!Opcode::ERG_LOAD_EMPTY_SLOT.has_arg());
+        assert!(!Opcode::NOT_IMPLEMENTED.has_arg());
+    }
+
+    #[test]
+    fn test_opcode_stack_effect() {
+        use Opcode::*;
+        // taken from `dis.stack_effect` of CPython 3.9
+        let cases = [
+            (LOAD_CONST, 0, false, 1),
+            (POP_TOP, 0, false, -1),
+            (CALL_FUNCTION, 3, false, -3),
+            (CALL_FUNCTION_KW, 2, false, -3),
+            (CALL_METHOD, 1, false, -2),
+            (CALL_FUNCTION_EX, 1, false, -2),
+            (BUILD_LIST, 4, false, -3),
+            (BUILD_MAP, 2, false, -3),
+            (BUILD_CONST_KEY_MAP, 3, false, -3),
+            (UNPACK_SEQUENCE, 3, false, 2),
+            (UNPACK_EX, 0x102, false, 3),
+            (MAKE_FUNCTION, 0x09, false, -3),
+            (FOR_ITER, 4, true, -1),
+            (FOR_ITER, 4, false, 1),
+            (SETUP_WITH, 4, true, 6),
+            (SETUP_WITH, 4, false, 1),
+            (JUMP_IF_TRUE_OR_POP, 4, true, 0),
+            (JUMP_IF_TRUE_OR_POP, 4, false, -1),
+            (POP_JUMP_IF_FALSE, 4, true, -1),
+            (FORMAT_VALUE, 0x04, false, -1),
+            (STORE_SUBSCR, 0, false, -3),
+            (RERAISE, 0, false, -3),
+            (EXTENDED_ARG, 1, false, 0),
+            // CPython 3.10
+            (MATCH_CLASS, 2, false, -1),
+        ];
+        for (op, arg, jump, effect) in cases {
+            assert_eq!(op.stack_effect(arg, jump), Some(effect), "{op}");
+        }
+        assert_eq!(ERG_LOAD_EMPTY_SLOT.stack_effect(0, false), None);
+    }
+
+    #[test]
+    fn test_opcode_name_roundtrip() {
+        let py310 = PythonVersion::new(3, 10, 0);
+        let mut count = 0;
+        for byte in 0..=u8::MAX {
+            let Some(op) = Opcode::from_u8(byte, py310) else {
+                continue;
+            };
+            if byte < Opcode::ERG_POP_NTH as u8 {
+                count += 1;
+            }
+            assert_eq!(op.to_byte(py310), Ok(byte));
+            assert_eq!(Opcode::from_name(op.name()), Some(op));
+            assert_eq!(op.to_string(), op.name());
+        }
+        // the instructions of CPython 3.10 defined in `Opcode`
+        assert_eq!(count, 101);
+        assert_eq!(Opcode::from_u8(119, py310), Some(Opcode::RERAISE));
+        assert_eq!(Opcode::from_u8(48, py310), None);
+        assert_eq!(Opcode::DUP_TOP2.name(), "DUP_TOP_TWO");
+        assert_eq!(Opcode::from_name("LOAD_FAST"), Some(Opcode::LOAD_FAST));
+        assert_eq!(Opcode::from_name("load_fast"), None);
+        // `from_u8` is the inverse of `to_byte` in every supported version
+        for minor in 7..=11 {
+            let version = PythonVersion::new(3, minor, 0);
+            for op in (0..=u8::MAX).filter_map(Opcode::try_from_byte) {
+                if let Ok(byte) = op.to_byte(version) {
+                    assert_eq!(
+                        Opcode::from_u8(byte, version),
+                        Some(op),
+                        "{op} in {version}"
+                    );
+                }
+            }
+        }
+        assert_eq!(
+            Opcode::from_u8(137, PythonVersion::new(3, 11, 0)),
+            Some(Opcode::LOAD_DEREF)
+        );
+        // only Erg-specific opcodes are known in an unsupported version
+        let py312 = PythonVersion::new(3, 12, 0);
+        assert_eq!(Opcode::from_u8(Opcode::LOAD_CONST as u8, py312), None);
+        assert_eq!(
+            Opcode::from_u8(Opcode::ERG_POP_NTH as u8, py312),
+            Some(Opcode::ERG_POP_NTH)
+        );
+    }
+
+    #[test]
+    fn test_opcode_table_version() {
+        let erg_vm = OpcodeTable::new();
+        let py311 = OpcodeTable::for_version(PythonVersion::new(3, 11, 0));
+        assert_eq!(erg_vm.decode(137), Some(Opcode::STORE_DEREF));
+        assert_eq!(py311.decode(137), Some(Opcode::LOAD_DEREF));
+        assert_eq!(py311.decode(Opcode::BINARY_ADD as u8), None);
+        assert_eq!(py311.encode(Opcode::LOAD_DEREF), 137);
+        assert_eq!(erg_vm.encode(Opcode::LOAD_DEREF), Opcode::LOAD_DEREF as u8);
+    }
+
+    #[test]
+    fn test_opcode_table_jump_arg() {
+        let erg_vm = OpcodeTable::new();
+        let py39 = OpcodeTable::for_version(PythonVersion::new(3, 9, 0));
+        let py310 = OpcodeTable::for_version(PythonVersion::new(3, 10, 0));
+        // jumps count bytes until Python 3.9, and instructions since 3.10
+        assert_eq!(py39.jump_arg(14), 14);
+        assert_eq!(py39.jump_bytes(14), 14);
+        assert_eq!(py310.jump_arg(14), 7);
+        assert_eq!(py310.jump_bytes(7), 14);
+        assert_eq!(erg_vm.jump_arg(14), 7);
+        assert_eq!(erg_vm.jump_bytes(7), 14);
+    }
+}
@@ -0,0 +1,10 @@
+def f(x, y=1):
+    return x + y, "abc"
+
+
+class C:
+    def m(self):
+        return f(1)
+
+
+print(f(2), C().m(), b"\x00", 1.5, 3j, frozenset({3}), ("abc", "abc"))
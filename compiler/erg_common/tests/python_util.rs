@@ -0,0 +1,171 @@
+extern crate erg_common;
+
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process::Command;
+    use std::sync::Mutex;
+
+    use erg_common::python_util::{
+        detect_magic_number, detect_python_version, find_python_executable, magic_number, run_pyc,
+        PythonVersion,
+    };
+
+    /// the tests that modify `$PATH` or `$PYTHON` must not run in parallel
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_parse_python_version() {
+        assert_eq!(
+            PythonVersion::parse("Python 3.10.13\n"),
+            Some(PythonVersion::new(3, 10, 13))
+        );
+        assert_eq!(
+            PythonVersion::parse("Python 3.12.0rc1"),
+            Some(PythonVersion::new(3, 12, 0))
+        );
+        assert_eq!(
+            PythonVersion::parse("Python 2.7"),
+            Some(PythonVersion::new(2, 7, 0))
+        );
+        assert_eq!(
+            PythonVersion::parse("pyenv: python3: command not found"),
+            None
+        );
+        assert_eq!(PythonVersion::parse("Python"), None);
+        assert_eq!(PythonVersion::parse(""), None);
+    }
+
+    #[test]
+    fn test_magic_number() {
+        // `importlib.util.MAGIC_NUMBER` of each CPython version
+        assert_eq!(
+            magic_number(PythonVersion::new(3, 8, 10)),
+            Some([0x55, 0x0d, 0x0d, 0x0a])
+        );
+        assert_eq!(
+            magic_number(PythonVersion::new(3, 9, 0)),
+            Some([0x61, 0x0d, 0x0d, 0x0a])
+        );
+        assert_eq!(u16::from_le_bytes([0x55, 0x0d]), 3413);
+        assert_eq!(u16::from_le_bytes([0x61, 0x0d]), 3425);
+        assert_eq!(magic_number(PythonVersion::new(3, 6, 0)), None);
+        assert_eq!(magic_number(PythonVersion::new(3, 12, 0)), None);
+    }
+
+    #[test]
+    fn test_detect_python_version() {
+        let _lock = ENV_LOCK.lock().unwrap();
+        env::set_var("PYTHON", "erg-no-such-python");
+        assert_eq!(detect_python_version(), None);
+        assert_eq!(detect_magic_number(), None);
+        env::remove_var("PYTHON");
+        let Ok(out) = Command::new("python3").arg("--version").output() else {
+            return;
+        };
+        let expected = PythonVersion::parse(&String::from_utf8_lossy(&out.stdout));
+        assert_eq!(detect_python_version(), expected);
+        let expected_magic = expected
+            .and_then(magic_number)
+            .map(|magic| u32::from(u16::from_le_bytes([magic[0], magic[1]])));
+        assert_eq!(detect_magic_number(), expected_magic);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_python_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _lock = ENV_LOCK.lock().unwrap();
+        let stub = env::temp_dir().join(format!("erg_test_find_python_{}", std::process::id()));
+        let broken = stub.join("broken");
+        let working = stub.join("working");
+        fs::create_dir_all(&broken).unwrap();
+        fs::create_dir_all(&working).unwrap();
+        let write_exe = |path: &std::path::Path, script: &str| {
+            fs::write(path, script).unwrap();
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        // exists but does not work
+        write_exe(&broken.join("python3"), "#!/bin/sh\nexit 1\n");
+        write_exe(&working.join("python3"), "#!/bin/sh\necho 'Python 3.9.1'\n");
+        // not executable
+        fs::write(working.join("python"), "").unwrap();
+        let path = env::var_os("PATH");
+        let python = env::var_os("PYTHON");
+        env::remove_var("PYTHON");
+        env::set_var("PATH", env::join_paths([&broken, &working]).unwrap());
+        assert_eq!(find_python_executable(), Some(working.join("python3")));
+        // `$PYTHON` takes precedence
+        write_exe(&working.join("py39"), "#!/bin/sh\necho 'Python 3.9.2'\n");
+        env::set_var("PYTHON", "py39");
+        assert_eq!(find_python_executable(), Some(working.join("py39")));
+        env::set_var("PYTHON", broken.join("python3"));
+        assert_eq!(find_python_executable(), Some(working.join("python3")));
+        env::set_var("PATH", &broken);
+        assert_eq!(find_python_executable(), None);
+        match path {
+            Some(path) => env::set_var("PATH", path),
+            None => env::remove_var("PATH"),
+        }
+        match python {
+            Some(python) => env::set_var("PYTHON", python),
+            None => env::remove_var("PYTHON"),
+        }
+        fs::remove_dir_all(&stub).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_pyc_selects_version() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _lock = ENV_LOCK.lock().unwrap();
+        let stub = env::temp_dir().join(format!("erg_test_run_pyc_{}", std::process::id()));
+        fs::create_dir_all(&stub).unwrap();
+        let out = stub.join("out.txt");
+        let write_exe = |name: &str, version: &str| {
+            let path = stub.join(name);
+            let script = format!(
+                "#!/bin/sh\n[ \"$1\" = --version ] && echo 'Python {version}' && exit 0\necho {name} \"$@\" > {}\n",
+                out.display()
+            );
+            fs::write(&path, script).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        write_exe("python3", "3.10.2");
+        write_exe("python3.9", "3.9.2");
+        // the header of a .pyc file generated by Python 3.9
+        let pyc = stub.join("a.pyc");
+        fs::write(&pyc, [0x61, 0x0d, 0x0d, 0x0a, 0, 0, 0, 0]).unwrap();
+        let path = env::var_os("PATH");
+        let python = env::var_os("PYTHON");
+        env::remove_var("PYTHON");
+        env::set_var("PATH", &stub);
+        assert!(run_pyc(&pyc, ["arg"]).unwrap().success());
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            format!("python3.9 {} arg\n", pyc.display())
+        );
+        // `$PYTHON` is used only if the version matches
+        env::set_var("PYTHON", stub.join("python3"));
+        assert!(run_pyc(&pyc, ["arg"]).unwrap().success());
+        assert!(fs::read_to_string(&out).unwrap().starts_with("python3.9 "));
+        fs::remove_file(stub.join("python3.9")).unwrap();
+        let err = run_pyc(&pyc, ["arg"]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        // unknown magic number
+        fs::write(&pyc, [0x00, 0x0d, 0x0d, 0x0a, 0, 0, 0, 0]).unwrap();
+        let err = run_pyc(&pyc, ["arg"]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        match path {
+            Some(path) => env::set_var("PATH", path),
+            None => env::remove_var("PATH"),
+        }
+        match python {
+            Some(python) => env::set_var("PYTHON", python),
+            None => env::remove_var("PYTHON"),
+        }
+        fs::remove_dir_all(&stub).unwrap();
+    }
+}
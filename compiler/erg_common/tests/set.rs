@@ -0,0 +1,22 @@
+extern crate erg_common;
+
+mod tests {
+    use erg_common::set;
+    use erg_common::set::Set;
+
+    #[test]
+    fn test_set_retain() {
+        let mut s: Set<i32> = set! {1, 2, 3, 4, 5, 6};
+        s.retain(|n| n % 2 == 0);
+        assert_eq!(s, set! {2, 4, 6});
+    }
+
+    #[test]
+    fn test_set_drain() {
+        let mut s: Set<i32> = set! {1, 2, 3};
+        let mut drained = s.drain().collect::<Vec<_>>();
+        drained.sort();
+        assert_eq!(drained, vec![1, 2, 3]);
+        assert!(s.is_empty());
+    }
+}
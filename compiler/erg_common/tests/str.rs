@@ -0,0 +1,90 @@
+extern crate erg_common;
+
+mod tests {
+    use std::rc::Rc;
+
+    use erg_common::cache::Cache;
+    use erg_common::Str;
+
+    fn same(l: &Str, r: &Str) -> bool {
+        Rc::ptr_eq(&l.clone().into_rc(), &r.clone().into_rc())
+    }
+
+    #[test]
+    fn test_str_split() {
+        let cache = Cache::<str>::new();
+        let pieces = Str::ever("a.b..c")
+            .split_str(".", &cache)
+            .collect::<Vec<_>>();
+        assert_eq!(pieces, ["a", "b", "", "c"].map(Str::ever));
+        let pieces = Str::rc("erg.compiler")
+            .split_str(".", &cache)
+            .collect::<Vec<_>>();
+        assert_eq!(pieces, [Str::ever("erg"), Str::ever("compiler")]);
+        // no separator: the string itself is returned (and interned)
+        let s = Str::rc("erg.py");
+        let pieces = s.split_str("/", &cache).collect::<Vec<_>>();
+        assert_eq!(pieces.len(), 1);
+        assert!(same(&pieces[0], &s));
+        assert_eq!(
+            Str::ever("").split_str(".", &cache).collect::<Vec<_>>(),
+            [Str::ever("")]
+        );
+    }
+
+    #[test]
+    fn test_str_split_intern() {
+        let cache = Cache::<str>::new();
+        let pieces = Str::rc("erg.compiler.erg")
+            .split_str(".", &cache)
+            .collect::<Vec<_>>();
+        assert_eq!(pieces, ["erg", "compiler", "erg"].map(Str::ever));
+        // equal pieces share one string
+        assert!(same(&pieces[0], &pieces[2]));
+        assert!(same(&pieces[1], &cache.get("compiler")));
+        // a string which has no separator is also interned
+        let whole = Str::rc("erg");
+        let pieces = whole.split_str(".", &cache).collect::<Vec<_>>();
+        assert!(same(&pieces[0], &cache.get("erg")));
+        assert!(!same(&pieces[0], &whole));
+        // static strings are interned, too
+        let pieces = Str::ever("erg.compiler")
+            .split_str(".", &cache)
+            .collect::<Vec<_>>();
+        assert!(matches!(pieces[0], Str::Rc(_)));
+        assert!(same(&pieces[0], &cache.get("erg")));
+    }
+
+    #[test]
+    fn test_str_replace() {
+        let cache = Cache::<str>::new();
+        assert_eq!(
+            Str::rc("print!").replace_str("!", "__erg_proc__", &cache),
+            Str::ever("print__erg_proc__")
+        );
+        assert_eq!(
+            Str::ever("a!b!").replace_str("!", "", &cache),
+            Str::ever("ab")
+        );
+        // nothing to replace: the string itself is returned (and interned)
+        let s = Str::rc("print");
+        let replaced = s.replace_str("!", "__erg_proc__", &cache);
+        assert!(same(&replaced, &s));
+    }
+
+    #[test]
+    fn test_str_replace_intern() {
+        let cache = Cache::<str>::new();
+        let replaced = Str::rc("print!").replace_str("!", "__erg_proc__", &cache);
+        assert!(same(&replaced, &cache.get("print__erg_proc__")));
+        // the same result shares one string
+        let again = Str::rc("print$").replace_str("$", "__erg_proc__", &cache);
+        assert!(same(&replaced, &again));
+        // a string which has nothing to replace is also interned
+        let print = cache.get("print");
+        let replaced = Str::rc("print").replace_str("!", "__erg_proc__", &cache);
+        assert!(same(&replaced, &print));
+        let replaced = Str::ever("print").replace_str("$", "__erg_shared__", &cache);
+        assert!(same(&replaced, &print));
+    }
+}
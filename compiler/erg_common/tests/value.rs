@@ -0,0 +1,709 @@
+extern crate erg_common;
+
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use erg_common::codeobj::CodeObjBuilder;
+    use erg_common::deserialize::Deserializer;
+    use erg_common::dict;
+    use erg_common::long::Long;
+    use erg_common::serialize::{
+        complex_into_bytes, dict_into_bytes, list_into_bytes, set_into_bytes, DataTypePrefix,
+        RefTable,
+    };
+    use erg_common::set::Set;
+    use erg_common::ty::{TyParam, Type, TypeCode, TypePair};
+    use erg_common::value::ValueObj;
+    use erg_common::Str;
+
+    #[test]
+    fn test_nat_marshals_as_int() {
+        let nat = ValueObj::from_str(Type::Nat, "1".into());
+        assert_eq!(nat, ValueObj::Nat(1));
+        assert_eq!(TypeCode::from(&nat.class()), TypeCode::Nat64);
+        assert_eq!(
+            nat.into_bytes(3425),
+            vec![DataTypePrefix::Int32 as u8, 1, 0, 0, 0]
+        );
+        let int = ValueObj::from_str(Type::Int, "1".into());
+        assert_eq!(TypeCode::from(&int.class()), TypeCode::Int32);
+        // 2^31 = 2 * 2^30 -> digits [0, 0, 2]
+        let big = ValueObj::Nat(1 << 31);
+        assert_eq!(
+            big.into_bytes(3425),
+            vec![DataTypePrefix::Long as u8, 3, 0, 0, 0, 0, 0, 0, 0, 2, 0]
+        );
+    }
+
+    #[test]
+    fn test_type_code() {
+        let int_array = Type::array(Type::Int, TyParam::value(1usize));
+        let cases = [
+            (Type::Int, TypeCode::Int32, 1),
+            (Type::Nat, TypeCode::Nat64, 2),
+            (Type::Float, TypeCode::Float64, 3),
+            (Type::Bool, TypeCode::Bool, 4),
+            (Type::Str, TypeCode::Str, 5),
+            (int_array, TypeCode::Array, 7),
+            (Type::Obj, TypeCode::Other, 15),
+            (Type::mono("Bytes"), TypeCode::Bytes, 17),
+            (Type::dict(Type::Str, Type::Int), TypeCode::Dict, 18),
+            (Type::set(Type::Int), TypeCode::Set, 19),
+            (Type::frozenset(Type::Int), TypeCode::Set, 19),
+            (Type::NoneType, TypeCode::NoneType, 20),
+            (Type::mono("Complex"), TypeCode::Complex, 21),
+        ];
+        for (t, code, tag) in cases {
+            assert_eq!(TypeCode::from(&t), code, "{t}");
+            assert_eq!(code as u8, tag, "{t}");
+        }
+        // the tags of values follow those of their classes
+        let values = [
+            ValueObj::Bytes(vec![]),
+            ValueObj::Complex { re: 0.0, im: 1.0 },
+            ValueObj::None,
+            ValueObj::Dict(vec![].into()),
+            ValueObj::Set(Set::new()),
+        ];
+        let codes = values.map(|v| TypeCode::from(&v.class()) as u8);
+        assert_eq!(codes, [17, 21, 20, 18, 19]);
+    }
+
+    #[test]
+    fn test_type_pair() {
+        let kinds = [
+            Type::Int,
+            Type::Nat,
+            Type::Float,
+            Type::Bool,
+            Type::Str,
+            Type::array(Type::Int, TyParam::value(1usize)),
+            Type::poly("Func", vec![]),
+            Type::poly("Proc", vec![]),
+        ];
+        let names = [
+            "Int", "Nat", "Float", "Bool", "Str", "Array", "Func", "Proc",
+        ];
+        for (lhs, l) in kinds.iter().zip(names) {
+            for (rhs, r) in kinds.iter().zip(names) {
+                let pair = TypePair::new(lhs, rhs);
+                assert_eq!(format!("{pair:?}"), format!("{l}{r}"));
+                assert_eq!(TypePair::from_u8(pair as u8), Some(pair));
+            }
+        }
+        // the tag values of the older versions are kept
+        assert_eq!(TypePair::IntStr as u8, 4);
+        assert_eq!(TypePair::IntBool as u8, 5);
+        assert_eq!(TypePair::BoolStr as u8, 28);
+        assert_eq!(TypePair::StrBool as u8, 36);
+        assert_eq!(TypePair::StrStr as u8, 37);
+        assert_eq!(TypePair::ArrayStr as u8, 44);
+        for code in 1..=66 {
+            assert_eq!(TypePair::from_u8(code).unwrap() as u8, code);
+        }
+        assert_eq!(TypePair::from_u8(0), None);
+        assert_eq!(TypePair::from_u8(67), None);
+        assert_eq!(TypePair::from(0), TypePair::Illegals);
+        assert_eq!(TypePair::new(&Type::Int, &Type::Float), TypePair::IntFloat);
+        assert_eq!(TypePair::new(&Type::Nat, &Type::Int), TypePair::NatInt);
+        assert_eq!(TypePair::new(&Type::IntMut, &Type::Nat), TypePair::IntNat);
+        assert_eq!(TypePair::ProcFunc as u8, 63);
+        assert_eq!(TypePair::ProcProc as u8, 64);
+        let dict = Type::dict(Type::Int, Type::Int);
+        assert_eq!(TypePair::new(&dict, &Type::Int), TypePair::Others);
+        assert_eq!(TypePair::Others as u8, 65);
+        assert_eq!(TypePair::Illegals as u8, 66);
+    }
+
+    fn marshal_roundtrip(obj: ValueObj) -> ValueObj {
+        let mut bytes = obj.into_bytes(3425);
+        let obj = Deserializer::new()
+            .deserialize_const(&mut bytes, 3425)
+            .unwrap();
+        assert!(bytes.is_empty());
+        obj
+    }
+
+    #[test]
+    fn test_singleton_marshal() {
+        // same as `marshal.dumps(None)` etc.
+        for (obj, byte) in [
+            (ValueObj::None, b'N'),
+            (ValueObj::True, b'T'),
+            (ValueObj::False, b'F'),
+            (ValueObj::Ellipsis, b'.'),
+        ] {
+            assert_eq!(obj.clone().into_bytes(3425), vec![byte]);
+            assert_eq!(marshal_roundtrip(obj.clone()), obj);
+        }
+        // `StopIteration` has no corresponding `ValueObj`
+        let mut bytes = vec![DataTypePrefix::StopIter as u8];
+        assert!(Deserializer::new()
+            .deserialize_const(&mut bytes, 3425)
+            .is_err());
+    }
+
+    fn hash_of(obj: &ValueObj) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        obj.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_dict_value() {
+        let a = || ValueObj::from("a");
+        let b = || ValueObj::from("b");
+        let ab = ValueObj::dict([(a(), ValueObj::Int(1)), (b(), ValueObj::Int(2))]);
+        let ba = ValueObj::dict([(b(), ValueObj::Int(2)), (a(), ValueObj::Int(1))]);
+        // the insertion order is kept, but not significant for `==`
+        assert_eq!(ab.to_string(), "{\"a\": 1, \"b\": 2}");
+        assert_eq!(ba.to_string(), "{\"b\": 2, \"a\": 1}");
+        assert_eq!(ab, ba);
+        assert_eq!(hash_of(&ab), hash_of(&ba));
+        // as constants, they are marshaled differently
+        assert!(!ab.marshal_eq(&ba));
+        assert_ne!(
+            ab,
+            ValueObj::dict([(a(), ValueObj::Int(1)), (b(), ValueObj::Int(3))])
+        );
+        // the first position and the last value of a duplicated key are kept
+        let dup = ValueObj::dict([
+            (a(), ValueObj::Int(0)),
+            (b(), ValueObj::Int(2)),
+            (a(), ValueObj::Int(1)),
+        ]);
+        assert!(dup.marshal_eq(&ab));
+        assert_eq!(
+            ValueObj::from(dict! {b() => ValueObj::Int(2), a() => ValueObj::Int(1)}),
+            ab
+        );
+        assert_eq!(ab.class(), Type::dict(Type::Str, Type::Int));
+    }
+
+    #[test]
+    fn test_set_value() {
+        let set = |elems: [i32; 2]| Set::from(elems.map(ValueObj::Int).to_vec());
+        let s12 = ValueObj::Set(set([1, 2]));
+        let s21 = ValueObj::Set(set([2, 1]));
+        assert_eq!(s12, s21);
+        assert_eq!(hash_of(&s12), hash_of(&s21));
+        assert_eq!(ValueObj::Set(set([1, 1])).to_string(), "{1}");
+        assert_ne!(s12, ValueObj::Set(set([1, 3])));
+        // `{1, 2} == frozenset({1, 2})` is also `True` in Python
+        let frozen = ValueObj::FrozenSet(set([2, 1]));
+        assert_eq!(s12, frozen);
+        assert_eq!(hash_of(&s12), hash_of(&frozen));
+        assert!(s12.marshal_eq(&s21));
+        assert!(!s12.marshal_eq(&frozen));
+        // the elements are sorted so that the output is deterministic
+        let bytes = s21.clone().into_bytes(3425);
+        assert_eq!(bytes, s12.clone().into_bytes(3425));
+        assert_eq!(bytes[0] & 0x7f, DataTypePrefix::Set as u8);
+        assert_eq!(
+            frozen.clone().into_bytes(3425)[0] & 0x7f,
+            DataTypePrefix::FrozenSet as u8
+        );
+        assert!(marshal_roundtrip(s12.clone()).marshal_eq(&s12));
+        assert!(marshal_roundtrip(frozen.clone()).marshal_eq(&frozen));
+        assert_eq!(frozen.class(), Type::frozenset(Type::Int));
+    }
+
+    #[test]
+    fn test_bytes_value() {
+        let bytes_t = Type::mono("Bytes");
+        let lit = |lit: &str| ValueObj::from_str(bytes_t.clone(), Str::rc(lit));
+        // same as `repr` of CPython
+        let cases = [
+            (r#"b"""#, vec![], "b''"),
+            (
+                r#"b"\x00\n\xff""#,
+                vec![0x00, b'\n', 0xff],
+                r"b'\x00\n\xff'",
+            ),
+            (r#"b"it's""#, b"it's".to_vec(), r#"b"it's""#),
+            (r#"b"'\"""#, b"'\"".to_vec(), r#"b'\'"'"#),
+            (r#"b"\\""#, b"\\".to_vec(), r"b'\\'"),
+            (
+                r#"b"\101\0\x7f\q""#,
+                b"A\0\x7f\\q".to_vec(),
+                r"b'A\x00\x7f\\q'",
+            ),
+        ];
+        for (src, bytes, repr) in cases {
+            let obj = lit(src);
+            assert_eq!(obj, ValueObj::from(bytes));
+            assert_eq!(obj.to_string(), repr);
+            assert_eq!(obj.class(), bytes_t);
+            // `marshal.dumps(...)` of CPython (without `FLAG_REF`)
+            let marshaled = obj.clone().into_bytes(3425);
+            assert_eq!(marshaled[0], DataTypePrefix::Str as u8);
+            assert_eq!(marshal_roundtrip(obj.clone()), obj);
+        }
+        assert_eq!(lit(r#"b"""#).into_bytes(3425), [b's', 0, 0, 0, 0]);
+        // `b"a"` is not equal to `"a"`
+        assert_ne!(lit(r#"b"a""#), ValueObj::from("a"));
+        assert_eq!(hash_of(&lit(r#"b"\x61""#)), hash_of(&lit(r#"b"a""#)));
+    }
+
+    #[test]
+    fn test_from_containers() {
+        // `((1, 2), 3)`
+        let nested = ValueObj::from(((1, 2), 3));
+        let expected = ValueObj::Array(
+            vec![
+                ValueObj::Array(vec![ValueObj::Int(1), ValueObj::Int(2)].into()),
+                ValueObj::Int(3),
+            ]
+            .into(),
+        );
+        assert!(nested.marshal_eq(&expected));
+        assert_eq!(nested.repr(), "((1, 2), 3)");
+        assert_eq!(marshal_roundtrip(nested.clone()), nested);
+        assert_eq!(
+            ValueObj::from(("a", [1.5, 2.0])).repr(),
+            "('a', (1.5, 2.0))"
+        );
+        assert_eq!(ValueObj::from([1u64, 2, 3]).repr(), "(1, 2, 3)");
+        assert_eq!(ValueObj::from([0u64; 0]), ValueObj::Array(vec![].into()));
+        let dict = ValueObj::from(vec![
+            (ValueObj::from("a"), ValueObj::from((1, 2))),
+            (ValueObj::from("b"), ValueObj::None),
+            (ValueObj::from("a"), ValueObj::from([3])),
+        ]);
+        assert_eq!(dict.repr(), "{'a': (3,), 'b': None}");
+    }
+
+    #[test]
+    fn test_long_roundtrip() {
+        assert_eq!(
+            marshal_roundtrip(ValueObj::Nat(1 << 31)),
+            ValueObj::Nat(1 << 31)
+        );
+        assert_eq!(
+            marshal_roundtrip(ValueObj::Nat(1 << 63)),
+            ValueObj::Nat(1 << 63)
+        );
+        // the length is negative for negative numbers (same as `marshal.dumps(-2**63)`)
+        let min = ValueObj::from(Long::from(i64::MIN));
+        assert_eq!(
+            min.clone().into_bytes(3425),
+            vec![
+                DataTypePrefix::Long as u8,
+                251,
+                255,
+                255,
+                255,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                8,
+                0
+            ]
+        );
+        assert_eq!(marshal_roundtrip(min.clone()), min);
+        let digits = "1234567890".repeat(20);
+        for lit in [digits.clone(), format!("-{digits}")] {
+            let long = ValueObj::from_str(Type::Int, lit.clone().into());
+            assert!(matches!(long, ValueObj::Long(_)));
+            assert_eq!(long.to_string(), lit);
+            assert_eq!(marshal_roundtrip(long.clone()), long);
+        }
+        // zero has no digits
+        assert_eq!(
+            Long::parse("0").unwrap(),
+            Long::from_digits(true, vec![0, 0])
+        );
+        assert_eq!(Long::parse("-0").unwrap().to_string(), "0");
+    }
+
+    #[test]
+    fn test_float_marshal() {
+        // `marshal.dumps` of CPython (without `FLAG_REF`)
+        let cases = [
+            (1.5, [0, 0, 0, 0, 0, 0, 248, 63]),
+            (f64::NAN, [0, 0, 0, 0, 0, 0, 248, 127]),
+            (f64::INFINITY, [0, 0, 0, 0, 0, 0, 240, 127]),
+            (f64::NEG_INFINITY, [0, 0, 0, 0, 0, 0, 240, 255]),
+            (-0.0, [0, 0, 0, 0, 0, 0, 0, 128]),
+        ];
+        for (f, expected) in cases {
+            let bytes = ValueObj::Float(f).into_bytes(3425);
+            assert_eq!(bytes[0], DataTypePrefix::BinFloat as u8);
+            assert_eq!(bytes[1..], expected);
+            let ValueObj::Float(g) = marshal_roundtrip(ValueObj::Float(f)) else {
+                panic!()
+            };
+            assert_eq!(f.to_bits(), g.to_bits());
+        }
+        assert_eq!(ValueObj::Inf.into_bytes(3425)[1..], cases[2].1);
+        // `FLAG_REF` is ignored when reading
+        let mut flagged = vec![DataTypePrefix::BinFloat as u8 | 0x80];
+        flagged.extend(cases[0].1);
+        assert_eq!(
+            Deserializer::new()
+                .deserialize_const(&mut flagged, 3425)
+                .unwrap(),
+            ValueObj::Float(1.5)
+        );
+        // marshal version 0
+        let mut text = vec![DataTypePrefix::Float as u8, 4];
+        text.extend(b"-inf");
+        assert_eq!(
+            Deserializer::new()
+                .deserialize_const(&mut text, 3425)
+                .unwrap(),
+            ValueObj::Float(f64::NEG_INFINITY)
+        );
+    }
+
+    #[test]
+    fn test_complex_marshal() {
+        // `marshal.dumps(complex(1, -0.0))`
+        let bytes = complex_into_bytes(1.0, -0.0);
+        assert_eq!(bytes[0], DataTypePrefix::BinComplex as u8);
+        assert_eq!(bytes[1..9], [0, 0, 0, 0, 0, 0, 240, 63]);
+        assert_eq!(bytes[9..], [0, 0, 0, 0, 0, 0, 0, 128]);
+        let (re, im) = Deserializer::deserialize_complex(&mut bytes[1..].to_vec()).unwrap();
+        assert_eq!(
+            (re.to_bits(), im.to_bits()),
+            (1f64.to_bits(), (-0f64).to_bits())
+        );
+        assert!(Deserializer::deserialize_complex(&mut vec![0; 15]).is_err());
+        let complex = ValueObj::complex(1.0, -0.0);
+        assert_eq!(complex.clone().into_bytes(3425), bytes);
+        assert_eq!(marshal_roundtrip(complex.clone()), complex);
+    }
+
+    #[test]
+    fn test_complex_value() {
+        // same as `repr(complex(...))[1:-1]` of CPython
+        assert_eq!(ValueObj::complex(1.0, 2.0).to_string(), "1+2j");
+        assert_eq!(ValueObj::complex(1.5, -2.0).to_string(), "1.5-2j");
+        assert_eq!(ValueObj::complex(0.0, 2.0).to_string(), "2j");
+        assert_eq!(ValueObj::complex(-0.0, 2.0).to_string(), "-0+2j");
+        assert_eq!(ValueObj::complex(1.0, f64::INFINITY).to_string(), "1+infj");
+        assert_eq!(-ValueObj::complex(1.0, 2.0), ValueObj::complex(-1.0, -2.0));
+        assert_ne!(ValueObj::complex(1.0, 2.0), ValueObj::complex(2.0, 1.0));
+        assert!(ValueObj::complex(0.0, 0.0) == ValueObj::complex(-0.0, 0.0));
+        assert!(!ValueObj::complex(0.0, 0.0).marshal_eq(&ValueObj::complex(-0.0, 0.0)));
+        assert_eq!(
+            hash_of(&ValueObj::complex(0.0, -0.0)),
+            hash_of(&ValueObj::complex(-0.0, 0.0))
+        );
+        assert_eq!(
+            hash_of(&ValueObj::Float(0.0)),
+            hash_of(&ValueObj::Float(-0.0))
+        );
+        let set = Set::from(vec![
+            ValueObj::complex(0.0, 0.0),
+            ValueObj::complex(-0.0, 0.0),
+        ]);
+        assert_eq!(set.len(), 1);
+        let complex_t = Type::mono("Complex");
+        assert_eq!(ValueObj::complex(1.0, 2.0).class(), complex_t);
+        assert_eq!(
+            ValueObj::Int(3).try_coerce(&complex_t),
+            Some(ValueObj::complex(3.0, 0.0))
+        );
+        assert_eq!(ValueObj::from("3").try_coerce(&complex_t), None);
+    }
+
+    #[test]
+    fn test_container_marshal() {
+        // `marshal.dumps({1: []})` and `marshal.dumps(frozenset())` of CPython
+        let dict = dict_into_bytes(
+            vec![(ValueObj::Int(1), ValueObj::Array(vec![].into()))],
+            &mut RefTable::new(),
+            3425,
+        );
+        assert_eq!(dict[..6], [123, 105, 1, 0, 0, 0]);
+        let list = list_into_bytes(vec![], &mut RefTable::new(), 3425);
+        assert_eq!(list, [91, 0, 0, 0, 0]);
+        let mut cpython = vec![123, 233, 1, 0, 0, 0, 91, 0, 0, 0, 0, 48];
+        // CPython writes the key (a cached small int) with `FLAG_REF`
+        cpython[1] &= 0x7f;
+        assert_eq!([dict[..6].to_vec(), list, vec![48]].concat(), cpython);
+        assert_eq!(
+            set_into_bytes(vec![], true, &mut RefTable::new(), 3425),
+            [62, 0, 0, 0, 0]
+        );
+
+        let tuple = ValueObj::Array(vec![].into());
+        assert_eq!(marshal_roundtrip(tuple.clone()), tuple);
+        let empty_dict = ValueObj::Dict(vec![].into());
+        assert_eq!(marshal_roundtrip(empty_dict.clone()), empty_dict);
+        let nested = ValueObj::Array(
+            vec![
+                ValueObj::Int(-1),
+                ValueObj::Dict(
+                    vec![
+                        (
+                            ValueObj::from("a"),
+                            ValueObj::Array(vec![ValueObj::None].into()),
+                        ),
+                        (ValueObj::Int(2), ValueObj::Dict(vec![].into())),
+                    ]
+                    .into(),
+                ),
+                ValueObj::Array(vec![ValueObj::Array(vec![].into())].into()),
+            ]
+            .into(),
+        );
+        assert_eq!(marshal_roundtrip(nested.clone()), nested);
+        // a list is read as an array
+        let mut list = list_into_bytes(
+            vec![ValueObj::True, ValueObj::Float(0.5)],
+            &mut RefTable::new(),
+            3425,
+        );
+        assert_eq!(
+            Deserializer::new()
+                .deserialize_const(&mut list, 3425)
+                .unwrap(),
+            ValueObj::Array(vec![ValueObj::True, ValueObj::Float(0.5)].into())
+        );
+        for frozen in [false, true] {
+            let elems = vec![ValueObj::Int(1), ValueObj::from("x"), ValueObj::Int(1)];
+            let mut set = set_into_bytes(elems.clone(), frozen, &mut RefTable::new(), 3425);
+            set.remove(0);
+            let set = Deserializer::new().deserialize_set(&mut set, 3425).unwrap();
+            assert_eq!(set, Set::from(elems));
+            assert_eq!(set.len(), 2);
+        }
+        // the terminator of the dict is missing
+        let mut broken = vec![123, 78];
+        assert!(Deserializer::new()
+            .deserialize_const(&mut broken, 3425)
+            .is_err());
+        // a code object in a container is written for the target version
+        let code = CodeObjBuilder::new("f").params(vec!["x"]).build();
+        let tuple = ValueObj::Array(vec![ValueObj::from(code.clone())].into());
+        let bytes = tuple.into_bytes(3495);
+        assert_eq!(bytes[5..], code.clone().into_bytes(3495));
+        assert_ne!(bytes[5..], code.into_bytes(3425));
+    }
+
+    #[test]
+    fn test_truncated_marshal() {
+        let mut float_text = vec![DataTypePrefix::Float as u8, 4];
+        float_text.extend(b"-inf");
+        let cases = [
+            ValueObj::Int(-1).into_bytes(3425),
+            ValueObj::from(Long::from(1u64 << 40)).into_bytes(3425),
+            ValueObj::from("abc").into_bytes(3425),
+            ValueObj::from(&"a".repeat(300)[..]).into_bytes(3425),
+            ValueObj::Bytes(vec![1, 2, 3]).into_bytes(3425),
+            ValueObj::Array(vec![ValueObj::Int(1), ValueObj::None].into()).into_bytes(3425),
+            float_text,
+        ];
+        for bytes in cases {
+            let mut whole = bytes.clone();
+            assert!(Deserializer::new()
+                .deserialize_const(&mut whole, 3425)
+                .is_ok());
+            // every proper prefix is an error, not a panic
+            for len in 0..bytes.len() {
+                assert!(Deserializer::new()
+                    .deserialize_const(&mut bytes[..len].to_vec(), 3425)
+                    .is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_repr() {
+        let tuple = |elems: Vec<ValueObj>| ValueObj::Array(elems.into());
+        let set = |elems: Vec<ValueObj>| Set::from(elems);
+        // `repr(...)` of CPython
+        let cases = [
+            (ValueObj::Int(1), "1"),
+            (ValueObj::Int(-2), "-2"),
+            (
+                ValueObj::from(Long::parse("1180591620717411303424").unwrap()),
+                "1180591620717411303424",
+            ),
+            (ValueObj::Float(1.0), "1.0"),
+            (ValueObj::Float(-0.0), "-0.0"),
+            (ValueObj::Float(0.1), "0.1"),
+            (ValueObj::Float(1e16), "1e+16"),
+            (ValueObj::Float(1.5e-5), "1.5e-05"),
+            (ValueObj::Float(123.456), "123.456"),
+            (ValueObj::Inf, "inf"),
+            (ValueObj::Float(f64::NAN), "nan"),
+            (ValueObj::complex(1.0, 2.0), "(1+2j)"),
+            (ValueObj::complex(0.0, 2.0), "2j"),
+            (ValueObj::complex(-0.0, -1.5), "(-0-1.5j)"),
+            (ValueObj::complex(1e16, 1.0), "(1e+16+1j)"),
+            (ValueObj::from("a\nb"), r"'a\nb'"),
+            (ValueObj::from("it's"), r#""it's""#),
+            (ValueObj::from("a'\""), r#"'a\'"'"#),
+            (
+                ValueObj::from("tab\there\x00\x7f\u{a0}é😀\u{200b}"),
+                r"'tab\there\x00\x7f\xa0é😀\u200b'",
+            ),
+            (ValueObj::Bytes(vec![0]), r"b'\x00'"),
+            (ValueObj::True, "True"),
+            (ValueObj::False, "False"),
+            (ValueObj::None, "None"),
+            (ValueObj::Ellipsis, "Ellipsis"),
+            (ValueObj::NotImplemented, "NotImplemented"),
+            (tuple(vec![]), "()"),
+            (tuple(vec![ValueObj::Nat(1)]), "(1,)"),
+            (
+                tuple(vec![ValueObj::Nat(1), ValueObj::from("a")]),
+                "(1, 'a')",
+            ),
+            (tuple(vec![tuple(vec![ValueObj::Nat(1)])]), "((1,),)"),
+            (ValueObj::Dict(vec![].into()), "{}"),
+            (
+                ValueObj::dict([
+                    (ValueObj::Nat(1), ValueObj::from("a")),
+                    (ValueObj::from("b"), tuple(vec![ValueObj::Nat(2)])),
+                ]),
+                "{1: 'a', 'b': (2,)}",
+            ),
+            (ValueObj::Set(set(vec![])), "set()"),
+            (ValueObj::FrozenSet(set(vec![])), "frozenset()"),
+            (ValueObj::Set(set(vec![ValueObj::Nat(3)])), "{3}"),
+            (
+                ValueObj::FrozenSet(set(vec![ValueObj::Nat(1)])),
+                "frozenset({1})",
+            ),
+        ];
+        for (obj, repr) in cases {
+            assert_eq!(obj.repr(), repr, "{obj}");
+        }
+        // `Display` is unchanged
+        assert_eq!(ValueObj::from("a").to_string(), "\"a\"");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let (int, nat, float) = (ValueObj::Int, ValueObj::Nat, ValueObj::Float);
+        // the results are checked with CPython
+        assert_eq!(int(1).try_add(float(0.5)), Some(float(1.5)));
+        assert_eq!(float(1.5).try_add(nat(2)), Some(float(3.5)));
+        assert_eq!(nat(1).try_add(nat(2)), Some(nat(3)));
+        assert_eq!(nat(3).try_sub(nat(1)), Some(nat(2)));
+        assert_eq!(nat(1).try_sub(nat(3)), Some(int(-2)));
+        assert_eq!(int(3).try_sub(nat(1)), Some(int(2)));
+        assert_eq!(nat(2).try_mul(int(-3)), Some(int(-6)));
+        // no overflow
+        assert_eq!(int(i32::MAX).try_add(int(1)), Some(nat(1 << 31)));
+        let min = int(i32::MIN).try_sub(int(1)).unwrap();
+        assert_eq!(min.to_string(), "-2147483649");
+        assert_eq!(
+            nat(u64::MAX).try_mul(nat(2)).unwrap().to_string(),
+            "36893488147419103230"
+        );
+        // true division
+        assert_eq!(int(1).try_div(int(3)), Some(float(1.0 / 3.0)));
+        assert_eq!(nat(7).try_div(nat(2)), Some(float(3.5)));
+        assert_eq!(int(-4).try_div(nat(2)), Some(float(-2.0)));
+        assert_eq!(float(1.0).try_div(int(4)), Some(float(0.25)));
+        // `(2**53 + 1) / 1` is rounded once by CPython, so it is not folded
+        assert_eq!(nat((1 << 53) + 1).try_div(nat(1)), None);
+        assert_eq!(float(1e308).try_mul(int(10)), Some(float(f64::INFINITY)));
+        // complex numbers
+        let complex = ValueObj::complex;
+        assert_eq!(
+            complex(1.0, 2.0).try_mul(complex(3.0, -4.0)),
+            Some(complex(11.0, 2.0))
+        );
+        let quot = complex(1.0, 2.0).try_div(complex(3.0, -4.0)).unwrap();
+        assert!(quot.marshal_eq(&complex(-0.2, 0.4)));
+        assert_eq!(int(2).try_mul(complex(0.5, 1.0)), Some(complex(1.0, 2.0)));
+        assert_eq!(int(3).try_sub(complex(0.0, 0.5)), Some(complex(3.0, -0.5)));
+        // division by zero raises `ZeroDivisionError` at runtime
+        assert_eq!(int(1).try_div(int(0)), None);
+        assert_eq!(nat(1).try_div(nat(0)), None);
+        assert_eq!(float(1.0).try_div(float(-0.0)), None);
+        assert_eq!(float(1.0).try_div(int(0)), None);
+        assert_eq!(complex(1.0, 0.0).try_div(complex(0.0, 0.0)), None);
+        // type mismatches
+        assert_eq!(int(1).try_add(ValueObj::from("a")), None);
+        assert_eq!(ValueObj::from("a").try_sub(float(1.0)), None);
+        assert_eq!(ValueObj::True.try_div(int(1)), None);
+        assert_eq!(
+            ValueObj::from("a").try_add(ValueObj::from("b")),
+            Some(ValueObj::from("ab"))
+        );
+    }
+
+    #[test]
+    fn test_try_cmp() {
+        use std::cmp::Ordering::*;
+        let tuple = |elems: Vec<ValueObj>| ValueObj::Array(elems.into());
+        let (int, nat, float) = (ValueObj::Int, ValueObj::Nat, ValueObj::Float);
+        let s = ValueObj::from;
+        // `1 < 1.5`
+        assert_eq!(nat(1).try_cmp(&float(1.5)), Some(Less));
+        assert_eq!(float(1.5).try_cmp(&int(1)), Some(Greater));
+        assert_eq!(int(-3).try_cmp(&float(-3.0)), Some(Equal));
+        assert_eq!(int(-1).try_cmp(&float(-0.5)), Some(Less));
+        // compared exactly (`2**53 + 1 > 2.0**53` in Python)
+        assert_eq!(
+            nat((1 << 53) + 1).try_cmp(&float(2f64.powi(53))),
+            Some(Greater)
+        );
+        // `10**30 < 1e30` (1e30 is 1000000000000000019884624838656)
+        let long = ValueObj::from(Long::parse("1000000000000000000000000000000").unwrap());
+        assert_eq!(long.try_cmp(&float(1e30)), Some(Less));
+        assert_eq!(long.try_cmp(&nat(u64::MAX)), Some(Greater));
+        let neg_long = ValueObj::from(Long::parse("-1000000000000000000000000000000").unwrap());
+        assert_eq!(neg_long.try_cmp(&float(-1e30)), Some(Greater));
+        assert_eq!(nat(u64::MAX).try_cmp(&float(f64::INFINITY)), Some(Less));
+        assert_eq!(ValueObj::True.try_cmp(&nat(1)), Some(Equal));
+        assert_eq!(ValueObj::False.try_cmp(&float(0.5)), Some(Less));
+        // `"a" < "b"`
+        assert_eq!(s("a").try_cmp(&s("b")), Some(Less));
+        assert_eq!(s("ab").try_cmp(&s("a")), Some(Greater));
+        assert_eq!(s("é").try_cmp(&s("z")), Some(Greater));
+        assert_eq!(
+            ValueObj::Bytes(vec![0]).try_cmp(&ValueObj::Bytes(vec![])),
+            Some(Greater)
+        );
+        assert_eq!(
+            tuple(vec![nat(1), s("a")]).try_cmp(&tuple(vec![float(1.0), s("b")])),
+            Some(Less)
+        );
+        assert_eq!(
+            tuple(vec![nat(1)]).try_cmp(&tuple(vec![nat(1), nat(2)])),
+            Some(Less)
+        );
+        // incomparable (`TypeError` in Python)
+        assert_eq!(int(1).try_cmp(&s("a")), None);
+        assert_eq!(s("a").try_cmp(&ValueObj::Bytes(vec![])), None);
+        assert_eq!(ValueObj::complex(1.0, 0.0).try_cmp(&int(1)), None);
+        assert_eq!(ValueObj::None.try_cmp(&ValueObj::None), None);
+        assert_eq!(
+            tuple(vec![nat(1), s("a")]).try_cmp(&tuple(vec![s("a"), nat(1)])),
+            None
+        );
+        assert_eq!(float(f64::NAN).try_cmp(&int(1)), None);
+        // the results of `COMPARE_OP`
+        assert_eq!(s("b").try_gt(s("a")), Some(ValueObj::True));
+        assert_eq!(int(1).try_ge(float(1.5)), Some(ValueObj::False));
+        assert_eq!(int(1).try_gt(s("a")), None);
+    }
+
+    #[test]
+    fn test_try_coerce() {
+        assert_eq!(
+            ValueObj::Nat(0).try_coerce(&Type::Float),
+            Some(ValueObj::Float(0.0))
+        );
+        assert_eq!(
+            ValueObj::Float(1.0).try_coerce(&Type::Int),
+            Some(ValueObj::Int(1))
+        );
+        assert_eq!(ValueObj::Float(1.5).try_coerce(&Type::Int), None);
+        assert_eq!(ValueObj::Int(-1).try_coerce(&Type::Nat), None);
+    }
+}
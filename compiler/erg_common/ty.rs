@@ -347,7 +347,7 @@ pub type FreeTyParam = Free<TyParam>;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct UserConstSubr {
-    code: CodeObj,
+    code: Box<CodeObj>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -1978,6 +1978,14 @@ impl Type {
         Self::poly("Dict", vec![TyParam::t(k_t), TyParam::t(v_t)])
     }
 
+    pub fn set(elem_t: Type) -> Self {
+        Self::poly("Set", vec![TyParam::t(elem_t)])
+    }
+
+    pub fn frozenset(elem_t: Type) -> Self {
+        Self::poly("FrozenSet", vec![TyParam::t(elem_t)])
+    }
+
     pub fn tuple(args: Vec<Type>) -> Self {
         Self::poly("Tuple", args.into_iter().map(TyParam::t).collect())
     }
@@ -2459,7 +2467,11 @@ impl Type {
                     return_t: _rr,
                 },
             ) => todo!(),
-            (Self::Record(_l), Self::Record(_r)) => todo!(),
+            (Self::Record(l), Self::Record(r)) => {
+                l.len() == r.len()
+                    && l.iter()
+                        .all(|(name, l)| r.get(name).is_some_and(|r| l.rec_eq(r)))
+            }
             (Self::Refinement(l), Self::Refinement(r)) => l.t.rec_eq(&r.t) && &l.preds == &r.preds,
             (Self::Quantified(l), Self::Quantified(r)) => {
                 l.unbound_callable.rec_eq(&r.unbound_callable) && &l.bounds == &r.bounds
@@ -2864,7 +2876,6 @@ pub enum TypeCode {
     StrMut,
     Array, // 要素数は検査済みなので、気にする必要はない
     ArrayMut,
-    // Dict,
     Func,
     Proc,
     MaybeBigInt,
@@ -2873,6 +2884,12 @@ pub enum TypeCode {
     MaybeBigStr,
     Other,
     Illegal,
+    // 既存のタグ値を変えないよう、後から追加したものは末尾に置く
+    Bytes = 17,
+    Dict,
+    Set, // FrozenSetも含む
+    NoneType,
+    Complex,
 }
 
 // TODO:
@@ -2884,8 +2901,16 @@ impl From<&Type> for TypeCode {
             Type::Float | Type::FloatMut => Self::Float64,
             Type::Bool | Type::BoolMut => Self::Bool,
             Type::Str | Type::StrMut => Self::Str,
+            Type::NoneType => Self::NoneType,
+            Type::Mono(name) => match &name[..] {
+                "Bytes" => Self::Bytes,
+                "Complex" => Self::Complex,
+                _ => Self::Other,
+            },
             Type::Poly { name, .. } => match &name[..] {
                 "Array" | "Array!" => Self::Array,
+                "Dict" | "Dict!" => Self::Dict,
+                "Set" | "Set!" | "FrozenSet" => Self::Set,
                 "Func" => Self::Func,
                 "Proc" => Self::Proc,
                 _ => Self::Other,
@@ -2898,6 +2923,11 @@ impl From<&Type> for TypeCode {
 /// バイトコード命令で、in-place型付けをするオブジェクトペア
 /// とりあえずは必要性の高いペアから登録する
 /// 全ての式の型が確認されているので、戻り値の型は不要
+///
+/// タグ値のビット配置: `Int, Nat, Float, Bool, Str, Array, Func, Proc`にこの順で0~7の番号を振り、
+/// `((lhs << 3) | rhs) + 1`とする(下位3ビットが右辺、その上の3ビットが左辺)
+/// ただし既存のタグ値を変えないよう、左辺が`Str`以外の行では右辺の`Bool`と`Str`の番号を入れ替える(e.g. `IntStr`は4)
+/// 0は型付けなしを表し、組み合わせがない場合は`Others`(65)、`Illegals`(66)となる
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum TypePair {
@@ -2972,183 +3002,111 @@ pub enum TypePair {
 
 impl From<u8> for TypePair {
     fn from(code: u8) -> Self {
-        match code {
-            1 => Self::IntInt,
-            2 => Self::IntNat,
-            3 => Self::IntFloat,
-            4 => Self::IntStr,
-            5 => Self::IntBool,
-            6 => Self::IntArray,
-            7 => Self::IntFunc,
-            8 => Self::IntProc,
-            9 => Self::NatInt,
-            10 => Self::NatNat,
-            11 => Self::NatFloat,
-            12 => Self::NatStr,
-            13 => Self::NatBool,
-            14 => Self::NatArray,
-            15 => Self::NatFunc,
-            16 => Self::NatProc,
-            17 => Self::FloatInt,
-            18 => Self::FloatNat,
-            19 => Self::FloatFloat,
-            20 => Self::FloatStr,
-            21 => Self::FloatBool,
-            22 => Self::FloatArray,
-            23 => Self::FloatFunc,
-            24 => Self::FloatProc,
-            25 => Self::BoolInt,
-            26 => Self::BoolNat,
-            27 => Self::BoolFloat,
-            28 => Self::BoolStr,
-            29 => Self::BoolBool,
-            30 => Self::BoolArray,
-            31 => Self::BoolFunc,
-            32 => Self::BoolProc,
-            33 => Self::StrInt,
-            34 => Self::StrNat,
-            35 => Self::StrFloat,
-            36 => Self::StrBool,
-            37 => Self::StrStr,
-            38 => Self::StrArray,
-            39 => Self::StrFunc,
-            40 => Self::StrProc,
-            // 要素数は検査済みなので、気にする必要はない
-            41 => Self::ArrayInt,
-            42 => Self::ArrayNat,
-            43 => Self::ArrayFloat,
-            44 => Self::ArrayStr,
-            45 => Self::ArrayBool,
-            46 => Self::ArrayArray,
-            47 => Self::ArrayFunc,
-            48 => Self::ArrayProc,
-            49 => Self::FuncInt,
-            50 => Self::FuncNat,
-            51 => Self::FuncFloat,
-            52 => Self::FuncStr,
-            53 => Self::FuncBool,
-            54 => Self::FuncArray,
-            55 => Self::FuncFunc,
-            56 => Self::FuncProc,
-            57 => Self::ProcInt,
-            58 => Self::ProcNat,
-            59 => Self::ProcFloat,
-            60 => Self::ProcStr,
-            61 => Self::ProcBool,
-            62 => Self::ProcArray,
-            63 => Self::ProcProc,
-            64 => Self::Others,
-            65 | _ => Self::Illegals,
-        }
+        Self::from_u8(code).unwrap_or(Self::Illegals)
     }
 }
 
-// TODO:
 impl TypePair {
+    /// ビット配置における型の番号
+    /// 可変型は不変型と同じ番号になる
+    fn kind(t: &Type) -> Option<u8> {
+        match TypeCode::from(t) {
+            TypeCode::Int32 => Some(0),
+            TypeCode::Nat64 => Some(1),
+            TypeCode::Float64 => Some(2),
+            TypeCode::Bool => Some(3),
+            TypeCode::Str => Some(4),
+            TypeCode::Array => Some(5),
+            TypeCode::Func => Some(6),
+            TypeCode::Proc => Some(7),
+            _ => None,
+        }
+    }
+
     pub fn new(lhs: &Type, rhs: &Type) -> Self {
-        match (lhs, rhs) {
-            (Type::Int, Type::Int) => Self::IntInt,
-            (Type::Int, Type::Nat) => Self::IntNat,
-            (Type::Int, Type::Float) => Self::IntFloat,
-            (Type::Int, Type::Str) => Self::IntStr,
-            (Type::Int, Type::Bool) => Self::IntBool,
-            (Type::Int, Type::Poly { name, .. }) if &name[..] == "Array" => Self::IntArray,
-            (Type::Int, Type::Poly { name, .. }) if &name[..] == "Func" => Self::IntFunc,
-            (Type::Int, Type::Poly { name, .. }) if &name[..] == "Proc" => Self::IntProc,
-            (Type::Nat, Type::Int) => Self::NatInt,
-            (Type::Nat, Type::Nat) => Self::NatNat,
-            (Type::Nat, Type::Float) => Self::NatFloat,
-            (Type::Nat, Type::Str) => Self::NatStr,
-            (Type::Nat, Type::Bool) => Self::NatBool,
-            (Type::Nat, Type::Poly { name, .. }) if &name[..] == "Array" => Self::NatArray,
-            (Type::Nat, Type::Poly { name, .. }) if &name[..] == "Func" => Self::NatFunc,
-            (Type::Nat, Type::Poly { name, .. }) if &name[..] == "Proc" => Self::NatProc,
-            (Type::Float, Type::Int) => Self::FloatInt,
-            (Type::Float, Type::Nat) => Self::FloatNat,
-            (Type::Float, Type::Float) => Self::FloatFloat,
-            (Type::Float, Type::Str) => Self::FloatStr,
-            (Type::Float, Type::Bool) => Self::FloatBool,
-            (Type::Float, Type::Poly { name, .. }) if &name[..] == "Array" => Self::FloatArray,
-            (Type::Float, Type::Poly { name, .. }) if &name[..] == "Func" => Self::FloatFunc,
-            (Type::Float, Type::Poly { name, .. }) if &name[..] == "Proc" => Self::FloatProc,
-            (Type::Bool, Type::Int) => Self::BoolInt,
-            (Type::Bool, Type::Nat) => Self::BoolNat,
-            (Type::Bool, Type::Float) => Self::BoolFloat,
-            (Type::Bool, Type::Str) => Self::BoolStr,
-            (Type::Bool, Type::Bool) => Self::BoolBool,
-            (Type::Bool, Type::Poly { name, .. }) if &name[..] == "Array" => Self::BoolArray,
-            (Type::Bool, Type::Poly { name, .. }) if &name[..] == "Func" => Self::BoolFunc,
-            (Type::Bool, Type::Poly { name, .. }) if &name[..] == "Proc" => Self::BoolProc,
-            (Type::Str, Type::Int) => Self::StrInt,
-            (Type::Str, Type::Nat) => Self::StrNat,
-            (Type::Str, Type::Float) => Self::StrFloat,
-            (Type::Str, Type::Bool) => Self::StrBool,
-            (Type::Str, Type::Str) => Self::StrStr,
-            (Type::Str, Type::Poly { name, .. }) if &name[..] == "Array" => Self::StrArray,
-            (Type::Str, Type::Poly { name, .. }) if &name[..] == "Func" => Self::StrFunc,
-            (Type::Str, Type::Poly { name, .. }) if &name[..] == "Proc" => Self::StrProc,
-            // 要素数は検査済みなので、気にする必要はない
-            (Type::Poly { name, .. }, Type::Int) if &name[..] == "Array" => Self::ArrayInt,
-            (Type::Poly { name, .. }, Type::Nat) if &name[..] == "Array" => Self::ArrayNat,
-            (Type::Poly { name, .. }, Type::Float) if &name[..] == "Array" => Self::ArrayFloat,
-            (Type::Poly { name, .. }, Type::Str) if &name[..] == "Array" => Self::ArrayStr,
-            (Type::Poly { name, .. }, Type::Bool) if &name[..] == "Array" => Self::ArrayBool,
-            (Type::Poly { name: ln, .. }, Type::Poly { name: rn, .. })
-                if &ln[..] == "Array" && &rn[..] == "Array" =>
-            {
-                Self::ArrayArray
-            }
-            (Type::Poly { name: ln, .. }, Type::Poly { name: rn, .. })
-                if &ln[..] == "Array" && &rn[..] == "Func" =>
-            {
-                Self::ArrayFunc
-            }
-            (Type::Poly { name: ln, .. }, Type::Poly { name: rn, .. })
-                if &ln[..] == "Array" && &rn[..] == "Proc" =>
-            {
-                Self::ArrayProc
-            }
-            (Type::Poly { name, .. }, Type::Int) if &name[..] == "Func" => Self::FuncInt,
-            (Type::Poly { name, .. }, Type::Nat) if &name[..] == "Func" => Self::FuncNat,
-            (Type::Poly { name, .. }, Type::Float) if &name[..] == "Func" => Self::FuncFloat,
-            (Type::Poly { name, .. }, Type::Str) if &name[..] == "Func" => Self::FuncStr,
-            (Type::Poly { name, .. }, Type::Bool) if &name[..] == "Func" => Self::FuncBool,
-            (Type::Poly { name: ln, .. }, Type::Poly { name: rn, .. })
-                if &ln[..] == "Func" && &rn[..] == "Array" =>
-            {
-                Self::FuncArray
-            }
-            (Type::Poly { name: ln, .. }, Type::Poly { name: rn, .. })
-                if &ln[..] == "Func" && &rn[..] == "Func" =>
-            {
-                Self::FuncFunc
-            }
-            (Type::Poly { name: ln, .. }, Type::Poly { name: rn, .. })
-                if &ln[..] == "Func" && &rn[..] == "Proc" =>
-            {
-                Self::FuncProc
-            }
-            (Type::Poly { name, .. }, Type::Int) if &name[..] == "Proc" => Self::ProcInt,
-            (Type::Poly { name, .. }, Type::Nat) if &name[..] == "Proc" => Self::ProcNat,
-            (Type::Poly { name, .. }, Type::Float) if &name[..] == "Proc" => Self::ProcFloat,
-            (Type::Poly { name, .. }, Type::Str) if &name[..] == "Proc" => Self::ProcStr,
-            (Type::Poly { name, .. }, Type::Bool) if &name[..] == "Proc" => Self::ProcBool,
-            (Type::Poly { name: ln, .. }, Type::Poly { name: rn, .. })
-                if &ln[..] == "Proc" && &rn[..] == "Array" =>
-            {
-                Self::ProcArray
-            }
-            (Type::Poly { name: ln, .. }, Type::Poly { name: rn, .. })
-                if &ln[..] == "Proc" && &rn[..] == "Func" =>
-            {
-                Self::ProcFunc
-            }
-            (Type::Poly { name: ln, .. }, Type::Poly { name: rn, .. })
-                if &ln[..] == "Proc" && &rn[..] == "Proc" =>
-            {
-                Self::ProcProc
-            }
-            (_, _) => Self::Others,
+        match (Self::kind(lhs), Self::kind(rhs)) {
+            (Some(l), Some(r)) => {
+                let r = match r {
+                    3 if l != 4 => 4,
+                    4 if l != 4 => 3,
+                    _ => r,
+                };
+                Self::from(((l << 3) | r) + 1)
+            }
+            _ => Self::Others,
+        }
+    }
+
+    /// `self as u8`の逆変換
+    pub const fn from_u8(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Self::IntInt),
+            2 => Some(Self::IntNat),
+            3 => Some(Self::IntFloat),
+            4 => Some(Self::IntStr),
+            5 => Some(Self::IntBool),
+            6 => Some(Self::IntArray),
+            7 => Some(Self::IntFunc),
+            8 => Some(Self::IntProc),
+            9 => Some(Self::NatInt),
+            10 => Some(Self::NatNat),
+            11 => Some(Self::NatFloat),
+            12 => Some(Self::NatStr),
+            13 => Some(Self::NatBool),
+            14 => Some(Self::NatArray),
+            15 => Some(Self::NatFunc),
+            16 => Some(Self::NatProc),
+            17 => Some(Self::FloatInt),
+            18 => Some(Self::FloatNat),
+            19 => Some(Self::FloatFloat),
+            20 => Some(Self::FloatStr),
+            21 => Some(Self::FloatBool),
+            22 => Some(Self::FloatArray),
+            23 => Some(Self::FloatFunc),
+            24 => Some(Self::FloatProc),
+            25 => Some(Self::BoolInt),
+            26 => Some(Self::BoolNat),
+            27 => Some(Self::BoolFloat),
+            28 => Some(Self::BoolStr),
+            29 => Some(Self::BoolBool),
+            30 => Some(Self::BoolArray),
+            31 => Some(Self::BoolFunc),
+            32 => Some(Self::BoolProc),
+            33 => Some(Self::StrInt),
+            34 => Some(Self::StrNat),
+            35 => Some(Self::StrFloat),
+            36 => Some(Self::StrBool),
+            37 => Some(Self::StrStr),
+            38 => Some(Self::StrArray),
+            39 => Some(Self::StrFunc),
+            40 => Some(Self::StrProc),
+            41 => Some(Self::ArrayInt),
+            42 => Some(Self::ArrayNat),
+            43 => Some(Self::ArrayFloat),
+            44 => Some(Self::ArrayStr),
+            45 => Some(Self::ArrayBool),
+            46 => Some(Self::ArrayArray),
+            47 => Some(Self::ArrayFunc),
+            48 => Some(Self::ArrayProc),
+            49 => Some(Self::FuncInt),
+            50 => Some(Self::FuncNat),
+            51 => Some(Self::FuncFloat),
+            52 => Some(Self::FuncStr),
+            53 => Some(Self::FuncBool),
+            54 => Some(Self::FuncArray),
+            55 => Some(Self::FuncFunc),
+            56 => Some(Self::FuncProc),
+            57 => Some(Self::ProcInt),
+            58 => Some(Self::ProcNat),
+            59 => Some(Self::ProcFloat),
+            60 => Some(Self::ProcStr),
+            61 => Some(Self::ProcBool),
+            62 => Some(Self::ProcArray),
+            63 => Some(Self::ProcFunc),
+            64 => Some(Self::ProcProc),
+            65 => Some(Self::Others),
+            66 => Some(Self::Illegals),
+            _ => None,
         }
     }
 }
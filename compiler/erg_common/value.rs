@@ -8,8 +8,11 @@ use std::ops::Neg;
 use std::rc::Rc;
 
 use crate::codeobj::CodeObj;
+use crate::dict::Dict;
+use crate::long::Long;
 use crate::serialize::*;
 use crate::set;
+use crate::set::Set;
 use crate::traits::HasType;
 use crate::ty::{fresh_varname, ConstObj, Predicate, TyParam, Type};
 use crate::{fmt_iter, impl_display_from_debug, switch_lang};
@@ -21,12 +24,21 @@ use crate::{RcArray, Str};
 pub enum ValueObj {
     Int(i32),
     Nat(u64),
+    /// an integer that does not fit in `Int` or `Nat`
+    Long(Rc<Long>),
     Float(f64),
+    Complex {
+        re: f64,
+        im: f64,
+    },
     Str(Str),
+    Bytes(Vec<u8>),
     True,
     False,
     Array(Rc<[ValueObj]>),
     Dict(Rc<[(ValueObj, ValueObj)]>),
+    Set(Set<ValueObj>),
+    FrozenSet(Set<ValueObj>),
     Code(Box<CodeObj>),
     None,
     Ellipsis,
@@ -41,6 +53,7 @@ impl fmt::Debug for ValueObj {
         match self {
             Self::Int(i) => write!(f, "{i}"),
             Self::Nat(n) => write!(f, "{n}"),
+            Self::Long(l) => write!(f, "{l}"),
             Self::Float(fl) => {
                 // In Rust, .0 is shown omitted.
                 if fl.fract() < 1e-10 {
@@ -49,7 +62,18 @@ impl fmt::Debug for ValueObj {
                     write!(f, "{fl}")
                 }
             }
+            // same as Python (e.g. `1+2j`, `1-2j`, `2j`)
+            Self::Complex { re, im } => {
+                if *re == 0.0 && re.is_sign_positive() {
+                    write!(f, "{im}j")
+                } else if im.is_sign_negative() {
+                    write!(f, "{re}{im}j")
+                } else {
+                    write!(f, "{re}+{im}j")
+                }
+            }
             Self::Str(s) => write!(f, "\"{s}\""),
+            Self::Bytes(bytes) => write!(f, "{}", bytes_repr(bytes)),
             Self::True => write!(f, "True"),
             Self::False => write!(f, "False"),
             Self::Array(arr) => write!(f, "[{}]", fmt_iter(arr.iter())),
@@ -60,8 +84,9 @@ impl fmt::Debug for ValueObj {
                 }
                 s.pop();
                 s.pop();
-                write!(f, "[{s}]")
+                write!(f, "{{{s}}}")
             }
+            Self::Set(set) | Self::FrozenSet(set) => write!(f, "{set}"),
             Self::Code(code) => write!(f, "{code}"),
             Self::None => write!(f, "None"),
             Self::Ellipsis => write!(f, "Ellipsis"),
@@ -85,6 +110,7 @@ impl Neg for ValueObj {
             Self::Int(i) => Self::Int(-i),
             Self::Nat(n) => Self::Int(-(n as i32)),
             Self::Float(fl) => Self::Float(-fl),
+            Self::Complex { re, im } => Self::Complex { re: -re, im: -im },
             Self::Inf => Self::NegInf,
             Self::NegInf => Self::Inf,
             other => panic!("cannot negate {other}"),
@@ -97,12 +123,24 @@ impl PartialEq for ValueObj {
         match (self, other) {
             (Self::Int(i), Self::Int(j)) => i == j,
             (Self::Nat(n), Self::Nat(m)) => n == m,
+            (Self::Long(l), Self::Long(r)) => l == r,
             (Self::Float(fl), Self::Float(fr)) => fl == fr,
+            (Self::Complex { re: lr, im: li }, Self::Complex { re: rr, im: ri }) => {
+                lr == rr && li == ri
+            }
             (Self::Str(s), Self::Str(t)) => s == t,
+            (Self::Bytes(l), Self::Bytes(r)) => l == r,
             (Self::True, Self::True) => true,
             (Self::False, Self::False) => true,
             (Self::Array(arr), Self::Array(arr2)) => arr == arr2,
-            (Self::Dict(dict), Self::Dict(dict2)) => dict == dict2,
+            // the insertion order is not significant (same as Python)
+            (Self::Dict(dict), Self::Dict(dict2)) => {
+                dict.len() == dict2.len() && dict.iter().all(|entry| dict2.contains(entry))
+            }
+            // a set and a frozenset with the same elements are equal (same as Python)
+            (Self::Set(set) | Self::FrozenSet(set), Self::Set(set2) | Self::FrozenSet(set2)) => {
+                set == set2
+            }
             (Self::Code(code), Self::Code(code2)) => code == code2,
             (Self::None, Self::None) => true,
             (Self::Ellipsis, Self::Ellipsis) => true,
@@ -121,13 +159,27 @@ impl Hash for ValueObj {
         match self {
             Self::Int(i) => i.hash(state),
             Self::Nat(n) => n.hash(state),
-            // TODO:
-            Self::Float(f) => f.to_bits().hash(state),
+            Self::Long(l) => l.hash(state),
+            // TODO: NaN
+            Self::Float(f) => float_hash_bits(*f).hash(state),
+            Self::Complex { re, im } => {
+                float_hash_bits(*re).hash(state);
+                float_hash_bits(*im).hash(state);
+            }
             Self::Str(s) => s.hash(state),
+            Self::Bytes(bytes) => bytes.hash(state),
             Self::True => true.hash(state),
             Self::False => false.hash(state),
             Self::Array(arr) => arr.hash(state),
-            Self::Dict(dict) => dict.hash(state),
+            // consistent with `==` (independent of the insertion order)
+            Self::Dict(dict) => {
+                let mut entries = dict.iter().collect::<Vec<_>>();
+                entries.sort_by(|(lk, lv), (rk, rv)| {
+                    lk.canonical_cmp(rk).then_with(|| lv.canonical_cmp(rv))
+                });
+                entries.hash(state)
+            }
+            Self::Set(set) | Self::FrozenSet(set) => canonical_elems(set).hash(state),
             Self::Code(code) => code.hash(state),
             Self::None => {
                 "literal".hash(state);
@@ -157,6 +209,19 @@ impl Hash for ValueObj {
     }
 }
 
+/// `Nat` or `Int` if the value fits in it
+impl From<Long> for ValueObj {
+    fn from(long: Long) -> Self {
+        if let Some(n) = long.to_u64() {
+            ValueObj::Nat(n)
+        } else if let Some(i) = long.to_i64().and_then(|i| i32::try_from(i).ok()) {
+            ValueObj::Int(i)
+        } else {
+            ValueObj::Long(Rc::new(long))
+        }
+    }
+}
+
 impl From<i32> for ValueObj {
     fn from(item: i32) -> Self {
         ValueObj::Int(item)
@@ -193,6 +258,12 @@ impl From<Str> for ValueObj {
     }
 }
 
+impl From<Vec<u8>> for ValueObj {
+    fn from(item: Vec<u8>) -> Self {
+        ValueObj::Bytes(item)
+    }
+}
+
 impl From<bool> for ValueObj {
     fn from(item: bool) -> Self {
         if item {
@@ -209,18 +280,53 @@ impl From<CodeObj> for ValueObj {
     }
 }
 
+impl From<Set<ValueObj>> for ValueObj {
+    fn from(item: Set<ValueObj>) -> Self {
+        ValueObj::Set(item)
+    }
+}
+
+/// The entries are in the iteration order of `Dict` (the insertion order is not kept)
+impl From<Dict<ValueObj, ValueObj>> for ValueObj {
+    fn from(item: Dict<ValueObj, ValueObj>) -> Self {
+        ValueObj::Dict(item.into_iter().collect::<Vec<_>>().into())
+    }
+}
+
 impl From<Vec<ValueObj>> for ValueObj {
     fn from(item: Vec<ValueObj>) -> Self {
         ValueObj::Array(RcArray::from(&item[..]))
     }
 }
 
+/// A dict with the entries in the given order (see `ValueObj::dict`)
+impl From<Vec<(ValueObj, ValueObj)>> for ValueObj {
+    fn from(item: Vec<(ValueObj, ValueObj)>) -> Self {
+        ValueObj::dict(item)
+    }
+}
+
+/// A tuple (e.g. `ValueObj::from(((1, 2), 3))`)
+impl<A: Into<ValueObj>, B: Into<ValueObj>> From<(A, B)> for ValueObj {
+    fn from((a, b): (A, B)) -> Self {
+        ValueObj::Array(RcArray::from([a.into(), b.into()]))
+    }
+}
+
+/// A tuple (e.g. `ValueObj::from([1, 2, 3])`)
+impl<T: Into<ValueObj>, const N: usize> From<[T; N]> for ValueObj {
+    fn from(item: [T; N]) -> Self {
+        ValueObj::Array(item.into_iter().map(Into::into).collect())
+    }
+}
+
 impl TryFrom<&ValueObj> for f64 {
     type Error = ();
     fn try_from(val: &ValueObj) -> Result<f64, Self::Error> {
         match val {
             ValueObj::Int(i) => Ok(*i as f64),
             ValueObj::Nat(n) => Ok(*n as f64),
+            ValueObj::Long(l) => l.to_i128().map(|i| i as f64).ok_or(()),
             ValueObj::Float(f) => Ok(*f),
             ValueObj::Inf => Ok(f64::INFINITY),
             ValueObj::NegInf => Ok(f64::NEG_INFINITY),
@@ -248,15 +354,422 @@ impl HasType for ValueObj {
     }
 }
 
+/// same as `repr(float)` of Python (e.g. `1.0`, `1e+16`, `inf`)
+/// `.0` is not appended to integral values if `add_dot_0` is `false` (as in the parts of a complex number)
+fn float_repr(f: f64, add_dot_0: bool) -> String {
+    if f.is_nan() {
+        return "nan".to_string();
+    } else if f.is_infinite() {
+        return if f > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    // the shortest digits that round-trip (e.g. `1.5e-5`),
+    // formatted again to be correctly rounded (`{:e}` may round up a tie)
+    let shortest = format!("{:e}", f.abs());
+    let precision = shortest.split_once('e').unwrap().0.len().saturating_sub(2);
+    let sci = format!("{:.*e}", precision, f.abs());
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp = exp.parse::<i32>().unwrap();
+    let digits = mantissa.replace('.', "");
+    // the position of the decimal point in `digits`
+    let point = exp + 1;
+    let sign = if f.is_sign_negative() { "-" } else { "" };
+    if !(-3..=16).contains(&point) {
+        let exp_sign = if exp < 0 { '-' } else { '+' };
+        format!("{sign}{mantissa}e{exp_sign}{:02}", exp.abs())
+    } else if point <= 0 {
+        format!("{sign}0.{}{digits}", "0".repeat(-point as usize))
+    } else if point as usize >= digits.len() {
+        let zeros = "0".repeat(point as usize - digits.len());
+        let dot_0 = if add_dot_0 { ".0" } else { "" };
+        format!("{sign}{digits}{zeros}{dot_0}")
+    } else {
+        let (int, frac) = digits.split_at(point as usize);
+        format!("{sign}{int}.{frac}")
+    }
+}
+
+/// whether `str.isprintable()` of Python is `True` for `c`
+/// (approximated without the Unicode database, so unassigned code points are regarded as printable)
+fn is_printable(c: char) -> bool {
+    !(c.is_control()
+        || (c.is_whitespace() && c != ' ')
+        || matches!(
+            c,
+            // format characters
+            '\u{ad}'
+                | '\u{600}'..='\u{605}'
+                | '\u{61c}'
+                | '\u{6dd}'
+                | '\u{70f}'
+                | '\u{180e}'
+                | '\u{200b}'..='\u{200f}'
+                | '\u{202a}'..='\u{202e}'
+                | '\u{2060}'..='\u{206f}'
+                | '\u{feff}'
+                | '\u{fff9}'..='\u{fffb}'
+                | '\u{e0000}'..='\u{e007f}'
+                // private use characters
+                | '\u{e000}'..='\u{f8ff}'
+                | '\u{f0000}'..='\u{10ffff}'
+                // noncharacters
+                | '\u{fdd0}'..='\u{fdef}'
+        )
+        || (c as u32 & 0xfffe) == 0xfffe)
+}
+
+/// same as `repr(str)` of Python (e.g. `'a\nb'`)
+fn str_repr(s: &str) -> String {
+    // single quotes are preferred, unless the string contains only single quotes
+    let quote = if s.contains('\'') && !s.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut repr = quote.to_string();
+    for c in s.chars() {
+        match c {
+            '\\' => repr.push_str("\\\\"),
+            '\t' => repr.push_str("\\t"),
+            '\n' => repr.push_str("\\n"),
+            '\r' => repr.push_str("\\r"),
+            _ if c == quote => {
+                repr.push('\\');
+                repr.push(quote);
+            }
+            _ if is_printable(c) => repr.push(c),
+            _ if (c as u32) < 0x100 => repr.push_str(&format!("\\x{:02x}", c as u32)),
+            _ if (c as u32) < 0x10000 => repr.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => repr.push_str(&format!("\\U{:08x}", c as u32)),
+        }
+    }
+    repr.push(quote);
+    repr
+}
+
+/// same as `repr(bytes)` of Python (e.g. `b'a\x00'`)
+fn bytes_repr(bytes: &[u8]) -> String {
+    // single quotes are preferred, unless the bytes contain only single quotes
+    let quote = if bytes.contains(&b'\'') && !bytes.contains(&b'"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut repr = format!("b{quote}");
+    for &b in bytes {
+        match b {
+            b'\\' => repr.push_str("\\\\"),
+            b'\t' => repr.push_str("\\t"),
+            b'\n' => repr.push_str("\\n"),
+            b'\r' => repr.push_str("\\r"),
+            _ if b as char == quote => {
+                repr.push('\\');
+                repr.push(quote);
+            }
+            b' '..=b'~' => repr.push(b as char),
+            _ => repr.push_str(&format!("\\x{b:02x}")),
+        }
+    }
+    repr.push(quote);
+    repr
+}
+
+/// converts a bytes literal (e.g. `b"\x00\n"`) into the bytes
+/// unknown escape sequences are left as they are (same as Python)
+fn unescape_bytes(lit: &str) -> Vec<u8> {
+    let lit = lit.strip_prefix('b').unwrap_or(lit).as_bytes();
+    let lit = &lit[1..lit.len() - 1];
+    let mut bytes = Vec::with_capacity(lit.len());
+    let mut i = 0;
+    while i < lit.len() {
+        if lit[i] != b'\\' || i + 1 == lit.len() {
+            bytes.push(lit[i]);
+            i += 1;
+            continue;
+        }
+        let escaped = match lit[i + 1] {
+            b'\\' => b'\\',
+            b'\'' => b'\'',
+            b'"' => b'"',
+            // octal (up to 3 digits)
+            b'0'..=b'7' => {
+                let len = lit[i + 1..]
+                    .iter()
+                    .take(3)
+                    .take_while(|d| matches!(d, b'0'..=b'7'))
+                    .count();
+                let n = lit[i + 1..i + 1 + len]
+                    .iter()
+                    .fold(0u32, |n, d| n * 8 + u32::from(d - b'0'));
+                // `b"\777"` is truncated (Python raises an error)
+                bytes.push(n as u8);
+                i += 1 + len;
+                continue;
+            }
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'v' => 0x0b,
+            b'x' => {
+                let hex = lit
+                    .get(i + 2..i + 4)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+                if let Some(hex) = hex {
+                    let hex = std::str::from_utf8(hex).unwrap();
+                    bytes.push(u8::from_str_radix(hex, 16).unwrap());
+                    i += 4;
+                } else {
+                    bytes.push(b'\\');
+                    i += 1;
+                }
+                continue;
+            }
+            _ => {
+                bytes.push(b'\\');
+                i += 1;
+                continue;
+            }
+        };
+        bytes.push(escaped);
+        i += 2;
+    }
+    bytes
+}
+
+/// the real part and the imaginary part of a complex number
+type ReIm = (f64, f64);
+
+/// same as `_Py_c_quot` of CPython (`r` is not zero)
+fn complex_div((lr, li): ReIm, (rr, ri): ReIm) -> ReIm {
+    if rr.abs() >= ri.abs() {
+        let ratio = ri / rr;
+        let denom = rr + ri * ratio;
+        ((lr + li * ratio) / denom, (li - lr * ratio) / denom)
+    } else if ri.abs() >= rr.abs() {
+        let ratio = rr / ri;
+        let denom = rr * ratio + ri;
+        ((lr * ratio + li) / denom, (li * ratio - lr) / denom)
+    } else {
+        // either of them is NaN
+        (f64::NAN, f64::NAN)
+    }
+}
+
+/// `0.0` and `-0.0` are equal, so they must have the same hash
+fn float_hash_bits(f: f64) -> u64 {
+    if f == 0.0 {
+        0f64.to_bits()
+    } else {
+        f.to_bits()
+    }
+}
+
+/// the elements in the order of `canonical_cmp` (independent of the hash order)
+fn canonical_elems(set: &Set<ValueObj>) -> Vec<&ValueObj> {
+    let mut elems = set.iter().collect::<Vec<_>>();
+    elems.sort_by(|l, r| l.canonical_cmp(r));
+    elems
+}
+
 impl ValueObj {
+    /// `(re, im)` is not converted with `From`, since tuples become `Array`s
+    pub const fn complex(re: f64, im: f64) -> Self {
+        Self::Complex { re, im }
+    }
+
+    /// A dict with the entries in the given order.
+    /// If a key appears more than once, the first position and the last value are kept (same as a Python dict literal).
+    pub fn dict<I: IntoIterator<Item = (ValueObj, ValueObj)>>(entries: I) -> Self {
+        let mut dict: Vec<(ValueObj, ValueObj)> = vec![];
+        for (key, value) in entries {
+            if let Some(entry) = dict.iter_mut().find(|(k, _)| k == &key) {
+                entry.1 = value;
+            } else {
+                dict.push((key, value));
+            }
+        }
+        Self::Dict(dict.into())
+    }
+
+    /// The same string as `repr` of the corresponding Python object
+    /// (e.g. `'a'` for `"a"`, `(1,)` for a 1-element tuple).
+    /// Unlike `Display`, this is used where Python's notation is expected (e.g. the disassembler).
+    /// The elements of a set are in the order of `canonical_cmp`, which may differ from Python's one.
+    pub fn repr(&self) -> String {
+        match self {
+            Self::Int(_) | Self::Nat(_) | Self::Long(_) => self.to_string(),
+            Self::Float(f) => float_repr(*f, true),
+            Self::Complex { re, im } => {
+                let im = float_repr(*im, false);
+                if *re == 0.0 && re.is_sign_positive() {
+                    format!("{im}j")
+                } else {
+                    let sign = if im.starts_with('-') { "" } else { "+" };
+                    format!("({}{sign}{im}j)", float_repr(*re, false))
+                }
+            }
+            Self::Str(s) => str_repr(s),
+            Self::Bytes(bytes) => bytes_repr(bytes),
+            Self::True => "True".to_string(),
+            Self::False => "False".to_string(),
+            Self::Array(arr) => match &arr[..] {
+                [elem] => format!("({},)", elem.repr()),
+                elems => format!("({})", Self::join_repr(elems.iter())),
+            },
+            Self::Dict(dict) => {
+                let entries = dict
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.repr(), v.repr()))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", entries.join(", "))
+            }
+            Self::Set(set) if set.is_empty() => "set()".to_string(),
+            Self::Set(set) => format!("{{{}}}", Self::join_repr(canonical_elems(set).into_iter())),
+            Self::FrozenSet(set) if set.is_empty() => "frozenset()".to_string(),
+            Self::FrozenSet(set) => format!(
+                "frozenset({{{}}})",
+                Self::join_repr(canonical_elems(set).into_iter())
+            ),
+            Self::Code(code) => format!("{code:?}"),
+            Self::None => "None".to_string(),
+            Self::Ellipsis => "Ellipsis".to_string(),
+            Self::NotImplemented => "NotImplemented".to_string(),
+            // marshaled as floats
+            Self::NegInf => "-inf".to_string(),
+            Self::Inf => "inf".to_string(),
+            Self::Illegal => "<illegal>".to_string(),
+        }
+    }
+
+    fn join_repr<'a>(elems: impl Iterator<Item = &'a ValueObj>) -> String {
+        elems.map(|elem| elem.repr()).collect::<Vec<_>>().join(", ")
+    }
+
     pub const fn is_num(&self) -> bool {
         matches!(self, Self::Int(_) | Self::Nat(_) | Self::Float(_))
     }
 
+    /// Equality as constants in a code object.
+    /// Unlike `==`, values of different types (e.g. `Int(1)` and `True`) are never equal,
+    /// and floats are compared bitwise (so `0.0` and `-0.0` are distinguished).
+    pub fn marshal_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Float(l), Self::Float(r)) => l.to_bits() == r.to_bits(),
+            (Self::Complex { re: lr, im: li }, Self::Complex { re: rr, im: ri }) => {
+                lr.to_bits() == rr.to_bits() && li.to_bits() == ri.to_bits()
+            }
+            (Self::Array(l), Self::Array(r)) => {
+                l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| l.marshal_eq(r))
+            }
+            (Self::Dict(l), Self::Dict(r)) => {
+                l.len() == r.len()
+                    && l.iter()
+                        .zip(r.iter())
+                        .all(|((lk, lv), (rk, rv))| lk.marshal_eq(rk) && lv.marshal_eq(rv))
+            }
+            (Self::Set(l), Self::Set(r)) | (Self::FrozenSet(l), Self::FrozenSet(r)) => {
+                l.len() == r.len()
+                    && canonical_elems(l)
+                        .into_iter()
+                        .zip(canonical_elems(r))
+                        .all(|(l, r)| l.marshal_eq(r))
+            }
+            (Self::Set(_), Self::FrozenSet(_)) | (Self::FrozenSet(_), Self::Set(_)) => false,
+            (l, r) => l == r,
+        }
+    }
+
+    /// A total order of constants, consistent with `marshal_eq`.
+    /// Values are ordered by type first (`None` comes first, so that a string constant
+    /// is not moved to the head of `co_consts`, where it would be taken as the docstring),
+    /// and then by value. Unlike `try_cmp`, this does not compare values numerically.
+    pub fn canonical_cmp(&self, other: &Self) -> Ordering {
+        fn rank(obj: &ValueObj) -> u8 {
+            match obj {
+                ValueObj::None => 0,
+                ValueObj::Int(_) => 1,
+                ValueObj::Nat(_) => 2,
+                ValueObj::Long(_) => 3,
+                ValueObj::Float(_) => 4,
+                ValueObj::Complex { .. } => 5,
+                ValueObj::Str(_) => 6,
+                ValueObj::Bytes(_) => 7,
+                ValueObj::True => 8,
+                ValueObj::False => 9,
+                ValueObj::Array(_) => 10,
+                ValueObj::Dict(_) => 11,
+                ValueObj::Set(_) => 12,
+                ValueObj::FrozenSet(_) => 13,
+                ValueObj::Code(_) => 14,
+                ValueObj::Ellipsis => 15,
+                ValueObj::NotImplemented => 16,
+                ValueObj::NegInf => 17,
+                ValueObj::Inf => 18,
+                ValueObj::Illegal => 19,
+            }
+        }
+        fn cmp_seq<'a>(
+            l: impl Iterator<Item = &'a ValueObj>,
+            mut r: impl Iterator<Item = &'a ValueObj>,
+        ) -> Ordering {
+            for l in l {
+                match r.next() {
+                    Some(r) => match l.canonical_cmp(r) {
+                        Ordering::Equal => {}
+                        other => return other,
+                    },
+                    None => return Ordering::Greater,
+                }
+            }
+            if r.next().is_some() {
+                Ordering::Less
+            } else {
+                Ordering::Equal
+            }
+        }
+        match (self, other) {
+            (Self::Int(l), Self::Int(r)) => l.cmp(r),
+            (Self::Nat(l), Self::Nat(r)) => l.cmp(r),
+            (Self::Long(l), Self::Long(r)) => {
+                (l.is_negative(), l.digits()).cmp(&(r.is_negative(), r.digits()))
+            }
+            (Self::Float(l), Self::Float(r)) => l.total_cmp(r),
+            (Self::Complex { re: lr, im: li }, Self::Complex { re: rr, im: ri }) => {
+                lr.total_cmp(rr).then_with(|| li.total_cmp(ri))
+            }
+            (Self::Str(l), Self::Str(r)) => l[..].cmp(&r[..]),
+            (Self::Bytes(l), Self::Bytes(r)) => l.cmp(r),
+            (Self::Array(l), Self::Array(r)) => cmp_seq(l.iter(), r.iter()),
+            (Self::Dict(l), Self::Dict(r)) => cmp_seq(
+                l.iter().flat_map(|(k, v)| [k, v]),
+                r.iter().flat_map(|(k, v)| [k, v]),
+            ),
+            (Self::Set(l), Self::Set(r)) | (Self::FrozenSet(l), Self::FrozenSet(r)) => cmp_seq(
+                canonical_elems(l).into_iter(),
+                canonical_elems(r).into_iter(),
+            ),
+            (Self::Code(l), Self::Code(r)) => (&l.name[..], l.firstlineno, &l.code)
+                .cmp(&(&r.name[..], r.firstlineno, &r.code))
+                .then_with(|| cmp_seq(l.consts.iter(), r.consts.iter())),
+            (l, r) => rank(l).cmp(&rank(r)),
+        }
+    }
+
     pub fn from_str(t: Type, content: Str) -> Self {
         match t {
-            Type::Int => Self::Int(content.replace("_", "").parse::<i32>().unwrap()),
-            Type::Nat => Self::Nat(content.replace("_", "").parse::<u64>().unwrap()),
+            // too large literals become `Long`s
+            Type::Int => content
+                .replace("_", "")
+                .parse::<i32>()
+                .map(Self::Int)
+                .unwrap_or_else(|_| Self::from(Long::parse(&content).unwrap())),
+            Type::Nat => content
+                .replace("_", "")
+                .parse::<u64>()
+                .map(Self::Nat)
+                .unwrap_or_else(|_| Self::from(Long::parse(&content).unwrap())),
             Type::Float => Self::Float(content.replace("_", "").parse::<f64>().unwrap()),
             // TODO:
             Type::Ratio => Self::Float(content.replace("_", "").parse::<f64>().unwrap()),
@@ -268,6 +781,7 @@ impl ValueObj {
                     Self::Str(Str::rc(replaced))
                 }
             }
+            Type::Mono(name) if &name[..] == "Bytes" => Self::Bytes(unescape_bytes(&content)),
             Type::Bool => {
                 if &content[..] == "True" {
                     Self::True
@@ -284,42 +798,50 @@ impl ValueObj {
         }
     }
 
-    pub fn into_bytes(self) -> Vec<u8> {
+    pub fn into_bytes(self, python_ver: u32) -> Vec<u8> {
+        self.into_bytes_with(&mut RefTable::new(), python_ver)
+    }
+
+    /// `refs` is shared with the enclosing code object (see `CodeObj::into_bytes_with`)
+    pub fn into_bytes_with(self, refs: &mut RefTable, python_ver: u32) -> Vec<u8> {
         match self {
             Self::Int(i) => [
                 vec![DataTypePrefix::Int32 as u8],
                 i32::from(i).to_le_bytes().to_vec(),
             ]
             .concat(),
-            // TODO: Natとしてシリアライズ
-            Self::Nat(n) => [
-                vec![DataTypePrefix::Int32 as u8],
-                i32::from(n as i32).to_le_bytes().to_vec(),
-            ]
-            .concat(),
-            Self::Float(f) => [
-                vec![DataTypePrefix::BinFloat as u8],
-                f64::from(f).to_le_bytes().to_vec(),
-            ]
-            .concat(),
+            // Natの型情報はコード生成時(TypeCode/TypePair)にのみ使われる
+            Self::Nat(n) => nat_into_bytes(n),
+            Self::Long(l) => long_into_bytes(&l),
+            Self::Float(f) => float_into_bytes(f),
+            Self::Complex { re, im } => complex_into_bytes(re, im),
+            Self::Inf => float_into_bytes(f64::INFINITY),
+            Self::NegInf => float_into_bytes(f64::NEG_INFINITY),
             Self::Str(s) => str_into_bytes(s, false),
+            Self::Bytes(bytes) => raw_string_into_bytes(bytes),
             Self::True => vec![DataTypePrefix::True as u8],
             Self::False => vec![DataTypePrefix::False as u8],
             // TODO: SmallTuple
-            Self::Array(arr) => {
-                let mut bytes = Vec::with_capacity(arr.len());
-                bytes.push(DataTypePrefix::Tuple as u8);
-                bytes.append(&mut (arr.len() as u32).to_le_bytes().to_vec());
-                for obj in arr.to_vec().into_iter() {
-                    bytes.append(&mut obj.into_bytes());
-                }
-                bytes
-            }
+            Self::Array(arr) => tuple_into_bytes(arr.to_vec(), refs, python_ver),
+            Self::Dict(dict) => dict_into_bytes(dict.to_vec(), refs, python_ver),
+            // sorted so that the output does not depend on the hash order
+            Self::Set(set) => set_into_bytes(
+                canonical_elems(&set).into_iter().cloned().collect(),
+                false,
+                refs,
+                python_ver,
+            ),
+            Self::FrozenSet(set) => set_into_bytes(
+                canonical_elems(&set).into_iter().cloned().collect(),
+                true,
+                refs,
+                python_ver,
+            ),
             Self::None => {
                 vec![DataTypePrefix::None as u8]
             }
-            Self::Code(c) => c.into_bytes(3425),
-            // Dict
+            Self::Ellipsis => vec![DataTypePrefix::Ellipsis as u8],
+            Self::Code(c) => c.into_bytes_with(refs, python_ver),
             other => {
                 panic!(
                     "{}",
@@ -336,15 +858,26 @@ impl ValueObj {
         match self {
             Self::Int(_) => Type::Int,
             Self::Nat(_) => Type::Nat,
+            Self::Long(l) if !l.is_negative() => Type::Nat,
+            Self::Long(_) => Type::Int,
             Self::Float(_) => Type::Float,
+            Self::Complex { .. } => Type::mono("Complex"),
             Self::Str(_) => Type::Str,
+            Self::Bytes(_) => Type::mono("Bytes"),
             Self::True | Self::False => Type::Bool,
             // TODO:
             Self::Array(arr) => Type::array(
                 arr.iter().next().unwrap().class(),
                 TyParam::value(arr.len()),
             ),
-            Self::Dict(_dict) => todo!(),
+            Self::Dict(dict) => match dict.first() {
+                Some((k, v)) => Type::dict(k.class(), v.class()),
+                None => Type::dict(Type::Never, Type::Never),
+            },
+            Self::Set(set) => Type::set(set.iter().next().map_or(Type::Never, |elem| elem.class())),
+            Self::FrozenSet(set) => {
+                Type::frozenset(set.iter().next().map_or(Type::Never, |elem| elem.class()))
+            }
             Self::Code(_) => Type::Code,
             Self::None => Type::NoneType,
             Self::Ellipsis => Type::Ellipsis,
@@ -355,11 +888,44 @@ impl ValueObj {
         }
     }
 
+    /// the value as a `Long` if it is an integer (`True` and `False` are `1` and `0`, same as Python)
+    fn to_long(&self) -> Option<Long> {
+        match self {
+            Self::Int(i) => Some(Long::from(*i as i64)),
+            Self::Nat(n) => Some(Long::from(*n)),
+            Self::Long(l) => Some(l.as_ref().clone()),
+            Self::True => Some(Long::from(1u64)),
+            Self::False => Some(Long::default()),
+            _ => None,
+        }
+    }
+
+    /// Compares values with the semantics of Python's `<`.
+    /// Numbers are compared exactly, even if they are of different types (e.g. `1 < 1.5`),
+    /// and strings, bytes and tuples lexicographically.
+    /// Returns `None` if the values are incomparable (e.g. `1 < "a"` is a `TypeError`) or either is NaN.
+    /// `PartialOrd` is not implemented, since this is not consistent with `==` (`1 == 1.0` is `false`).
     pub fn try_cmp(&self, other: &Self) -> Option<Ordering> {
+        /// `int <=> float` without rounding the integer
+        fn cmp_int_float(int: &Long, f: f64) -> Option<Ordering> {
+            if f.is_nan() {
+                None
+            } else if f.is_infinite() {
+                Some(if f > 0.0 {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                })
+            } else {
+                let trunc = Long::from_f64(f)?;
+                // e.g. `1 < 1.5` (`1 == trunc(1.5)`, `0.0 < 0.5`)
+                Some(
+                    int.cmp(&trunc)
+                        .then_with(|| 0f64.partial_cmp(&f.fract()).unwrap()),
+                )
+            }
+        }
         match (self, other) {
-            (l, r) if l.is_num() && r.is_num() => f64::try_from(l)
-                .unwrap()
-                .partial_cmp(&f64::try_from(r).unwrap()),
             (Self::Inf, n) | (n, Self::NegInf) if n.is_num() => Some(Ordering::Greater),
             (n, Self::Inf) | (Self::NegInf, n) if n.is_num() => Some(Ordering::Less),
             (Self::NegInf, Self::Inf) => Some(Ordering::Less),
@@ -371,115 +937,191 @@ impl ValueObj {
             (l, Self::PlusEpsilon(r)) => l.try_cmp(r)
                 .map(|o| if matches!(o, Ordering::Equal) { Ordering::Greater } else { o }),
             */
-            // TODO: cmp with str
-            (_s, _o) => None,
+            (Self::Float(l), Self::Float(r)) => l.partial_cmp(r),
+            (Self::Float(f), int) => cmp_int_float(&int.to_long()?, *f).map(Ordering::reverse),
+            (int, Self::Float(f)) => cmp_int_float(&int.to_long()?, *f),
+            (Self::Str(l), Self::Str(r)) => Some(l[..].cmp(&r[..])),
+            (Self::Bytes(l), Self::Bytes(r)) => Some(l.cmp(r)),
+            // the first elements that are not equal decide the order
+            (Self::Array(l), Self::Array(r)) => {
+                for (l, r) in l.iter().zip(r.iter()) {
+                    if l == r {
+                        continue;
+                    }
+                    match l.try_cmp(r)? {
+                        Ordering::Equal => {}
+                        ord => return Some(ord),
+                    }
+                }
+                Some(l.len().cmp(&r.len()))
+            }
+            (l, r) => Some(l.to_long()?.cmp(&r.to_long()?)),
+        }
+    }
+
+    /// converts a numeric value into `t` (e.g. `0` annotated as `Float` -> `0.0`)
+    /// returns `None` if the conversion is lossy (e.g. `1.5` -> `Int`)
+    pub fn try_coerce(&self, t: &Type) -> Option<Self> {
+        match (self, t) {
+            (Self::Int(i), Type::Float) => Some(Self::Float(*i as f64)),
+            (Self::Nat(n), Type::Float) => Some(Self::Float(*n as f64)),
+            (Self::Int(_) | Self::Nat(_) | Self::Float(_), Type::Mono(name))
+                if &name[..] == "Complex" =>
+            {
+                let re = f64::try_from(self).ok()?;
+                Some(Self::complex(re, 0.0))
+            }
+            (Self::Nat(n), Type::Int) if *n <= i32::MAX as u64 => Some(Self::Int(*n as i32)),
+            (Self::Int(i), Type::Nat) if *i >= 0 => Some(Self::Nat(*i as u64)),
+            (Self::Float(f), Type::Int)
+                if f.fract() == 0.0 && *f >= i32::MIN as f64 && *f <= i32::MAX as f64 =>
+            {
+                Some(Self::Int(*f as i32))
+            }
+            (Self::Float(f), Type::Nat)
+                if f.fract() == 0.0 && *f >= 0.0 && *f <= u64::MAX as f64 =>
+            {
+                Some(Self::Nat(*f as u64))
+            }
+            (_, t) if &self.class() == t => Some(self.clone()),
+            _ => None,
+        }
+    }
+
+    /// the value as an integer (`None` if it is not an integer or does not fit in `i128`)
+    fn to_i128(&self) -> Option<i128> {
+        match self {
+            Self::Int(i) => Some(*i as i128),
+            Self::Nat(n) => Some(*n as i128),
+            Self::Long(l) => l.to_i128(),
+            _ => None,
+        }
+    }
+
+    /// `Nat` if `nat` is `true` and `n` is non-negative, otherwise `Int` (or `Long` if it does not fit)
+    fn from_i128(n: i128, nat: bool) -> Self {
+        match (u64::try_from(n), i32::try_from(n)) {
+            (Ok(n), _) if nat => Self::Nat(n),
+            (_, Ok(i)) => Self::Int(i),
+            _ => Self::from(Long::from(n)),
+        }
+    }
+
+    fn to_complex(&self) -> Option<ReIm> {
+        match self {
+            Self::Complex { re, im } => Some((*re, *im)),
+            other => Some((f64::try_from(other).ok()?, 0.0)),
+        }
+    }
+
+    const fn is_zero(&self) -> bool {
+        match self {
+            Self::Int(i) => *i == 0,
+            Self::Nat(n) => *n == 0,
+            Self::Float(f) => *f == 0.0,
+            Self::Complex { re, im } => *re == 0.0 && *im == 0.0,
+            _ => false,
+        }
+    }
+
+    /// A binary operation on numbers with the promotion rules of Python (integer < `Float` < `Complex`).
+    /// `Nat op Nat` stays `Nat` if the result is non-negative.
+    fn try_num_op(
+        &self,
+        other: &Self,
+        int_op: fn(i128, i128) -> Option<i128>,
+        float_op: fn(f64, f64) -> f64,
+        complex_op: fn(ReIm, ReIm) -> ReIm,
+    ) -> Option<Self> {
+        match (self, other) {
+            (Self::Complex { .. }, _) | (_, Self::Complex { .. }) => {
+                let (re, im) = complex_op(self.to_complex()?, other.to_complex()?);
+                Some(Self::complex(re, im))
+            }
+            (Self::Float(_), _) | (_, Self::Float(_)) => {
+                let (l, r) = (f64::try_from(self).ok()?, f64::try_from(other).ok()?);
+                Some(Self::Float(float_op(l, r)))
+            }
+            _ => {
+                let n = int_op(self.to_i128()?, other.to_i128()?)?;
+                let nat = matches!((self, other), (Self::Nat(_), Self::Nat(_)));
+                Some(Self::from_i128(n, nat))
+            }
         }
     }
 
     // REVIEW: allow_divergenceオプションを付けるべきか?
     pub fn try_add(self, other: Self) -> Option<Self> {
         match (self, other) {
-            (Self::Int(l), Self::Int(r)) => Some(Self::Int(l + r)),
-            (Self::Nat(l), Self::Nat(r)) => Some(Self::Nat(l + r)),
-            (Self::Float(l), Self::Float(r)) => Some(Self::Float(l + r)),
-            (Self::Int(l), Self::Nat(r)) => Some(Self::Int(l + r as i32)),
-            (Self::Nat(l), Self::Int(r)) => Some(Self::Int(l as i32 + r)),
-            (Self::Float(l), Self::Nat(r)) => Some(Self::Float(l + r as f64)),
-            (Self::Nat(l), Self::Float(r)) => Some(Self::Float(l as f64 + r)),
-            (Self::Float(l), Self::Int(r)) => Some(Self::Float(l + r as f64)),
-            (Self::Int(l), Self::Float(r)) => Some(Self::Float(l as f64 + r)),
             (Self::Str(l), Self::Str(r)) => Some(Self::Str(Str::from(format!("{}{}", l, r)))),
             (inf @ (Self::Inf | Self::NegInf), _) | (_, inf @ (Self::Inf | Self::NegInf)) => {
                 Some(inf)
             }
-            _ => None,
+            (l, r) => l.try_num_op(
+                &r,
+                i128::checked_add,
+                |l, r| l + r,
+                |l, r| (l.0 + r.0, l.1 + r.1),
+            ),
         }
     }
 
     pub fn try_sub(self, other: Self) -> Option<Self> {
         match (self, other) {
-            (Self::Int(l), Self::Int(r)) => Some(Self::Int(l - r)),
-            (Self::Nat(l), Self::Nat(r)) => Some(Self::Nat(l - r)),
-            (Self::Float(l), Self::Float(r)) => Some(Self::Float(l - r)),
-            (Self::Int(l), Self::Nat(r)) => Some(Self::Int(l - r as i32)),
-            (Self::Nat(l), Self::Int(r)) => Some(Self::Int(l as i32 - r)),
-            (Self::Float(l), Self::Nat(r)) => Some(Self::Float(l - r as f64)),
-            (Self::Nat(l), Self::Float(r)) => Some(Self::Float(l as f64 - r)),
-            (Self::Float(l), Self::Int(r)) => Some(Self::Float(l - r as f64)),
-            (Self::Int(l), Self::Float(r)) => Some(Self::Float(l as f64 - r)),
             (inf @ (Self::Inf | Self::NegInf), other)
             | (other, inf @ (Self::Inf | Self::NegInf))
                 if other != Self::Inf && other != Self::NegInf =>
             {
                 Some(inf)
             }
-            _ => None,
+            (l, r) => l.try_num_op(
+                &r,
+                i128::checked_sub,
+                |l, r| l - r,
+                |l, r| (l.0 - r.0, l.1 - r.1),
+            ),
         }
     }
 
     pub fn try_mul(self, other: Self) -> Option<Self> {
         match (self, other) {
-            (Self::Int(l), Self::Int(r)) => Some(Self::Int(l * r)),
-            (Self::Nat(l), Self::Nat(r)) => Some(Self::Nat(l * r)),
-            (Self::Float(l), Self::Float(r)) => Some(Self::Float(l * r)),
-            (Self::Int(l), Self::Nat(r)) => Some(Self::Int(l * r as i32)),
-            (Self::Nat(l), Self::Int(r)) => Some(Self::Int(l as i32 * r)),
-            (Self::Float(l), Self::Nat(r)) => Some(Self::Float(l * r as f64)),
-            (Self::Nat(l), Self::Float(r)) => Some(Self::Float(l as f64 * r)),
-            (Self::Float(l), Self::Int(r)) => Some(Self::Float(l * r as f64)),
-            (Self::Int(l), Self::Float(r)) => Some(Self::Float(l as f64 * r)),
             (Self::Str(l), Self::Nat(r)) => Some(Self::Str(Str::from(l.repeat(r as usize)))),
             (inf @ (Self::Inf | Self::NegInf), _) | (_, inf @ (Self::Inf | Self::NegInf)) => {
                 Some(inf)
             }
-            _ => None,
+            (l, r) => l.try_num_op(
+                &r,
+                i128::checked_mul,
+                |l, r| l * r,
+                |l, r| (l.0 * r.0 - l.1 * r.1, l.0 * r.1 + l.1 * r.0),
+            ),
         }
     }
 
+    /// True division (`int / int` is a `Float`, same as Python).
+    /// Division by zero is left to the runtime (`ZeroDivisionError`), so `None` is returned.
     pub fn try_div(self, other: Self) -> Option<Self> {
-        match (self, other) {
-            (Self::Int(l), Self::Int(r)) => Some(Self::Int(l / r)),
-            (Self::Nat(l), Self::Nat(r)) => Some(Self::Nat(l / r)),
-            (Self::Float(l), Self::Float(r)) => Some(Self::Float(l / r)),
-            (Self::Int(l), Self::Nat(r)) => Some(Self::Int(l / r as i32)),
-            (Self::Nat(l), Self::Int(r)) => Some(Self::Int(l as i32 / r)),
-            (Self::Float(l), Self::Nat(r)) => Some(Self::Float(l / r as f64)),
-            (Self::Nat(l), Self::Float(r)) => Some(Self::Float(l as f64 / r)),
-            (Self::Float(l), Self::Int(r)) => Some(Self::Float(l / r as f64)),
-            (Self::Int(l), Self::Float(r)) => Some(Self::Float(l as f64 / r)),
-            // TODO: x/±Inf = 0
-            _ => None,
+        if other.is_zero() {
+            return None;
         }
+        if let (Some(l), Some(r)) = (self.to_i128(), other.to_i128()) {
+            // CPython rounds the exact quotient once, so the operands must be exact as floats
+            const EXACT: u128 = 1 << f64::MANTISSA_DIGITS;
+            if l.unsigned_abs() > EXACT || r.unsigned_abs() > EXACT {
+                return None;
+            }
+            return Some(Self::Float(l as f64 / r as f64));
+        }
+        // TODO: x/±Inf = 0
+        self.try_num_op(&other, |_, _| None, |l, r| l / r, complex_div)
     }
 
     pub fn try_gt(self, other: Self) -> Option<Self> {
-        match (self, other) {
-            (Self::Int(l), Self::Int(r)) => Some(Self::from(l > r)),
-            (Self::Nat(l), Self::Nat(r)) => Some(Self::from(l > r)),
-            (Self::Float(l), Self::Float(r)) => Some(Self::from(l > r)),
-            (Self::Int(l), Self::Nat(r)) => Some(Self::from(l > r as i32)),
-            (Self::Nat(l), Self::Int(r)) => Some(Self::from(l as i32 > r)),
-            (Self::Float(l), Self::Nat(r)) => Some(Self::from(l > r as f64)),
-            (Self::Nat(l), Self::Float(r)) => Some(Self::from(l as f64 > r)),
-            (Self::Float(l), Self::Int(r)) => Some(Self::from(l > r as f64)),
-            (Self::Int(l), Self::Float(r)) => Some(Self::from(l as f64 > r)),
-            _ => None,
-        }
+        self.try_cmp(&other).map(|ord| Self::from(ord.is_gt()))
     }
 
     pub fn try_ge(self, other: Self) -> Option<Self> {
-        match (self, other) {
-            (Self::Int(l), Self::Int(r)) => Some(Self::from(l >= r)),
-            (Self::Nat(l), Self::Nat(r)) => Some(Self::from(l >= r)),
-            (Self::Float(l), Self::Float(r)) => Some(Self::from(l >= r)),
-            (Self::Int(l), Self::Nat(r)) => Some(Self::from(l >= r as i32)),
-            (Self::Nat(l), Self::Int(r)) => Some(Self::from(l as i32 >= r)),
-            (Self::Float(l), Self::Nat(r)) => Some(Self::from(l >= r as f64)),
-            (Self::Nat(l), Self::Float(r)) => Some(Self::from(l as f64 >= r)),
-            (Self::Float(l), Self::Int(r)) => Some(Self::from(l >= r as f64)),
-            (Self::Int(l), Self::Float(r)) => Some(Self::from(l as f64 >= r)),
-            _ => None,
-        }
+        self.try_cmp(&other).map(|ord| Self::from(ord.is_ge()))
     }
 
     pub fn try_eq(self, other: Self) -> Option<Self> {
@@ -5,13 +5,15 @@ use std::fmt;
 use std::process;
 
 use erg_common::cache::Cache;
-use erg_common::codeobj::{CodeObj, CodeObjFlags};
+use erg_common::codeobj::{compute_stacksize_with, CodeObj, CodeObjFlags};
 use erg_common::color::{GREEN, RESET};
 use erg_common::config::{ErgConfig, Input};
 use erg_common::error::{Location, MultiErrorDisplay};
-use erg_common::opcode::Opcode;
+use erg_common::opcode::{CompareOp, CustomOpcode, Opcode, OpcodeTable};
+use erg_common::python_util::detect_magic_number;
+use erg_common::serialize::get_python_version;
 use erg_common::traits::{HasType, Locational, Stream};
-use erg_common::ty::{TypeCode, TypePair};
+use erg_common::ty::{Type, TypeCode, TypePair};
 use erg_common::value::ValueObj;
 use erg_common::Str;
 use erg_common::{
@@ -19,16 +21,41 @@ use erg_common::{
 };
 use Opcode::*;
 
-use erg_parser::ast::{ParamPattern, Params, VarPattern};
+use erg_parser::ast;
+use erg_parser::ast::{
+    ConstExpr, ParamArrayPattern, ParamPattern, ParamTuplePattern, Params, VarPattern,
+};
 use erg_parser::token::{Token, TokenCategory, TokenKind};
 
 use crate::compile::{AccessKind, Name, StoreLoadKind};
 use crate::error::{CompileError, CompileErrors, CompileResult};
 use crate::hir::{
-    Accessor, Args, Block, DefBody, Expr, Signature, SubrSignature, VarSignature, HIR,
+    Accessor, Args, ArrayComprehension, AttrAssign, BinOp, Block, DefBody, Dict, Expr, Literal,
+    Module, NamedExpr, Record, Set, Signature, StrInterp, StrSegment, SubrSignature, TypeCast,
+    VarSignature, Yield, HIR,
 };
 use AccessKind::*;
 
+/// the target if `cfg.python_ver` is not specified and no supported Python is installed (Python 3.9)
+const DEFAULT_PYTHON_VER: u32 = 3425;
+
+/// the newest Python the bytecode can be generated for (3.11+ need an exception table)
+const MAX_PYTHON_VER: u32 = 3439;
+
+/// The magic number of the target Python: `cfg.python_ver` if specified,
+/// otherwise that of the installed Python if it is supported (see `detect_magic_number`),
+/// otherwise `DEFAULT_PYTHON_VER`.
+pub fn target_python_ver(cfg: &ErgConfig) -> u32 {
+    cfg.python_ver.unwrap_or_else(|| {
+        detect_magic_number()
+            .filter(|magic| *magic <= MAX_PYTHON_VER)
+            .unwrap_or(DEFAULT_PYTHON_VER)
+    })
+}
+
+/// `x ** n` is unrolled into repeated multiplication while `n` is at most this value
+const POW_UNROLL_LIMIT: u64 = 4;
+
 fn obj_name(obj: &Expr) -> Option<String> {
     match obj {
         Expr::Accessor(Accessor::Local(n)) => Some(n.inspect().to_string()),
@@ -38,22 +65,108 @@ fn obj_name(obj: &Expr) -> Option<String> {
     }
 }
 
+fn compare_op(kind: &TokenKind) -> Option<CompareOp> {
+    match kind {
+        TokenKind::Less => Some(CompareOp::LT),
+        TokenKind::LessEq => Some(CompareOp::LE),
+        TokenKind::DblEq => Some(CompareOp::EQ),
+        TokenKind::NotEq => Some(CompareOp::NE),
+        TokenKind::Gre => Some(CompareOp::GT),
+        TokenKind::GreEq => Some(CompareOp::GE),
+        _ => None,
+    }
+}
+
+/// `(start_excluded, stop_included)` of the range operator
+fn range_bounds(kind: &TokenKind) -> Option<(bool, bool)> {
+    match kind {
+        TokenKind::Closed => Some((false, true)),
+        TokenKind::LeftOpen => Some((true, true)),
+        TokenKind::RightOpen => Some((false, false)),
+        TokenKind::Open => Some((true, false)),
+        _ => None,
+    }
+}
+
+/// `x += 1` -> `INPLACE_ADD`, etc.
+fn inplace_op(kind: &TokenKind) -> Option<Opcode> {
+    match kind {
+        TokenKind::PlusEqual => Some(Opcode::INPLACE_ADD),
+        TokenKind::MinusEqual => Some(Opcode::INPLACE_SUBTRACT),
+        TokenKind::StarEqual => Some(Opcode::INPLACE_MULTIPLY),
+        TokenKind::SlashEqual => Some(Opcode::INPLACE_TRUE_DIVIDE),
+        TokenKind::FloorDivEqual => Some(Opcode::INPLACE_FLOOR_DIVIDE),
+        TokenKind::ModEqual => Some(Opcode::INPLACE_MODULO),
+        _ => None,
+    }
+}
+
+/// Python builtin methods that an Erg `!`-suffixed method can be lowered to as-is
+/// e.g. `s.upper!()` -> `s.upper()`
+const PY_BUILTIN_METHODS: [&str; 20] = [
+    "capitalize",
+    "copy",
+    "count",
+    "endswith",
+    "find",
+    "format",
+    "index",
+    "join",
+    "lower",
+    "lstrip",
+    "replace",
+    "rstrip",
+    "split",
+    "startswith",
+    "strip",
+    "swapcase",
+    "title",
+    "update",
+    "upper",
+    "zfill",
+];
+
+/// Builtin classes (and modules) whose `!`-suffixed methods may map to `PY_BUILTIN_METHODS`
+const PY_BUILTIN_CLASSES: [&str; 9] = [
+    "Array", "Array!", "Dict", "Dict!", "Module", "Set", "Set!", "Str", "Str!",
+];
+
 fn convert_to_python_attr(class: &str, uniq_obj_name: Option<&str>, name: Str) -> Str {
     match (class, uniq_obj_name, &name[..]) {
         ("Array!", _, "push!") => Str::ever("append"),
+        ("Array!", _, "extend!") => Str::ever("extend"),
+        ("Array!", _, "insert!") => Str::ever("insert"),
+        ("Array!", _, "remove!") => Str::ever("remove"),
+        ("Array!", _, "pop!") => Str::ever("pop"),
+        ("Array!", _, "clear!") => Str::ever("clear"),
+        ("Array!", _, "sort!") => Str::ever("sort"),
+        ("Array!", _, "reverse!") => Str::ever("reverse"),
+        ("Dict!", _, "insert!") => Str::ever("__setitem__"),
+        ("Dict!", _, "remove!") => Str::ever("pop"),
+        ("Dict!", _, "clear!") => Str::ever("clear"),
+        ("Set!", _, "add!") => Str::ever("add"),
+        ("Set!", _, "remove!") => Str::ever("remove"),
+        ("Set!", _, "discard!") => Str::ever("discard"),
+        ("Set!", _, "clear!") => Str::ever("clear"),
         ("Complex" | "Real" | "Int" | "Nat" | "Float", _, "Real") => Str::ever("real"),
         ("Complex" | "Real" | "Int" | "Nat" | "Float", _, "Imag") => Str::ever("imag"),
         ("Module", Some("random"), "randint!") => Str::ever("randint"),
         ("Module", Some("random"), "choice!") => Str::ever("choice"),
-        _ => name,
+        (_, _, other) => match other.strip_suffix('!') {
+            Some(pure)
+                if PY_BUILTIN_CLASSES.contains(&class) && PY_BUILTIN_METHODS.contains(&pure) =>
+            {
+                Str::rc(pure)
+            }
+            _ => name,
+        },
     }
 }
 
-fn escape_attr(class: &str, uniq_obj_name: Option<&str>, name: Str) -> Str {
-    let mut name = convert_to_python_attr(class, uniq_obj_name, name).to_string();
-    name = name.replace("!", "__erg_proc__");
-    name = name.replace("$", "__erg_shared__");
-    Str::rc(&name)
+fn escape_attr(class: &str, uniq_obj_name: Option<&str>, name: Str, cache: &Cache<str>) -> Str {
+    convert_to_python_attr(class, uniq_obj_name, name)
+        .replace_str("!", "__erg_proc__", cache)
+        .replace_str("$", "__erg_shared__", cache)
 }
 
 fn convert_to_python_name(name: Str) -> Str {
@@ -62,25 +175,149 @@ fn convert_to_python_name(name: Str) -> Str {
         // assert is implemented in bytecode
         "classof" => Str::ever("type"),
         "compile" => Str::ever("compile"),
+        // del! is implemented in bytecode
         // discard is implemented in bytecode
         // for is implemented in bytecode
         "id" => Str::ever("id"),
         // if is implemented in bytecode
         "import" => Str::ever("__import__"),
         "input!" => Str::ever("input"),
-        "log" => Str::ever("print"), // TODO: log != print (prints after executing)
+        // `log(...)` calls are implemented in bytecode (printed after the statement)
+        "log" => Str::ever("print"),
+        "open!" => Str::ever("open"),
         "print!" => Str::ever("print"),
         "py" | "pyimport" => Str::ever("__import__"),
         "quit" | "exit" => Str::ever("quit"),
+        // with! is implemented in bytecode
         _ => name,
     }
 }
 
-fn escape_name(name: Str) -> Str {
-    let mut name = convert_to_python_name(name).to_string();
-    name = name.replace("!", "__erg_proc__");
-    name = name.replace("$", "__erg_shared__");
-    Str::rc(&name)
+fn escape_name(name: Str, cache: &Cache<str>) -> Str {
+    convert_to_python_name(name)
+        .replace_str("!", "__erg_proc__", cache)
+        .replace_str("$", "__erg_shared__", cache)
+}
+
+/// the field names of a record (as Python attributes)
+fn record_fields(record: &Record, cache: &Cache<str>) -> Vec<Str> {
+    record
+        .attrs
+        .iter()
+        .map(|attr| escape_attr("Record", None, attr.ident.content.clone(), cache))
+        .collect()
+}
+
+/// the name of the global variable holding the namedtuple type of the records with `fields`
+/// (not an identifier, so that it does not conflict with the user's variables)
+fn record_type_name(fields: &[Str]) -> Str {
+    Str::from(format!("__erg_record({})__", fields.join(", ")))
+}
+
+fn collect_record_types_in_args(args: &Args, types: &mut Vec<Vec<Str>>, cache: &Cache<str>) {
+    for arg in args.pos_args().iter() {
+        collect_record_types(&arg.expr, types, cache);
+    }
+    for arg in args.kw_args().iter() {
+        collect_record_types(&arg.expr, types, cache);
+    }
+    for arg in args.var_args().into_iter().chain(args.kw_var_args()) {
+        collect_record_types(&arg.expr, types, cache);
+    }
+}
+
+/// collects the distinct field names of the record literals in `expr`
+fn collect_record_types(expr: &Expr, types: &mut Vec<Vec<Str>>, cache: &Cache<str>) {
+    match expr {
+        Expr::Lit(_) | Expr::Decl(_) => {}
+        Expr::Accessor(Accessor::Local(_) | Accessor::SelfDot(_)) => {}
+        Expr::Accessor(Accessor::Attr(attr)) => collect_record_types(&attr.obj, types, cache),
+        Expr::Accessor(Accessor::Subscr(subscr)) => {
+            collect_record_types(&subscr.obj, types, cache);
+            collect_record_types(&subscr.index, types, cache);
+        }
+        Expr::Array(array) => {
+            collect_record_types_in_args(&array.elems, types, cache);
+            if let Some(guard) = &array.guard {
+                collect_record_types(guard, types, cache);
+            }
+        }
+        Expr::ArrayComprehension(comp) => {
+            collect_record_types(&comp.elem, types, cache);
+            collect_record_types(&comp.iter, types, cache);
+            for guard in comp.guards.iter() {
+                collect_record_types(guard, types, cache);
+            }
+        }
+        Expr::Tuple(tuple) => collect_record_types_in_args(&tuple.elems, types, cache),
+        Expr::Dict(dict) => {
+            for kv in dict.kvs.iter() {
+                collect_record_types(&kv.key, types, cache);
+                collect_record_types(&kv.value, types, cache);
+            }
+        }
+        Expr::Set(set) => collect_record_types_in_args(&set.elems, types, cache),
+        Expr::Record(record) => {
+            for attr in record.attrs.iter() {
+                collect_record_types(&attr.value, types, cache);
+            }
+            let fields = record_fields(record, cache);
+            if !types.contains(&fields) {
+                types.push(fields);
+            }
+        }
+        Expr::BinOp(bin) => {
+            collect_record_types(&bin.lhs, types, cache);
+            collect_record_types(&bin.rhs, types, cache);
+        }
+        Expr::UnaryOp(unary) => collect_record_types(&unary.expr, types, cache),
+        Expr::Call(call) => {
+            collect_record_types(&call.obj, types, cache);
+            collect_record_types_in_args(&call.args, types, cache);
+        }
+        Expr::Lambda(lambda) => {
+            for expr in lambda.body.iter() {
+                collect_record_types(expr, types, cache);
+            }
+        }
+        Expr::Def(def) => {
+            if let Signature::Subr(sig) = &def.sig {
+                for deco in sig.decorators.iter() {
+                    collect_record_types(deco, types, cache);
+                }
+            }
+            for expr in def.body.block.iter() {
+                collect_record_types(expr, types, cache);
+            }
+        }
+        Expr::NamedExpr(named) => collect_record_types(&named.value, types, cache),
+        Expr::StrInterp(interp) => {
+            for seg in interp.segments.iter() {
+                if let StrSegment::Expr { expr, .. } = seg {
+                    collect_record_types(expr, types, cache);
+                }
+            }
+        }
+        Expr::Slice(slice) => {
+            for bound in [&slice.start, &slice.stop, &slice.step]
+                .into_iter()
+                .flatten()
+            {
+                collect_record_types(bound, types, cache);
+            }
+        }
+        Expr::SubscrAssign(assign) => {
+            collect_record_types(&assign.subscr.obj, types, cache);
+            collect_record_types(&assign.subscr.index, types, cache);
+            collect_record_types(&assign.value, types, cache);
+        }
+        Expr::AttrAssign(assign) => {
+            collect_record_types(&assign.attr.obj, types, cache);
+            collect_record_types(&assign.value, types, cache);
+        }
+        Expr::Yield(yield_) => collect_record_types(&yield_.value, types, cache),
+        Expr::TypeCast(cast) => collect_record_types(&cast.expr, types, cache),
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -91,6 +328,10 @@ pub struct CodeGenUnit {
     pub(crate) prev_lineno: usize,
     pub(crate) lasti: usize,
     pub(crate) prev_lasti: usize,
+    /// (index of the jump instruction, arg) of the jumps whose arg does not fit in a byte
+    pub(crate) long_jumps: Vec<(usize, u32)>,
+    /// whether the unit is a class body (the units in it are not qualified with `<locals>`)
+    pub(crate) is_class: bool,
     pub(crate) _refs: Vec<ValueObj>, // ref-counted objects
 }
 
@@ -127,6 +368,8 @@ impl CodeGenUnit {
             prev_lineno: firstlineno,
             lasti: 0,
             prev_lasti: 0,
+            long_jumps: vec![],
+            is_class: false,
             _refs: vec![],
         }
     }
@@ -137,36 +380,94 @@ pub struct CodeGenStack(Vec<CodeGenUnit>);
 
 impl_stream_for_wrapper!(CodeGenStack, CodeGenUnit);
 
+impl CodeGenStack {
+    /// Pushes `unit`, qualifying its name with the enclosing units (`co_qualname`):
+    /// `f.<locals>.g` for `g` in a function `f`, `C.f` for `f` in a class `C`
+    pub fn push_unit(&mut self, mut unit: CodeGenUnit) {
+        // the names in the module are not qualified
+        if self.len() >= 2 {
+            let outer = self.last().unwrap();
+            let sep = if outer.is_class { "." } else { ".<locals>." };
+            unit.codeobj.qualname = Str::from(format!(
+                "{}{sep}{}",
+                outer.codeobj.qualname, unit.codeobj.name
+            ));
+        }
+        self.push(unit);
+    }
+}
+
 #[derive(Debug)]
 pub struct CodeGenerator {
     cfg: ErgConfig,
     str_cache: Cache<str>,
     unit_size: usize,
     units: CodeGenStack,
+    /// the number of `log` calls compiled so far (for naming the temporary variables)
+    log_count: usize,
+    /// the temporary variables holding the arguments of `log` calls to be printed after the current statement
+    pending_logs: Vec<Vec<Str>>,
+    /// the fields of the record types defined at the head of the module (see `emit_record_types`)
+    record_types: Vec<Vec<Str>>,
+    /// custom opcodes (for a custom VM) and the target Python version
+    opcode_table: OpcodeTable,
+    /// the opcodes unavailable in the target version that have already been reported
+    unsupported_ops: Vec<Opcode>,
     pub(crate) errs: CompileErrors,
 }
 
 impl CodeGenerator {
     pub fn new(cfg: ErgConfig) -> Self {
+        Self::with_opcode_table(cfg, OpcodeTable::new())
+    }
+
+    /// The target Python is detected here unless `cfg.python_ver` is specified (see `target_python_ver`).
+    pub fn with_opcode_table(mut cfg: ErgConfig, mut opcode_table: OpcodeTable) -> Self {
+        if !cfg.erg_vm() {
+            let python_ver = target_python_ver(&cfg);
+            cfg.python_ver = Some(python_ver);
+            opcode_table.set_version(get_python_version(python_ver));
+        }
         Self {
             cfg,
             str_cache: Cache::new(),
             unit_size: 0,
             units: CodeGenStack::empty(),
+            log_count: 0,
+            pending_logs: vec![],
+            record_types: vec![],
+            opcode_table,
+            unsupported_ops: vec![],
             errs: CompileErrors::empty(),
         }
     }
 
     pub fn clear(&mut self) {
         self.units.clear();
+        self.unsupported_ops.clear();
         self.errs.clear();
     }
 
+    /// the table to decode the generated code with
+    pub fn opcode_table(&self) -> &OpcodeTable {
+        &self.opcode_table
+    }
+
+    /// the errors found by the last `codegen` call
+    pub fn errs(&self) -> &CompileErrors {
+        &self.errs
+    }
+
     #[inline]
     fn input(&self) -> &Input {
         &self.cfg.input
     }
 
+    #[inline]
+    fn python_ver(&self) -> u32 {
+        self.cfg.python_ver.unwrap_or(DEFAULT_PYTHON_VER)
+    }
+
     fn get_cached(&self, s: &str) -> Str {
         self.str_cache.get(s)
     }
@@ -207,18 +508,102 @@ impl CodeGenerator {
         *self.mut_cur_block_codeobj().code.get_mut(idx).unwrap() = code as u8;
     }
 
+    /// the arg of a jump to the offset `bytes` or over `bytes` bytes, in the unit of the target version
+    fn jump_arg(&self, bytes: usize) -> usize {
+        self.opcode_table.jump_arg(bytes)
+    }
+
+    /// Back-patches the arg of the jump instruction at `idx`.
+    /// `EXTENDED_ARG`s cannot be inserted here (the offsets recorded so far would be invalidated),
+    /// so an `arg` larger than 255 is recorded and fixed by `CodeObj::relax_jumps` when the unit is finished.
+    fn edit_jump(&mut self, idx: usize, arg: usize) {
+        if arg > 0xff {
+            self.mut_cur_block().long_jumps.push((idx, arg as u32));
+        }
+        self.edit_code(idx + 1, arg);
+    }
+
+    /// writes the byte of `code` in the target Python version
+    /// (the discriminant is written as is for the Erg VM)
+    /// an opcode unavailable in the version is reported once per compilation
     fn write_instr(&mut self, code: Opcode) {
-        self.mut_cur_block_codeobj().code.push(code as u8);
+        let byte = match self.opcode_table.version().map(|ver| code.to_byte(ver)) {
+            Some(Ok(byte)) => byte,
+            Some(Err(err)) => {
+                if !self.unsupported_ops.contains(&code) {
+                    self.unsupported_ops.push(code);
+                    self.errs.push(CompileError::feature_error(
+                        self.cfg.input.clone(),
+                        Location::Unknown,
+                        &err.to_string(),
+                        self.cur_block_codeobj().name.clone(),
+                    ));
+                }
+                code as u8
+            }
+            None => code as u8,
+        };
+        self.mut_cur_block_codeobj().code.push(byte);
         self.mut_cur_block().lasti += 1;
         // log!("wrote: {}", code);
     }
 
+    /// the fused instruction for `instr` with the operand types `pair`
+    /// only the Erg VM can execute it, so it is not used unless `cfg.erg_vm` is set
+    fn fast_path(&self, instr: Opcode, pair: TypePair) -> Option<Opcode> {
+        if !self.cfg.erg_vm() {
+            return None;
+        }
+        instr.specialize(pair)
+    }
+
     fn write_arg(&mut self, code: u8) {
         self.mut_cur_block_codeobj().code.push(code);
         self.mut_cur_block().lasti += 1;
         // log!("wrote: {}", code);
     }
 
+    /// `arg`s larger than 255 are prefixed with `EXTENDED_ARG`s
+    fn write_instr_arg(&mut self, instr: Opcode, arg: usize) {
+        for shift in [24, 16, 8] {
+            if arg >> shift != 0 {
+                self.write_instr(EXTENDED_ARG);
+                self.write_arg((arg >> shift) as u8);
+            }
+        }
+        self.write_instr(instr);
+        self.write_arg(arg as u8);
+    }
+
+    /// Finishes the current unit: inserts the `EXTENDED_ARG`s for long jumps,
+    /// runs the peephole optimizer (`opt_level >= 2`) and removes the unreachable code (`opt_level >= 1`),
+    /// and replaces the stack size tracked by `stack_inc`/`stack_dec` with the one computed from the code
+    /// (if the code fails the check, the tracked one is kept and a `bytecode_bug` is reported).
+    fn pop_unit(&mut self) -> CodeGenUnit {
+        let mut unit = self.units.pop().unwrap();
+        unit.codeobj
+            .relax_jumps_with(&unit.long_jumps, &self.opcode_table);
+        if self.cfg.opt_level() >= 2 {
+            unit.codeobj.peephole_optimize_with(&self.opcode_table);
+        }
+        if self.cfg.opt_level() >= 1 {
+            unit.codeobj.eliminate_dead_code_with(&self.opcode_table);
+        }
+        match compute_stacksize_with(&unit.codeobj.code, &unit.codeobj.consts, &self.opcode_table) {
+            Ok(stacksize) => {
+                unit.codeobj.stacksize = stacksize;
+            }
+            Err(err) => {
+                self.errs.push(CompileError::bytecode_bug(
+                    self.cfg.input.clone(),
+                    &unit.codeobj.name,
+                    &err,
+                ));
+            }
+        }
+        unit
+    }
+
     fn stack_inc(&mut self) {
         self.mut_cur_block().stack_len += 1;
         if self.cur_block().stack_len > self.cur_block_codeobj().stacksize {
@@ -256,13 +641,12 @@ impl CodeGenerator {
             .mut_cur_block_codeobj()
             .consts
             .iter()
-            .position(|c| c == &cons)
+            .position(|c| c.marshal_eq(&cons))
             .unwrap_or_else(|| {
                 self.mut_cur_block_codeobj().consts.push(cons);
                 self.mut_cur_block_codeobj().consts.len() - 1
             });
-        self.write_instr(Opcode::LOAD_CONST);
-        self.write_arg(idx as u8);
+        self.write_instr_arg(Opcode::LOAD_CONST, idx);
         self.stack_inc();
     }
 
@@ -331,7 +715,7 @@ impl CodeGenerator {
 
     fn register_name(&mut self, name: Str) -> Name {
         let current_is_toplevel = self.cur_block() == self.toplevel_block();
-        let name = escape_name(name);
+        let name = escape_name(name, &self.str_cache);
         match self.rec_search(&name) {
             Some(st @ (StoreLoadKind::Local | StoreLoadKind::Global)) => {
                 let st = if current_is_toplevel {
@@ -363,17 +747,24 @@ impl CodeGenerator {
     }
 
     fn register_attr(&mut self, class: &str, uniq_obj_name: Option<&str>, name: Str) -> Name {
-        let name = Str::rc(name.split(".").last().unwrap());
-        let name = escape_attr(class, uniq_obj_name, name);
-        self.mut_cur_block_codeobj().names.push(name);
-        Name::local(self.cur_block_codeobj().names.len() - 1)
+        let name = name.split_str(".", &self.str_cache).last().unwrap();
+        let name = escape_attr(class, uniq_obj_name, name, &self.str_cache);
+        Name::local(self.name_idx(name))
     }
 
     fn register_method(&mut self, class: &str, uniq_obj_name: Option<&str>, name: Str) -> Name {
-        let name = Str::rc(name.split(".").last().unwrap());
-        let name = escape_attr(class, uniq_obj_name, name);
-        self.mut_cur_block_codeobj().names.push(name);
-        Name::local(self.cur_block_codeobj().names.len() - 1)
+        let name = name.split_str(".", &self.str_cache).last().unwrap();
+        let name = escape_attr(class, uniq_obj_name, name, &self.str_cache);
+        Name::local(self.name_idx(name))
+    }
+
+    /// the index of `name` in `co_names` (registered if not yet)
+    fn name_idx(&mut self, name: Str) -> usize {
+        let names = &mut self.mut_cur_block_codeobj().names;
+        names.iter().position(|n| *n == name).unwrap_or_else(|| {
+            names.push(name);
+            names.len() - 1
+        })
     }
 
     fn emit_load_name_instr(&mut self, name: Str) -> CompileResult<()> {
@@ -386,8 +777,7 @@ impl CodeGenerator {
             StoreLoadKind::Deref | StoreLoadKind::DerefConst => Opcode::LOAD_DEREF,
             StoreLoadKind::Local | StoreLoadKind::LocalConst => Opcode::LOAD_NAME,
         };
-        self.write_instr(instr);
-        self.write_arg(name.idx as u8);
+        self.write_instr_arg(instr, name.idx);
         self.stack_inc();
         Ok(())
     }
@@ -407,8 +797,7 @@ impl CodeGenerator {
             StoreLoadKind::Deref | StoreLoadKind::DerefConst => Opcode::LOAD_DEREF,
             StoreLoadKind::Local | StoreLoadKind::LocalConst => Opcode::LOAD_ATTR,
         };
-        self.write_instr(instr);
-        self.write_arg(name.idx as u8);
+        self.write_instr_arg(instr, name.idx);
         Ok(())
     }
 
@@ -427,8 +816,7 @@ impl CodeGenerator {
             StoreLoadKind::Deref | StoreLoadKind::DerefConst => Opcode::LOAD_DEREF,
             StoreLoadKind::Local | StoreLoadKind::LocalConst => Opcode::LOAD_METHOD,
         };
-        self.write_instr(instr);
-        self.write_arg(name.idx as u8);
+        self.write_instr_arg(instr, name.idx);
         Ok(())
     }
 
@@ -450,8 +838,7 @@ impl CodeGenerator {
                 }
             }
         };
-        self.write_instr(instr);
-        self.write_arg(name.idx as u8);
+        self.write_instr_arg(instr, name.idx);
         self.stack_dec();
     }
 
@@ -500,18 +887,19 @@ impl CodeGenerator {
     fn emit_var_pat(&mut self, pat: &VarPattern, op: &Token) {
         match pat {
             VarPattern::VarName(var) => {
-                if op.category_is(TokenCategory::DefOp) {
+                if op.category_is(TokenCategory::DefOp) || inplace_op(&op.kind).is_some() {
                     self.emit_store_instr(var.inspect().clone(), Name);
                 } else {
                     todo!()
                 }
             }
+            // `*xs` of `[x, *xs] = ...`: the unpacked rest is already on the stack as a list
+            VarPattern::VarArgsName(var) => {
+                self.emit_store_instr(var.inspect().clone(), Name);
+            }
             VarPattern::Array(a) => {
                 if op.category_is(TokenCategory::DefOp) {
-                    // TODO: UNPACK_EX
-                    self.write_instr(UNPACK_SEQUENCE);
-                    self.write_arg(a.len() as u8);
-                    self.stack_inc_n(a.len() - 1);
+                    self.emit_unpack_instr(a.ref_payload());
                     for sig in a.iter() {
                         self.emit_var_pat(&sig.pat, op);
                     }
@@ -519,10 +907,51 @@ impl CodeGenerator {
                     switch_unreachable!()
                 }
             }
+            VarPattern::Tuple(t) => {
+                if op.category_is(TokenCategory::DefOp) {
+                    self.emit_unpack_instr(t.ref_payload());
+                    for sig in t.iter() {
+                        self.emit_var_pat(&sig.pat, op);
+                    }
+                } else {
+                    switch_unreachable!()
+                }
+            }
             _ => todo!(),
         }
     }
 
+    /// `UNPACK_SEQUENCE` or, if there is a starred target, `UNPACK_EX`
+    fn emit_unpack_instr(&mut self, elems: &[ast::VarSignature]) {
+        let starred = elems
+            .iter()
+            .position(|sig| matches!(sig.pat, VarPattern::VarArgsName(_)));
+        if let Some(before) = starred {
+            let after = elems.len() - before - 1;
+            if before > 0xff || after > 0xff {
+                self.errs.push(CompileError::feature_error(
+                    self.cfg.input.clone(),
+                    elems[before].loc(),
+                    "more than 255 targets around a starred target",
+                    self.cur_block_codeobj().name.clone(),
+                ));
+                self.stack_inc_n(elems.len() - 1);
+                return;
+            }
+            // arg: `before | after << 8`
+            if after > 0 {
+                self.write_instr(EXTENDED_ARG);
+                self.write_arg(after as u8);
+            }
+            self.write_instr(UNPACK_EX);
+            self.write_arg(before as u8);
+            self.stack_inc_n(elems.len() - 1);
+        } else {
+            self.write_instr_arg(UNPACK_SEQUENCE, elems.len());
+            self.stack_inc_n(elems.len() - 1);
+        }
+    }
+
     fn emit_mono_type_def(&mut self, sig: VarSignature, body: DefBody) {
         self.write_instr(Opcode::LOAD_BUILD_CLASS);
         self.write_arg(0);
@@ -544,113 +973,933 @@ impl CodeGenerator {
         if body.is_type() {
             return self.emit_mono_type_def(sig, body);
         }
+        // `x += 1`: the target is loaded first, and stored back to the same name
+        let inplace = inplace_op(&body.op.kind);
+        if inplace.is_some() {
+            match &sig.pat {
+                VarPattern::VarName(var) => {
+                    self.emit_load_name_instr(var.inspect().clone())
+                        .unwrap_or_else(|err| {
+                            self.errs.push(err);
+                        });
+                }
+                _ => switch_unreachable!(),
+            }
+        }
         if body.block.len() == 1 {
             self.codegen_expr(body.block.remove(0));
         } else {
             self.codegen_frameless_block(body.block, vec![]);
         }
+        if let Some(inplace) = inplace {
+            self.write_instr(inplace);
+            self.write_arg(0);
+            self.stack_dec();
+        }
         self.emit_var_pat(&sig.pat, &body.op);
     }
 
+    /// `obj.x = v`: `v; obj; STORE_ATTR x`
+    /// `obj.x += v`: `obj; DUP_TOP; LOAD_ATTR x; v; INPLACE_ADD; ROT_TWO; STORE_ATTR x`
+    fn emit_attr_assign(&mut self, assign: AttrAssign) {
+        let class = Str::rc(assign.attr.obj.ref_t().name());
+        let uniq_obj_name = assign.attr.obj.__name__().map(Str::rc);
+        let name = assign.attr.name.content.clone();
+        let escaped = escape_attr(
+            &class,
+            uniq_obj_name.as_ref().map(|s| &s[..]),
+            name.clone(),
+            &self.str_cache,
+        );
+        // the load and the store must refer to the same entry of `names`
+        let name = match self
+            .cur_block_codeobj()
+            .names
+            .iter()
+            .position(|n| *n == escaped)
+        {
+            Some(idx) => Name::local(idx),
+            None => self.register_attr(&class, uniq_obj_name.as_ref().map(|s| &s[..]), name),
+        };
+        match inplace_op(&assign.op.kind) {
+            Some(inplace) => {
+                self.codegen_expr(*assign.attr.obj);
+                self.write_instr(DUP_TOP);
+                self.write_arg(0);
+                self.stack_inc();
+                self.write_instr_arg(LOAD_ATTR, name.idx);
+                self.codegen_expr(*assign.value);
+                self.write_instr(inplace);
+                self.write_arg(0);
+                self.stack_dec();
+                self.write_instr(ROT_TWO);
+                self.write_arg(0);
+            }
+            None => {
+                self.codegen_expr(*assign.value);
+                self.codegen_expr(*assign.attr.obj);
+            }
+        }
+        self.write_instr_arg(STORE_ATTR, name.idx);
+        self.stack_dec_n(2);
+    }
+
+    /// `"x = \{x!r:>5}"`: `LOAD_CONST "x = "; <x>; LOAD_CONST ">5"; FORMAT_VALUE 2 | 0x04; BUILD_STRING 2`
+    fn emit_str_interp(&mut self, interp: StrInterp) {
+        let len = interp.segments.len();
+        if len == 0 {
+            self.emit_load_const("");
+            return;
+        }
+        for seg in interp.segments.into_iter() {
+            match seg {
+                StrSegment::Lit(s) => self.emit_load_const(s),
+                StrSegment::Expr {
+                    expr,
+                    conversion,
+                    spec,
+                } => {
+                    self.codegen_expr(*expr);
+                    let mut flags = conversion.map(|conv| conv as u8).unwrap_or(0);
+                    if let Some(spec) = spec {
+                        self.emit_load_const(spec);
+                        flags |= 0x04;
+                    }
+                    self.write_instr(FORMAT_VALUE);
+                    self.write_arg(flags);
+                    if flags & 0x04 != 0 {
+                        self.stack_dec();
+                    }
+                }
+            }
+        }
+        // a single segment is used as it is
+        if len > 1 {
+            self.write_instr_arg(BUILD_STRING, len);
+            self.stack_dec_n(len - 1);
+        }
+    }
+
+    /// `(n := x)`: the value is left on the stack after being stored
+    fn emit_named_expr(&mut self, named: NamedExpr) {
+        self.codegen_expr(*named.value);
+        self.write_instr(DUP_TOP);
+        self.write_arg(0);
+        self.stack_inc();
+        let name = named.name.inspect().clone();
+        // the name is a local variable of the subroutine, even if an outer scope has the same name (same as Python)
+        let is_toplevel = self.cur_block() == self.toplevel_block();
+        if !is_toplevel && self.local_search(&name, Name).is_none() {
+            let name = escape_name(name.clone(), &self.str_cache);
+            self.mut_cur_block_codeobj().varnames.push(name);
+        }
+        self.emit_store_instr(name, Name);
+    }
+
+    /// `yield x`: the code object containing this is flagged as a generator
+    fn emit_yield(&mut self, yield_: Yield) {
+        self.codegen_expr(*yield_.value);
+        // YIELD_VALUE pops the value and pushes the sent value (1 -> 1)
+        self.write_instr(YIELD_VALUE);
+        self.write_arg(0);
+        let flags = &mut self.mut_cur_block_codeobj().flags;
+        *flags |= CodeObjFlags::Generator as u32;
+    }
+
+    /// `[elem | x <- iter, guard]` is compiled like CPython's `<listcomp>`:
+    /// a function that takes an iterator `.0` and appends `elem` for each `x` is called with `iter(iter)`.
+    /// If a guard does not hold, it jumps back to `FOR_ITER` without appending
+    fn emit_array_comprehension(&mut self, comp: ArrayComprehension) {
+        let firstlineno = comp.ln_begin().unwrap();
+        self.unit_size += 1;
+        self.units.push_unit(CodeGenUnit::new(
+            self.unit_size,
+            vec![Str::ever(".0")],
+            Str::rc(self.cfg.input.enclosed_name()),
+            "<listcomp>",
+            firstlineno,
+        ));
+        // the loop variable is always local to the comprehension
+        self.mut_cur_block_codeobj()
+            .varnames
+            .push(comp.var.content.clone());
+        self.write_instr(BUILD_LIST);
+        self.write_arg(0);
+        self.stack_inc();
+        self.emit_load_name_instr(Str::ever(".0")).unwrap();
+        let idx_for_iter = self.cur_block().lasti;
+        self.write_instr(FOR_ITER);
+        // cannot detect where to jump to at this moment, so put as 0
+        self.write_arg(0);
+        self.stack_inc();
+        self.emit_store_instr(comp.var.content, Name);
+        for guard in comp.guards {
+            self.codegen_expr(guard);
+            self.write_instr_arg(POP_JUMP_IF_FALSE, self.jump_arg(idx_for_iter));
+            self.stack_dec();
+        }
+        self.codegen_expr(*comp.elem);
+        // the list is at the 2nd position from TOS (under the iterator)
+        self.write_instr(LIST_APPEND);
+        self.write_arg(2);
+        self.stack_dec();
+        self.write_instr_arg(JUMP_ABSOLUTE, self.jump_arg(idx_for_iter));
+        let idx_end = self.cur_block().lasti;
+        self.edit_jump(idx_for_iter, self.jump_arg(idx_end - idx_for_iter - 2));
+        // FOR_ITER pops the exhausted iterator
+        self.stack_dec();
+        self.write_instr(RETURN_VALUE);
+        self.write_arg(0);
+        let nlocals = self.cur_block_codeobj().varnames.len() as u32;
+        self.mut_cur_block_codeobj().nlocals = nlocals;
+        self.mut_cur_block_codeobj().flags += CodeObjFlags::NewLocals as u32;
+        let unit = self.pop_unit();
+        self.sync_lineno(unit.prev_lineno);
+        let qualname = unit.codeobj.qualname.clone();
+        self.emit_load_const(unit.codeobj);
+        self.emit_load_const(qualname);
+        self.write_instr(MAKE_FUNCTION);
+        self.write_arg(0);
+        // stack_dec: <code obj> + <qualname> -> <function>
+        self.stack_dec();
+        self.codegen_expr(*comp.iter);
+        self.write_instr(GET_ITER);
+        self.write_arg(0);
+        self.write_instr(CALL_FUNCTION);
+        self.write_arg(1);
+        self.stack_dec();
+    }
+
+    /// `x as T`: a conversion between builtin numeric types calls the Python constructor (e.g. `float(x)`),
+    /// other casts (upcasts) do nothing at runtime
+    fn emit_type_cast(&mut self, cast: TypeCast) {
+        let constructor = match (cast.expr.ref_t(), cast.ref_t()) {
+            (Type::Int | Type::Nat | Type::Bool | Type::Ratio, Type::Float) => Some("float"),
+            (Type::Float | Type::Ratio, Type::Int | Type::Nat) => Some("int"),
+            _ => None,
+        };
+        if let Some(constructor) = constructor {
+            self.emit_load_name_instr(Str::ever(constructor)).unwrap();
+            self.codegen_expr(*cast.expr);
+            self.write_instr(CALL_FUNCTION);
+            self.write_arg(1);
+            self.stack_dec();
+        } else {
+            self.codegen_expr(*cast.expr);
+        }
+    }
+
+    /// The default values of the parameters (`y := 1` of `f x, y := 1 = ...`).
+    /// They are constant expressions, so the tuple of them can be loaded as a constant.
+    fn gen_param_defaults(&mut self, params: &Params) -> Vec<ValueObj> {
+        params
+            .defaults
+            .iter()
+            .map(|param| match &param.opt_default_val {
+                Some(ConstExpr::Lit(lit)) => ValueObj::from(lit),
+                Some(other) => {
+                    self.errs.push(CompileError::feature_error(
+                        self.cfg.input.clone(),
+                        other.loc(),
+                        "non-literal default values",
+                        "".into(),
+                    ));
+                    ValueObj::None
+                }
+                None => switch_unreachable!(),
+            })
+            .collect()
+    }
+
+    /// Pushes the default values of the parameters (flag `0x01` of `MAKE_FUNCTION`), if any.
+    /// Erg has no keyword-only parameters, so the keyword-only defaults (flag `0x02`) are never pushed.
+    /// returns the flag
+    fn emit_param_defaults(&mut self, params: &Params) -> u8 {
+        let defaults = self.gen_param_defaults(params);
+        if defaults.is_empty() {
+            0
+        } else {
+            self.emit_load_const(defaults);
+            0x01
+        }
+    }
+
     fn emit_subr_def(&mut self, sig: SubrSignature, body: DefBody) {
         let name = sig.name.inspect().clone();
-        let mut opcode_flag = 0u8;
         let params = self.gen_param_names(&sig.params);
         let code = self.codegen_block(body.block, Some(name.clone()), params);
-        self.emit_load_const(code);
+        // the decorators are evaluated before the function is created
+        let decorators = sig.decorators.len();
+        for deco in sig.decorators {
+            self.codegen_expr(deco);
+        }
+        // the optional items come first, in the order of the flag bits:
+        // defaults (0x01), kwdefaults (0x02), annotations (0x04), closure (0x08)
+        let mut opcode_flag = self.emit_param_defaults(&sig.params);
         if !self.cur_block_codeobj().cellvars.is_empty() {
             let cellvars_len = self.cur_block_codeobj().cellvars.len() as u8;
             for i in 0..cellvars_len {
                 self.write_instr(LOAD_CLOSURE);
                 self.write_arg(i);
+                self.stack_inc();
             }
             self.write_instr(BUILD_TUPLE);
             self.write_arg(cellvars_len);
+            self.stack_dec_n(cellvars_len as usize - 1);
             opcode_flag += 8;
         }
-        self.emit_load_const(name.clone());
+        let qualname = code.qualname.clone();
+        self.emit_load_const(code);
+        self.emit_load_const(qualname);
         self.write_instr(MAKE_FUNCTION);
         self.write_arg(opcode_flag);
-        // stack_dec: <code obj> + <name> -> <function>
-        self.stack_dec();
+        // stack_dec: (<optional items>) + <code obj> + <qualname> -> <function>
+        self.stack_dec_n(1 + opcode_flag.count_ones() as usize);
+        // the decorators are applied from the nearest one to the definition (on the top of the stack)
+        for _ in 0..decorators {
+            self.write_instr(CALL_FUNCTION);
+            self.write_arg(1);
+            self.stack_dec();
+        }
         self.emit_store_instr(name, Name);
     }
 
     fn emit_discard_instr(&mut self, mut args: Args) -> CompileResult<()> {
         while let Some(arg) = args.try_remove(0) {
+            // 副作用のない式は評価する必要がない
+            if self.cfg.opt_level() > 0 && arg.is_pure() {
+                continue;
+            }
             self.codegen_expr(arg);
             self.emit_pop_top();
         }
         Ok(())
     }
 
-    fn emit_if_instr(&mut self, mut args: Args) -> CompileResult<()> {
+    /// `if cond, () -> then, () -> else`
+    ///
+    /// If `value` is true, each branch leaves exactly one object
+    /// (`None` if the branch leaves nothing or the else block is missing), so the result is one object.
+    /// Otherwise (the result is not used), the branches leave nothing.
+    fn emit_if_instr(&mut self, mut args: Args, value: bool) -> CompileResult<()> {
         let cond = args.remove(0);
         self.codegen_expr(cond);
         let idx_pop_jump_if_false = self.cur_block().lasti;
         self.write_instr(POP_JUMP_IF_FALSE);
         // cannot detect where to jump to at this moment, so put as 0
         self.write_arg(0 as u8);
-        match args.remove(0) {
-            // then block
-            Expr::Lambda(lambda) => {
-                let params = self.gen_param_names(&lambda.params);
-                self.codegen_frameless_block(lambda.body, params);
-            }
-            other => {
-                self.codegen_expr(other);
-            }
-        }
-        if args.get(0).is_some() {
+        self.stack_dec();
+        let stack_len = self.cur_block().stack_len;
+        // then block
+        self.emit_if_branch(args.remove(0), stack_len, value);
+        let has_else = args.get(0).is_some();
+        if has_else || value {
+            let idx_jump_forward = self.cur_block().lasti;
             self.write_instr(JUMP_FORWARD); // jump to end
             self.write_arg(0 as u8);
             // else block
             let idx_else_begin = self.cur_block().lasti;
-            self.edit_code(idx_pop_jump_if_false + 1, idx_else_begin / 2);
-            match args.remove(0) {
-                Expr::Lambda(lambda) => {
-                    let params = self.gen_param_names(&lambda.params);
-                    self.codegen_frameless_block(lambda.body, params);
-                }
-                other => {
-                    self.codegen_expr(other);
-                }
+            self.edit_jump(idx_pop_jump_if_false, self.jump_arg(idx_else_begin));
+            // the value of the then block is not on the stack here
+            self.mut_cur_block().stack_len = stack_len;
+            if has_else {
+                self.emit_if_branch(args.remove(0), stack_len, value);
+            } else {
+                self.emit_load_const(ValueObj::None);
             }
-            let idx_jump_forward = idx_else_begin - 2;
             let idx_end = self.cur_block().lasti;
-            self.edit_code(idx_jump_forward + 1, (idx_end - idx_jump_forward - 2) / 2);
-            self.stack_dec();
-            self.stack_dec();
+            self.edit_jump(
+                idx_jump_forward,
+                self.jump_arg(idx_end - idx_jump_forward - 2),
+            );
         } else {
             // no else block
             let idx_end = self.cur_block().lasti;
-            self.edit_code(idx_pop_jump_if_false + 1, idx_end / 2);
-            self.stack_dec();
+            self.edit_jump(idx_pop_jump_if_false, self.jump_arg(idx_end));
         }
         Ok(())
     }
 
-    fn emit_for_instr(&mut self, mut args: Args) -> CompileResult<()> {
-        let iterable = args.remove(0);
-        self.codegen_expr(iterable);
-        self.write_instr(GET_ITER);
-        self.write_arg(0);
-        let idx_for_iter = self.cur_block().lasti;
-        self.write_instr(FOR_ITER);
-        // FOR_ITER pushes a value onto the stack, but we can't know how many
-        // but after executing this instruction, stack_len should be 1
-        // cannot detect where to jump to at this moment, so put as 0
-        self.write_arg(0);
-        let lambda = enum_unwrap!(args.remove(0), Expr::Lambda);
-        let params = self.gen_param_names(&lambda.params);
-        self.codegen_frameless_block(lambda.body, params); // ここでPOPされる
-        self.write_instr(JUMP_ABSOLUTE);
-        self.write_arg((idx_for_iter / 2) as u8);
-        let idx_end = self.cur_block().lasti;
-        self.edit_code(idx_for_iter + 1, (idx_end - idx_for_iter - 2) / 2);
-        self.emit_load_const(ValueObj::None);
-        Ok(())
-    }
+    /// Compiles a branch of `if` so that it leaves exactly one object on `stack_len` if `value` is true,
+    /// and nothing otherwise.
+    fn emit_if_branch(&mut self, branch: Expr, stack_len: u32, value: bool) {
+        match branch {
+            Expr::Lambda(lambda) => {
+                let params = self.gen_param_names(&lambda.params);
+                self.codegen_frameless_block(lambda.body, params);
+            }
+            other => {
+                self.codegen_expr(other);
+            }
+        }
+        let has_value = self.cur_block().stack_len > stack_len;
+        match (value, has_value) {
+            (true, false) => self.emit_load_const(ValueObj::None),
+            (false, true) => self.emit_pop_top(),
+            _ => {}
+        }
+    }
+
+    /// `import "a.b"` and `pyimport "a.b"` are compiled like `import a.b as ...` (not as `__import__("a.b")`),
+    /// so that the result is the submodule `a.b` rather than the top-level package `a`.
+    /// The instructions are emitted into the current code object,
+    /// so an import in a subroutine body is executed when the subroutine is called
+    // a non-literal module name is reported to `emit_call_name` as a `CompileError`
+    #[allow(clippy::result_large_err)]
+    fn emit_import_instr(&mut self, mut args: Args) -> CompileResult<()> {
+        let path = args.remove(0);
+        let path = match &path {
+            Expr::Lit(Literal {
+                data: ValueObj::Str(path),
+                ..
+            }) => path.clone(),
+            other => {
+                return Err(CompileError::feature_error(
+                    self.cfg.input.clone(),
+                    other.loc(),
+                    "importing a module by a non-literal path",
+                    "".into(),
+                ));
+            }
+        };
+        // level (absolute import), fromlist
+        self.emit_load_const(0);
+        self.emit_load_const(ValueObj::None);
+        self.mut_cur_block_codeobj().names.push(path.clone());
+        self.write_instr_arg(IMPORT_NAME, self.cur_block_codeobj().names.len() - 1);
+        self.stack_dec();
+        // `IMPORT_NAME` returns the top-level package, so follow the rest of the path
+        let parts = path
+            .split_str(".", &self.str_cache)
+            .skip(1)
+            .collect::<Vec<_>>();
+        for part in parts {
+            self.mut_cur_block_codeobj().names.push(part);
+            self.write_instr_arg(IMPORT_FROM, self.cur_block_codeobj().names.len() - 1);
+            self.stack_inc();
+            self.write_instr(ROT_TWO);
+            self.write_arg(0);
+            self.emit_pop_top();
+        }
+        Ok(())
+    }
+
+    fn emit_set(&mut self, mut set: Set) {
+        let len = set.elems.len();
+        let consts = set
+            .elems
+            .pos_args()
+            .iter()
+            .map(|arg| match &arg.expr {
+                Expr::Lit(lit) => Some(lit.data.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        // a set of constants is copied from a frozenset constant (same as CPython)
+        if let Some(elems) = consts.filter(|_| len > 0 && self.python_ver() >= 3425) {
+            self.write_instr_arg(BUILD_SET, 0);
+            self.stack_inc();
+            self.emit_load_const(ValueObj::FrozenSet(elems.into()));
+            self.write_instr_arg(SET_UPDATE, 1);
+            self.stack_dec();
+            return;
+        }
+        while let Some(arg) = set.elems.try_remove_pos(0) {
+            self.codegen_expr(arg.expr);
+        }
+        self.write_instr_arg(BUILD_SET, len);
+        if len == 0 {
+            self.stack_inc();
+        } else {
+            self.stack_dec_n(len - 1);
+        }
+    }
+
+    fn emit_dict(&mut self, dict: Dict) {
+        let len = dict.kvs.len();
+        let consts = dict
+            .kvs
+            .iter()
+            .map(|kv| match (&kv.key, &kv.value) {
+                (Expr::Lit(key), Expr::Lit(value)) => Some((key.data.clone(), value.data.clone())),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        // a dict of constants is copied from a constant (dicts are mutable, so it cannot be loaded as is)
+        // DICT_MERGE does not raise here since the merged dict is empty
+        if let Some(entries) = consts.filter(|_| len > 0 && self.python_ver() >= 3425) {
+            self.write_instr_arg(BUILD_MAP, 0);
+            self.stack_inc();
+            self.emit_load_const(ValueObj::dict(entries));
+            self.write_instr_arg(DICT_MERGE, 1);
+            self.stack_dec();
+            return;
+        }
+        let const_keys = dict
+            .kvs
+            .iter()
+            .map(|kv| match &kv.key {
+                Expr::Lit(lit) => Some(lit.data.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        match const_keys {
+            Some(keys) if len > 0 => {
+                for kv in dict.kvs.into_iter() {
+                    self.codegen_expr(kv.value);
+                }
+                self.emit_load_const(keys);
+                self.write_instr_arg(BUILD_CONST_KEY_MAP, len);
+                // values + key tuple -> dict
+                self.stack_dec_n(len);
+            }
+            _ => {
+                for kv in dict.kvs.into_iter() {
+                    self.codegen_expr(kv.key);
+                    self.codegen_expr(kv.value);
+                }
+                self.write_instr_arg(BUILD_MAP, len);
+                if len == 0 {
+                    self.stack_inc();
+                } else {
+                    self.stack_dec_n(2 * len - 1);
+                }
+            }
+        }
+    }
+
+    /// A record is compiled as an instance of a namedtuple,
+    /// so that the attributes can be accessed (and matched with `MATCH_CLASS`) by name:
+    /// `{x = 1; y = 2}` -> `__import__("collections").namedtuple("Record", ("x", "y"))(1, 2)`
+    fn emit_record(&mut self, record: Record) {
+        let attrs_len = record.attrs.len();
+        let fields = record_fields(&record, &self.str_cache);
+        if self.record_types.contains(&fields) {
+            self.emit_load_name_instr(record_type_name(&fields))
+                .unwrap();
+        } else {
+            self.emit_load_namedtuple();
+            self.emit_record_type(fields);
+        }
+        for attr in record.attrs.into_iter() {
+            self.codegen_expr(attr.value);
+        }
+        self.write_instr_arg(CALL_FUNCTION, attrs_len);
+        // (1 (type) + attrs_len) input objects -> 1 return object
+        self.stack_dec_n(attrs_len);
+    }
+
+    /// `__import__("collections").namedtuple`
+    fn emit_load_namedtuple(&mut self) {
+        // level (absolute import), fromlist
+        self.emit_load_const(0);
+        self.emit_load_const(ValueObj::None);
+        let idx = self.name_idx(Str::ever("collections"));
+        self.write_instr_arg(IMPORT_NAME, idx);
+        self.stack_dec();
+        let idx = self.name_idx(Str::ever("namedtuple"));
+        self.write_instr_arg(LOAD_ATTR, idx);
+    }
+
+    /// `namedtuple` -> `namedtuple("Record", fields)`
+    fn emit_record_type(&mut self, fields: Vec<Str>) {
+        self.emit_load_const("Record");
+        self.emit_load_const(fields.into_iter().map(ValueObj::Str).collect::<Vec<_>>());
+        self.write_instr_arg(CALL_FUNCTION, 2);
+        // (1 (namedtuple) + 2 (typename, field_names)) input objects -> 1 return object (the type)
+        self.stack_dec_n((1 + 2) - 1);
+    }
+
+    /// Defines the types of the records in `module` at the head of it,
+    /// so that a new type is not created each time a record literal is evaluated.
+    fn emit_record_types(&mut self, module: &Module) {
+        let mut types = vec![];
+        for expr in module.iter() {
+            collect_record_types(expr, &mut types, &self.str_cache);
+        }
+        self.record_types.clear();
+        if types.is_empty() {
+            return;
+        }
+        self.emit_load_namedtuple();
+        for fields in types.iter() {
+            self.write_instr(DUP_TOP);
+            self.write_arg(0);
+            self.stack_inc();
+            self.emit_record_type(fields.clone());
+            self.emit_store_instr(record_type_name(fields), Name);
+        }
+        self.emit_pop_top();
+        self.record_types = types;
+    }
+
+    /// `del! x`, `del! arr[i]`, `del! arr[1..3]`
+    // unsupported targets are reported to `emit_call_name` as a `CompileError`
+    #[allow(clippy::result_large_err)]
+    fn emit_del_instr(&mut self, mut args: Args) -> CompileResult<()> {
+        match args.remove(0) {
+            Expr::Accessor(Accessor::Subscr(subscr)) => {
+                self.codegen_expr(*subscr.obj);
+                self.emit_subscr_index(*subscr.index);
+                self.write_instr(DELETE_SUBSCR);
+                self.write_arg(0);
+                self.stack_dec_n(2);
+            }
+            Expr::Accessor(Accessor::Local(local)) => {
+                let instr = match self
+                    .local_search(local.inspect(), Name)
+                    .map(|n| (n.kind, n.idx))
+                {
+                    Some((StoreLoadKind::Local | StoreLoadKind::LocalConst, idx)) => {
+                        (DELETE_NAME, idx)
+                    }
+                    Some((StoreLoadKind::Fast | StoreLoadKind::FastConst, idx)) => {
+                        (DELETE_FAST, idx)
+                    }
+                    _ => {
+                        return Err(CompileError::feature_error(
+                            self.cfg.input.clone(),
+                            local.loc(),
+                            "deleting a non-local variable",
+                            "".into(),
+                        ));
+                    }
+                };
+                self.write_instr_arg(instr.0, instr.1);
+            }
+            other => {
+                return Err(CompileError::feature_error(
+                    self.cfg.input.clone(),
+                    other.loc(),
+                    "deleting this expression",
+                    "".into(),
+                ));
+            }
+        }
+        // the result of `del!` (`NoneType`)
+        self.emit_load_const(ValueObj::None);
+        Ok(())
+    }
+
+    /// A range index (e.g. `arr[1..3]`) is compiled as a slice (`arr[slice(1, 4)]`)
+    fn emit_subscr_index(&mut self, index: Expr) {
+        match index {
+            Expr::BinOp(bin) if range_bounds(&bin.op.kind).is_some() => {
+                let (start_excluded, stop_included) = range_bounds(&bin.op.kind).unwrap();
+                self.emit_range_bound(*bin.lhs, start_excluded);
+                self.emit_range_bound(*bin.rhs, stop_included);
+                self.write_instr(BUILD_SLICE);
+                self.write_arg(2);
+                self.stack_dec();
+            }
+            // `arr[a:b]` -> `BUILD_SLICE 2`, `arr[a:b:c]` -> `BUILD_SLICE 3`
+            Expr::Slice(slice) => {
+                let argc = if slice.step.is_some() { 3 } else { 2 };
+                for bound in [slice.start, slice.stop] {
+                    match bound {
+                        Some(bound) => self.codegen_expr(*bound),
+                        None => self.emit_load_const(ValueObj::None),
+                    }
+                }
+                if let Some(step) = slice.step {
+                    self.codegen_expr(*step);
+                }
+                self.write_instr(BUILD_SLICE);
+                self.write_arg(argc);
+                self.stack_dec_n(argc as usize - 1);
+            }
+            other => self.codegen_expr(other),
+        }
+    }
+
+    /// Python slices and `range`s are half-open, so some bounds of Erg ranges are shifted by 1
+    fn emit_range_bound(&mut self, bound: Expr, shift: bool) {
+        if !shift {
+            self.codegen_expr(bound);
+            return;
+        }
+        let shifted = match &bound {
+            Expr::Lit(Literal {
+                data: ValueObj::Nat(n),
+                ..
+            }) => Some(n.checked_add(1).map(ValueObj::Nat)),
+            Expr::Lit(Literal {
+                data: ValueObj::Int(i),
+                ..
+            }) => Some(i.checked_add(1).map(ValueObj::Int)),
+            _ => None,
+        };
+        match shifted {
+            Some(Some(value)) => self.emit_load_const(value),
+            Some(None) => {
+                self.errs.push(CompileError::feature_error(
+                    self.cfg.input.clone(),
+                    bound.loc(),
+                    "a range bound at the maximum integer",
+                    self.cur_block_codeobj().name.clone(),
+                ));
+                self.codegen_expr(bound);
+            }
+            None => {
+                let other = bound;
+                self.codegen_expr(other);
+                self.emit_load_const(1);
+                self.write_instr(BINARY_ADD);
+                self.write_arg(0);
+                self.stack_dec();
+            }
+        }
+    }
+
+    /// `log x`: evaluates and returns `x`, but prints it after the current statement is executed
+    // `emit_load_name_instr` reports an undefined name through the result
+    #[allow(clippy::result_large_err)]
+    fn emit_log_instr(&mut self, mut args: Args) -> CompileResult<()> {
+        let mut tmps = Vec::with_capacity(args.len());
+        while let Some(arg) = args.try_remove_pos(0) {
+            let tmp = Str::from(format!("__erg_log_{}__", self.log_count));
+            self.log_count += 1;
+            self.codegen_expr(arg.expr);
+            self.emit_store_instr(tmp.clone(), Name);
+            tmps.push(tmp);
+        }
+        if tmps.len() == 1 {
+            self.emit_load_name_instr(tmps[0].clone())?;
+        } else {
+            self.emit_load_const(ValueObj::None);
+        }
+        self.pending_logs.push(tmps);
+        Ok(())
+    }
+
+    /// prints the values of the `log` calls in the statement just compiled
+    /// (the temporaries are deleted once loaded, so they do not outlive the statement)
+    fn emit_pending_logs(&mut self) {
+        for tmps in std::mem::take(&mut self.pending_logs) {
+            let argc = tmps.len();
+            self.emit_load_name_instr(Str::ever("print")).unwrap();
+            for tmp in tmps {
+                self.emit_load_name_instr(tmp.clone()).unwrap();
+                let instr = match self.local_search(&tmp, Name) {
+                    Some(Name {
+                        kind: StoreLoadKind::Fast | StoreLoadKind::FastConst,
+                        idx,
+                    }) => (DELETE_FAST, idx),
+                    Some(name) => (DELETE_NAME, name.idx),
+                    None => switch_unreachable!(),
+                };
+                self.write_instr_arg(instr.0, instr.1);
+            }
+            self.write_instr_arg(CALL_FUNCTION, argc);
+            self.stack_dec_n(argc);
+            self.emit_pop_top();
+        }
+    }
+
+    fn emit_for_instr(&mut self, mut args: Args) -> CompileResult<()> {
+        let iterable = args.remove(0);
+        self.codegen_expr(iterable);
+        self.write_instr(GET_ITER);
+        self.write_arg(0);
+        let idx_for_iter = self.cur_block().lasti;
+        self.write_instr(FOR_ITER);
+        // FOR_ITER pushes a value onto the stack, but we can't know how many
+        // but after executing this instruction, stack_len should be 1
+        // cannot detect where to jump to at this moment, so put as 0
+        self.write_arg(0);
+        let lambda = enum_unwrap!(args.remove(0), Expr::Lambda);
+        let params = self.gen_param_names(&lambda.params);
+        self.codegen_frameless_block(lambda.body, params); // ここでPOPされる
+        self.write_instr_arg(JUMP_ABSOLUTE, self.jump_arg(idx_for_iter));
+        let idx_end = self.cur_block().lasti;
+        self.edit_jump(idx_for_iter, self.jump_arg(idx_end - idx_for_iter - 2));
+        self.emit_load_const(ValueObj::None);
+        Ok(())
+    }
+
+    /// ```erg
+    /// with! open!("f"), f => ...
+    /// ```
+    /// is compiled like `with open("f") as f: ...` of CPython 3.9/3.10.
+    /// Python 3.11+ is not supported, because it requires an exception table instead of `SETUP_WITH`.
+    // `with!` for Python 3.11+ is reported to `emit_call_name` as a `CompileError`
+    #[allow(clippy::result_large_err)]
+    fn emit_with_instr(&mut self, mut args: Args) -> CompileResult<()> {
+        let ctx = args.remove(0);
+        if self.python_ver() >= 3495 {
+            return Err(CompileError::feature_error(
+                self.cfg.input.clone(),
+                ctx.loc(),
+                "`with!` for Python 3.11+",
+                "".into(),
+            ));
+        }
+        let lambda = enum_unwrap!(args.remove(0), Expr::Lambda);
+        self.codegen_expr(ctx);
+        let idx_setup_with = self.cur_block().lasti;
+        self.write_instr(SETUP_WITH);
+        // cannot detect where to jump to at this moment, so put as 0
+        self.write_arg(0);
+        // `__exit__` (replaces the context manager) and the result of `__enter__`
+        self.stack_inc();
+        let params = self.gen_param_names(&lambda.params);
+        if params.is_empty() {
+            self.emit_pop_top();
+        }
+        // `__exit__` is not counted while the body is executed (like the iterator of `for!`),
+        // so the depth of the body is measured separately and adjusted
+        self.stack_dec();
+        let stacksize = std::mem::take(&mut self.mut_cur_block_codeobj().stacksize);
+        self.codegen_frameless_block(lambda.body, params);
+        if self.cur_block().stack_len > 0 {
+            self.emit_pop_top();
+        }
+        let body_stacksize = self.cur_block_codeobj().stacksize + 1;
+        self.mut_cur_block_codeobj().stacksize = stacksize.max(body_stacksize);
+        self.stack_inc();
+        self.write_instr(POP_BLOCK);
+        self.write_arg(0);
+        // __exit__(None, None, None)
+        self.emit_load_const(ValueObj::None);
+        self.write_instr(DUP_TOP);
+        self.write_arg(0);
+        self.write_instr(DUP_TOP);
+        self.write_arg(0);
+        self.stack_inc_n(2);
+        self.write_instr(CALL_FUNCTION);
+        self.write_arg(3);
+        self.stack_dec_n(3);
+        self.emit_pop_top();
+        let idx_jump_forward = self.cur_block().lasti;
+        self.write_instr(JUMP_FORWARD);
+        self.write_arg(0);
+        // the handler: `__exit__` + the previous and the raised exception (3 + 3 values)
+        let idx_handler = self.cur_block().lasti;
+        self.edit_jump(
+            idx_setup_with,
+            self.jump_arg(idx_handler - idx_setup_with - 2),
+        );
+        self.stack_inc_n(7);
+        self.write_instr(WITH_EXCEPT_START);
+        self.write_arg(0);
+        self.stack_inc();
+        let idx_pop_jump_if_true = self.cur_block().lasti;
+        self.write_instr(POP_JUMP_IF_TRUE);
+        self.write_arg(0);
+        self.stack_dec();
+        // `__exit__` returned false
+        self.emit_reraise();
+        // `__exit__` returned true: the exception is suppressed
+        let idx_suppress = self.cur_block().lasti;
+        self.edit_jump(idx_pop_jump_if_true, self.jump_arg(idx_suppress));
+        for _ in 0..3 {
+            self.emit_pop_top();
+        }
+        self.write_instr(POP_EXCEPT);
+        self.write_arg(0);
+        self.stack_dec_n(3);
+        self.emit_pop_top();
+        let idx_end = self.cur_block().lasti;
+        self.edit_jump(
+            idx_jump_forward,
+            self.jump_arg(idx_end - idx_jump_forward - 2),
+        );
+        self.emit_load_const(ValueObj::None);
+        Ok(())
+    }
+
+    /// `RERAISE` is 48 (no arg) until 3.9, and 119 (arg: whether to restore `f_lasti`) since 3.10
+    fn emit_reraise(&mut self) {
+        self.write_instr(RERAISE);
+        self.write_arg((self.python_ver() >= 3439) as u8);
+    }
+
+    /// ```erg
+    /// while! cond, () => ...
+    /// ```
+    /// The condition is evaluated before each iteration.
+    // never fails, but returns `CompileResult` like `emit_for_instr` (see `emit_call_name`)
+    #[allow(clippy::result_large_err)]
+    fn emit_while_instr(&mut self, mut args: Args) -> CompileResult<()> {
+        let stack_len = self.cur_block().stack_len;
+        let idx_while = self.cur_block().lasti;
+        match args.remove(0) {
+            Expr::Lambda(lambda) => {
+                let params = self.gen_param_names(&lambda.params);
+                self.codegen_frameless_block(lambda.body, params);
+            }
+            other => {
+                self.codegen_expr(other);
+            }
+        }
+        let idx_pop_jump_if_false = self.cur_block().lasti;
+        self.write_instr(POP_JUMP_IF_FALSE);
+        // cannot detect where to jump to at this moment, so put as 0
+        self.write_arg(0);
+        self.stack_dec();
+        match args.remove(0) {
+            Expr::Lambda(lambda) => {
+                let params = self.gen_param_names(&lambda.params);
+                self.codegen_frameless_block(lambda.body, params);
+            }
+            other => {
+                self.codegen_expr(other);
+                self.write_instr(CALL_FUNCTION);
+                self.write_arg(0);
+            }
+        }
+        // the result of the body is not used
+        if self.cur_block().stack_len > stack_len {
+            self.emit_pop_top();
+        }
+        self.write_instr_arg(JUMP_ABSOLUTE, self.jump_arg(idx_while));
+        let idx_end = self.cur_block().lasti;
+        self.edit_jump(idx_pop_jump_if_false, self.jump_arg(idx_end));
+        self.emit_load_const(ValueObj::None);
+        Ok(())
+    }
+
+    /// ```erg
+    /// repeat_until! p!, () -> cond
+    /// ```
+    /// The body is executed at least once, and the condition is checked after each iteration.
+    // never fails, but returns `CompileResult` to be dispatched by `emit_call_name` like `emit_for_instr`
+    #[allow(clippy::result_large_err)]
+    fn emit_repeat_until_instr(&mut self, mut args: Args) -> CompileResult<()> {
+        let stack_len = self.cur_block().stack_len;
+        let idx_body_begin = self.cur_block().lasti;
+        match args.remove(0) {
+            Expr::Lambda(lambda) => {
+                let params = self.gen_param_names(&lambda.params);
+                self.codegen_frameless_block(lambda.body, params);
+            }
+            other => {
+                self.codegen_expr(other);
+                self.write_instr(CALL_FUNCTION);
+                self.write_arg(0);
+            }
+        }
+        // the result of the body is not used
+        if self.cur_block().stack_len > stack_len {
+            self.emit_pop_top();
+        }
+        match args.remove(0) {
+            Expr::Lambda(lambda) => {
+                let params = self.gen_param_names(&lambda.params);
+                self.codegen_frameless_block(lambda.body, params);
+            }
+            other => {
+                self.codegen_expr(other);
+                self.write_instr(CALL_FUNCTION);
+                self.write_arg(0);
+            }
+        }
+        // jump back to the top of the body while the condition is false
+        self.write_instr_arg(POP_JUMP_IF_FALSE, self.jump_arg(idx_body_begin));
+        self.stack_dec();
+        self.emit_load_const(ValueObj::None);
+        Ok(())
+    }
 
     fn emit_match_instr(&mut self, mut args: Args, _use_erg_specific: bool) -> CompileResult<()> {
         let expr = args.remove(0);
@@ -658,8 +1907,9 @@ impl CodeGenerator {
         let len = args.len();
         let mut absolute_jump_points = vec![];
         while let Some(expr) = args.try_remove(0) {
+            let is_last = args.len() == 0;
             // パターンが複数ある場合引数を複製する、ただし最後はしない
-            if len > 1 && args.len() > 0 {
+            if len > 1 && !is_last {
                 self.write_instr(Opcode::DUP_TOP);
                 self.write_arg(0);
                 self.stack_inc();
@@ -667,28 +1917,174 @@ impl CodeGenerator {
             // compilerで型チェック済み(可読性が下がるため、matchでNamedは使えない)
             let mut lambda = enum_unwrap!(expr, Expr::Lambda);
             debug_power_assert!(lambda.params.len(), ==, 1);
-            if !lambda.params.defaults.is_empty() {
-                todo!("default values in match expression are not supported yet")
-            }
-            let pat = lambda.params.non_defaults.remove(0).pat;
+            // the subject is always given, so the default value of the case (`(x |= 0) -> ...`) is never used
+            let pat = if lambda.params.non_defaults.is_empty() {
+                lambda.params.defaults.remove(0).pat
+            } else {
+                lambda.params.non_defaults.remove(0).pat
+            };
+            // the pattern consumes the copy of the subject whether it matches or not
             let pop_jump_points = self.emit_match_pattern(pat)?;
+            if len > 1 && !is_last {
+                self.emit_pop_top();
+            }
             self.codegen_frameless_block(lambda.body, Vec::new());
-            for pop_jump_point in pop_jump_points.into_iter() {
-                let idx = self.cur_block().lasti + 2;
-                self.edit_code(pop_jump_point + 1, idx / 2); // jump to POP_TOP
+            if !pop_jump_points.is_empty() {
                 absolute_jump_points.push(self.cur_block().lasti);
                 self.write_instr(Opcode::JUMP_ABSOLUTE); // jump to the end
                 self.write_arg(0);
             }
+            // `fail_pops[n]`: pops the `n` objects left by a failed pattern, then falls through to the next case
+            // (these are reachable only by jumps, so the stack size is not changed)
+            let max_leftovers = pop_jump_points.iter().map(|(_, n)| *n).max().unwrap_or(0);
+            let mut fail_pops = vec![0; max_leftovers + 1];
+            for n in (1..=max_leftovers).rev() {
+                fail_pops[n] = self.cur_block().lasti;
+                self.write_instr(Opcode::POP_TOP);
+                self.write_arg(0);
+            }
+            fail_pops[0] = self.cur_block().lasti;
+            for (pop_jump_point, n_leftovers) in pop_jump_points.into_iter() {
+                self.edit_jump(pop_jump_point, self.jump_arg(fail_pops[n_leftovers]));
+            }
+        }
+        let lasti = self.cur_block().lasti;
+        for absolute_jump_point in absolute_jump_points.into_iter() {
+            self.edit_jump(absolute_jump_point, self.jump_arg(lasti));
+        }
+        Ok(())
+    }
+
+    /// `[a, b |= 0, c]`: a default value precedes a required element
+    fn has_middle_default(elems: &Params) -> bool {
+        let pos = |sig: &ast::ParamSignature| (sig.ln_begin(), sig.col_begin());
+        match (elems.defaults.first(), elems.non_defaults.last()) {
+            (Some(default), Some(last)) => pos(default) < pos(last),
+            _ => false,
+        }
+    }
+
+    /// `Params` keeps the defaults apart from the required elements, so the elements are sorted back into the source order
+    fn elems_in_order(elems: Params) -> Vec<ast::ParamSignature> {
+        let mut elems = elems
+            .non_defaults
+            .into_iter()
+            .chain(elems.defaults)
+            .collect::<Vec<_>>();
+        elems.sort_by_key(|sig| (sig.ln_begin(), sig.col_begin()));
+        elems
+    }
+
+    /// Jumps to the next case if TOS is falsy.
+    /// Returns the index of the jump and the number of objects left on the stack when jumping
+    fn emit_match_fail_jump(&mut self, n_leftovers: usize) -> (usize, usize) {
+        let idx = self.cur_block().lasti;
+        self.write_instr(Opcode::POP_JUMP_IF_FALSE);
+        self.write_arg(0);
+        self.stack_dec();
+        (idx, n_leftovers)
+    }
+
+    /// Jumps to the next case unless `len(subject) <op> len`
+    fn emit_match_len_check(&mut self, op: CompareOp, len: usize) -> (usize, usize) {
+        self.write_instr(Opcode::GET_LEN);
+        self.write_arg(0);
+        self.stack_inc();
+        self.emit_load_const(len);
+        self.write_instr(Opcode::COMPARE_OP);
+        self.write_arg(op.arg(self.python_ver()));
+        self.stack_dec();
+        self.emit_match_fail_jump(1)
+    }
+
+    /// Replaces the subject sequence with a list of all the elements in the source order,
+    /// for the patterns with a default value in the middle (see `has_middle_default`).
+    /// The given elements fill the required ones and then the defaults from the left:
+    /// `[a, b |= 10, c |= 20, d]` matches `[1, 2, 3]` as `[1, 2, 20, 3]`.
+    /// The subject of the full length is left as is.
+    fn emit_select_elems(&mut self, elems: &Params) {
+        let defaults = self.gen_param_defaults(elems);
+        let n_required = elems.non_defaults.len();
+        let pos = |sig: &ast::ParamSignature| (sig.ln_begin(), sig.col_begin());
+        // `None`: a required element, `Some(i)`: the `i`th default
+        let mut order = elems
+            .non_defaults
+            .iter()
+            .map(|sig| (pos(sig), None))
+            .chain(
+                elems
+                    .defaults
+                    .iter()
+                    .enumerate()
+                    .map(|(i, sig)| (pos(sig), Some(i))),
+            )
+            .collect::<Vec<_>>();
+        order.sort_by_key(|(pos, _)| *pos);
+        let mut jump_forwards = vec![];
+        for n_given in n_required..elems.len() {
+            // jumps to the check of the next length (not to the next case)
+            let (idx_next_len, _) = self.emit_match_len_check(CompareOp::EQ, n_given);
+            // [subject] -> [list, subject]
+            self.write_instr_arg(Opcode::BUILD_LIST, 0);
+            self.stack_inc();
+            self.write_instr_arg(Opcode::ROT_TWO, 0);
+            let mut subject_idx = 0;
+            for (_, default_idx) in order.iter() {
+                match default_idx {
+                    Some(i) if *i >= n_given - n_required => {
+                        self.emit_load_const(defaults[*i].clone());
+                    }
+                    _ => {
+                        self.write_instr_arg(Opcode::DUP_TOP, 0);
+                        self.stack_inc();
+                        self.emit_load_const(subject_idx);
+                        self.write_instr_arg(Opcode::BINARY_SUBSCR, 0);
+                        self.stack_dec();
+                        subject_idx += 1;
+                    }
+                }
+                self.write_instr_arg(Opcode::LIST_APPEND, 2);
+                self.stack_dec();
+            }
+            self.emit_pop_top();
+            jump_forwards.push(self.cur_block().lasti);
+            self.write_instr_arg(Opcode::JUMP_FORWARD, 0);
+            self.edit_jump(idx_next_len, self.jump_arg(self.cur_block().lasti));
         }
-        let lasti = self.cur_block().lasti;
-        for absolute_jump_point in absolute_jump_points.into_iter() {
-            self.edit_code(absolute_jump_point + 1, lasti / 2);
+        let idx_end = self.cur_block().lasti;
+        for idx in jump_forwards {
+            self.edit_jump(idx, self.jump_arg(idx_end - idx - 2));
         }
-        Ok(())
     }
 
-    fn emit_match_pattern(&mut self, pat: ParamPattern) -> CompileResult<Vec<usize>> {
+    /// Replaces the subject sequence with a list padded by the missing default values:
+    /// `[*subject, *defaults[len(subject) - n_required:]]`
+    fn emit_fill_defaults(&mut self, elems: &Params, n_required: usize) {
+        let defaults = self.gen_param_defaults(elems);
+        self.write_instr_arg(Opcode::BUILD_LIST, 0);
+        self.stack_inc();
+        self.write_instr_arg(Opcode::ROT_TWO, 0);
+        self.write_instr_arg(Opcode::LIST_EXTEND, 1);
+        self.stack_dec();
+        self.write_instr_arg(Opcode::GET_LEN, 0);
+        self.stack_inc();
+        self.emit_load_const(n_required);
+        self.write_instr_arg(Opcode::BINARY_SUBTRACT, 0);
+        self.stack_dec();
+        self.emit_load_const(defaults);
+        self.write_instr_arg(Opcode::ROT_TWO, 0);
+        self.emit_load_const(ValueObj::None);
+        self.write_instr_arg(Opcode::BUILD_SLICE, 2);
+        self.stack_dec();
+        self.write_instr_arg(Opcode::BINARY_SUBSCR, 0);
+        self.stack_dec();
+        self.write_instr_arg(Opcode::LIST_EXTEND, 1);
+        self.stack_dec();
+    }
+
+    /// Matches TOS (the subject) with `pat`. The subject is consumed whether it matches or not.
+    /// Returns the jumps to the next case (see `emit_match_fail_jump`)
+    fn emit_match_pattern(&mut self, pat: ParamPattern) -> CompileResult<Vec<(usize, usize)>> {
         let mut pop_jump_points = vec![];
         match pat {
             ParamPattern::VarName(name) => {
@@ -697,40 +2093,112 @@ impl CodeGenerator {
             ParamPattern::Lit(lit) => {
                 self.emit_load_const(ValueObj::from(&lit));
                 self.write_instr(Opcode::COMPARE_OP);
-                self.write_arg(2); // ==
-                self.stack_dec();
-                pop_jump_points.push(self.cur_block().lasti);
-                self.write_instr(Opcode::POP_JUMP_IF_FALSE); // jump to the next case
-                self.write_arg(0);
-                self.emit_pop_top();
+                self.write_arg(CompareOp::EQ.arg(self.python_ver()));
                 self.stack_dec();
+                pop_jump_points.push(self.emit_match_fail_jump(0));
             }
-            ParamPattern::Array(arr) => {
-                let len = arr.len();
+            // `[a, b]` and `(a, b)` are both sequence patterns
+            ParamPattern::Array(ParamArrayPattern { elems, .. })
+            | ParamPattern::Tuple(ParamTuplePattern { elems, .. }) => {
+                let len = elems.len();
+                let n_required = elems.non_defaults.len();
                 self.write_instr(Opcode::MATCH_SEQUENCE);
                 self.write_arg(0);
-                pop_jump_points.push(self.cur_block().lasti);
-                self.write_instr(Opcode::POP_JUMP_IF_FALSE);
-                self.write_arg(0);
-                self.stack_dec();
-                self.write_instr(Opcode::GET_LEN);
-                self.write_arg(0);
-                self.emit_load_const(len);
-                self.write_instr(Opcode::COMPARE_OP);
-                self.write_arg(2); // ==
-                self.stack_dec();
-                pop_jump_points.push(self.cur_block().lasti);
-                self.write_instr(Opcode::POP_JUMP_IF_FALSE);
-                self.write_arg(0);
+                self.stack_inc();
+                pop_jump_points.push(self.emit_match_fail_jump(1));
+                if elems.defaults.is_empty() {
+                    pop_jump_points.push(self.emit_match_len_check(CompareOp::EQ, len));
+                } else {
+                    // `[a, b |= 0]` matches `[1]` and `[1, 2]`
+                    pop_jump_points.push(self.emit_match_len_check(CompareOp::GE, n_required));
+                    pop_jump_points.push(self.emit_match_len_check(CompareOp::LE, len));
+                    if Self::has_middle_default(&elems) {
+                        self.emit_select_elems(&elems);
+                    } else {
+                        self.emit_fill_defaults(&elems, n_required);
+                    }
+                }
+                self.write_instr_arg(Opcode::UNPACK_SEQUENCE, len);
                 self.stack_dec();
-                self.write_instr(Opcode::UNPACK_SEQUENCE);
-                self.write_arg(len as u8);
-                self.stack_inc_n(len - 1);
-                for elem in arr.elems.non_defaults {
-                    pop_jump_points.append(&mut self.emit_match_pattern(elem.pat)?);
+                self.stack_inc_n(len);
+                for (i, elem) in Self::elems_in_order(elems).into_iter().enumerate() {
+                    // the rest of the elements are also left when the `i`th element does not match
+                    let rest = len - 1 - i;
+                    let points = self.emit_match_pattern(elem.pat)?;
+                    pop_jump_points.extend(points.into_iter().map(|(idx, n)| (idx, n + rest)));
+                }
+            }
+            ParamPattern::Record(rec) => {
+                if let Some(default) = rec.elems.defaults.first() {
+                    return Err(CompileError::feature_error(
+                        self.cfg.input.clone(),
+                        default.loc(),
+                        "default values in match patterns",
+                        "".into(),
+                    ));
+                }
+                let mut fields = Vec::with_capacity(rec.elems.non_defaults.len());
+                for elem in rec.elems.non_defaults.iter() {
+                    if let Some(field) = elem.inspect() {
+                        fields.push(field.clone());
+                    } else {
+                        return Err(CompileError::feature_error(
+                            self.cfg.input.clone(),
+                            elem.loc(),
+                            "nested record patterns",
+                            "".into(),
+                        ));
+                    }
+                }
+                let len = fields.len();
+                if self.python_ver() >= 3439 {
+                    // `case object(field1=.., field2=..)`
+                    self.emit_load_name_instr(Str::ever("object"))?;
+                    let fields = fields
+                        .into_iter()
+                        .map(|field| {
+                            ValueObj::Str(escape_attr("Record", None, field, &self.str_cache))
+                        })
+                        .collect::<Vec<_>>();
+                    self.emit_load_const(fields);
+                    self.write_instr(Opcode::MATCH_CLASS);
+                    self.write_arg(0);
+                    if self.python_ver() >= 3495 {
+                        // subject, class, names -> attrs (or None)
+                        self.stack_dec_n(2);
+                        self.write_instr(Opcode::DUP_TOP);
+                        self.write_arg(0);
+                        self.stack_inc();
+                        self.emit_load_const(ValueObj::None);
+                        self.write_instr(Opcode::IS_OP);
+                        self.write_arg(1);
+                        self.stack_dec();
+                    } else {
+                        // subject, class, names -> attrs, success
+                        self.stack_dec();
+                    }
+                    // attrs (None) is left if not matched
+                    pop_jump_points.push(self.emit_match_fail_jump(1));
+                    self.write_instr_arg(Opcode::UNPACK_SEQUENCE, len);
+                    self.stack_inc_n(len);
+                    self.stack_dec();
+                } else {
+                    // MATCH_CLASS is not available, so the type cannot be checked
+                    for field in fields.into_iter().rev() {
+                        self.write_instr(Opcode::DUP_TOP);
+                        self.write_arg(0);
+                        self.stack_inc();
+                        let name = self.register_attr("Record", None, field);
+                        self.write_instr_arg(Opcode::LOAD_ATTR, name.idx);
+                        self.write_instr(Opcode::ROT_TWO);
+                        self.write_arg(0);
+                    }
+                    self.emit_pop_top();
                 }
-                if !arr.elems.defaults.is_empty() {
-                    todo!("default values in match are not supported yet")
+                for (i, elem) in rec.elems.non_defaults.into_iter().enumerate() {
+                    let rest = len - 1 - i;
+                    let points = self.emit_match_pattern(elem.pat)?;
+                    pop_jump_points.extend(points.into_iter().map(|(idx, n)| (idx, n + rest)));
                 }
             }
             _other => {
@@ -740,17 +2208,69 @@ impl CodeGenerator {
         Ok(pop_jump_points)
     }
 
+    /// `f(x, y)` where `f` is the intrinsic of a custom opcode: `<push x>; <push y>; <op> 2`
+    /// Only up to 255 positional arguments can be passed.
+    fn emit_custom_instr(&mut self, op: CustomOpcode, mut args: Args) {
+        let unsupported = if let Some(kw) = args.kw_args().first() {
+            Some((kw.loc(), "keyword arguments to a custom opcode"))
+        } else if let Some(spread) = args.var_args().or(args.kw_var_args()) {
+            Some((spread.loc(), "spread arguments to a custom opcode"))
+        } else {
+            args.pos_args()
+                .get(u8::MAX as usize)
+                .map(|arg| (arg.loc(), "more than 255 arguments to a custom opcode"))
+        };
+        if let Some((loc, name)) = unsupported {
+            self.errs.push(CompileError::feature_error(
+                self.cfg.input.clone(),
+                loc,
+                name,
+                "".into(),
+            ));
+            // a dummy result, to keep the stack consistent
+            self.emit_load_const(ValueObj::None);
+            return;
+        }
+        let argc = args.len() as u32;
+        while let Some(arg) = args.try_remove_pos(0) {
+            self.codegen_expr(arg.expr);
+        }
+        self.mut_cur_block_codeobj().code.push(op.byte);
+        self.mut_cur_block().lasti += 1;
+        self.write_arg(argc as u8);
+        let effect = op.stack_effect_with(argc);
+        if effect >= 0 {
+            self.stack_inc_n(effect as usize);
+        } else {
+            self.stack_dec_n(-effect as usize);
+        }
+    }
+
     fn emit_call_name(&mut self, name: Str, mut args: Args) -> CompileResult<()> {
+        if let Some(op) = self.opcode_table.get_intrinsic(&name).cloned() {
+            self.emit_custom_instr(op, args);
+            return Ok(());
+        }
         match &name[..] {
             "assert" => self.emit_assert_instr(args),
+            "del!" => self.emit_del_instr(args),
             "discard" => self.emit_discard_instr(args),
             "for" | "for!" => self.emit_for_instr(args),
-            "if" | "if!" => self.emit_if_instr(args),
+            "while" | "while!" => self.emit_while_instr(args),
+            "if" | "if!" => self.emit_if_instr(args, true),
+            "log" => self.emit_log_instr(args),
+            "import" | "py" | "pyimport" => self.emit_import_instr(args),
             "match" | "match!" => self.emit_match_instr(args, true),
+            "repeat_until!" => self.emit_repeat_until_instr(args),
+            "with!" => self.emit_with_instr(args),
             _ => {
                 self.emit_load_name_instr(name).unwrap_or_else(|e| {
                     self.errs.push(e);
                 });
+                if args.has_spread() {
+                    self.emit_call_ex(args);
+                    return Ok(());
+                }
                 let argc = args.len();
                 let mut kws = Vec::with_capacity(args.kw_len());
                 while let Some(arg) = args.try_remove_pos(0) {
@@ -760,16 +2280,14 @@ impl CodeGenerator {
                     kws.push(ValueObj::Str(arg.keyword.content.clone()));
                     self.codegen_expr(arg.expr);
                 }
-                let kwsc = if !kws.is_empty() {
+                let (instr, kwsc) = if !kws.is_empty() {
                     let kws_tuple = ValueObj::from(kws);
                     self.emit_load_const(kws_tuple);
-                    self.write_instr(CALL_FUNCTION_KW);
-                    1
+                    (CALL_FUNCTION_KW, 1)
                 } else {
-                    self.write_instr(CALL_FUNCTION);
-                    0
+                    (CALL_FUNCTION, 0)
                 };
-                self.write_arg(argc as u8);
+                self.write_instr_arg(instr, argc);
                 // (1 (subroutine) + argc + kwsc) input objects -> 1 return object
                 self.stack_dec_n((1 + argc + kwsc) - 1);
                 Ok(())
@@ -782,6 +2300,10 @@ impl CodeGenerator {
             self.emit_load_name_instr(name).unwrap_or_else(|err| {
                 self.errs.push(err);
             });
+            if args.has_spread() {
+                self.emit_call_ex(args);
+                return;
+            }
             let argc = args.len();
             let mut kws = Vec::with_capacity(args.kw_len());
             while let Some(arg) = args.try_remove_pos(0) {
@@ -791,22 +2313,29 @@ impl CodeGenerator {
                 kws.push(ValueObj::Str(arg.keyword.content.clone()));
                 self.codegen_expr(arg.expr);
             }
-            let kwsc = if !kws.is_empty() {
+            let (instr, kwsc) = if !kws.is_empty() {
                 let kws_tuple = ValueObj::from(kws);
                 self.emit_load_const(kws_tuple);
-                self.write_instr(CALL_FUNCTION_KW);
-                1
+                (CALL_FUNCTION_KW, 1)
             } else {
-                self.write_instr(CALL_FUNCTION);
-                0
+                (CALL_FUNCTION, 0)
             };
-            self.write_arg(1 + argc as u8);
+            self.write_instr_arg(instr, 1 + argc);
             // (1 (method as subroutine) + 1 (obj) + argc + kwsc) input objects -> 1 return object
             self.stack_dec_n((1 + 1 + argc + kwsc) - 1);
         } else {
             let class = Str::rc(obj.ref_t().name());
             let uniq_obj_name = obj.__name__().map(Str::rc);
             self.codegen_expr(obj);
+            if args.has_spread() {
+                // `CALL_FUNCTION_EX` takes a bound method, not the (method, self) pair
+                self.emit_load_attr_instr(&class, uniq_obj_name.as_ref().map(|s| &s[..]), name)
+                    .unwrap_or_else(|err| {
+                        self.errs.push(err);
+                    });
+                self.emit_call_ex(args);
+                return;
+            }
             self.emit_load_method_instr(&class, uniq_obj_name.as_ref().map(|s| &s[..]), name)
                 .unwrap_or_else(|err| {
                     self.errs.push(err);
@@ -820,16 +2349,14 @@ impl CodeGenerator {
                 kws.push(ValueObj::Str(arg.keyword.content.clone()));
                 self.codegen_expr(arg.expr);
             }
-            let kwsc = if !kws.is_empty() {
+            let (instr, kwsc) = if !kws.is_empty() {
                 let kws_tuple = ValueObj::from(kws);
                 self.emit_load_const(kws_tuple);
-                self.write_instr(CALL_FUNCTION_KW);
-                1
+                (CALL_FUNCTION_KW, 1)
             } else {
-                self.write_instr(CALL_METHOD);
-                0
+                (CALL_METHOD, 0)
             };
-            self.write_arg(argc as u8);
+            self.write_instr_arg(instr, argc);
             // (1 (method) + argc + kwsc) input objects -> 1 return object
             self.stack_dec_n((1 + argc + kwsc) - 1);
         }
@@ -837,6 +2364,10 @@ impl CodeGenerator {
 
     fn emit_call_callable_obj(&mut self, obj: Expr, mut args: Args) {
         self.codegen_expr(obj);
+        if args.has_spread() {
+            self.emit_call_ex(args);
+            return;
+        }
         let argc = args.len();
         let mut kws = Vec::with_capacity(args.kw_len());
         while let Some(arg) = args.try_remove_pos(0) {
@@ -846,22 +2377,216 @@ impl CodeGenerator {
             kws.push(ValueObj::Str(arg.keyword.content.clone()));
             self.codegen_expr(arg.expr);
         }
-        let kwsc = if !kws.is_empty() {
+        let (instr, kwsc) = if !kws.is_empty() {
             let kws_tuple = ValueObj::from(kws);
             self.emit_load_const(kws_tuple);
-            self.write_instr(CALL_FUNCTION_KW);
-            1
+            (CALL_FUNCTION_KW, 1)
         } else {
-            self.write_instr(CALL_FUNCTION);
-            0
+            (CALL_FUNCTION, 0)
         };
-        self.write_arg(argc as u8);
+        self.write_instr_arg(instr, argc);
         // (1 (name) + argc + kwsc) objects -> 1 return object
         self.stack_dec_n((1 + argc + kwsc) - 1);
     }
 
+    /// Emits a call with `*args` and/or `**kwargs`. The callable must be already on the stack.
+    ///
+    /// The positional arguments are packed into a tuple and the keyword arguments into a dict,
+    /// then `CALL_FUNCTION_EX` is called with them.
+    /// Python 3.9 and later use `LIST_EXTEND`/`DICT_MERGE`, older versions use `BUILD_*_UNPACK_WITH_CALL`.
+    fn emit_call_ex(&mut self, mut args: Args) {
+        let var_args = args.take_var_args();
+        let kw_var_args = args.take_kw_var_args();
+        let since_39 = self.python_ver() >= 3425;
+        let argc = args.pos_args().len();
+        while let Some(arg) = args.try_remove_pos(0) {
+            self.codegen_expr(arg.expr);
+        }
+        match var_args {
+            // `f(*xs)`: `CALL_FUNCTION_EX` converts `xs` to a tuple
+            Some(var_args) if argc == 0 => {
+                self.codegen_expr(var_args.expr);
+            }
+            Some(var_args) if since_39 => {
+                self.write_instr_arg(BUILD_LIST, argc);
+                self.stack_dec_n(argc - 1);
+                self.codegen_expr(var_args.expr);
+                self.write_instr_arg(LIST_EXTEND, 1);
+                self.stack_dec();
+                self.write_instr(LIST_TO_TUPLE);
+                self.write_arg(0);
+            }
+            Some(var_args) => {
+                self.write_instr_arg(BUILD_TUPLE, argc);
+                self.stack_dec_n(argc - 1);
+                self.codegen_expr(var_args.expr);
+                self.write_instr_arg(BUILD_TUPLE_UNPACK_WITH_CALL, 2);
+                self.stack_dec();
+            }
+            None => {
+                self.write_instr_arg(BUILD_TUPLE, argc);
+                if argc == 0 {
+                    self.stack_inc();
+                } else {
+                    self.stack_dec_n(argc - 1);
+                }
+            }
+        }
+        let kwsc = args.kw_len();
+        let flag = if kwsc > 0 || kw_var_args.is_some() {
+            1
+        } else {
+            0
+        };
+        if kwsc > 0 || (since_39 && kw_var_args.is_some()) {
+            while let Some(arg) = args.try_remove_kw(0) {
+                self.emit_load_const(ValueObj::Str(arg.keyword.content.clone()));
+                self.codegen_expr(arg.expr);
+            }
+            self.write_instr_arg(BUILD_MAP, kwsc);
+            if kwsc == 0 {
+                self.stack_inc();
+            } else {
+                self.stack_dec_n(kwsc * 2 - 1);
+            }
+        }
+        if let Some(kw_var_args) = kw_var_args {
+            self.codegen_expr(kw_var_args.expr);
+            if since_39 {
+                self.write_instr_arg(DICT_MERGE, 1);
+                self.stack_dec();
+            } else if kwsc > 0 {
+                self.write_instr_arg(BUILD_MAP_UNPACK_WITH_CALL, 2);
+                self.stack_dec();
+            }
+        }
+        self.write_instr_arg(CALL_FUNCTION_EX, flag);
+        // (1 (subroutine) + 1 (args) + flag (kwargs)) input objects -> 1 return object
+        self.stack_dec_n(1 + flag);
+    }
+
+    /// Returns the exponent if `bin` is `x ** n` with a small constant `n` that can be unrolled.
+    /// `x ** 0` is only unrolled when `x` has no side effects, because `x` is not evaluated.
+    fn pow_unroll_exponent(&self, bin: &BinOp) -> Option<u64> {
+        if self.cfg.opt_level() == 0 || !bin.op.is(TokenKind::Pow) {
+            return None;
+        }
+        let exp = match bin.rhs.as_ref() {
+            Expr::Lit(lit) => match &lit.data {
+                ValueObj::Nat(n) => *n,
+                ValueObj::Int(i) if *i >= 0 => *i as u64,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        match (exp, bin.lhs.as_ref()) {
+            (0, Expr::Lit(_) | Expr::Accessor(Accessor::Local(_))) => Some(0),
+            (0, _) => None,
+            (n, _) if n <= POW_UNROLL_LIMIT => Some(n),
+            _ => None,
+        }
+    }
+
+    /// `x ** 0` -> `1`, `x ** 1` -> `x`, `x ** n` -> `x * x * ... * x`
+    fn emit_pow_by_mul(&mut self, lhs: Expr, exp: u64) {
+        if exp == 0 {
+            // literals have refinement types (e.g. `{x: Float | x == 2.0}`)
+            let t = match lhs.ref_t() {
+                Type::Refinement(refine) => refine.t.as_ref(),
+                t => t,
+            };
+            if matches!(t, Type::Float | Type::FloatMut) {
+                self.emit_load_const(1.0);
+            } else {
+                self.emit_load_const(1usize);
+            }
+            return;
+        }
+        let type_pair = TypePair::new(lhs.ref_t(), lhs.ref_t());
+        self.codegen_expr(lhs);
+        for _ in 1..exp {
+            self.write_instr(DUP_TOP);
+            self.write_arg(0);
+            self.stack_inc();
+        }
+        for _ in 1..exp {
+            self.write_instr(BINARY_MULTIPLY);
+            self.write_arg(type_pair as u8);
+            self.stack_dec();
+        }
+    }
+
+    /// `a < b < c` -> `a < b and b < c` (`b` is evaluated only once):
+    /// `a; b; DUP_TOP; ROT_THREE; COMPARE_OP <; JUMP_IF_FALSE_OR_POP cleanup; c; COMPARE_OP <;
+    /// JUMP_FORWARD end; cleanup: ROT_TWO; POP_TOP; end:`
+    fn emit_chained_compare(&mut self, bin: BinOp) {
+        // `a < b < c` is parsed as `(a < b) < c` (marked as chained)
+        let mut ops = vec![bin.op.kind];
+        let mut operands = vec![*bin.rhs];
+        let mut first = *bin.lhs;
+        let mut chained = bin.chained;
+        while chained {
+            let bin = enum_unwrap!(first, Expr::BinOp);
+            chained = bin.chained;
+            ops.push(bin.op.kind);
+            operands.push(*bin.rhs);
+            first = *bin.lhs;
+        }
+        self.codegen_expr(first);
+        let last = ops.len() - 1;
+        let mut idx_cleanups = vec![];
+        for (i, (op, operand)) in ops
+            .into_iter()
+            .rev()
+            .zip(operands.into_iter().rev())
+            .enumerate()
+        {
+            self.codegen_expr(operand);
+            if i != last {
+                self.write_instr(DUP_TOP);
+                self.write_arg(0);
+                self.stack_inc();
+                self.write_instr(ROT_THREE);
+                self.write_arg(0);
+            }
+            self.write_instr(COMPARE_OP);
+            self.write_arg(compare_op(&op).unwrap().arg(self.python_ver()));
+            self.stack_dec();
+            if i != last {
+                idx_cleanups.push(self.cur_block().lasti);
+                self.write_instr(JUMP_IF_FALSE_OR_POP);
+                // cannot detect where to jump to at this moment, so put as 0
+                self.write_arg(0);
+                self.stack_dec();
+            }
+        }
+        let idx_jump_forward = self.cur_block().lasti;
+        self.write_instr(JUMP_FORWARD);
+        self.write_arg(0);
+        // cleanup: the false result is on top of the duplicated operand
+        let idx_cleanup = self.cur_block().lasti;
+        for idx in idx_cleanups {
+            self.edit_jump(idx, self.jump_arg(idx_cleanup));
+        }
+        self.stack_inc();
+        self.write_instr(ROT_TWO);
+        self.write_arg(0);
+        self.write_instr(POP_TOP);
+        self.write_arg(0);
+        self.stack_dec();
+        let idx_end = self.cur_block().lasti;
+        self.edit_jump(
+            idx_jump_forward,
+            self.jump_arg(idx_end - idx_jump_forward - 2),
+        );
+    }
+
     // assert takes 1 or 2 arguments (0: cond, 1: message)
     fn emit_assert_instr(&mut self, mut args: Args) -> CompileResult<()> {
+        // `-O`: the condition is not evaluated either (same as Python)
+        if self.cfg.optimize_asserts() {
+            return Ok(());
+        }
         self.codegen_expr(args.remove(0));
         let pop_jump_point = self.cur_block().lasti;
         self.write_instr(Opcode::POP_JUMP_IF_TRUE);
@@ -877,41 +2602,85 @@ impl CodeGenerator {
         self.write_instr(Opcode::RAISE_VARARGS);
         self.write_arg(1);
         let idx = self.cur_block().lasti;
-        self.edit_code(pop_jump_point + 1, idx / 2); // jump to POP_TOP
+        self.edit_jump(pop_jump_point, self.jump_arg(idx)); // jump to POP_TOP
         Ok(())
     }
 
-    fn codegen_expr(&mut self, expr: Expr) {
-        if expr.ln_begin().unwrap() > self.cur_block().prev_lineno {
+    /// `if __name__ == "__main__":`
+    /// returns the index of `POP_JUMP_IF_FALSE` (the jump target is set by `close_main_guard`)
+    fn emit_main_guard(&mut self) -> usize {
+        self.emit_load_name_instr(Str::ever("__name__")).unwrap();
+        self.emit_load_const("__main__");
+        self.write_instr(COMPARE_OP);
+        self.write_arg(CompareOp::EQ.arg(self.python_ver()));
+        self.stack_dec();
+        let idx_pop_jump_if_false = self.cur_block().lasti;
+        self.write_instr(POP_JUMP_IF_FALSE);
+        self.write_arg(0);
+        self.stack_dec();
+        idx_pop_jump_if_false
+    }
+
+    fn close_main_guard(&mut self, idx_pop_jump_if_false: usize) {
+        let idx_end = self.cur_block().lasti;
+        self.edit_jump(idx_pop_jump_if_false, self.jump_arg(idx_end));
+    }
+
+    /// `cfg.coverage`が有効な場合、命令が1つもない行(コメント行など)にNOPを置く
+    fn emit_coverage_markers(&mut self, lineno: usize) {
+        while lineno > self.cur_block().prev_lineno + 1 {
             let sd = self.cur_block().lasti - self.cur_block().prev_lasti;
-            let ld = expr.ln_begin().unwrap() - self.cur_block().prev_lineno;
-            if ld != 0 {
-                if sd != 0 {
-                    self.mut_cur_block_codeobj().lnotab.push(sd as u8);
-                    self.mut_cur_block_codeobj().lnotab.push(ld as u8);
-                } else {
-                    // empty lines
-                    if let Some(last_ld) = self.mut_cur_block_codeobj().lnotab.last_mut() {
-                        *last_ld += ld as u8;
-                    } else {
-                        // a block starts with an empty line
-                        self.mut_cur_block_codeobj().lnotab.push(0);
-                        self.mut_cur_block_codeobj().lnotab.push(ld as u8);
-                    }
-                }
-                self.mut_cur_block().prev_lineno += ld;
-                self.mut_cur_block().prev_lasti = self.cur_block().lasti;
-            } else {
-                self.errs.push(CompileError::compiler_bug(
-                    0,
-                    self.cfg.input.clone(),
-                    expr.loc(),
-                    fn_name_full!(),
-                    line!(),
-                ));
-                self.crash("codegen failed: invalid bytecode format");
+            self.mut_cur_block_codeobj().push_lnotab(sd, 1);
+            self.mut_cur_block().prev_lineno += 1;
+            self.mut_cur_block().prev_lasti = self.cur_block().lasti;
+            self.write_instr(NOP);
+            self.write_arg(0);
+        }
+    }
+
+    /// Records the line of `expr` in `lnotab` (and emits the coverage markers) if it starts a new line.
+    /// The line may be before the current one (e.g. the value of `arr[i] = x` is compiled before `arr`)
+    fn update_lineno(&mut self, expr: &Expr) {
+        let lineno = expr.ln_begin().unwrap();
+        if self.cfg.coverage() {
+            self.emit_coverage_markers(lineno);
+        }
+        if lineno != self.cur_block().prev_lineno {
+            let sd = self.cur_block().lasti - self.cur_block().prev_lasti;
+            let ld = lineno as isize - self.cur_block().prev_lineno as isize;
+            self.mut_cur_block_codeobj().push_lnotab(sd, ld);
+            self.mut_cur_block().prev_lineno = lineno;
+            self.mut_cur_block().prev_lasti = self.cur_block().lasti;
+        }
+    }
+
+    /// Moves the current line to the last line of the block that has just been compiled
+    fn sync_lineno(&mut self, block_lineno: usize) {
+        let ld = block_lineno as isize - self.cur_block().prev_lineno as isize;
+        if ld != 0 {
+            self.mut_cur_block_codeobj().push_lnotab(0, ld);
+            self.mut_cur_block().prev_lineno = block_lineno;
+        }
+    }
+
+    /// Compiles a statement of a block.
+    /// Only the last statement is used as the value of the block,
+    /// so an `if` call without an else block elsewhere does not need to push `None` when the condition is false
+    /// (with an else block, popping the merged value once is shorter than popping in each branch).
+    fn codegen_stmt(&mut self, expr: Expr, is_last: bool) {
+        match expr {
+            Expr::Call(call) if !is_last && call.is_if_call() && call.args.len() == 2 => {
+                self.update_lineno(&Expr::Call(call.clone()));
+                self.emit_if_instr(call.args, false).unwrap_or_else(|err| {
+                    self.errs.push(err);
+                });
             }
+            other => self.codegen_expr(other),
         }
+    }
+
+    fn codegen_expr(&mut self, expr: Expr) {
+        self.update_lineno(&expr);
         match expr {
             Expr::Lit(lit) => {
                 self.emit_load_const(lit.data);
@@ -935,19 +2704,36 @@ impl CodeGenerator {
                     self.errs.push(err);
                 });
             }
+            // `obj[index]`: `obj; index; BINARY_SUBSCR`
+            Expr::Accessor(Accessor::Subscr(subscr)) => {
+                self.codegen_expr(*subscr.obj);
+                self.emit_subscr_index(*subscr.index);
+                self.write_instr(BINARY_SUBSCR);
+                self.write_arg(0);
+                self.stack_dec();
+            }
             Expr::Def(def) => match def.sig {
                 Signature::Subr(sig) => self.emit_subr_def(sig, def.body),
                 Signature::Var(sig) => self.emit_var_def(sig, def.body),
             },
+            Expr::NamedExpr(named) => self.emit_named_expr(named),
+            Expr::AttrAssign(assign) => self.emit_attr_assign(assign),
+            Expr::StrInterp(interp) => self.emit_str_interp(interp),
+            Expr::Yield(yield_) => self.emit_yield(yield_),
+            Expr::ArrayComprehension(comp) => self.emit_array_comprehension(comp),
+            Expr::TypeCast(cast) => self.emit_type_cast(cast),
             // TODO:
             Expr::Lambda(lambda) => {
                 let params = self.gen_param_names(&lambda.params);
-                self.codegen_block(lambda.body, Some("<lambda>".into()), params);
-                self.emit_load_const("<lambda>");
+                let code = self.codegen_block(lambda.body, Some("<lambda>".into()), params);
+                let opcode_flag = self.emit_param_defaults(&lambda.params);
+                let qualname = code.qualname.clone();
+                self.emit_load_const(code);
+                self.emit_load_const(qualname);
                 self.write_instr(MAKE_FUNCTION);
-                self.write_arg(0u8);
-                // stack_dec: <lambda code obj> + <name "<lambda>"> -> <function>
-                self.stack_dec();
+                self.write_arg(opcode_flag);
+                // stack_dec: (<defaults>) + <lambda code obj> + <name "<lambda>"> -> <function>
+                self.stack_dec_n(1 + opcode_flag.count_ones() as usize);
             }
             Expr::UnaryOp(unary) => {
                 let tycode = TypeCode::from(unary.lhs_t());
@@ -956,6 +2742,7 @@ impl CodeGenerator {
                     // TODO:
                     TokenKind::PrePlus => UNARY_POSITIVE,
                     TokenKind::PreMinus => UNARY_NEGATIVE,
+                    TokenKind::PreNot => UNARY_NOT,
                     TokenKind::Mutate => NOP, // ERG_MUTATE,
                     // TokenKind::PreStar =>,
                     // TokenKind::PreRng =>,
@@ -973,15 +2760,45 @@ impl CodeGenerator {
                 self.write_arg(tycode as u8);
             }
             Expr::BinOp(bin) => {
-                // TODO: and/orのプリミティブ命令の実装
+                if let Some(exp) = self.pow_unroll_exponent(&bin) {
+                    self.emit_pow_by_mul(*bin.lhs, exp);
+                    return;
+                }
+                // `a and b`: if `a` is falsy, `b` is not evaluated and `a` is the result
+                let short_circuit = match &bin.op.kind {
+                    TokenKind::AndOp => Some(JUMP_IF_FALSE_OR_POP),
+                    TokenKind::OrOp => Some(JUMP_IF_TRUE_OR_POP),
+                    _ => None,
+                };
+                if let Some(jump) = short_circuit {
+                    self.codegen_expr(*bin.lhs);
+                    let idx_jump = self.cur_block().lasti;
+                    self.write_instr(jump);
+                    // cannot detect where to jump to at this moment, so put as 0
+                    self.write_arg(0);
+                    // the lhs is popped if the rhs is evaluated
+                    self.stack_dec();
+                    self.codegen_expr(*bin.rhs);
+                    let idx_end = self.cur_block().lasti;
+                    self.edit_jump(idx_jump, self.jump_arg(idx_end));
+                    return;
+                }
+                if bin.chained {
+                    self.emit_chained_compare(bin);
+                    return;
+                }
                 // Range operators are not operators in Python
-                match &bin.op.kind {
-                    // l..<r == range(l, r)
-                    TokenKind::RightOpen => {
-                        self.emit_load_name_instr(Str::ever("range")).unwrap();
-                    }
-                    TokenKind::LeftOpen | TokenKind::Closed | TokenKind::Open => todo!(),
-                    _ => {}
+                // l..<r == range(l, r), l..r == range(l, r + 1), l<..r == range(l + 1, r + 1), ...
+                if let Some((start_excluded, stop_included)) = range_bounds(&bin.op.kind) {
+                    self.emit_load_name_instr(Str::ever("range")).unwrap();
+                    self.emit_range_bound(*bin.lhs, start_excluded);
+                    self.emit_range_bound(*bin.rhs, stop_included);
+                    // range(start, stop)
+                    let argc = 2;
+                    self.write_instr(CALL_FUNCTION);
+                    self.write_arg(argc);
+                    self.stack_dec_n(argc as usize);
+                    return;
                 }
                 let type_pair = TypePair::new(bin.lhs_t(), bin.rhs_t());
                 self.codegen_expr(*bin.lhs);
@@ -993,18 +2810,12 @@ impl CodeGenerator {
                     TokenKind::Slash => BINARY_TRUE_DIVIDE,
                     TokenKind::Pow => BINARY_POWER,
                     TokenKind::Mod => BINARY_MODULO,
-                    TokenKind::AndOp => BINARY_AND,
-                    TokenKind::OrOp => BINARY_OR,
                     TokenKind::Less
                     | TokenKind::LessEq
                     | TokenKind::DblEq
                     | TokenKind::NotEq
                     | TokenKind::Gre
                     | TokenKind::GreEq => COMPARE_OP,
-                    TokenKind::LeftOpen
-                    | TokenKind::RightOpen
-                    | TokenKind::Closed
-                    | TokenKind::Open => CALL_FUNCTION, // ERG_BINARY_RANGE,
                     _ => {
                         self.errs.push(CompileError::feature_error(
                             self.cfg.input.clone(),
@@ -1015,38 +2826,27 @@ impl CodeGenerator {
                         NOT_IMPLEMENTED
                     }
                 };
-                let arg = match &bin.op.kind {
-                    TokenKind::Less => 0,
-                    TokenKind::LessEq => 1,
-                    TokenKind::DblEq => 2,
-                    TokenKind::NotEq => 3,
-                    TokenKind::Gre => 4,
-                    TokenKind::GreEq => 5,
-                    TokenKind::LeftOpen
-                    | TokenKind::RightOpen
-                    | TokenKind::Closed
-                    | TokenKind::Open => 2,
-                    _ => type_pair as u8,
+                let arg = if let Some(cmp) = compare_op(&bin.op.kind) {
+                    cmp.arg(self.python_ver())
+                } else {
+                    type_pair as u8
+                };
+                let (instr, arg) = match self.fast_path(instr, type_pair) {
+                    Some(fused) => (fused, 0),
+                    None => (instr, arg),
                 };
                 self.write_instr(instr);
                 self.write_arg(arg);
                 self.stack_dec();
-                match &bin.op.kind {
-                    TokenKind::LeftOpen
-                    | TokenKind::RightOpen
-                    | TokenKind::Open
-                    | TokenKind::Closed => {
-                        self.stack_dec();
-                    }
-                    _ => {}
-                }
             }
             Expr::Call(call) => {
                 // TODO: unwrap
                 let name = Str::from(obj_name(&call.obj).unwrap());
                 match *call.obj {
                     Expr::Accessor(Accessor::Local(_)) => {
-                        self.emit_call_name(name, call.args).unwrap();
+                        self.emit_call_name(name, call.args).unwrap_or_else(|err| {
+                            self.errs.push(err);
+                        });
                     }
                     Expr::Accessor(Accessor::Attr(a)) => {
                         // TODO: impl static dispatch mode
@@ -1057,20 +2857,51 @@ impl CodeGenerator {
                     }
                 }
             }
-            // TODO: list comprehension
             Expr::Array(mut arr) => {
                 let len = arr.elems.len();
                 while let Some(arg) = arr.elems.try_remove_pos(0) {
                     self.codegen_expr(arg.expr);
                 }
-                self.write_instr(BUILD_LIST);
-                self.write_arg(len as u8);
+                self.write_instr_arg(BUILD_LIST, len);
                 if len == 0 {
                     self.stack_inc();
                 } else {
                     self.stack_dec_n(len - 1);
                 }
             }
+            Expr::Tuple(mut tuple) => {
+                let consts = tuple
+                    .elems
+                    .pos_args()
+                    .iter()
+                    .map(|arg| match &arg.expr {
+                        Expr::Lit(lit) => Some(lit.data.clone()),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>();
+                if let Some(consts) = consts {
+                    // a tuple of constants is itself a constant
+                    self.emit_load_const(consts);
+                } else {
+                    let len = tuple.elems.len();
+                    while let Some(arg) = tuple.elems.try_remove_pos(0) {
+                        self.codegen_expr(arg.expr);
+                    }
+                    self.write_instr_arg(BUILD_TUPLE, len);
+                    self.stack_dec_n(len - 1);
+                }
+            }
+            Expr::Set(set) => self.emit_set(set),
+            Expr::SubscrAssign(assign) => {
+                self.codegen_expr(*assign.value);
+                self.codegen_expr(*assign.subscr.obj);
+                self.emit_subscr_index(*assign.subscr.index);
+                self.write_instr(STORE_SUBSCR);
+                self.write_arg(0);
+                self.stack_dec_n(3);
+            }
+            Expr::Record(record) => self.emit_record(record),
+            Expr::Dict(dict) => self.emit_dict(dict),
             other => {
                 self.errs.push(CompileError::feature_error(
                     self.cfg.input.clone(),
@@ -1083,35 +2914,48 @@ impl CodeGenerator {
         }
     }
 
+    /// A pure statement whose value is discarded (i.e. not the last one of the block) is not compiled.
+    fn is_dead_stmt(&self, stmt: &Expr, is_last: bool) -> bool {
+        self.cfg.opt_level() > 0 && !is_last && stmt.is_pure()
+    }
+
     /// forブロックなどで使う
     fn codegen_frameless_block(&mut self, block: Block, params: Vec<Str>) {
         for param in params {
             self.emit_store_instr(param, Name);
         }
-        for expr in block.into_iter() {
-            self.codegen_expr(expr);
-            // TODO: discard
+        let outer_logs = std::mem::take(&mut self.pending_logs);
+        let len = block.len();
+        for (i, expr) in block.into_iter().enumerate() {
+            if self.is_dead_stmt(&expr, i == len - 1) {
+                continue;
+            }
+            self.codegen_stmt(expr, i == len - 1);
+            self.emit_pending_logs();
             // 最終的に帳尻を合わせる(コード生成の順番的にスタックの整合性が一時的に崩れる場合がある)
             if self.cur_block().stack_len == 1 {
                 self.emit_pop_top();
             }
         }
+        self.pending_logs = outer_logs;
         self.cancel_pop_top();
     }
 
     fn codegen_typedef_block(&mut self, name: Str, block: Block) -> CodeObj {
         self.unit_size += 1;
-        self.units.push(CodeGenUnit::new(
+        self.units.push_unit(CodeGenUnit::new(
             self.unit_size,
             vec![],
             Str::rc(self.cfg.input.enclosed_name()),
             &name,
             block[0].ln_begin().unwrap(),
         ));
+        self.mut_cur_block().is_class = true;
         let mod_name = self.toplevel_block_codeobj().name.clone();
         self.emit_load_const(mod_name);
         self.emit_store_instr(Str::from("__module__"), Attr);
-        self.emit_load_const(name);
+        let qualname = self.cur_block_codeobj().qualname.clone();
+        self.emit_load_const(qualname);
         self.emit_store_instr(Str::from("__qualname__"), Attr);
         // TODO: サブルーチンはT.subという書式でSTORE
         for expr in block.into_iter() {
@@ -1141,15 +2985,9 @@ impl CodeGenerator {
             self.mut_cur_block_codeobj().flags += CodeObjFlags::NewLocals as u32;
         }
         // end of flagging
-        let unit = self.units.pop().unwrap();
+        let unit = self.pop_unit();
         if !self.units.is_empty() {
-            let ld = unit.prev_lineno - self.cur_block().prev_lineno;
-            if ld != 0 {
-                self.mut_cur_block_codeobj().lnotab.last_mut().map(|l| {
-                    *l += ld as u8;
-                });
-                self.mut_cur_block().prev_lineno += ld;
-            }
+            self.sync_lineno(unit.prev_lineno);
         }
         unit.codeobj
     }
@@ -1162,23 +3000,35 @@ impl CodeGenerator {
             "<block>".into()
         };
         let firstlineno = block.first().unwrap().ln_begin().unwrap();
-        self.units.push(CodeGenUnit::new(
+        self.units.push_unit(CodeGenUnit::new(
             self.unit_size,
             params,
             Str::rc(self.cfg.input.enclosed_name()),
             &name,
             firstlineno,
         ));
-        for expr in block.into_iter() {
-            self.codegen_expr(expr);
+        let outer_logs = std::mem::take(&mut self.pending_logs);
+        let mut last_has_value = false;
+        let len = block.len();
+        for (i, expr) in block.into_iter().enumerate() {
+            if self.is_dead_stmt(&expr, i == len - 1) {
+                continue;
+            }
+            self.codegen_stmt(expr, i == len - 1);
+            self.emit_pending_logs();
             // NOTE: 各行のトップレベルでは0個または1個のオブジェクトが残っている
             // Pythonの場合使わなかったオブジェクトはそのまま捨てられるが、Ergではdiscardを使う必要がある
-            // TODO: discard
-            if self.cur_block().stack_len == 1 {
+            last_has_value = self.cur_block().stack_len == 1;
+            if last_has_value {
                 self.emit_pop_top();
             }
         }
-        self.cancel_pop_top(); // 最後の値は戻り値として取っておく
+        // 最後の値は戻り値として取っておく
+        // 最後の式が定義などで値を残さない場合はNoneを返す(直前の式のPOP_TOPを取り消してはいけない)
+        self.pending_logs = outer_logs;
+        if last_has_value {
+            self.cancel_pop_top();
+        }
         if self.cur_block().stack_len == 0 {
             self.emit_load_const(ValueObj::None);
         } else if self.cur_block().stack_len > 1 {
@@ -1200,15 +3050,9 @@ impl CodeGenerator {
             self.mut_cur_block_codeobj().flags += CodeObjFlags::NewLocals as u32;
         }
         // end of flagging
-        let unit = self.units.pop().unwrap();
+        let unit = self.pop_unit();
         if !self.units.is_empty() {
-            let ld = unit.prev_lineno - self.cur_block().prev_lineno;
-            if ld != 0 {
-                self.mut_cur_block_codeobj().lnotab.last_mut().map(|l| {
-                    *l += ld as u8;
-                });
-                self.mut_cur_block().prev_lineno += ld;
-            }
+            self.sync_lineno(unit.prev_lineno);
         }
         unit.codeobj
     }
@@ -1216,26 +3060,59 @@ impl CodeGenerator {
     pub fn codegen(&mut self, hir: HIR) -> CodeObj {
         log!("{GREEN}[DEBUG] the code-generating process has started.{RESET}");
         self.unit_size += 1;
-        self.units.push(CodeGenUnit::new(
+        self.units.push_unit(CodeGenUnit::new(
             self.unit_size,
             vec![],
             Str::rc(self.cfg.input.enclosed_name()),
             "<module>",
             1,
         ));
+        if !self.cfg.erg_vm() && self.opcode_table.version().is_none() {
+            self.errs.push(CompileError::feature_error(
+                self.cfg.input.clone(),
+                Location::Unknown,
+                &format!("Python of the magic number {}", self.python_ver()),
+                self.cur_block_codeobj().name.clone(),
+            ));
+        }
+        self.emit_record_types(&hir.module);
         let mut print_point = 0;
         if self.input().is_repl() {
             print_point = self.cur_block().lasti;
             self.emit_load_name_instr(Str::ever("print")).unwrap();
         }
-        for expr in hir.module.into_iter() {
-            self.codegen_expr(expr);
-            // TODO: discard
-            if self.cur_block().stack_len == 1 {
+        let mut idx_main_guard = None;
+        let mut last_has_value = false;
+        let len = hir.module.len();
+        for (i, expr) in hir.module.into_iter().enumerate() {
+            if self.is_dead_stmt(&expr, i == len - 1) {
+                continue;
+            }
+            if self.cfg.main_guard() {
+                let is_def = matches!(expr, Expr::Def(_) | Expr::Decl(_));
+                match (is_def, idx_main_guard) {
+                    (false, None) => idx_main_guard = Some(self.emit_main_guard()),
+                    (true, Some(idx)) => {
+                        self.close_main_guard(idx);
+                        idx_main_guard = None;
+                    }
+                    _ => {}
+                }
+            }
+            self.codegen_stmt(expr, i == len - 1);
+            self.emit_pending_logs();
+            last_has_value = self.cur_block().stack_len == 1;
+            if last_has_value {
                 self.emit_pop_top();
             }
         }
-        self.cancel_pop_top(); // 最後の値は戻り値として取っておく
+        if let Some(idx) = idx_main_guard {
+            self.close_main_guard(idx);
+        }
+        // guardの中の値は戻り値にできない
+        if last_has_value && !self.cfg.main_guard() {
+            self.cancel_pop_top(); // 最後の値は戻り値として取っておく
+        }
         if self.input().is_repl() {
             if self.cur_block().stack_len == 1 {
                 // remains `print`, nothing to be printed
@@ -1267,15 +3144,9 @@ impl CodeGenerator {
             self.mut_cur_block_codeobj().flags += CodeObjFlags::NewLocals as u32;
         }
         // end of flagging
-        let unit = self.units.pop().unwrap();
+        let unit = self.pop_unit();
         if !self.units.is_empty() {
-            let ld = unit.prev_lineno - self.cur_block().prev_lineno;
-            if ld != 0 {
-                self.mut_cur_block_codeobj().lnotab.last_mut().map(|l| {
-                    *l += ld as u8;
-                });
-                self.mut_cur_block().prev_lineno += ld;
-            }
+            self.sync_lineno(unit.prev_lineno);
         }
         log!("{GREEN}[DEBUG] the code-generating process has completed.{RESET}");
         unit.codeobj
@@ -13,7 +13,7 @@ use erg_common::Str;
 
 use erg_parser::ParserRunner;
 
-use crate::codegen::CodeGenerator;
+use crate::codegen::{target_python_ver, CodeGenerator};
 use crate::effectcheck::SideEffectChecker;
 use crate::error::{CompileError, CompileErrors, TyCheckErrors};
 use crate::lower::ASTLowerer;
@@ -105,7 +105,11 @@ impl Runnable for Compiler {
     type Err = CompileError;
     type Errs = CompileErrors;
 
-    fn new(cfg: ErgConfig) -> Self {
+    /// The target Python is resolved here, so that the bytecode and the .pyc header agree.
+    fn new(mut cfg: ErgConfig) -> Self {
+        if !cfg.erg_vm() {
+            cfg.python_ver = Some(target_python_ver(&cfg));
+        }
         Self {
             code_generator: CodeGenerator::new(cfg.copy()),
             lowerer: ASTLowerer::new(),
@@ -132,10 +136,17 @@ impl Runnable for Compiler {
 
     fn eval(&mut self, src: Str) -> Result<String, CompileErrors> {
         let codeobj = self.compile(src, "eval")?;
-        Ok(codeobj.code_info())
+        Ok(codeobj.code_info_with(self.code_generator.opcode_table()))
     }
 }
 
+/// compiles `src` and returns the disassembled bytecode (for debugging)
+pub fn disassemble_source(src: &str, cfg: ErgConfig) -> Result<String, CompileErrors> {
+    let mut compiler = Compiler::new(cfg);
+    let codeobj = compiler.compile(Str::rc(src), "exec")?;
+    Ok(codeobj.disassemble_with(compiler.code_generator.opcode_table()))
+}
+
 impl Compiler {
     fn convert(&self, errs: TyCheckErrors) -> CompileErrors {
         errs.into_iter()
@@ -151,9 +162,8 @@ impl Compiler {
         mode: &str,
     ) -> Result<(), CompileErrors> {
         let code = self.compile(src, mode)?;
-        code.dump_as_pyc(path, self.cfg.python_ver)
-            .expect("failed to dump a .pyc file");
-        Ok(())
+        code.dump_as_pyc(&path, self.cfg.python_ver)
+            .map_err(|err| CompileError::io_error(self.input().clone(), path.as_ref(), &err).into())
     }
 
     pub fn compile(&mut self, src: Str, mode: &str) -> Result<CodeObj, CompileErrors> {
@@ -176,7 +186,7 @@ impl Compiler {
         if warns.is_empty() {
             dynamic = false;
         }
-        if self.cfg.verbose >= 2 {
+        if self.cfg.verbose() >= 2 {
             let warns = self.convert(warns);
             warns.fmt_all_stderr();
         }
@@ -192,15 +202,14 @@ impl Compiler {
         if dynamic {
             codeobj.flags += CodeObjFlags::EvmDynamic as u32;
         }
-        log!("{GREEN}code object:\n{}", codeobj.code_info());
+        log!(
+            "{GREEN}code object:\n{}",
+            codeobj.code_info_with(self.code_generator.opcode_table())
+        );
         log!(
             "[DEBUG] the compiling process has completed, found errors: {}{RESET}",
             self.code_generator.errs.len()
         );
-        if self.code_generator.errs.is_empty() {
-            Ok(codeobj)
-        } else {
-            Err(self.code_generator.errs.flush())
-        }
+        self.code_generator.errs.flush().into_result(codeobj)
     }
 }
@@ -813,7 +813,16 @@ impl Context {
                     Ok(())
                 }
             }
-            ast::VarPattern::SelfDot(_) => todo!(),
+            ast::VarPattern::SelfDot(_) => Err(TyCheckError::feature_error(
+                sig.loc(),
+                "assignment to `self.<attr>`",
+                self.caused_by(),
+            )),
+            ast::VarPattern::VarArgsName(_) => Err(TyCheckError::feature_error(
+                sig.loc(),
+                "starred assignment targets",
+                self.caused_by(),
+            )),
             ast::VarPattern::Array(arr) => {
                 for (elem, inf) in arr.iter().zip(generalized.inner_ts().iter()) {
                     let id = DefId(get_hash(&(&self.name, elem)));
@@ -864,7 +873,8 @@ impl Context {
                     Ok(())
                 }
             }
-            ast::ParamPattern::Array(arr) => {
+            ast::ParamPattern::Array(ast::ParamArrayPattern { elems, .. })
+            | ast::ParamPattern::Tuple(ast::ParamTuplePattern { elems, .. }) => {
                 let mut array_nth = 0;
                 let array_outer = if let Some(outer) = outer {
                     ParamIdx::nested(outer, nth)
@@ -872,8 +882,7 @@ impl Context {
                     ParamIdx::Nth(nth)
                 };
                 if let Some(decl_t) = opt_decl_t {
-                    for (elem, p) in arr
-                        .elems
+                    for (elem, p) in elems
                         .non_defaults
                         .iter()
                         .zip(decl_t.ty.non_default_params().unwrap())
@@ -881,8 +890,7 @@ impl Context {
                         self.assign_param(elem, Some(array_outer.clone()), array_nth, Some(p))?;
                         array_nth += 1;
                     }
-                    for (elem, p) in arr
-                        .elems
+                    for (elem, p) in elems
                         .defaults
                         .iter()
                         .zip(decl_t.ty.default_params().unwrap())
@@ -891,11 +899,11 @@ impl Context {
                         array_nth += 1;
                     }
                 } else {
-                    for elem in arr.elems.non_defaults.iter() {
+                    for elem in elems.non_defaults.iter() {
                         self.assign_param(elem, Some(array_outer.clone()), array_nth, None)?;
                         array_nth += 1;
                     }
-                    for elem in arr.elems.defaults.iter() {
+                    for elem in elems.defaults.iter() {
                         self.assign_param(elem, Some(array_outer.clone()), array_nth, None)?;
                         array_nth += 1;
                     }
@@ -1466,7 +1474,12 @@ impl Context {
         match instance {
             Type::Subr(subr) => {
                 let params_len = subr.non_default_params.len() + subr.default_params.len();
-                if params_len < pos_args.len() + kw_args.len() {
+                // `...T`: the rest of the positional arguments
+                let var_args_t = subr.non_default_params.last().and_then(|p| match &p.ty {
+                    Type::VarArgs(t) => Some(t.as_ref()),
+                    _ => None,
+                });
+                if var_args_t.is_none() && params_len < pos_args.len() + kw_args.len() {
                     return Err(TyCheckError::too_many_args_error(
                         callee.loc(),
                         &callee.to_string(),
@@ -1482,25 +1495,24 @@ impl Context {
                     .iter()
                     .chain(subr.default_params.iter());
                 for (param_ty, pos_arg) in params.clone().zip(pos_args) {
-                    self.sub_unify(
-                        pos_arg.expr.ref_t(),
-                        &param_ty.ty,
-                        None,
-                        Some(pos_arg.loc()),
-                    )
-                    .map_err(|e| {
-                        // REVIEW:
-                        let name = callee.var_full_name().unwrap_or("".to_string());
-                        let name =
-                            name + "::" + param_ty.name.as_ref().map(|s| &s[..]).unwrap_or("");
-                        TyCheckError::type_mismatch_error(
-                            e.core.loc,
-                            e.caused_by,
-                            &name[..],
-                            &param_ty.ty,
-                            pos_arg.expr.ref_t(),
-                        )
-                    })?;
+                    let expect_t = match &param_ty.ty {
+                        Type::VarArgs(t) => t.as_ref(),
+                        t => t,
+                    };
+                    self.sub_unify(pos_arg.expr.ref_t(), expect_t, None, Some(pos_arg.loc()))
+                        .map_err(|e| {
+                            // REVIEW:
+                            let name = callee.var_full_name().unwrap_or("".to_string());
+                            let name =
+                                name + "::" + param_ty.name.as_ref().map(|s| &s[..]).unwrap_or("");
+                            TyCheckError::type_mismatch_error(
+                                e.core.loc,
+                                e.caused_by,
+                                &name[..],
+                                &param_ty.ty,
+                                pos_arg.expr.ref_t(),
+                            )
+                        })?;
                     if let Some(name) = &param_ty.name {
                         if passed_params.contains(name) {
                             return Err(TyCheckError::multiple_args_error(
@@ -1514,6 +1526,16 @@ impl Context {
                         }
                     }
                 }
+                if let Some(var_args_t) = var_args_t {
+                    for pos_arg in pos_args.iter().skip(params_len) {
+                        self.sub_unify(
+                            pos_arg.expr.ref_t(),
+                            var_args_t,
+                            None,
+                            Some(pos_arg.loc()),
+                        )?;
+                    }
+                }
                 let param_ts = {
                     let mut param_ts = Dict::new();
                     for param_ty in params {
@@ -2699,7 +2721,17 @@ impl Context {
         match self_t {
             ASTOmitted => panic!(),
             Type => todo!(),
-            Type::Record(_) => todo!(),
+            Type::Record(ref attrs) => {
+                return attrs.get(&name.inspect()[..]).cloned().ok_or_else(|| {
+                    TyCheckError::no_attr_error(
+                        name.loc(),
+                        namespace.clone(),
+                        &self_t,
+                        name.inspect(),
+                        None,
+                    )
+                });
+            }
             Module => {
                 let mod_ctx = self.get_context(obj, Some(ContextKind::Module), namespace)?;
                 let t = mod_ctx.get_var_t(name, namespace)?;
@@ -2726,6 +2758,66 @@ impl Context {
         }
     }
 
+    /// the type of `obj[index]`
+    /// TODO: `__getitem__`
+    // an unsupported object is reported as an unboxed `TyCheckError`, like in `get_iter_elem_t`
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn get_subscr_t(
+        &self,
+        obj: &hir::Expr,
+        index: &hir::Expr,
+        namespace: &Str,
+    ) -> TyCheckResult<Type> {
+        let is_slice = matches!(index, hir::Expr::Slice(_));
+        let obj_t = match obj.ref_t() {
+            Refinement(refine) => refine.t.as_ref(),
+            other => other,
+        };
+        match obj_t {
+            Type::Poly { name, params } if &name[..] == "Array" || &name[..] == "Array!" => {
+                let elem_t = match params.first() {
+                    Some(TyParam::Type(t)) => t.as_ref().clone(),
+                    _ => Obj,
+                };
+                if is_slice {
+                    Ok(Type::array(elem_t, TyParam::erased(Nat)))
+                } else {
+                    Ok(elem_t)
+                }
+            }
+            Str => Ok(Str),
+            other => Err(TyCheckError::feature_error(
+                obj.loc(),
+                &format!("subscript of {other}"),
+                namespace.clone(),
+            )),
+        }
+    }
+
+    /// the type of `x` in `x <- iter`
+    // `TyCheckError` is unboxed in all the `get_*_t` methods
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn get_iter_elem_t(&self, iter: &hir::Expr, namespace: &Str) -> TyCheckResult<Type> {
+        let iter_t = match iter.ref_t() {
+            Refinement(refine) => refine.t.as_ref(),
+            other => other,
+        };
+        match iter_t {
+            Type::Poly { name, params } if &name[..] == "Array" || &name[..] == "Array!" => {
+                match params.first() {
+                    Some(TyParam::Type(t)) => Ok(t.as_ref().clone()),
+                    _ => Ok(Obj),
+                }
+            }
+            Str => Ok(Str),
+            other => Err(TyCheckError::feature_error(
+                iter.loc(),
+                &format!("iteration over {other}"),
+                namespace.clone(),
+            )),
+        }
+    }
+
     /// 戻り値ではなく、call全体の型を返す
     /// objは現時点ではAccessorのみ対応
     /// 受け入れるobj(Accessor)はcheckしてないハリボテ
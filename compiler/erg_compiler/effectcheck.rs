@@ -8,7 +8,7 @@ use erg_common::traits::Stream;
 use erg_common::Str;
 
 use crate::error::{EffectError, EffectErrors, EffectResult};
-use crate::hir::{Accessor, Def, Expr, Signature, HIR};
+use crate::hir::{Accessor, Def, Expr, Signature, StrSegment, HIR};
 use crate::varinfo::Visibility;
 use Visibility::*;
 
@@ -184,6 +184,46 @@ impl SideEffectChecker {
                 self.check_expr(&bin.lhs, allow_self_effect);
                 self.check_expr(&bin.rhs, allow_self_effect);
             }
+            Expr::NamedExpr(named) => {
+                self.check_expr(&named.value, allow_self_effect);
+            }
+            Expr::Slice(slice) => {
+                for bound in [&slice.start, &slice.stop, &slice.step]
+                    .into_iter()
+                    .flatten()
+                {
+                    self.check_expr(bound, allow_self_effect);
+                }
+            }
+            Expr::StrInterp(interp) => {
+                for seg in interp.segments.iter() {
+                    if let StrSegment::Expr { expr, .. } = seg {
+                        self.check_expr(expr, allow_self_effect);
+                    }
+                }
+            }
+            Expr::SubscrAssign(assign) => {
+                self.check_expr(&assign.subscr.obj, allow_self_effect);
+                self.check_expr(&assign.subscr.index, allow_self_effect);
+                self.check_expr(&assign.value, allow_self_effect);
+            }
+            Expr::AttrAssign(assign) => {
+                self.check_expr(&assign.attr.obj, allow_self_effect);
+                self.check_expr(&assign.value, allow_self_effect);
+            }
+            Expr::Yield(yield_) => {
+                self.check_expr(&yield_.value, allow_self_effect);
+            }
+            Expr::ArrayComprehension(comp) => {
+                self.check_expr(&comp.elem, allow_self_effect);
+                self.check_expr(&comp.iter, allow_self_effect);
+                for guard in comp.guards.iter() {
+                    self.check_expr(guard, allow_self_effect);
+                }
+            }
+            Expr::TypeCast(cast) => {
+                self.check_expr(&cast.expr, allow_self_effect);
+            }
             Expr::Lambda(lambda) => {
                 let is_proc = lambda.is_procedural();
                 if is_proc {
@@ -1,6 +1,8 @@
 use std::fmt::Display;
 use std::ops::Add;
+use std::path::Path;
 
+use erg_common::codeobj::CodeObjError;
 use erg_common::color::{GREEN, RED, RESET, YELLOW};
 use erg_common::config::Input;
 use erg_common::error::{ErrorCore, ErrorDisplay, ErrorKind::*, Location, MultiErrorDisplay};
@@ -49,6 +51,7 @@ pub fn unaryop_to_dname(op: &str) -> &str {
         "+" => "__pos__",
         "-" => "__neg__",
         "~" => "__invert__",
+        "not" => "__not__",
         "!" => "__mutate__",
         "..." => "__spread__",
         _ => todo!(),
@@ -84,6 +87,7 @@ pub fn readable_name(name: &str) -> &str {
         "__pos__" => "`+`",
         "__neg__" => "`-`",
         "__invert__" => "`~`",
+        "__not__" => "`not`",
         "__mutate__" => "`!`",
         "__spread__" => "`...`",
         other => other,
@@ -161,6 +165,42 @@ impl CompileError {
         ), None), input, "".into())
     }
 
+    /// the bytecode of `code_name` failed the check by `compute_stacksize` (`err`)
+    pub fn bytecode_bug(input: Input, code_name: &str, err: &CodeObjError) -> Self {
+        Self::new(
+            ErrorCore::new(
+                0,
+                CompilerSystemError,
+                Location::Unknown,
+                switch_lang!(
+                    format!("invalid bytecode was generated for {code_name} ({err})"),
+                    format!("{code_name}に不正なバイトコードが生成されました ({err})")
+                ),
+                None,
+            ),
+            input,
+            "".into(),
+        )
+    }
+
+    /// failed to dump the compiled code to `path` (`err`)
+    pub fn io_error(input: Input, path: &Path, err: &std::io::Error) -> Self {
+        Self::new(
+            ErrorCore::new(
+                0,
+                IoError,
+                Location::Unknown,
+                switch_lang!(
+                    format!("failed to dump the code to {}: {err}", path.display()),
+                    format!("{}へのコードの出力に失敗しました: {err}", path.display())
+                ),
+                None,
+            ),
+            input,
+            "".into(),
+        )
+    }
+
     pub fn feature_error(input: Input, loc: Location, name: &str, caused_by: Str) -> Self {
         Self::new(
             ErrorCore::new(
@@ -745,6 +785,15 @@ impl CompileErrors {
     pub fn flush(&mut self) -> Self {
         Self(self.0.drain(..).collect())
     }
+
+    /// `Ok(ok)` if there are no errors, otherwise `Err(self)`
+    pub fn into_result<T>(self, ok: T) -> Result<T, CompileErrors> {
+        if self.is_empty() {
+            Ok(ok)
+        } else {
+            Err(self)
+        }
+    }
 }
 
 pub type CompileResult<T> = Result<T, CompileError>;
@@ -177,6 +177,16 @@ impl Evaluator {
             Expr::UnaryOp(unary) => self.eval_const_unary(unary),
             Expr::Call(call) => self.eval_const_call(call, ctx),
             Expr::Def(def) => self.eval_const_def(def),
+            // TODO: tuples, records, dicts, sets, comprehensions
+            Expr::Tuple(_)
+            | Expr::Record(_)
+            | Expr::Dict(_)
+            | Expr::Set(_)
+            | Expr::ArrayComprehension(_) => None,
+            // binds a name at runtime
+            Expr::NamedExpr(_) => None,
+            // TODO: interpolated strings of constants
+            Expr::StrInterp(_) => None,
             other => todo!("{other}"),
         }
     }
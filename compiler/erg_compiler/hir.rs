@@ -11,6 +11,7 @@ use erg_common::{
     impl_nested_display_for_enum, impl_stream_for_wrapper,
 };
 
+pub use erg_parser::ast::FormatConversion;
 use erg_parser::ast::{fmt_lines, DefId, Params, VarName, VarPattern};
 use erg_parser::token::{Token, TokenKind};
 
@@ -47,6 +48,20 @@ impl Locational for Literal {
     }
 }
 
+impl Literal {
+    /// converts the literal into `t` (e.g. `0` annotated as `Float` emits `0.0`)
+    /// returns `false` (and leaves the literal as is) if the conversion is lossy
+    pub fn coerce(&mut self, t: &Type) -> bool {
+        if let Some(data) = self.data.try_coerce(t) {
+            self.t = data.class();
+            self.data = data;
+            true
+        } else {
+            false
+        }
+    }
+}
+
 impl From<Token> for Literal {
     fn from(token: Token) -> Self {
         let data = ValueObj::from_str(Type::from(token.kind), token.content.clone());
@@ -130,7 +145,11 @@ impl KwArg {
 #[derive(Debug, Clone)]
 pub struct Args {
     pos_args: Vec<PosArg>,
+    /// `*args`
+    var_args: Option<Box<PosArg>>,
     kw_args: Vec<KwArg>,
+    /// `**kwargs`
+    kw_var_args: Option<Box<PosArg>>,
     paren: Option<(Token, Token)>,
 }
 
@@ -139,9 +158,17 @@ impl NestedDisplay for Args {
         if !self.pos_args.is_empty() {
             fmt_lines(self.pos_args.iter(), f, level)?;
         }
+        if let Some(var_args) = &self.var_args {
+            writeln!(f, "*")?;
+            var_args.fmt_nest(f, level)?;
+        }
         if !self.kw_args.is_empty() {
             fmt_lines(self.kw_args.iter(), f, level)?;
         }
+        if let Some(kw_var_args) = &self.kw_var_args {
+            writeln!(f, "**")?;
+            kw_var_args.fmt_nest(f, level)?;
+        }
         Ok(())
     }
 }
@@ -175,7 +202,9 @@ impl Args {
     ) -> Self {
         Self {
             pos_args,
+            var_args: None,
             kw_args,
+            kw_var_args: None,
             paren,
         }
     }
@@ -184,6 +213,39 @@ impl Args {
         Self::new(vec![], vec![], None)
     }
 
+    /// `*args` is passed after the positional arguments
+    pub fn set_var_args(&mut self, var_args: PosArg) {
+        self.var_args = Some(Box::new(var_args));
+    }
+
+    /// `**kwargs` is passed after the keyword arguments
+    pub fn set_kw_var_args(&mut self, kw_var_args: PosArg) {
+        self.kw_var_args = Some(Box::new(kw_var_args));
+    }
+
+    pub fn var_args(&self) -> Option<&PosArg> {
+        self.var_args.as_deref()
+    }
+
+    pub fn kw_var_args(&self) -> Option<&PosArg> {
+        self.kw_var_args.as_deref()
+    }
+
+    pub fn take_var_args(&mut self) -> Option<PosArg> {
+        self.var_args.take().map(|arg| *arg)
+    }
+
+    pub fn take_kw_var_args(&mut self) -> Option<PosArg> {
+        self.kw_var_args.take().map(|arg| *arg)
+    }
+
+    /// whether `*args` or `**kwargs` is passed
+    #[inline]
+    pub fn has_spread(&self) -> bool {
+        self.var_args.is_some() || self.kw_var_args.is_some()
+    }
+
+    /// The number of the arguments (`*args` and `**kwargs` are not counted)
     #[inline]
     pub fn len(&self) -> usize {
         self.pos_args.len() + self.kw_args.len()
@@ -345,8 +407,8 @@ impl Attribute {
 
 #[derive(Debug, Clone)]
 pub struct Subscript {
-    obj: Box<Expr>,
-    index: Box<Expr>,
+    pub obj: Box<Expr>,
+    pub index: Box<Expr>,
     t: Type,
 }
 
@@ -514,19 +576,138 @@ impl Array {
     }
 }
 
+/// represents an array comprehension (e.g. `[x + 1 | x <- xs, x > 0]`)
+#[derive(Debug, Clone)]
+pub struct ArrayComprehension {
+    pub l_sqbr: Token,
+    pub r_sqbr: Token,
+    t: Type,
+    pub elem: Box<Expr>,
+    /// the loop variable (`x` of `x <- xs`)
+    pub var: Token,
+    pub iter: Box<Expr>,
+    /// the filter predicates (`x > 0`), an element is appended only if all of them hold
+    pub guards: Vec<Expr>,
+}
+
+impl HasType for ArrayComprehension {
+    #[inline]
+    fn ref_t(&self) -> &Type {
+        &self.t
+    }
+    #[inline]
+    fn signature_t(&self) -> Option<&Type> {
+        None
+    }
+}
+
+impl NestedDisplay for ArrayComprehension {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "[{} | {} <- {}", self.elem, self.var.content, self.iter)?;
+        for guard in self.guards.iter() {
+            write!(f, ", {guard}")?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl_display_from_nested!(ArrayComprehension);
+impl_locational!(ArrayComprehension, l_sqbr, r_sqbr);
+
+impl ArrayComprehension {
+    pub fn new(
+        l_sqbr: Token,
+        r_sqbr: Token,
+        elem: Expr,
+        var: Token,
+        iter: Expr,
+        guards: Vec<Expr>,
+    ) -> Self {
+        let t = Type::array(elem.t(), TyParam::erased(Type::Nat));
+        Self {
+            l_sqbr,
+            r_sqbr,
+            t,
+            elem: Box::new(elem),
+            var,
+            iter: Box::new(iter),
+            guards,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tuple {
+    t: Type,
+    pub elems: Args,
+}
+
+impl HasType for Tuple {
+    #[inline]
+    fn ref_t(&self) -> &Type {
+        &self.t
+    }
+    #[inline]
+    fn signature_t(&self) -> Option<&Type> {
+        None
+    }
+}
+
+impl NestedDisplay for Tuple {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "({})", self.elems)
+    }
+}
+
+impl_display_from_nested!(Tuple);
+impl_locational!(Tuple, elems, elems);
+
+impl Tuple {
+    pub fn new(elems: Args) -> Self {
+        let t = Type::tuple(elems.pos_args().iter().map(|a| a.expr.t()).collect());
+        Self { t, elems }
+    }
+}
+
+/// e.g. `"a": 1` of `{"a": 1}`
+#[derive(Debug, Clone)]
+pub struct KeyValue {
+    pub key: Expr,
+    pub value: Expr,
+}
+
+impl NestedDisplay for KeyValue {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.value)
+    }
+}
+
+impl_display_from_nested!(KeyValue);
+
+impl Locational for KeyValue {
+    fn loc(&self) -> Location {
+        Location::concat(&self.key, &self.value)
+    }
+}
+
+impl KeyValue {
+    pub const fn new(key: Expr, value: Expr) -> Self {
+        Self { key, value }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Dict {
     pub l_brace: Token,
     pub r_brace: Token,
-    pub attrs: Args, // TODO: keyをTokenではなくExprにする
+    t: Type,
+    pub kvs: Vec<KeyValue>,
 }
 
 impl HasType for Dict {
+    #[inline]
     fn ref_t(&self) -> &Type {
-        todo!()
-    }
-    fn t(&self) -> Type {
-        todo!()
+        &self.t
     }
     #[inline]
     fn signature_t(&self) -> Option<&Type> {
@@ -536,7 +717,14 @@ impl HasType for Dict {
 
 impl NestedDisplay for Dict {
     fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
-        write!(f, "{{{}}}", self.attrs)
+        write!(f, "{{")?;
+        for (i, kv) in self.kvs.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{kv}")?;
+        }
+        write!(f, "}}")
     }
 }
 
@@ -544,10 +732,149 @@ impl_display_from_nested!(Dict);
 impl_locational!(Dict, l_brace, r_brace);
 
 impl Dict {
-    pub const fn new(l_brace: Token, r_brace: Token, attrs: Args) -> Self {
+    pub fn new(l_brace: Token, r_brace: Token, level: usize, kvs: Vec<KeyValue>) -> Self {
+        let (k_t, v_t) = kvs
+            .first()
+            .map(|kv| (kv.key.t(), kv.value.t()))
+            .unwrap_or_else(|| {
+                (
+                    Type::free_var(level, Constraint::TypeOf(Type::Type)),
+                    Type::free_var(level, Constraint::TypeOf(Type::Type)),
+                )
+            });
+        Self {
+            l_brace,
+            r_brace,
+            t: Type::dict(k_t, v_t),
+            kvs,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Set {
+    pub l_brace: Token,
+    pub r_brace: Token,
+    t: Type,
+    pub elems: Args,
+}
+
+impl HasType for Set {
+    #[inline]
+    fn ref_t(&self) -> &Type {
+        &self.t
+    }
+    #[inline]
+    fn signature_t(&self) -> Option<&Type> {
+        None
+    }
+}
+
+impl NestedDisplay for Set {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "{{{}}}", self.elems)
+    }
+}
+
+impl_display_from_nested!(Set);
+impl_locational!(Set, l_brace, r_brace);
+
+impl Set {
+    pub fn new(l_brace: Token, r_brace: Token, level: usize, elems: Args) -> Self {
+        let elem_t = elems
+            .pos_args()
+            .first()
+            .map(|a| a.expr.t())
+            .unwrap_or_else(|| Type::free_var(level, Constraint::TypeOf(Type::Type)));
         Self {
             l_brace,
             r_brace,
+            t: Type::poly("Set", vec![TyParam::t(elem_t)]),
+            elems,
+        }
+    }
+
+    pub fn push(&mut self, elem: Expr) {
+        self.elems.push_pos(PosArg::new(elem));
+    }
+}
+
+/// e.g. `x = 1` of `{x = 1; y = 2}`
+#[derive(Debug, Clone)]
+pub struct RecordAttr {
+    pub ident: Token,
+    pub value: Expr,
+}
+
+impl NestedDisplay for RecordAttr {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "{} = {}", self.ident.content, self.value)
+    }
+}
+
+impl_display_from_nested!(RecordAttr);
+
+impl Locational for RecordAttr {
+    fn loc(&self) -> Location {
+        Location::concat(&self.ident, &self.value)
+    }
+}
+
+impl RecordAttr {
+    pub const fn new(ident: Token, value: Expr) -> Self {
+        Self { ident, value }
+    }
+}
+
+/// e.g. `{x = 1; y = 2}`
+/// The attributes are kept in the order of definition
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub l_brace: Token,
+    pub r_brace: Token,
+    t: Type,
+    pub attrs: Vec<RecordAttr>,
+}
+
+impl HasType for Record {
+    #[inline]
+    fn ref_t(&self) -> &Type {
+        &self.t
+    }
+    #[inline]
+    fn signature_t(&self) -> Option<&Type> {
+        None
+    }
+}
+
+impl NestedDisplay for Record {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, attr) in self.attrs.iter().enumerate() {
+            if i != 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{attr}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl_display_from_nested!(Record);
+impl_locational!(Record, l_brace, r_brace);
+
+impl Record {
+    pub fn new(l_brace: Token, r_brace: Token, attrs: Vec<RecordAttr>) -> Self {
+        let t = Type::Record(
+            attrs
+                .iter()
+                .map(|attr| (attr.ident.content.clone(), attr.value.t()))
+                .collect(),
+        );
+        Self {
+            l_brace,
+            r_brace,
+            t,
             attrs,
         }
     }
@@ -559,6 +886,8 @@ pub struct BinOp {
     pub lhs: Box<Expr>,
     pub rhs: Box<Expr>,
     pub sig_t: Type, // e.g. (Int, Int) -> Int
+    /// the lhs is a comparison chained with this one (`a < b < c`)
+    pub chained: bool,
 }
 
 impl NestedDisplay for BinOp {
@@ -599,6 +928,7 @@ impl BinOp {
             lhs: Box::new(lhs),
             rhs: Box::new(rhs),
             sig_t,
+            chained: false,
         }
     }
 }
@@ -701,6 +1031,13 @@ impl Call {
         }
     }
 
+    pub fn is_if_call(&self) -> bool {
+        matches!(
+            self.obj.as_ref(),
+            Expr::Accessor(Accessor::Local(local)) if &local.inspect()[..] == "if" || &local.inspect()[..] == "if!"
+        )
+    }
+
     pub fn is_import_call(&self) -> bool {
         self.obj
             .var_full_name()
@@ -772,6 +1109,8 @@ impl VarSignature {
 
 #[derive(Debug, Clone)]
 pub struct SubrSignature {
+    /// in the written order (the last one is applied first)
+    pub decorators: Vec<Expr>,
     pub name: VarName,
     pub params: Params,
     pub t: Type,
@@ -779,6 +1118,9 @@ pub struct SubrSignature {
 
 impl fmt::Display for SubrSignature {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for deco in self.decorators.iter() {
+            write!(f, "@{deco} ")?;
+        }
         write!(f, "{}{} (: {})", self.name, self.params, self.t)
     }
 }
@@ -790,8 +1132,13 @@ impl Locational for SubrSignature {
 }
 
 impl SubrSignature {
-    pub const fn new(name: VarName, params: Params, t: Type) -> Self {
-        Self { name, params, t }
+    pub const fn new(decorators: Vec<Expr>, name: VarName, params: Params, t: Type) -> Self {
+        Self {
+            decorators,
+            name,
+            params,
+            t,
+        }
     }
 
     pub fn is_procedural(&self) -> bool {
@@ -957,25 +1304,328 @@ impl Def {
     }
 }
 
+/// represents an assignment expression (e.g. `(n := len(xs)) > 0`)
+/// unlike `Def`, this evaluates to the assigned value
+#[derive(Debug, Clone)]
+pub struct NamedExpr {
+    pub name: Local,
+    pub value: Box<Expr>,
+}
+
+impl NestedDisplay for NamedExpr {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, level: usize) -> fmt::Result {
+        writeln!(f, "{} :=", self.name)?;
+        self.value.fmt_nest(f, level + 1)
+    }
+}
+
+impl HasType for NamedExpr {
+    #[inline]
+    fn ref_t(&self) -> &Type {
+        self.value.ref_t()
+    }
+    #[inline]
+    fn signature_t(&self) -> Option<&Type> {
+        None
+    }
+}
+
+impl_display_from_nested!(NamedExpr);
+impl_locational!(NamedExpr, name, value);
+
+impl NamedExpr {
+    pub fn new(name: Local, value: Expr) -> Self {
+        Self {
+            name,
+            value: Box::new(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StrSegment {
+    Lit(Str),
+    /// `\{x}`, `\{x!r}`, `\{x:>5}`
+    Expr {
+        expr: Box<Expr>,
+        conversion: Option<FormatConversion>,
+        spec: Option<Str>,
+    },
+}
+
+impl fmt::Display for StrSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lit(s) => write!(f, "{s}"),
+            Self::Expr {
+                expr,
+                conversion,
+                spec,
+            } => {
+                write!(f, "\\{{{expr}")?;
+                if let Some(conv) = conversion {
+                    write!(f, "!{}", conv.as_char())?;
+                }
+                if let Some(spec) = spec {
+                    write!(f, ":{spec}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// represents an interpolated string (e.g. `"x = \{x}"`)
+#[derive(Debug, Clone)]
+pub struct StrInterp {
+    /// `"x = \{`
+    pub left: Token,
+    /// `}"`
+    pub right: Token,
+    pub segments: Vec<StrSegment>,
+}
+
+impl NestedDisplay for StrInterp {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "\"")?;
+        for seg in self.segments.iter() {
+            write!(f, "{seg}")?;
+        }
+        write!(f, "\"")
+    }
+}
+
+impl_display_from_nested!(StrInterp);
+impl_locational!(StrInterp, left, right);
+
+impl HasType for StrInterp {
+    #[inline]
+    fn ref_t(&self) -> &Type {
+        &Type::Str
+    }
+    #[inline]
+    fn signature_t(&self) -> Option<&Type> {
+        None
+    }
+}
+
+impl StrInterp {
+    pub const fn new(left: Token, right: Token, segments: Vec<StrSegment>) -> Self {
+        Self {
+            left,
+            right,
+            segments,
+        }
+    }
+}
+
+/// represents a Python-style slice used as a subscript index (e.g. `1:`, `:2`, `::-1`)
+/// omitted bounds are `None`
+#[derive(Debug, Clone)]
+pub struct Slice {
+    /// the first `:`
+    pub colon: Token,
+    pub start: Option<Box<Expr>>,
+    pub stop: Option<Box<Expr>>,
+    pub step: Option<Box<Expr>>,
+}
+
+impl NestedDisplay for Slice {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        if let Some(start) = &self.start {
+            write!(f, "{start}")?;
+        }
+        write!(f, ":")?;
+        if let Some(stop) = &self.stop {
+            write!(f, "{stop}")?;
+        }
+        if let Some(step) = &self.step {
+            write!(f, ":{step}")?;
+        }
+        Ok(())
+    }
+}
+
+impl_display_from_nested!(Slice);
+impl_locational!(Slice, colon, colon);
+
+impl Slice {
+    pub fn new(colon: Token, start: Option<Expr>, stop: Option<Expr>, step: Option<Expr>) -> Self {
+        Self {
+            colon,
+            start: start.map(Box::new),
+            stop: stop.map(Box::new),
+            step: step.map(Box::new),
+        }
+    }
+}
+
+/// represents an assignment to a subscript (e.g. `arr[0] = x`, `arr[1..3] = xs`)
+#[derive(Debug, Clone)]
+pub struct SubscrAssign {
+    pub subscr: Subscript,
+    pub op: Token,
+    pub value: Box<Expr>,
+}
+
+impl NestedDisplay for SubscrAssign {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, level: usize) -> fmt::Result {
+        writeln!(f, "{} {}", self.subscr, self.op.content)?;
+        self.value.fmt_nest(f, level + 1)
+    }
+}
+
+impl_display_from_nested!(SubscrAssign);
+impl_locational!(SubscrAssign, subscr, value);
+
+impl SubscrAssign {
+    pub fn new(subscr: Subscript, op: Token, value: Expr) -> Self {
+        Self {
+            subscr,
+            op,
+            value: Box::new(value),
+        }
+    }
+}
+
+/// represents an assignment to an attribute (e.g. `obj.x = 1`, `obj.x += 1`)
+#[derive(Debug, Clone)]
+pub struct AttrAssign {
+    pub attr: Attribute,
+    pub op: Token,
+    pub value: Box<Expr>,
+}
+
+impl NestedDisplay for AttrAssign {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, level: usize) -> fmt::Result {
+        writeln!(f, "{} {}", self.attr, self.op.content)?;
+        self.value.fmt_nest(f, level + 1)
+    }
+}
+
+impl_display_from_nested!(AttrAssign);
+impl_locational!(AttrAssign, attr, value);
+
+impl AttrAssign {
+    pub fn new(attr: Attribute, op: Token, value: Expr) -> Self {
+        Self {
+            attr,
+            op,
+            value: Box::new(value),
+        }
+    }
+}
+
+/// represents a `yield` expression
+/// a subroutine containing this is compiled as a generator
+#[derive(Debug, Clone)]
+pub struct Yield {
+    pub yield_token: Token,
+    pub value: Box<Expr>,
+    /// the type of the value sent to the generator
+    t: Type,
+}
+
+impl NestedDisplay for Yield {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, level: usize) -> fmt::Result {
+        writeln!(f, "{}", self.yield_token.content)?;
+        self.value.fmt_nest(f, level + 1)
+    }
+}
+
+impl HasType for Yield {
+    #[inline]
+    fn ref_t(&self) -> &Type {
+        &self.t
+    }
+    #[inline]
+    fn signature_t(&self) -> Option<&Type> {
+        None
+    }
+}
+
+impl_display_from_nested!(Yield);
+impl_locational!(Yield, yield_token, value);
+
+impl Yield {
+    pub fn new(yield_token: Token, value: Expr, t: Type) -> Self {
+        Self {
+            yield_token,
+            value: Box::new(value),
+            t,
+        }
+    }
+}
+
+/// represents an explicit type cast (e.g. `x as Float`)
+#[derive(Debug, Clone)]
+pub struct TypeCast {
+    pub expr: Box<Expr>,
+    pub as_token: Token,
+    /// the target type
+    t: Type,
+}
+
+impl NestedDisplay for TypeCast {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, level: usize) -> fmt::Result {
+        self.expr.fmt_nest(f, level)?;
+        write!(f, " {} {}", self.as_token.content, self.t)
+    }
+}
+
+impl HasType for TypeCast {
+    #[inline]
+    fn ref_t(&self) -> &Type {
+        &self.t
+    }
+    #[inline]
+    fn signature_t(&self) -> Option<&Type> {
+        None
+    }
+}
+
+impl_display_from_nested!(TypeCast);
+impl_locational!(TypeCast, expr, as_token);
+
+impl TypeCast {
+    pub fn new(expr: Expr, as_token: Token, t: Type) -> Self {
+        Self {
+            expr: Box::new(expr),
+            as_token,
+            t,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Expr {
     Lit(Literal),
     Accessor(Accessor),
     Array(Array),
+    ArrayComprehension(ArrayComprehension),
+    Tuple(Tuple),
     // Dict(Dict),
-    // Set(Set),
     Dict(Dict),
+    Set(Set),
+    Record(Record),
     BinOp(BinOp),
     UnaryOp(UnaryOp),
     Call(Call),
     Lambda(Lambda),
     Decl(Decl),
     Def(Def),
+    NamedExpr(NamedExpr),
+    StrInterp(StrInterp),
+    Slice(Slice),
+    SubscrAssign(SubscrAssign),
+    AttrAssign(AttrAssign),
+    Yield(Yield),
+    TypeCast(TypeCast),
 }
 
-impl_nested_display_for_enum!(Expr; Lit, Accessor, Array, Dict, BinOp, UnaryOp, Call, Lambda, Decl, Def);
+impl_nested_display_for_enum!(Expr; Lit, Accessor, Array, ArrayComprehension, Tuple, Dict, Set, Record, BinOp, UnaryOp, Call, Lambda, Decl, Def, NamedExpr, StrInterp, Slice, SubscrAssign, AttrAssign, Yield, TypeCast);
 impl_display_from_nested!(Expr);
-impl_locational_for_enum!(Expr; Lit, Accessor, Array, Dict, BinOp, UnaryOp, Call, Lambda, Decl, Def);
+impl_locational_for_enum!(Expr; Lit, Accessor, Array, ArrayComprehension, Tuple, Dict, Set, Record, BinOp, UnaryOp, Call, Lambda, Decl, Def, NamedExpr, StrInterp, Slice, SubscrAssign, AttrAssign, Yield, TypeCast);
 
 impl HasType for Expr {
     fn ref_t(&self) -> &Type {
@@ -983,11 +1633,19 @@ impl HasType for Expr {
             Expr::Lit(lit) => lit.ref_t(),
             Expr::Accessor(accessor) => accessor.ref_t(),
             Expr::Array(array) => array.ref_t(),
+            Expr::ArrayComprehension(comp) => comp.ref_t(),
+            Expr::Tuple(tuple) => tuple.ref_t(),
             Expr::Dict(dict) => dict.ref_t(),
+            Expr::Set(set) => set.ref_t(),
+            Expr::Record(record) => record.ref_t(),
             Expr::BinOp(bin) => bin.ref_t(),
             Expr::UnaryOp(unary) => unary.ref_t(),
             Expr::Call(call) => call.ref_t(),
             Expr::Lambda(lambda) => lambda.ref_t(),
+            Expr::NamedExpr(named) => named.ref_t(),
+            Expr::Yield(yield_) => yield_.ref_t(),
+            Expr::TypeCast(cast) => cast.ref_t(),
+            Expr::StrInterp(interp) => interp.ref_t(),
             _ => &Type::NoneType,
         }
     }
@@ -1016,6 +1674,39 @@ impl Expr {
         }
     }
 
+    /// Whether evaluating the expression has no side effects (conservative, i.e. `false` if unknown).
+    /// A pure expression whose value is discarded can be removed.
+    pub fn is_pure(&self) -> bool {
+        fn args_are_pure(args: &Args) -> bool {
+            args.pos_args().iter().all(|arg| arg.expr.is_pure())
+                && args.kw_args().iter().all(|arg| arg.expr.is_pure())
+                // unpacking may call `__iter__`/`keys`
+                && !args.has_spread()
+        }
+        match self {
+            Expr::Lit(_) | Expr::Accessor(Accessor::Local(_)) | Expr::Lambda(_) => true,
+            Expr::Array(array) => array.guard.is_none() && args_are_pure(&array.elems),
+            Expr::Tuple(tuple) => args_are_pure(&tuple.elems),
+            Expr::Set(set) => args_are_pure(&set.elems),
+            Expr::Record(record) => record.attrs.iter().all(|attr| attr.value.is_pure()),
+            Expr::Dict(dict) => dict
+                .kvs
+                .iter()
+                .all(|kv| kv.key.is_pure() && kv.value.is_pure()),
+            // `if c, () -> a, () -> b`: the branches are evaluated
+            Expr::Call(call) => match call.obj.as_ref() {
+                Expr::Accessor(Accessor::Local(local)) if &local.inspect()[..] == "if" => {
+                    call.args.pos_args().iter().all(|arg| match &arg.expr {
+                        Expr::Lambda(lambda) => lambda.body.iter().all(|expr| expr.is_pure()),
+                        other => other.is_pure(),
+                    }) && call.args.kw_args().iter().all(|arg| arg.expr.is_pure())
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
     /// 参照するオブジェクト自体が持っている名前(e.g. Int.__name__ == Some("int"))
     pub fn __name__(&self) -> Option<&str> {
         match self {
@@ -519,7 +519,7 @@ impl Context {
             mono_q("T"),
         );
         let t_cond = quant(t_cond, set! {static_instance("T", Type)});
-        let t_discard = nd_func(vec![param_t("o", Obj)], NoneType);
+        let t_discard = nd_func(vec![param_t("objs", Type::var_args(Obj))], NoneType);
         let t_id = nd_func(vec![param_t("o", Obj)], Nat);
         // FIXME: quantify
         let t_if = func(
@@ -558,6 +558,7 @@ impl Context {
             NoneType,
         );
         let t_input = nd_proc(vec![param_t("msg", Str)], Str);
+        let t_del = nd_proc(vec![param_t("obj", Obj)], NoneType);
         let t_if = proc(
             vec![
                 param_t("cond", Bool),
@@ -582,11 +583,31 @@ impl Context {
             ],
             NoneType,
         );
+        let t_repeat_until = nd_proc(
+            vec![
+                param_t("p", nd_proc(vec![], NoneType)),
+                param_t("cond", nd_func(vec![], Bool)),
+            ],
+            NoneType,
+        );
+        let t_open = nd_proc(vec![param_t("file", Str)], Obj);
+        // the parameter of `p` is the result of `ctx.__enter__()`
+        let t_with = nd_proc(
+            vec![
+                param_t("ctx", Obj),
+                param_t("p", nd_proc(vec![anon(Obj)], NoneType)),
+            ],
+            NoneType,
+        );
         self.register_impl("print!", t_print, Const, Private);
         self.register_impl("input!", t_input, Const, Private);
+        self.register_impl("open!", t_open, Const, Private);
+        self.register_impl("del!", t_del, Const, Private);
         self.register_impl("if!", t_if, Const, Private);
         self.register_impl("for!", t_for, Const, Private);
         self.register_impl("while!", t_while, Const, Private);
+        self.register_impl("repeat_until!", t_repeat_until, Const, Private);
+        self.register_impl("with!", t_with, Const, Private);
     }
 
     fn init_builtin_operators(&mut self) {
@@ -650,6 +671,7 @@ impl Context {
         let op_t = quant(op_t, set! {subtype(n, mono("Num"))});
         self.register_decl("__pos__", op_t.clone(), Private);
         self.register_decl("__neg__", op_t, Private);
+        self.register_impl("__not__", Type::func1(Bool, Bool), Const, Private);
         let t = mono_q("T");
         let op_t = Type::func2(t.clone(), t.clone(), Type::range(t.clone()));
         let op_t = quant(op_t, set! {subtype(t, mono("Ord"))});
@@ -4,7 +4,7 @@ pub extern crate erg_parser;
 
 mod compile;
 pub use compile::*;
-mod codegen;
+pub mod codegen;
 pub mod effectcheck;
 pub mod error;
 pub mod eval;
@@ -10,6 +10,7 @@ use erg_common::{fn_name, log, switch_lang};
 
 use erg_parser::ast;
 use erg_parser::ast::AST;
+use erg_parser::token::TokenKind::{Equal, SlashEqual};
 
 use crate::context::{Context, ContextKind, RegistrationMode};
 use crate::error::{LowerError, LowerErrors, LowerResult, LowerWarnings};
@@ -92,17 +93,168 @@ impl ASTLowerer {
 
     fn lower_array(&mut self, array: ast::Array, check: bool) -> LowerResult<hir::Array> {
         log!("[DEBUG] entered {}({array})", fn_name!());
-        let mut hir_array = hir::Array::new(
+        let mut elems = hir::Args::empty();
+        for elem in array.elems.into_iters().0 {
+            elems.push_pos(hir::PosArg::new(self.lower_expr(elem.expr, check)?));
+        }
+        // the element type is inferred from the elements
+        Ok(hir::Array::new(
             array.l_sqbr,
             array.r_sqbr,
             self.ctx.level,
-            hir::Args::empty(),
+            elems,
             None,
-        );
-        for elem in array.elems.into_iters().0 {
-            hir_array.push(self.lower_expr(elem.expr, check)?);
+        ))
+    }
+
+    /// The loop variable is defined in the comprehension's own scope
+    // passes on the errors of the iterable and the body unboxed, like the other `lower_*`
+    #[allow(clippy::result_large_err)]
+    fn lower_array_comprehension(
+        &mut self,
+        comp: ast::ArrayComprehension,
+    ) -> LowerResult<hir::ArrayComprehension> {
+        log!("[DEBUG] entered {}({comp})", fn_name!());
+        let iter = self.lower_expr(*comp.iter, true)?;
+        let elem_t = self.ctx.get_iter_elem_t(&iter, &self.ctx.name)?;
+        self.ctx.grow("<listcomp>", ContextKind::Instant, Private)?;
+        // the scope is popped whether the body is lowered or not
+        let body =
+            self.lower_comprehension_body(&comp.var, comp.id, &elem_t, *comp.elem, comp.guards);
+        self.pop_append_errs();
+        let (elem, guards) = body?;
+        Ok(hir::ArrayComprehension::new(
+            comp.l_sqbr,
+            comp.r_sqbr,
+            elem,
+            comp.var.into_token(),
+            iter,
+            guards,
+        ))
+    }
+
+    /// `elem | var <- _, guards...` (in the scope of the comprehension)
+    // see `lower_array_comprehension`
+    #[allow(clippy::result_large_err)]
+    fn lower_comprehension_body(
+        &mut self,
+        var: &ast::VarName,
+        id: ast::DefId,
+        elem_t: &Type,
+        elem: ast::Expr,
+        guards: Vec<ast::Expr>,
+    ) -> LowerResult<(hir::Expr, Vec<hir::Expr>)> {
+        let sig = ast::VarSignature::new(ast::VarPattern::VarName(var.clone()), None);
+        self.ctx.assign_var(&sig, id, elem_t)?;
+        let elem = self.lower_expr(elem, true)?;
+        let mut lowered_guards = Vec::with_capacity(guards.len());
+        for guard in guards.into_iter() {
+            let guard = self.lower_expr(guard, true)?;
+            if !self.ctx.rec_full_subtype_of(guard.ref_t(), &Type::Bool) {
+                return Err(LowerError::type_mismatch_error(
+                    guard.loc(),
+                    self.ctx.caused_by(),
+                    "guard",
+                    &Type::Bool,
+                    guard.ref_t(),
+                ));
+            }
+            lowered_guards.push(guard);
+        }
+        Ok((elem, lowered_guards))
+    }
+
+    // the errors of the embedded expressions are passed on unboxed, like in the other `lower_*`
+    #[allow(clippy::result_large_err)]
+    fn lower_str_interp(&mut self, interp: ast::StrInterp) -> LowerResult<hir::StrInterp> {
+        log!("[DEBUG] entered {}({interp})", fn_name!());
+        let mut segments = Vec::with_capacity(interp.segments.len());
+        for seg in interp.segments.into_iter() {
+            let seg = match seg {
+                ast::StrSegment::Lit(s) => hir::StrSegment::Lit(s),
+                // any object can be formatted
+                ast::StrSegment::Expr {
+                    expr,
+                    conversion,
+                    spec,
+                } => hir::StrSegment::Expr {
+                    expr: Box::new(self.lower_expr(*expr, true)?),
+                    conversion,
+                    spec,
+                },
+            };
+            segments.push(seg);
+        }
+        Ok(hir::StrInterp::new(interp.left, interp.right, segments))
+    }
+
+    // the errors of the attribute values are passed on unboxed, like in the other `lower_*`
+    #[allow(clippy::result_large_err)]
+    fn lower_record(&mut self, record: ast::Record) -> LowerResult<hir::Record> {
+        log!("[DEBUG] entered {}({record})", fn_name!());
+        let mut attrs = Vec::with_capacity(record.attrs.len());
+        for attr in record.attrs.into_iter() {
+            // the parser accepts only `name = expr`
+            let ident = attr.sig.name().unwrap().token().clone();
+            if attr.body.block.len() != 1 {
+                return Err(LowerError::feature_error(
+                    attr.loc(),
+                    "multi-line record attributes",
+                    self.ctx.name.clone(),
+                ));
+            }
+            let value = attr.body.block.into_iter().next().unwrap();
+            let value = self.lower_expr(value, true)?;
+            attrs.push(hir::RecordAttr::new(ident, value));
         }
-        Ok(hir_array)
+        Ok(hir::Record::new(record.l_brace, record.r_brace, attrs))
+    }
+
+    // the errors of the elements are passed on unboxed, as in `lower_array`
+    #[allow(clippy::result_large_err)]
+    fn lower_tuple(&mut self, tuple: ast::Tuple, check: bool) -> LowerResult<hir::Tuple> {
+        log!("[DEBUG] entered {}({tuple})", fn_name!());
+        let (pos_args, _, paren) = tuple.elems.deconstruct();
+        let mut elems = Vec::with_capacity(pos_args.len());
+        for elem in pos_args.into_iter() {
+            elems.push(hir::PosArg::new(self.lower_expr(elem.expr, check)?));
+        }
+        Ok(hir::Tuple::new(hir::Args::new(elems, vec![], paren)))
+    }
+
+    // the errors of the keys and values are passed on unboxed, as in `lower_array`
+    #[allow(clippy::result_large_err)]
+    fn lower_dict(&mut self, dict: ast::Dict, check: bool) -> LowerResult<hir::Dict> {
+        log!("[DEBUG] entered {}({dict})", fn_name!());
+        let mut kvs = Vec::with_capacity(dict.kvs.len());
+        for kv in dict.kvs.into_iter() {
+            let key = self.lower_expr(kv.key, check)?;
+            let value = self.lower_expr(kv.value, check)?;
+            kvs.push(hir::KeyValue::new(key, value));
+        }
+        Ok(hir::Dict::new(
+            dict.l_brace,
+            dict.r_brace,
+            self.ctx.level,
+            kvs,
+        ))
+    }
+
+    // the errors of the elements are passed on unboxed, as in `lower_array`
+    #[allow(clippy::result_large_err)]
+    fn lower_set(&mut self, set: ast::Set, check: bool) -> LowerResult<hir::Set> {
+        log!("[DEBUG] entered {}({set})", fn_name!());
+        let mut elems = hir::Args::empty();
+        for elem in set.elems.into_iters().0 {
+            elems.push_pos(hir::PosArg::new(self.lower_expr(elem.expr, check)?));
+        }
+        // the element type is inferred from the elements
+        Ok(hir::Set::new(
+            set.l_brace,
+            set.r_brace,
+            self.ctx.level,
+            elems,
+        ))
     }
 
     /// call全体で推論できる場合があり、そのときはcheck: falseにする
@@ -131,10 +283,36 @@ impl ASTLowerer {
                 let acc = hir::Accessor::Attr(hir::Attribute::new(obj, a.name.symbol, t));
                 Ok(acc)
             }
+            ast::Accessor::Subscr(subscr) => {
+                let obj = self.lower_expr(*subscr.obj, true)?;
+                let index = self.lower_expr(*subscr.index, true)?;
+                let t = self.ctx.get_subscr_t(&obj, &index, &self.ctx.name)?;
+                let acc = hir::Accessor::Subscr(hir::Subscript::new(obj, index, t));
+                Ok(acc)
+            }
             _ => todo!(),
         }
     }
 
+    // the errors of the bounds are passed on unboxed, like in the other `lower_*`
+    #[allow(clippy::result_large_err)]
+    fn lower_slice(&mut self, slice: ast::Slice) -> LowerResult<hir::Slice> {
+        log!("[DEBUG] entered {}({slice})", fn_name!());
+        let start = match slice.start {
+            Some(start) => Some(self.lower_expr(*start, true)?),
+            None => None,
+        };
+        let stop = match slice.stop {
+            Some(stop) => Some(self.lower_expr(*stop, true)?),
+            None => None,
+        };
+        let step = match slice.step {
+            Some(step) => Some(self.lower_expr(*step, true)?),
+            None => None,
+        };
+        Ok(hir::Slice::new(slice.colon, start, stop, step))
+    }
+
     fn lower_bin(&mut self, bin: ast::BinOp) -> LowerResult<hir::BinOp> {
         log!("[DEBUG] entered {}({bin})", fn_name!());
         let mut args = bin.args.into_iter();
@@ -145,7 +323,9 @@ impl ASTLowerer {
         let mut args = args.into_iter();
         let lhs = args.next().unwrap().expr;
         let rhs = args.next().unwrap().expr;
-        Ok(hir::BinOp::new(bin.op, lhs, rhs, t))
+        let mut hir_bin = hir::BinOp::new(bin.op, lhs, rhs, t);
+        hir_bin.chained = bin.chained;
+        Ok(hir_bin)
     }
 
     fn lower_unary(&mut self, unary: ast::UnaryOp) -> LowerResult<hir::UnaryOp> {
@@ -159,8 +339,10 @@ impl ASTLowerer {
         Ok(hir::UnaryOp::new(unary.op, expr, t))
     }
 
-    fn lower_call(&mut self, call: ast::Call) -> LowerResult<hir::Call> {
+    fn lower_call(&mut self, mut call: ast::Call) -> LowerResult<hir::Call> {
         log!("[DEBUG] entered {}({}(...))", fn_name!(), call.obj);
+        let var_args = call.args.take_var_args();
+        let kw_var_args = call.args.take_kw_var_args();
         let (pos_args, kw_args, paren) = call.args.deconstruct();
         let mut hir_args = hir::Args::new(
             Vec::with_capacity(pos_args.len()),
@@ -176,6 +358,14 @@ impl ASTLowerer {
                 self.lower_expr(arg.expr, true)?,
             ));
         }
+        // TODO: check the types of the unpacked elements
+        // (only the other arguments are checked against the parameters)
+        if let Some(var_args) = var_args {
+            hir_args.set_var_args(hir::PosArg::new(self.lower_expr(var_args.expr, true)?));
+        }
+        if let Some(kw_var_args) = kw_var_args {
+            hir_args.set_kw_var_args(hir::PosArg::new(self.lower_expr(kw_var_args.expr, true)?));
+        }
         let mut obj = self.lower_expr(*call.obj, false)?;
         let t = self.ctx.get_call_t(
             &mut obj,
@@ -258,6 +448,9 @@ impl ASTLowerer {
             .grow(def.sig.name_as_str(), ContextKind::Instant, Private)?;
         let res = match def.sig {
             ast::Signature::Subr(sig) => self.lower_subr_def(sig, def.body),
+            ast::Signature::Var(sig) if !def.body.op.is(Equal) => {
+                self.lower_aug_assign(sig, def.body)
+            }
             ast::Signature::Var(sig) => self.lower_var_def(sig, def.body),
         };
         // TODO: Context上の関数に型境界情報を追加
@@ -272,7 +465,10 @@ impl ASTLowerer {
     ) -> LowerResult<hir::Def> {
         log!("[DEBUG] entered {}({sig})", fn_name!());
         self.ctx.preregister(body.block.ref_payload())?;
-        let block = self.lower_block(body.block)?;
+        let mut block = self.lower_block(body.block)?;
+        if sig.t_spec.is_some() {
+            self.coerce_literal_body(&sig, &mut block)?;
+        }
         let found_body_t = block.ref_t();
         let opt_expect_body_t = self
             .ctx
@@ -315,6 +511,88 @@ impl ASTLowerer {
         Ok(hir::Def::new(hir::Signature::Var(sig), body))
     }
 
+    /// `x += v`: `x` must be defined in the current scope and keeps its type
+    // an undefined or mismatched target is reported as an unboxed `LowerError`, like in `lower_var_def`
+    #[allow(clippy::result_large_err)]
+    fn lower_aug_assign(
+        &mut self,
+        sig: ast::VarSignature,
+        body: ast::DefBody,
+    ) -> LowerResult<hir::Def> {
+        log!("[DEBUG] entered {}({sig})", fn_name!());
+        let name = sig.inspect().unwrap();
+        let target_t = match self.ctx.outer.as_ref().unwrap().get_current_scope_var(name) {
+            Some(vi) => vi.t.clone(),
+            None => {
+                return Err(LowerError::no_var_error(
+                    sig.loc(),
+                    self.ctx.caused_by(),
+                    name,
+                    None,
+                ))
+            }
+        };
+        // `x = 1` is `{1}`, but `x += 1` can be any `Nat`
+        let target_t = match target_t {
+            Type::Refinement(refine) => *refine.t,
+            other => other,
+        };
+        // `/=` always makes a float (same as Python)
+        if body.op.is(SlashEqual) && !self.ctx.rec_full_subtype_of(&Type::Float, &target_t) {
+            return Err(LowerError::type_mismatch_error(
+                sig.loc(),
+                self.ctx.caused_by(),
+                name,
+                &Type::Float,
+                &target_t,
+            ));
+        }
+        let block = self.lower_block(body.block)?;
+        // TODO: use the type of the operator once binary operations are type-checked
+        if !self.ctx.rec_full_subtype_of(block.ref_t(), &target_t) {
+            return Err(LowerError::type_mismatch_error(
+                block.loc(),
+                self.ctx.caused_by(),
+                name,
+                &target_t,
+                block.ref_t(),
+            ));
+        }
+        let sig = hir::VarSignature::new(sig.pat, target_t);
+        let body = hir::DefBody::new(body.op, block, body.id);
+        Ok(hir::Def::new(hir::Signature::Var(sig), body))
+    }
+
+    /// `x: Float = 0`のように数値リテラルに型指定がある場合、指定された型の定数に変換する
+    // a literal that does not fit the annotation is reported as an unboxed `LowerError`, like in the other `lower_*`
+    #[allow(clippy::result_large_err)]
+    fn coerce_literal_body(
+        &self,
+        sig: &ast::VarSignature,
+        block: &mut hir::Block,
+    ) -> LowerResult<()> {
+        let spec_t = self.ctx.outer.as_ref().unwrap().instantiate_var_sig_t(
+            sig,
+            None,
+            RegistrationMode::Normal,
+        )?;
+        if !matches!(spec_t, Type::Int | Type::Nat | Type::Float) || block.len() != 1 {
+            return Ok(());
+        }
+        if let Some(hir::Expr::Lit(lit)) = block.ref_mut_payload().first_mut() {
+            if !lit.coerce(&spec_t) {
+                return Err(LowerError::type_mismatch_error(
+                    lit.loc(),
+                    self.ctx.caused_by(),
+                    sig.inspect().map(|s| &s[..]).unwrap_or("_"),
+                    &spec_t,
+                    lit.ref_t(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
     // NOTE: 呼ばれている間はinner scopeなので注意
     fn lower_subr_def(
         &mut self,
@@ -351,11 +629,28 @@ impl ASTLowerer {
             .as_mut()
             .unwrap()
             .assign_subr(&sig, id, found_body_t)?;
-        let sig = hir::SubrSignature::new(sig.name, sig.params, t);
+        let mut decorators = Vec::with_capacity(sig.decorators.len());
+        for deco in sig.decorators.into_iter() {
+            decorators.push(self.lower_expr(deco.into_expr(), true)?);
+        }
+        let sig = hir::SubrSignature::new(decorators, sig.name, sig.params, t);
         let body = hir::DefBody::new(body.op, block, body.id);
         Ok(hir::Def::new(hir::Signature::Subr(sig), body))
     }
 
+    /// the name is defined in the current scope (same as `:=` of Python)
+    // passes on the errors of `lower_expr` and `assign_var` unboxed, like the other `lower_*`
+    #[allow(clippy::result_large_err)]
+    fn lower_named_expr(&mut self, named: ast::NamedExpr) -> LowerResult<hir::NamedExpr> {
+        log!("[DEBUG] entered {}({named})", fn_name!());
+        let value = self.lower_expr(*named.value, true)?;
+        let sig = ast::VarSignature::new(ast::VarPattern::VarName(named.name.clone()), None);
+        self.ctx.assign_var(&sig, named.id, value.ref_t())?;
+        let __name__ = self.ctx.get_local_uniq_obj_name(named.name.token());
+        let name = hir::Local::new(named.name.into_token(), __name__, value.t());
+        Ok(hir::NamedExpr::new(name, value))
+    }
+
     // Call.obj == Accessor cannot be type inferred by itself (it can only be inferred with arguments)
     // so turn off type checking (check=false)
     fn lower_expr(&mut self, expr: ast::Expr, check: bool) -> LowerResult<hir::Expr> {
@@ -363,12 +658,24 @@ impl ASTLowerer {
         match expr {
             ast::Expr::Lit(lit) => Ok(hir::Expr::Lit(hir::Literal::from(lit.token))),
             ast::Expr::Array(arr) => Ok(hir::Expr::Array(self.lower_array(arr, check)?)),
+            ast::Expr::ArrayComprehension(comp) => Ok(hir::Expr::ArrayComprehension(
+                self.lower_array_comprehension(comp)?,
+            )),
+            ast::Expr::Tuple(tuple) => Ok(hir::Expr::Tuple(self.lower_tuple(tuple, check)?)),
+            ast::Expr::Dict(dict) => Ok(hir::Expr::Dict(self.lower_dict(dict, check)?)),
+            ast::Expr::Set(set) => Ok(hir::Expr::Set(self.lower_set(set, check)?)),
+            ast::Expr::Record(rec) => Ok(hir::Expr::Record(self.lower_record(rec)?)),
             ast::Expr::Accessor(acc) => Ok(hir::Expr::Accessor(self.lower_acc(acc, check)?)),
             ast::Expr::BinOp(bin) => Ok(hir::Expr::BinOp(self.lower_bin(bin)?)),
             ast::Expr::UnaryOp(unary) => Ok(hir::Expr::UnaryOp(self.lower_unary(unary)?)),
             ast::Expr::Call(call) => Ok(hir::Expr::Call(self.lower_call(call)?)),
             ast::Expr::Lambda(lambda) => Ok(hir::Expr::Lambda(self.lower_lambda(lambda)?)),
             ast::Expr::Def(def) => Ok(hir::Expr::Def(self.lower_def(def)?)),
+            ast::Expr::NamedExpr(named) => Ok(hir::Expr::NamedExpr(self.lower_named_expr(named)?)),
+            ast::Expr::StrInterp(interp) => {
+                Ok(hir::Expr::StrInterp(self.lower_str_interp(interp)?))
+            }
+            ast::Expr::Slice(slice) => Ok(hir::Expr::Slice(self.lower_slice(slice)?)),
             other => todo!("{other}"),
         }
     }
@@ -15,7 +15,7 @@ use erg_parser::ParserRunner;
 
 fn main() {
     let cfg = ErgConfig::parse();
-    match cfg.mode {
+    match cfg.mode() {
         "lex" => {
             LexerRunner::run(cfg);
         }
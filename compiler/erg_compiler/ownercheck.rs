@@ -8,7 +8,7 @@ use erg_common::ty::{ArgsOwnership, Ownership};
 use erg_common::Str;
 
 use crate::error::{OwnershipError, OwnershipErrors, OwnershipResult};
-use crate::hir::{Accessor, Block, Def, Expr, Signature, HIR};
+use crate::hir::{Accessor, Block, Def, Expr, Signature, StrSegment, HIR};
 use crate::varinfo::Visibility;
 use Visibility::*;
 
@@ -126,6 +126,11 @@ impl OwnershipChecker {
                     todo!("ownership checking {a}")
                 }
             }
+            // `obj[index]` only borrows `obj`
+            Expr::Accessor(Accessor::Subscr(subscr)) => {
+                self.check_expr(&subscr.obj, Ownership::Ref);
+                self.check_expr(&subscr.index, Ownership::Ref);
+            }
             Expr::Accessor(_a) => todo!(),
             // TODO: referenced
             Expr::Call(call) => {
@@ -182,12 +187,50 @@ impl OwnershipChecker {
                     self.check_expr(&a.expr, ownership);
                 }
             }
-            Expr::Dict(dict) => {
-                for a in dict.attrs.kw_args().iter() {
-                    // self.check_expr(&a.key);
+            Expr::ArrayComprehension(comp) => {
+                self.check_expr(&comp.iter, Ownership::Ref);
+                for guard in comp.guards.iter() {
+                    self.check_expr(guard, Ownership::Ref);
+                }
+                self.check_expr(&comp.elem, ownership);
+            }
+            Expr::Tuple(tuple) => {
+                for a in tuple.elems.pos_args().iter() {
                     self.check_expr(&a.expr, ownership);
                 }
             }
+            Expr::Set(set) => {
+                for a in set.elems.pos_args().iter() {
+                    self.check_expr(&a.expr, ownership);
+                }
+            }
+            Expr::Dict(dict) => {
+                for kv in dict.kvs.iter() {
+                    self.check_expr(&kv.key, ownership);
+                    self.check_expr(&kv.value, ownership);
+                }
+            }
+            Expr::Record(record) => {
+                for attr in record.attrs.iter() {
+                    self.check_expr(&attr.value, ownership);
+                }
+            }
+            // formatting only borrows the embedded objects
+            Expr::StrInterp(interp) => {
+                for seg in interp.segments.iter() {
+                    if let StrSegment::Expr { expr, .. } = seg {
+                        self.check_expr(expr, Ownership::Ref);
+                    }
+                }
+            }
+            Expr::Slice(slice) => {
+                for bound in [&slice.start, &slice.stop, &slice.step]
+                    .into_iter()
+                    .flatten()
+                {
+                    self.check_expr(bound, Ownership::Ref);
+                }
+            }
             // TODO: capturing
             Expr::Lambda(lambda) => {
                 let name_and_vis = (Str::from(format!("<lambda_{}>", lambda.id)), Private);
@@ -0,0 +1,3324 @@
+extern crate erg_common;
+extern crate erg_compiler;
+extern crate erg_parser;
+
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process::Command;
+
+    use erg_common::codeobj::{compute_stacksize, compute_stacksize_with, CodeObjError};
+    use erg_common::codeobj::{CodeObj, CodeObjFlags};
+    use erg_common::config::{ErgConfig, Input};
+    use erg_common::opcode::{CustomOpcode, Opcode, OpcodeTable};
+    use erg_common::python_util::{find_python_executable_for, PythonVersion};
+    use erg_common::traits::Stream;
+    use erg_common::ty::{Type, TypePair};
+    use erg_common::value::ValueObj;
+    use erg_common::{enum_unwrap, Str};
+
+    use erg_parser::ast::{
+        ConstExpr, DefId, ParamArrayPattern, ParamPattern, ParamRecordPattern, ParamSignature,
+        ParamTuplePattern, Params, VarName, VarPattern, VarTuplePattern, Vars,
+    };
+    use erg_parser::token::{Token, TokenKind};
+
+    use erg_compiler::codegen::CodeGenerator;
+    use erg_compiler::hir::{
+        Accessor, Args, Array, ArrayComprehension, AttrAssign, Attribute, BinOp, Block, Call, Def,
+        DefBody, Dict, Expr, FormatConversion, KeyValue, KwArg, Lambda, Literal, Local, Module,
+        PosArg, Record, RecordAttr, Set, Signature, Slice, StrInterp, StrSegment, SubrSignature,
+        SubscrAssign, Subscript, Tuple, TypeCast, VarSignature, Yield, HIR,
+    };
+
+    /// `<obj>.<method>()` where `obj: <class>`
+    fn method_call_names(class: &'static str, method: &'static str) -> Vec<Str> {
+        let obj = Local::new(
+            Token::new(TokenKind::Symbol, "obj", 1, 0),
+            None,
+            Type::mono(class),
+        );
+        let attr = Attribute::new(
+            Expr::Accessor(Accessor::Local(obj)),
+            Token::new(TokenKind::Symbol, method, 1, 4),
+            Type::Obj,
+        );
+        let call = Call::new(
+            Expr::Accessor(Accessor::Attr(attr)),
+            Args::new(vec![], vec![], None),
+            Type::NoneType,
+        );
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Call(call)]));
+        let code = CodeGenerator::new(ErgConfig::default()).codegen(hir);
+        code.names
+    }
+
+    #[test]
+    fn test_array_mut_methods() {
+        let names = method_call_names("Array!", "push!");
+        assert!(names.contains(&Str::ever("append")));
+        let names = method_call_names("Array!", "sort!");
+        assert!(names.contains(&Str::ever("sort")));
+        assert!(!names.iter().any(|name| name.contains("__erg_proc__")));
+    }
+
+    #[test]
+    fn test_str_mut_methods() {
+        let names = method_call_names("Str!", "replace!");
+        assert!(names.contains(&Str::ever("replace")));
+        let names = method_call_names("Str", "upper!");
+        assert!(names.contains(&Str::ever("upper")));
+        // unknown procedural methods are still escaped
+        let names = method_call_names("Str!", "foo!");
+        assert!(names.contains(&Str::ever("foo__erg_proc__")));
+        // the procedural methods of user-defined classes are kept as they are
+        let names = method_call_names("Point", "update!");
+        assert!(names.contains(&Str::ever("update__erg_proc__")));
+        assert!(!names.contains(&Str::ever("update")));
+    }
+
+    #[test]
+    fn test_yield_makes_generator() {
+        // `() -> yield 1`
+        let yield_ = Yield::new(
+            Token::new(TokenKind::Symbol, "yield", 1, 6),
+            Expr::Lit(Literal::new(ValueObj::Nat(1), 1, 12)),
+            Type::NoneType,
+        );
+        let lambda = Lambda::new(
+            0,
+            Params::new(
+                vec![],
+                vec![],
+                Some((
+                    Token::new(TokenKind::LParen, "(", 1, 0),
+                    Token::new(TokenKind::RParen, ")", 1, 1),
+                )),
+            ),
+            Token::new(TokenKind::FuncArrow, "->", 1, 3),
+            Block::new(vec![Expr::Yield(yield_)]),
+            Type::Obj,
+        );
+        let hir = HIR::new(
+            Str::ever("<module>"),
+            Module::new(vec![Expr::Lambda(lambda)]),
+        );
+        let code = CodeGenerator::new(ErgConfig::default()).codegen(hir);
+        assert_eq!(code.flags & CodeObjFlags::Generator as u32, 0);
+        let gen = code
+            .consts
+            .iter()
+            .find_map(|c| match c {
+                ValueObj::Code(code) => Some(code),
+                _ => None,
+            })
+            .unwrap();
+        assert_ne!(gen.flags & CodeObjFlags::Generator as u32, 0);
+        assert!(gen
+            .code
+            .chunks(2)
+            .any(|instr| instr[0] == Opcode::YIELD_VALUE as u8));
+    }
+
+    /// `g() = <body>`
+    fn nullary_def(name: &'static str, body: Vec<Expr>) -> Expr {
+        let sig = SubrSignature::new(
+            vec![],
+            VarName::new(Token::new(TokenKind::Symbol, name, 1, 0)),
+            Params::new(
+                vec![],
+                vec![],
+                Some((
+                    Token::new(TokenKind::LParen, "(", 1, 1),
+                    Token::new(TokenKind::RParen, ")", 1, 2),
+                )),
+            ),
+            Type::Obj,
+        );
+        let body = DefBody::new(
+            Token::new(TokenKind::Equal, "=", 1, 4),
+            Block::new(body),
+            DefId(0),
+        );
+        Expr::Def(Def::new(Signature::Subr(sig), body))
+    }
+
+    #[test]
+    fn test_generator_def() {
+        let yield_ = |n: u64, col: usize| {
+            Expr::Yield(Yield::new(
+                Token::new(TokenKind::Symbol, "yield", 1, col),
+                nat(n, col + 6),
+                Type::NoneType,
+            ))
+        };
+        // `g() = yield 1; yield 2`
+        let gen = module_code(nullary_def("g", vec![yield_(1, 6), yield_(2, 15)]));
+        // `g() = 1; 2`
+        let func = module_code(nullary_def("g", vec![nat(1, 6), nat(2, 9)]));
+        // only the flags of the code object differ
+        assert_eq!(gen.code, func.code);
+        let make_function = gen
+            .code
+            .chunks(2)
+            .find(|instr| instr[0] == Opcode::MAKE_FUNCTION as u8)
+            .unwrap();
+        assert_eq!(make_function[1], 0);
+        let g = inner_code(&gen);
+        assert_ne!(g.flags & CodeObjFlags::Generator as u32, 0);
+        assert_eq!(inner_code(&func).flags & CodeObjFlags::Generator as u32, 0);
+        let ops = g
+            .code
+            .chunks(2)
+            .map(|instr| Opcode::from(instr[0]))
+            .collect::<Vec<_>>();
+        // the value sent to the first `yield` is discarded
+        assert_eq!(
+            ops,
+            [
+                Opcode::LOAD_CONST,
+                Opcode::YIELD_VALUE,
+                Opcode::POP_TOP,
+                Opcode::LOAD_CONST,
+                Opcode::YIELD_VALUE,
+                Opcode::RETURN_VALUE,
+            ]
+        );
+        assert_eq!(compute_stacksize(&g.code, &g.consts), Ok(g.stacksize));
+    }
+
+    fn local(name: &'static str, col: usize) -> Expr {
+        let name = Token::new(TokenKind::Symbol, name, 1, col);
+        Expr::Accessor(Accessor::Local(Local::new(name, None, Type::Obj)))
+    }
+
+    /// `match x, ({a, b}) -> a`
+    fn record_match_disassembly(python_ver: Option<u32>) -> String {
+        let param = |name: &'static str, col: usize| {
+            let name = VarName::new(Token::new(TokenKind::Symbol, name, 1, col));
+            ParamSignature::new(ParamPattern::VarName(name), None, None)
+        };
+        let pat = ParamRecordPattern::new(
+            Token::new(TokenKind::LBrace, "{", 1, 10),
+            Params::new(vec![param("a", 11), param("b", 14)], vec![], None),
+            Token::new(TokenKind::RBrace, "}", 1, 15),
+        );
+        let params = Params::new(
+            vec![ParamSignature::new(ParamPattern::Record(pat), None, None)],
+            vec![],
+            Some((
+                Token::new(TokenKind::LParen, "(", 1, 9),
+                Token::new(TokenKind::RParen, ")", 1, 16),
+            )),
+        );
+        let lambda = Lambda::new(
+            0,
+            params,
+            Token::new(TokenKind::FuncArrow, "->", 1, 18),
+            Block::new(vec![local("a", 21)]),
+            Type::Obj,
+        );
+        let args = Args::new(
+            vec![
+                PosArg::new(local("x", 6)),
+                PosArg::new(Expr::Lambda(lambda)),
+            ],
+            vec![],
+            None,
+        );
+        let call = Call::new(local("match", 0), args, Type::Obj);
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Call(call)]));
+        let cfg = ErgConfig {
+            python_ver,
+            ..ErgConfig::default()
+        };
+        CodeGenerator::new(cfg).codegen(hir).code_info()
+    }
+
+    #[test]
+    fn test_record_pattern() {
+        // 3.11
+        let dis = record_match_disassembly(Some(3495));
+        let lines = dis.lines().collect::<Vec<_>>();
+        let match_class = lines
+            .iter()
+            .position(|l| l.contains("MATCH_CLASS"))
+            .unwrap();
+        assert!(lines[match_class - 1].contains("[\"a\", \"b\"]"));
+        let pop_jump = lines
+            .iter()
+            .position(|l| l.contains("POP_JUMP_IF_FALSE"))
+            .unwrap();
+        assert!(match_class < pop_jump);
+        assert!(
+            lines[pop_jump + 1].contains("UNPACK_SEQUENCE") && lines[pop_jump + 1].ends_with('2')
+        );
+        assert!(lines[pop_jump + 2].contains("STORE_NAME") && lines[pop_jump + 2].contains("(a)"));
+        assert!(lines[pop_jump + 3].contains("STORE_NAME") && lines[pop_jump + 3].contains("(b)"));
+        // a non-match falls through past the body
+        let target = lines[pop_jump].rsplit("to ").next().unwrap();
+        let target = target.trim_end_matches(')').parse::<usize>().unwrap();
+        let body = lines
+            .iter()
+            .rposition(|l| l.contains("LOAD_NAME") && l.contains("(a)"))
+            .unwrap();
+        let body_offset = lines[body].split_whitespace().next().unwrap();
+        assert!(target > body_offset.parse::<usize>().unwrap());
+        // 3.9: the fields are loaded directly
+        let dis = record_match_disassembly(Some(3425));
+        assert!(!dis.contains("MATCH_CLASS"));
+        let loads = dis
+            .lines()
+            .filter(|l| l.contains("LOAD_ATTR"))
+            .collect::<Vec<_>>();
+        assert_eq!(loads.len(), 2);
+        assert!(loads[0].contains("(b)") && loads[1].contains("(a)"));
+    }
+
+    #[test]
+    fn test_record_pattern_stacksize() {
+        // `match x, ({a, b}) -> a, y -> y` (3.10)
+        let name = |name: &'static str, col: usize| {
+            VarName::new(Token::new(TokenKind::Symbol, name, 1, col))
+        };
+        let param = |n: &'static str, col: usize| {
+            ParamSignature::new(ParamPattern::VarName(name(n, col)), None, None)
+        };
+        let pat = ParamRecordPattern::new(
+            Token::new(TokenKind::LBrace, "{", 1, 10),
+            Params::new(vec![param("a", 11), param("b", 14)], vec![], None),
+            Token::new(TokenKind::RBrace, "}", 1, 15),
+        );
+        let args = Args::new(
+            vec![
+                PosArg::new(local("x", 6)),
+                case(ParamPattern::Record(pat), local("a", 21)),
+                case(ParamPattern::VarName(name("y", 24)), local("y", 29)),
+            ],
+            vec![],
+            None,
+        );
+        let call = Call::new(local("match", 0), args, Type::Obj);
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Call(call)]));
+        let cfg = ErgConfig {
+            python_ver: Some(3439),
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        assert!(code
+            .code
+            .chunks(2)
+            .any(|instr| instr[0] == Opcode::MATCH_CLASS as u8));
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+    }
+
+    #[test]
+    fn test_invalid_stack_depth_is_reported() {
+        // `match x, ({a, b}) -> a` (3.10): the subject is left in place of `a` if not matched,
+        // so the stack depths of the two paths differ
+        let name = |name: &'static str, col: usize| {
+            VarName::new(Token::new(TokenKind::Symbol, name, 1, col))
+        };
+        let param = |n: &'static str, col: usize| {
+            ParamSignature::new(ParamPattern::VarName(name(n, col)), None, None)
+        };
+        let pat = ParamRecordPattern::new(
+            Token::new(TokenKind::LBrace, "{", 1, 10),
+            Params::new(vec![param("a", 11), param("b", 14)], vec![], None),
+            Token::new(TokenKind::RBrace, "}", 1, 15),
+        );
+        let args = Args::new(
+            vec![
+                PosArg::new(local("x", 6)),
+                case(ParamPattern::Record(pat), local("a", 21)),
+            ],
+            vec![],
+            None,
+        );
+        let call = Call::new(local("match", 0), args, Type::Obj);
+        let print = Call::new(
+            local("print!", 0),
+            Args::new(vec![PosArg::new(Expr::Call(call))], vec![], None),
+            Type::NoneType,
+        );
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Call(print)]));
+        let cfg = ErgConfig {
+            python_ver: Some(3439),
+            ..ErgConfig::default()
+        };
+        let mut gen = CodeGenerator::new(cfg);
+        gen.codegen(hir);
+        let errs = gen.errs().iter().collect::<Vec<_>>();
+        assert_eq!(errs.len(), 1);
+        assert!(errs[0].core.desc.contains("inconsistent stack depth"));
+    }
+
+    #[test]
+    fn test_record_pattern_defaults() {
+        // `match x, ({a, b |= 1}) -> a`
+        let name = |name: &'static str, col: usize| {
+            VarName::new(Token::new(TokenKind::Symbol, name, 1, col))
+        };
+        let default = ConstExpr::Lit(erg_parser::ast::Literal::from(Token::new(
+            TokenKind::NatLit,
+            "1",
+            1,
+            19,
+        )));
+        let pat = ParamRecordPattern::new(
+            Token::new(TokenKind::LBrace, "{", 1, 10),
+            Params::new(
+                vec![ParamSignature::new(
+                    ParamPattern::VarName(name("a", 11)),
+                    None,
+                    None,
+                )],
+                vec![ParamSignature::new(
+                    ParamPattern::VarName(name("b", 14)),
+                    None,
+                    Some(default),
+                )],
+                None,
+            ),
+            Token::new(TokenKind::RBrace, "}", 1, 20),
+        );
+        let args = Args::new(
+            vec![
+                PosArg::new(local("x", 6)),
+                case(ParamPattern::Record(pat), local("a", 25)),
+            ],
+            vec![],
+            None,
+        );
+        let call = Call::new(local("match", 0), args, Type::Obj);
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Call(call)]));
+        // reported as a compile error instead of panicking, before any field is loaded
+        let code = CodeGenerator::new(ErgConfig::default()).codegen(hir);
+        assert!(!code
+            .code
+            .chunks(2)
+            .any(|instr| instr[0] == Opcode::LOAD_ATTR as u8));
+    }
+
+    #[test]
+    fn test_tuple_pattern() {
+        // `match x, ((a, b)) -> a, y -> y`
+        let name = |name: &'static str, col: usize| {
+            VarName::new(Token::new(TokenKind::Symbol, name, 1, col))
+        };
+        let param = |n: &'static str, col: usize| {
+            ParamSignature::new(ParamPattern::VarName(name(n, col)), None, None)
+        };
+        let pat = ParamTuplePattern::new(
+            Token::new(TokenKind::LParen, "(", 1, 10),
+            Params::new(vec![param("a", 11), param("b", 14)], vec![], None),
+            Token::new(TokenKind::RParen, ")", 1, 15),
+        );
+        let args = Args::new(
+            vec![
+                PosArg::new(local("x", 6)),
+                case(ParamPattern::Tuple(pat), local("a", 21)),
+                case(ParamPattern::VarName(name("y", 24)), local("y", 29)),
+            ],
+            vec![],
+            None,
+        );
+        let code = module_code_py310(Expr::Call(Call::new(local("match", 0), args, Type::Obj)));
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        let instrs = decode(&code)
+            .into_iter()
+            .map(|(_, op, arg)| (op, arg))
+            .collect::<Vec<_>>();
+        // same as `case (a, b)` in Python
+        assert_eq!(
+            instrs,
+            [
+                (Opcode::LOAD_NAME, 0),
+                (Opcode::DUP_TOP, 0),
+                (Opcode::MATCH_SEQUENCE, 0),
+                (Opcode::POP_JUMP_IF_FALSE, 14), // (to 28)
+                (Opcode::GET_LEN, 0),
+                (Opcode::LOAD_CONST, 0),
+                (Opcode::COMPARE_OP, 2),
+                (Opcode::POP_JUMP_IF_FALSE, 14),
+                (Opcode::UNPACK_SEQUENCE, 2),
+                (Opcode::STORE_NAME, 1),
+                (Opcode::STORE_NAME, 2),
+                (Opcode::POP_TOP, 0),
+                (Opcode::LOAD_NAME, 1),
+                (Opcode::JUMP_ABSOLUTE, 17), // to the end
+                // the copy of `x` is left if not matched
+                (Opcode::POP_TOP, 0),
+                (Opcode::STORE_NAME, 3),
+                (Opcode::LOAD_NAME, 3),
+                (Opcode::RETURN_VALUE, 0),
+            ]
+        );
+        assert_eq!(code.consts[0], ValueObj::Nat(2));
+    }
+
+    /// `match <subject>, ([<elems>]) -> (a, b, c), y -> y`
+    /// (`elems`: the name, the column and the default value of each element)
+    fn pattern_defaults_match(
+        subject: Expr,
+        elems: Vec<(&'static str, usize, Option<&'static str>)>,
+    ) -> Expr {
+        let name = |name: &'static str, col: usize| {
+            VarName::new(Token::new(TokenKind::Symbol, name, 1, col))
+        };
+        let (mut non_defaults, mut defaults) = (vec![], vec![]);
+        for (n, col, default) in elems {
+            let default = default.map(|lit| {
+                let token = Token::new(TokenKind::NatLit, lit, 1, col + 5);
+                ConstExpr::Lit(erg_parser::ast::Literal::from(token))
+            });
+            let has_default = default.is_some();
+            let param = ParamSignature::new(ParamPattern::VarName(name(n, col)), None, default);
+            if has_default {
+                defaults.push(param);
+            } else {
+                non_defaults.push(param);
+            }
+        }
+        let pat = ParamArrayPattern::new(
+            Token::new(TokenKind::LSqBr, "[", 1, 10),
+            Params::new(non_defaults, defaults, None),
+            Token::new(TokenKind::RSqBr, "]", 1, 31),
+        );
+        let body = tuple(vec![local("a", 37), local("b", 40), local("c", 43)]);
+        let args = Args::new(
+            vec![
+                PosArg::new(subject),
+                case(ParamPattern::Array(pat), body),
+                case(ParamPattern::VarName(name("y", 47)), local("y", 52)),
+            ],
+            vec![],
+            None,
+        );
+        Expr::Call(Call::new(local("match", 0), args, Type::Obj))
+    }
+
+    #[test]
+    fn test_pattern_defaults() {
+        // `[a, b |= 10, c |= 20]`
+        let code = module_code_py310(pattern_defaults_match(
+            local("x", 6),
+            vec![
+                ("a", 11, None),
+                ("b", 14, Some("10")),
+                ("c", 23, Some("20")),
+            ],
+        ));
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        let instrs = decode(&code)
+            .into_iter()
+            .map(|(_, op, arg)| (op, arg))
+            .collect::<Vec<_>>();
+        // 1 <= len(x) <= 3
+        let compares = instrs
+            .iter()
+            .filter(|(op, _)| *op == Opcode::COMPARE_OP)
+            .map(|(_, arg)| *arg)
+            .collect::<Vec<_>>();
+        assert_eq!(compares, [5, 1]);
+        // the missing defaults are taken from the end of `[10, 20]`
+        let defaults = code
+            .consts
+            .iter()
+            .position(|c| c == &ValueObj::from(vec![ValueObj::Nat(10), ValueObj::Nat(20)]))
+            .unwrap();
+        let unpack = instrs
+            .iter()
+            .position(|(op, _)| *op == Opcode::UNPACK_SEQUENCE)
+            .unwrap();
+        assert_eq!(
+            &instrs[unpack - 12..=unpack],
+            [
+                (Opcode::BUILD_LIST, 0),
+                (Opcode::ROT_TWO, 0),
+                (Opcode::LIST_EXTEND, 1),
+                (Opcode::GET_LEN, 0),
+                (Opcode::LOAD_CONST, 0), // 1 (the number of the required elements)
+                (Opcode::BINARY_SUBTRACT, 0),
+                (Opcode::LOAD_CONST, defaults),
+                (Opcode::ROT_TWO, 0),
+                (Opcode::LOAD_CONST, 3),
+                (Opcode::BUILD_SLICE, 2),
+                (Opcode::BINARY_SUBSCR, 0),
+                (Opcode::LIST_EXTEND, 1),
+                (Opcode::UNPACK_SEQUENCE, 3),
+            ]
+        );
+    }
+
+    /// runs `print(<expr>)` compiled for Python 3.10 and returns the output
+    /// (`None` if Python 3.10 is not installed)
+    fn run_py310(name: &str, expr: Expr) -> Option<String> {
+        let python = find_python_executable_for(PythonVersion::new(3, 10, 0))?;
+        let args = Args::new(vec![PosArg::new(expr)], vec![], None);
+        let print = Expr::Call(Call::new(local("print", 0), args, Type::Obj));
+        let dir = env::temp_dir().join(format!("erg_test_{name}_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pyc = dir.join(format!("{name}.pyc"));
+        module_code_py310(print)
+            .dump_as_pyc(&pyc, Some(3439))
+            .unwrap();
+        let out = Command::new(python).arg(&pyc).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        Some(String::from_utf8(out.stdout).unwrap())
+    }
+
+    /// `(<elems>)`
+    fn nat_tuple(elems: &[u64]) -> Expr {
+        tuple(elems.iter().map(|n| nat(*n, 7)).collect())
+    }
+
+    #[test]
+    fn test_pattern_defaults_at_the_end() {
+        // `[a, b |= 10, c |= 20]`
+        let elems = || {
+            vec![
+                ("a", 11, None),
+                ("b", 14, Some("10")),
+                ("c", 23, Some("20")),
+            ]
+        };
+        for (subject, expected) in [
+            (&[1][..], "(1, 10, 20)\n"),
+            (&[1, 2], "(1, 2, 20)\n"),
+            (&[1, 2, 3], "(1, 2, 3)\n"),
+            (&[], "()\n"),
+        ] {
+            let expr = pattern_defaults_match(nat_tuple(subject), elems());
+            let Some(out) = run_py310("pattern_defaults_at_the_end", expr) else {
+                return;
+            };
+            assert_eq!(out, expected, "{subject:?}");
+        }
+    }
+
+    #[test]
+    fn test_pattern_default_in_the_middle() {
+        // `[a, b |= 10, c]`
+        let elems = || vec![("a", 11, None), ("b", 14, Some("10")), ("c", 23, None)];
+        let code = module_code_py310(pattern_defaults_match(local("x", 6), elems()));
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        let instrs = decode(&code)
+            .into_iter()
+            .map(|(_, op, arg)| (op, arg))
+            .collect::<Vec<_>>();
+        // 2 <= len(x) <= 3, then `len(x) == 2` selects the path that inserts the default
+        let compares = instrs
+            .iter()
+            .filter(|(op, _)| *op == Opcode::COMPARE_OP)
+            .map(|(_, arg)| *arg)
+            .collect::<Vec<_>>();
+        assert_eq!(compares, [5, 1, 2]);
+        // the elements are bound in the source order
+        let stores = instrs
+            .iter()
+            .skip_while(|(op, _)| *op != Opcode::UNPACK_SEQUENCE)
+            .take(4)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stores,
+            [
+                &(Opcode::UNPACK_SEQUENCE, 3),
+                &(Opcode::STORE_NAME, 1),
+                &(Opcode::STORE_NAME, 2),
+                &(Opcode::STORE_NAME, 3),
+            ]
+        );
+        assert_eq!(
+            &code.names[1..4],
+            [Str::ever("a"), Str::ever("b"), Str::ever("c")]
+        );
+        for (subject, expected) in [
+            (&[1, 3][..], "(1, 10, 3)\n"),
+            (&[1, 2, 3], "(1, 2, 3)\n"),
+            (&[1], "(1,)\n"),
+        ] {
+            let expr = pattern_defaults_match(nat_tuple(subject), elems());
+            let Some(out) = run_py310("pattern_default_in_the_middle", expr) else {
+                return;
+            };
+            assert_eq!(out, expected, "{subject:?}");
+        }
+        // `[a |= 1, b, c |= 3]`: the given elements fill the defaults from the left
+        let elems = || vec![("a", 11, Some("1")), ("b", 20, None), ("c", 23, Some("3"))];
+        for (subject, expected) in [
+            (&[5][..], "(1, 5, 3)\n"),
+            (&[5, 6], "(5, 6, 3)\n"),
+            (&[5, 6, 7], "(5, 6, 7)\n"),
+        ] {
+            let expr = pattern_defaults_match(nat_tuple(subject), elems());
+            let Some(out) = run_py310("pattern_defaults_around", expr) else {
+                return;
+            };
+            assert_eq!(out, expected, "{subject:?}");
+        }
+    }
+
+    #[test]
+    fn test_starred_assignment() {
+        // `a, *b, c = xs`
+        let var = |pat: fn(VarName) -> VarPattern, name: &'static str, col: usize| {
+            let name = VarName::new(Token::new(TokenKind::Symbol, name, 1, col));
+            erg_parser::ast::VarSignature::new(pat(name), None)
+        };
+        let pat = VarTuplePattern::new(
+            None,
+            Vars::new(vec![
+                var(VarPattern::VarName, "a", 0),
+                var(VarPattern::VarArgsName, "b", 4),
+                var(VarPattern::VarName, "c", 7),
+            ]),
+        );
+        let body = DefBody::new(
+            Token::new(TokenKind::Equal, "=", 1, 9),
+            Block::new(vec![local("xs", 11)]),
+            DefId(0),
+        );
+        let def = Def::new(
+            Signature::Var(VarSignature::new(VarPattern::Tuple(pat), Type::Obj)),
+            body,
+        );
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Def(def)]));
+        let code = CodeGenerator::new(ErgConfig::default()).codegen(hir);
+        let instrs = code.code.chunks(2).collect::<Vec<_>>();
+        let unpack = instrs
+            .iter()
+            .position(|instr| instr[0] == Opcode::UNPACK_EX as u8)
+            .unwrap();
+        // before = 1, after = 1 (`EXTENDED_ARG 1; UNPACK_EX 1` = `UNPACK_EX 0x0101`)
+        assert_eq!(instrs[unpack - 1], [Opcode::EXTENDED_ARG as u8, 1]);
+        assert_eq!(instrs[unpack][1], 1);
+        let stores = instrs[unpack + 1..=unpack + 3]
+            .iter()
+            .map(|instr| {
+                assert_eq!(instr[0], Opcode::STORE_NAME as u8);
+                &code.names[instr[1] as usize][..]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(stores, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_too_many_targets_before_starred() {
+        // `x0, ..., x255, *rest = xs`: `UNPACK_EX` can only encode 255 targets on each side
+        let mut elems = (0..256)
+            .map(|i| {
+                let name = VarName::new(Token::new(TokenKind::Symbol, format!("x{i}"), 1, 0));
+                erg_parser::ast::VarSignature::new(VarPattern::VarName(name), None)
+            })
+            .collect::<Vec<_>>();
+        let rest = VarName::new(Token::new(TokenKind::Symbol, "rest", 1, 0));
+        elems.push(erg_parser::ast::VarSignature::new(
+            VarPattern::VarArgsName(rest),
+            None,
+        ));
+        let pat = VarTuplePattern::new(None, Vars::new(elems));
+        let body = DefBody::new(
+            Token::new(TokenKind::Equal, "=", 1, 0),
+            Block::new(vec![local("xs", 0)]),
+            DefId(0),
+        );
+        let def = Def::new(
+            Signature::Var(VarSignature::new(VarPattern::Tuple(pat), Type::Obj)),
+            body,
+        );
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Def(def)]));
+        // reported as a compile error instead of panicking
+        let code = CodeGenerator::new(ErgConfig::default()).codegen(hir);
+        assert!(!code
+            .code
+            .chunks(2)
+            .any(|instr| instr[0] == Opcode::UNPACK_EX as u8));
+    }
+
+    /// `while! c, () => print! 1`
+    fn while_loop_code() -> erg_common::codeobj::CodeObj {
+        let print = Call::new(
+            local("print!", 16),
+            Args::new(
+                vec![PosArg::new(Expr::Lit(Literal::new(
+                    ValueObj::Nat(1),
+                    1,
+                    23,
+                )))],
+                vec![],
+                None,
+            ),
+            Type::NoneType,
+        );
+        let lambda = Lambda::new(
+            0,
+            Params::new(
+                vec![],
+                vec![],
+                Some((
+                    Token::new(TokenKind::LParen, "(", 1, 10),
+                    Token::new(TokenKind::RParen, ")", 1, 11),
+                )),
+            ),
+            Token::new(TokenKind::ProcArrow, "=>", 1, 13),
+            Block::new(vec![Expr::Call(print)]),
+            Type::Obj,
+        );
+        let args = Args::new(
+            vec![
+                PosArg::new(local("c", 7)),
+                PosArg::new(Expr::Lambda(lambda)),
+            ],
+            vec![],
+            None,
+        );
+        let call = Call::new(local("while!", 0), args, Type::NoneType);
+        module_code(Expr::Call(call))
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let code = while_loop_code();
+        let instrs = code.code.chunks(2).collect::<Vec<_>>();
+        let op = |i: usize| instrs[i][0];
+        // the condition is evaluated at the top of the loop
+        assert_eq!(op(0), Opcode::LOAD_NAME as u8);
+        assert_eq!(&code.names[instrs[0][1] as usize][..], "c");
+        assert_eq!(op(1), Opcode::POP_JUMP_IF_FALSE as u8);
+        let back_edge = instrs
+            .iter()
+            .position(|instr| instr[0] == Opcode::JUMP_ABSOLUTE as u8)
+            .unwrap();
+        assert_eq!(instrs[back_edge][1], 0);
+        // the exit jumps just past the back-edge, where `None` is loaded as the result
+        assert_eq!(instrs[1][1] as usize, (back_edge + 1) * 2);
+        assert_eq!(op(back_edge + 1), Opcode::LOAD_CONST as u8);
+        assert_eq!(
+            code.consts[instrs[back_edge + 1][1] as usize],
+            ValueObj::None
+        );
+        // the result of `print!` is popped in the body
+        assert_eq!(op(back_edge - 1), Opcode::POP_TOP as u8);
+        assert_eq!(stacksize(&code), Ok(code.stacksize));
+    }
+
+    /// `with! open!("f"), f => print! f`
+    fn with_code(python_ver: Option<u32>) -> (erg_common::codeobj::CodeObj, Vec<Str>) {
+        let open = Call::new(
+            local("open!", 6),
+            Args::new(vec![PosArg::new(str_("f", 12))], vec![], None),
+            Type::Obj,
+        );
+        let print = Call::new(
+            local("print!", 22),
+            Args::new(vec![PosArg::new(local("f", 29))], vec![], None),
+            Type::NoneType,
+        );
+        let args = Args::new(
+            vec![
+                PosArg::new(Expr::Call(open)),
+                PosArg::new(lambda_with(vec!["f"], Expr::Call(print))),
+            ],
+            vec![],
+            None,
+        );
+        let call = Call::new(local("with!", 0), args, Type::NoneType);
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Call(call)]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            python_ver,
+            ..ErgConfig::default()
+        };
+        let mut gen = CodeGenerator::new(cfg);
+        let code = gen.codegen(hir);
+        let errs = gen.errs().iter().map(|e| e.core.desc.clone()).collect();
+        (code, errs)
+    }
+
+    #[test]
+    fn test_with() {
+        let (code, errs) = with_code(Some(3439));
+        assert!(errs.is_empty(), "{errs:?}");
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1] as usize))
+            .collect::<Vec<_>>();
+        let pos = |op: Opcode| instrs.iter().position(|(o, _)| *o == op).unwrap();
+        let setup_with = pos(Opcode::SETUP_WITH);
+        let handler = pos(Opcode::WITH_EXCEPT_START);
+        // the result of `__enter__` is bound to `f`
+        assert_eq!(instrs[setup_with + 1].0, Opcode::STORE_NAME);
+        assert_eq!(&code.names[instrs[setup_with + 1].1][..], "f");
+        // SETUP_WITH jumps (relatively) to the handler
+        assert_eq!(setup_with + 1 + instrs[setup_with].1, handler);
+        // the normal exit calls `__exit__(None, None, None)` and skips the handler
+        let pop_block = pos(Opcode::POP_BLOCK);
+        assert_eq!(
+            instrs[pop_block + 1..pop_block + 7]
+                .iter()
+                .map(|(op, _)| *op)
+                .collect::<Vec<_>>(),
+            [
+                Opcode::LOAD_CONST,
+                Opcode::DUP_TOP,
+                Opcode::DUP_TOP,
+                Opcode::CALL_FUNCTION,
+                Opcode::POP_TOP,
+                Opcode::JUMP_FORWARD,
+            ]
+        );
+        assert_eq!(instrs[pop_block + 4].1, 3);
+        let jump_forward = pop_block + 6;
+        let end = jump_forward + 1 + instrs[jump_forward].1;
+        assert_eq!(instrs[end - 2].0, Opcode::POP_EXCEPT);
+        assert_eq!(instrs[end].0, Opcode::LOAD_CONST);
+        // the exception is re-raised unless `__exit__` returns true
+        assert_eq!(instrs[handler + 1].0, Opcode::POP_JUMP_IF_TRUE);
+        assert_eq!(instrs[handler + 1].1, handler + 3);
+        assert_eq!(code.code[(handler + 2) * 2..(handler + 3) * 2], [119, 1]);
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        // `RERAISE` of Python 3.9 takes no arg
+        let (code, errs) = with_code(Some(3425));
+        assert!(errs.is_empty(), "{errs:?}");
+        let handler = code
+            .code
+            .chunks(2)
+            .position(|instr| instr[0] == Opcode::WITH_EXCEPT_START as u8)
+            .unwrap();
+        assert_eq!(
+            code.code[(handler + 2) * 2..(handler + 3) * 2],
+            [Opcode::RERAISE as u8, 0]
+        );
+        assert_eq!(stacksize(&code), Ok(code.stacksize));
+    }
+
+    #[test]
+    fn test_with_python_311() {
+        // 3.11 has no `SETUP_WITH` (`BEFORE_WITH` needs an exception table)
+        let (code, errs) = with_code(Some(3495));
+        assert_eq!(errs.len(), 1, "{errs:?}");
+        assert!(errs[0].contains("`with!` for Python 3.11+"), "{}", errs[0]);
+        assert!(!code.code.contains(&(Opcode::SETUP_WITH as u8)));
+    }
+
+    /// the code of the module `expr` compiled for Python 3.9
+    /// (not for the installed Python, so that the tests do not depend on it)
+    fn module_code(expr: Expr) -> erg_common::codeobj::CodeObj {
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![expr]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            python_ver: Some(3425),
+            ..ErgConfig::default()
+        };
+        CodeGenerator::new(cfg).codegen(hir)
+    }
+
+    /// `module_code` for Python 3.10 (the sequence patterns need `MATCH_SEQUENCE` and `GET_LEN`)
+    fn module_code_py310(expr: Expr) -> CodeObj {
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![expr]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            python_ver: Some(3439),
+            ..ErgConfig::default()
+        };
+        CodeGenerator::new(cfg).codegen(hir)
+    }
+
+    /// `compute_stacksize` of the code generated for Python 3.9 (the jump args are byte offsets)
+    fn stacksize(code: &CodeObj) -> Result<u32, CodeObjError> {
+        let py39 = OpcodeTable::for_version(PythonVersion::new(3, 9, 0));
+        compute_stacksize_with(&code.code, &code.consts, &py39)
+    }
+
+    /// `x as <to>` where `x: <from>`
+    fn cast(from: Type, to: Type) -> Expr {
+        let x = Local::new(Token::new(TokenKind::Symbol, "x", 1, 0), None, from);
+        Expr::TypeCast(TypeCast::new(
+            Expr::Accessor(Accessor::Local(x)),
+            Token::new(TokenKind::Symbol, "as", 1, 2),
+            to,
+        ))
+    }
+
+    #[test]
+    fn test_type_cast() {
+        let code = module_code(cast(Type::Int, Type::Float));
+        let instrs = code.code.chunks(2).collect::<Vec<_>>();
+        assert_eq!(instrs[0][0], Opcode::LOAD_NAME as u8);
+        assert_eq!(&code.names[instrs[0][1] as usize][..], "float");
+        assert_eq!(&code.names[instrs[1][1] as usize][..], "x");
+        assert_eq!(instrs[2], [Opcode::CALL_FUNCTION as u8, 1]);
+        // widening casts emit nothing
+        let code = module_code(cast(Type::Nat, Type::Int));
+        assert_eq!(code.code, module_code(local("x", 0)).code);
+        assert!(!code.names.iter().any(|name| &name[..] == "int"));
+    }
+
+    #[test]
+    fn test_complex_literal() {
+        let complex = |re, im, col| Expr::Lit(Literal::new(ValueObj::complex(re, im), 1, col));
+        // `print(1+2j, 1+2j, -0.0+2j)`
+        let args = [
+            complex(1.0, 2.0, 6),
+            complex(1.0, 2.0, 12),
+            complex(-0.0, 2.0, 18),
+        ];
+        let args = Args::new(args.into_iter().map(PosArg::new).collect(), vec![], None);
+        let code = module_code(Expr::Call(Call::new(
+            local("print", 0),
+            args,
+            Type::NoneType,
+        )));
+        let complexes = code
+            .consts
+            .iter()
+            .filter(|c| matches!(c, ValueObj::Complex { .. }))
+            .collect::<Vec<_>>();
+        // the same constant is shared, but `-0.0` is distinguished from `1.0`
+        assert_eq!(complexes.len(), 2);
+        assert_eq!(complexes[0].to_string(), "1+2j");
+        assert_eq!(complexes[1].to_string(), "-0+2j");
+        let idx = code.consts.iter().position(|c| c == complexes[0]).unwrap();
+        assert_eq!(code.code[2..4], [Opcode::LOAD_CONST as u8, idx as u8]);
+        // the const pool survives marshaling
+        let mut bytes = code.clone().into_bytes(3425);
+        bytes.remove(0);
+        let code2 = CodeObj::from_bytes(&mut bytes, 3425).unwrap();
+        assert_eq!(code2.consts, code.consts);
+    }
+
+    #[test]
+    fn test_bytes_literal() {
+        let bytes = |lit, col| {
+            let token = Token::new(TokenKind::BytesLit, lit, 1, col);
+            Expr::Lit(Literal::from(token))
+        };
+        // `print(b"", b"\x00\n\xff")`
+        let args = [bytes("b\"\"", 6), bytes("b\"\\x00\\n\\xff\"", 10)];
+        let args = Args::new(args.into_iter().map(PosArg::new).collect(), vec![], None);
+        let code = module_code(Expr::Call(Call::new(
+            local("print", 0),
+            args,
+            Type::NoneType,
+        )));
+        let empty = ValueObj::from(Vec::<u8>::new());
+        let non_printable = ValueObj::Bytes(vec![0x00, b'\n', 0xff]);
+        assert_eq!(non_printable.to_string(), "b'\\x00\\n\\xff'");
+        let idx = code.consts.iter().position(|c| c == &empty).unwrap();
+        assert_eq!(code.code[2..4], [Opcode::LOAD_CONST as u8, idx as u8]);
+        let idx = code
+            .consts
+            .iter()
+            .position(|c| c == &non_printable)
+            .unwrap();
+        assert_eq!(code.code[4..6], [Opcode::LOAD_CONST as u8, idx as u8]);
+        // marshaled as `bytes` (TYPE_STRING)
+        assert_eq!(
+            non_printable.clone().into_bytes(3425),
+            [b's', 3, 0, 0, 0, 0x00, b'\n', 0xff]
+        );
+        let mut bytes = code.clone().into_bytes(3425);
+        bytes.remove(0);
+        let code2 = CodeObj::from_bytes(&mut bytes, 3425).unwrap();
+        assert_eq!(code2.consts, code.consts);
+    }
+
+    #[test]
+    fn test_custom_opcode() {
+        // `pair(x, y)` is compiled into `VM_PAIR 2` (pops 2, pushes 1)
+        let mut table = OpcodeTable::new();
+        table
+            .register(CustomOpcode::new(230, "VM_PAIR", 1, Some("pair")))
+            .unwrap();
+        let args = Args::new(
+            vec![PosArg::new(local("x", 5)), PosArg::new(local("y", 8))],
+            vec![],
+            None,
+        );
+        let call = Call::new(local("pair", 0), args, Type::Obj);
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Call(call)]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::with_opcode_table(cfg, table.clone()).codegen(hir);
+        assert!(!code.names.iter().any(|name| &name[..] == "pair"));
+        assert_eq!(&code.code[4..6], [230, 2]);
+        let dis = code.code_info_with(&table);
+        let line = dis.lines().find(|l| l.contains("VM_PAIR")).unwrap();
+        assert!(line.trim_start().starts_with('4') && line.ends_with('2'));
+        assert_eq!(
+            compute_stacksize_with(&code.code, &code.consts, &table),
+            Ok(code.stacksize)
+        );
+        assert_eq!(code.stacksize, 2);
+        // unknown without the table
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Err(CodeObjError::InvalidOpcode {
+                offset: 4,
+                byte: 230
+            })
+        );
+    }
+
+    #[test]
+    fn test_custom_opcode_args() {
+        let mut table = OpcodeTable::new();
+        table
+            .register(CustomOpcode::new(230, "VM_PACK", 1, Some("pack")))
+            .unwrap();
+        let codegen = |args: Args| {
+            let call = Call::new(local("pack", 0), args, Type::Obj);
+            let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Call(call)]));
+            let cfg = ErgConfig {
+                input: Input::Dummy,
+                ..ErgConfig::default()
+            };
+            let mut gen = CodeGenerator::with_opcode_table(cfg, table.clone());
+            let code = gen.codegen(hir);
+            let errs = gen
+                .errs()
+                .iter()
+                .map(|e| e.core.desc.clone())
+                .collect::<Vec<_>>();
+            (code, errs)
+        };
+        // the stack effect depends on the number of the arguments (pops 3, pushes 1)
+        let pos_args = |n: usize| {
+            (0..n)
+                .map(|i| PosArg::new(local("x", 5 + i)))
+                .collect::<Vec<_>>()
+        };
+        let (code, errs) = codegen(Args::new(pos_args(3), vec![], None));
+        assert!(errs.is_empty());
+        assert_eq!(&code.code[6..8], [230, 3]);
+        assert_eq!(
+            compute_stacksize_with(&code.code, &code.consts, &table),
+            Ok(3)
+        );
+        assert_eq!(code.stacksize, 3);
+        // the arg is a byte
+        let (_, errs) = codegen(Args::new(pos_args(255), vec![], None));
+        assert!(errs.is_empty());
+        let (_, errs) = codegen(Args::new(pos_args(256), vec![], None));
+        assert_eq!(errs.len(), 1, "{errs:?}");
+        assert!(errs[0].contains("more than 255 arguments"), "{}", errs[0]);
+        // keyword arguments cannot be passed through the stack
+        let kw = KwArg::new(Token::new(TokenKind::Symbol, "y", 1, 5), local("x", 7));
+        let (_, errs) = codegen(Args::new(pos_args(1), vec![kw], None));
+        assert_eq!(errs.len(), 1);
+        assert!(errs[0].contains("keyword arguments"), "{}", errs[0]);
+    }
+
+    #[test]
+    fn test_custom_opcode_conflict() {
+        let mut table = OpcodeTable::new();
+        let err = table
+            .register(CustomOpcode::new(
+                Opcode::LOAD_CONST as u8,
+                "VM_LOAD",
+                1,
+                None,
+            ))
+            .unwrap_err();
+        assert_eq!(err.byte, Opcode::LOAD_CONST as u8);
+        assert_eq!(&err.used_by[..], "LOAD_CONST");
+        table
+            .register(CustomOpcode::new(230, "VM_PAIR", 1, Some("pair")))
+            .unwrap();
+        let err = table
+            .register(CustomOpcode::new(230, "VM_SWAP", 0, None))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "230 is already used by VM_PAIR");
+        // the first registration is kept
+        assert_eq!(&table.get(230).unwrap().name[..], "VM_PAIR");
+    }
+
+    /// `x + y` where `x: <lhs>`, `y: <rhs>`
+    fn add_instrs(lhs: Type, rhs: Type, erg_vm: bool) -> Vec<(Opcode, u8)> {
+        let add = BinOp::new(
+            Token::new(TokenKind::Plus, "+", 1, 2),
+            local("x", 0),
+            local("y", 4),
+            Type::func2(lhs, rhs, Type::Obj),
+        );
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::BinOp(add)]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            erg_vm: Some(erg_vm),
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        code.code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1]))
+            .collect()
+    }
+
+    #[test]
+    fn test_binary_add_fast_path() {
+        let erg_vm = true;
+        let instrs = add_instrs(Type::Int, Type::Int, erg_vm);
+        assert_eq!(instrs[2], (Opcode::ERG_BINARY_ADD_INT, 0));
+        assert!(!instrs.iter().any(|(op, _)| *op == Opcode::BINARY_ADD));
+        let instrs = add_instrs(Type::IntMut, Type::Int, erg_vm);
+        assert_eq!(instrs[2].0, Opcode::ERG_BINARY_ADD_INT);
+        // falls back to the generic form unless both operands are `Int`s
+        for (lhs, rhs) in [
+            (Type::Int, Type::Nat),
+            (Type::Nat, Type::Int),
+            (Type::Int, Type::Float),
+            (Type::Obj, Type::Int),
+            (Type::Int, Type::Obj),
+            (Type::Str, Type::Str),
+        ] {
+            let pair = TypePair::new(&lhs, &rhs);
+            let instrs = add_instrs(lhs, rhs, erg_vm);
+            assert_eq!(instrs[2], (Opcode::BINARY_ADD, pair as u8));
+        }
+        // CPython cannot execute the fused instruction
+        let instrs = add_instrs(Type::Int, Type::Int, false);
+        assert_eq!(instrs[2], (Opcode::BINARY_ADD, TypePair::IntInt as u8));
+    }
+
+    #[test]
+    fn test_unknown_python_version() {
+        // an unknown magic number is not taken as the Erg VM
+        let add = BinOp::new(
+            Token::new(TokenKind::Plus, "+", 1, 2),
+            local("x", 0),
+            local("y", 4),
+            Type::func2(Type::Int, Type::Int, Type::Int),
+        );
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::BinOp(add)]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            python_ver: Some(3531),
+            ..ErgConfig::default()
+        };
+        let mut gen = CodeGenerator::new(cfg);
+        let code = gen.codegen(hir);
+        let errs = gen
+            .errs()
+            .iter()
+            .map(|e| e.core.desc.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(errs.len(), 1, "{errs:?}");
+        assert!(errs[0].contains("magic number 3531"), "{}", errs[0]);
+        assert!(!code.code.contains(&(Opcode::ERG_BINARY_ADD_INT as u8)));
+    }
+
+    #[test]
+    fn test_unsupported_opcode_is_reported_once() {
+        // `x + y; x + y`: `BINARY_ADD` is replaced by `BINARY_OP` in Python 3.11
+        let add = || {
+            Expr::BinOp(BinOp::new(
+                Token::new(TokenKind::Plus, "+", 1, 2),
+                local("x", 0),
+                local("y", 4),
+                Type::func2(Type::Int, Type::Int, Type::Int),
+            ))
+        };
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![add(), add()]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            python_ver: Some(3495),
+            ..ErgConfig::default()
+        };
+        let mut gen = CodeGenerator::new(cfg);
+        gen.codegen(hir);
+        let errs = gen
+            .errs()
+            .iter()
+            .map(|e| e.core.desc.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errs.iter()
+                .filter(|desc| desc.contains("BINARY_ADD"))
+                .count(),
+            1,
+            "{errs:?}"
+        );
+    }
+
+    /// `<lhs> ** <exp>`
+    fn pow_code(lhs: Expr, exp: u64, opt_level: u8) -> erg_common::codeobj::CodeObj {
+        let pow = BinOp::new(
+            Token::new(TokenKind::Pow, "**", 1, 2),
+            lhs,
+            nat(exp, 5),
+            Type::func2(Type::Obj, Type::Nat, Type::Obj),
+        );
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::BinOp(pow)]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            opt_level: Some(opt_level),
+            ..ErgConfig::default()
+        };
+        CodeGenerator::new(cfg).codegen(hir)
+    }
+
+    fn pow_instrs(lhs: Expr, exp: u64, opt_level: u8) -> Vec<Opcode> {
+        pow_code(lhs, exp, opt_level)
+            .code
+            .chunks(2)
+            .map(|instr| Opcode::from(instr[0]))
+            .collect()
+    }
+
+    #[test]
+    fn test_pow_unrolling() {
+        let code = pow_code(local("x", 0), 3, 1);
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1]))
+            .collect::<Vec<_>>();
+        let mul = (Opcode::BINARY_MULTIPLY, TypePair::Others as u8);
+        assert_eq!(
+            instrs[..5],
+            [
+                (Opcode::LOAD_NAME, 0),
+                (Opcode::DUP_TOP, 0),
+                (Opcode::DUP_TOP, 0),
+                mul,
+                mul,
+            ]
+        );
+        assert_eq!(code.stacksize, 3);
+        // `x ** 1` is `x` itself
+        let instrs = pow_instrs(local("x", 0), 1, 1);
+        assert_eq!(instrs[0], Opcode::LOAD_NAME);
+        assert!(!instrs.contains(&Opcode::BINARY_MULTIPLY));
+        assert!(!instrs.contains(&Opcode::BINARY_POWER));
+        // large exponents and `--opt-level 0` keep `BINARY_POWER`
+        for (exp, opt_level) in [(5, 1), (3, 0), (0, 0)] {
+            let instrs = pow_instrs(local("x", 0), exp, opt_level);
+            assert!(instrs.contains(&Opcode::BINARY_POWER), "{exp} {opt_level}");
+            assert!(!instrs.contains(&Opcode::BINARY_MULTIPLY));
+        }
+    }
+
+    #[test]
+    fn test_pow_zero() {
+        // `x ** 0` does not evaluate `x`
+        let code = pow_code(local("x", 0), 0, 1);
+        assert_eq!(Opcode::from(code.code[0]), Opcode::LOAD_CONST);
+        assert_eq!(code.consts[0], ValueObj::Nat(1));
+        assert!(code.names.is_empty());
+        let float = Expr::Lit(Literal::new(ValueObj::Float(2.0), 1, 0));
+        let code = pow_code(float, 0, 1);
+        assert_eq!(code.consts[0], ValueObj::Float(1.0));
+        // `(x + y) ** 0` may have side effects
+        let add = Expr::BinOp(BinOp::new(
+            Token::new(TokenKind::Plus, "+", 1, 2),
+            local("x", 0),
+            local("y", 4),
+            Type::func2(Type::Obj, Type::Obj, Type::Obj),
+        ));
+        let instrs = pow_instrs(add, 0, 1);
+        assert!(instrs.contains(&Opcode::BINARY_ADD));
+        assert!(instrs.contains(&Opcode::BINARY_POWER));
+    }
+
+    fn nat(n: u64, col: usize) -> Expr {
+        Expr::Lit(Literal::new(ValueObj::Nat(n), 1, col))
+    }
+
+    /// `[x + 1 | x <- [1, 2, 3], <guards>]`
+    fn array_comprehension(guards: Vec<Expr>) -> Expr {
+        let elem = BinOp::new(
+            Token::new(TokenKind::Plus, "+", 1, 3),
+            local("x", 1),
+            nat(1, 5),
+            Type::func2(Type::Nat, Type::Nat, Type::Nat),
+        );
+        let mut iter = Array::new(
+            Token::new(TokenKind::LSqBr, "[", 1, 14),
+            Token::new(TokenKind::RSqBr, "]", 1, 22),
+            0,
+            Args::new(vec![], vec![], None),
+            None,
+        );
+        for (n, col) in [(1, 15), (2, 18), (3, 21)] {
+            iter.push(nat(n, col));
+        }
+        Expr::ArrayComprehension(ArrayComprehension::new(
+            Token::new(TokenKind::LSqBr, "[", 1, 0),
+            Token::new(TokenKind::RSqBr, "]", 1, 23),
+            Expr::BinOp(elem),
+            Token::new(TokenKind::Symbol, "x", 1, 9),
+            Expr::Array(iter),
+            guards,
+        ))
+    }
+
+    #[test]
+    fn test_array_comprehension() {
+        let code = module_code(array_comprehension(vec![]));
+        let listcomp = code
+            .consts
+            .iter()
+            .find_map(|c| match c {
+                ValueObj::Code(code) => Some(code),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(&listcomp.name[..], "<listcomp>");
+        assert_eq!(listcomp.argcount, 1);
+        assert_eq!(&listcomp.varnames[..], [Str::ever(".0"), Str::ever("x")]);
+        assert_eq!(listcomp.nlocals, 2);
+        let dis = listcomp.code_info();
+        assert!(dis.contains("LIST_APPEND"));
+        assert!(dis.contains("LOAD_FAST                0 (.0)"));
+        assert!(dis.contains("STORE_FAST               1 (x)"));
+        assert_eq!(stacksize(listcomp), Ok(listcomp.stacksize));
+        // the outer scope calls the function with the iterator
+        let dis = code.code_info();
+        let lines = dis.lines().collect::<Vec<_>>();
+        let get_iter = lines.iter().position(|l| l.contains("GET_ITER")).unwrap();
+        assert!(
+            lines[get_iter + 1].contains("CALL_FUNCTION") && lines[get_iter + 1].ends_with('1')
+        );
+    }
+
+    #[test]
+    fn test_array_comprehension_guards() {
+        // `[x + 1 | x <- [1, 2, 3], x > 1, x > 2]`
+        let gt = |n: u64, col: usize| {
+            Expr::BinOp(BinOp::new(
+                Token::new(TokenKind::Gre, ">", 1, col + 2),
+                local("x", col),
+                nat(n, col + 4),
+                Type::func2(Type::Nat, Type::Nat, Type::Bool),
+            ))
+        };
+        let code = module_code(array_comprehension(vec![gt(1, 25), gt(2, 32)]));
+        let listcomp = inner_code(&code);
+        let instrs = listcomp
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1] as usize))
+            .collect::<Vec<_>>();
+        let for_iter = instrs
+            .iter()
+            .position(|(op, _)| *op == Opcode::FOR_ITER)
+            .unwrap();
+        let append = instrs
+            .iter()
+            .position(|(op, _)| *op == Opcode::LIST_APPEND)
+            .unwrap();
+        let guards = instrs
+            .iter()
+            .enumerate()
+            .filter(|(_, (op, _))| *op == Opcode::POP_JUMP_IF_FALSE)
+            .collect::<Vec<_>>();
+        assert_eq!(guards.len(), 2);
+        for (i, (_, target)) in guards {
+            // evaluated before the append, and jumps back to the loop head (skipping the append)
+            assert!(for_iter < i && i < append);
+            assert_eq!(*target, for_iter * 2);
+        }
+        assert_eq!(stacksize(listcomp), Ok(listcomp.stacksize));
+    }
+
+    fn dict(kvs: Vec<(Expr, Expr)>) -> erg_common::codeobj::CodeObj {
+        let kvs = kvs.into_iter().map(|(k, v)| KeyValue::new(k, v)).collect();
+        module_code(Expr::Dict(Dict::new(
+            Token::new(TokenKind::LBrace, "{", 1, 0),
+            Token::new(TokenKind::RBrace, "}", 1, 20),
+            0,
+            kvs,
+        )))
+    }
+
+    fn str_(s: &'static str, col: usize) -> Expr {
+        Expr::Lit(Literal::new(ValueObj::Str(Str::ever(s)), 1, col))
+    }
+
+    #[test]
+    fn test_dict_literal() {
+        // `{"a": 1, "b": 2, "a": 3}`: copied from a dict constant
+        let code = dict(vec![
+            (str_("a", 1), nat(1, 6)),
+            (str_("b", 9), nat(2, 14)),
+            (str_("a", 17), nat(3, 22)),
+        ]);
+        let instrs = code.code.chunks(2).collect::<Vec<_>>();
+        assert_eq!(instrs[0], [Opcode::BUILD_MAP as u8, 0]);
+        assert_eq!(instrs[1][0], Opcode::LOAD_CONST as u8);
+        assert_eq!(instrs[2], [Opcode::DICT_MERGE as u8, 1]);
+        let a_b = ValueObj::dict([
+            (ValueObj::Str(Str::ever("a")), ValueObj::Nat(3)),
+            (ValueObj::Str(Str::ever("b")), ValueObj::Nat(2)),
+        ]);
+        assert!(code.consts[instrs[1][1] as usize].marshal_eq(&a_b));
+        assert_eq!(code.stacksize, 2);
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        // `{"a": 1, "b": y}`: the keys are pushed as a tuple constant
+        let code = dict(vec![
+            (str_("a", 1), nat(1, 6)),
+            (str_("b", 9), local("y", 14)),
+        ]);
+        let dis = code.code_info();
+        assert!(dis.contains("BUILD_CONST_KEY_MAP      2"));
+        assert!(!dis.contains("BUILD_MAP"));
+        assert!(code
+            .consts
+            .iter()
+            .any(|c| c.marshal_eq(&ValueObj::from(vec![
+                ValueObj::Str(Str::ever("a")),
+                ValueObj::Str(Str::ever("b")),
+            ]))));
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        // `{x: 1, "b": y}`: the keys and values are pushed in order
+        let code = dict(vec![
+            (local("x", 1), nat(1, 4)),
+            (str_("b", 7), local("y", 12)),
+        ]);
+        let dis = code.code_info();
+        assert!(dis.contains("BUILD_MAP                2"));
+        let instrs = code.code.chunks(2).collect::<Vec<_>>();
+        assert_eq!(instrs[0][0], Opcode::LOAD_NAME as u8);
+        assert_eq!(instrs[1][0], Opcode::LOAD_CONST as u8);
+        assert_eq!(instrs[2][0], Opcode::LOAD_CONST as u8);
+        assert_eq!(instrs[3][0], Opcode::LOAD_NAME as u8);
+        assert_eq!(code.stacksize, 4);
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        // `{}`
+        let code = dict(vec![]);
+        let instrs = code.code.chunks(2).collect::<Vec<_>>();
+        assert_eq!(instrs[0], [Opcode::BUILD_MAP as u8, 0]);
+        assert_eq!(compute_stacksize(&code.code, &code.consts), Ok(1));
+    }
+
+    #[test]
+    fn test_const_dict_before_3_9() {
+        // `{"a": 1}`: DICT_MERGE is not available in Python 3.8
+        let kv = KeyValue::new(str_("a", 1), nat(1, 6));
+        let dict = Dict::new(
+            Token::new(TokenKind::LBrace, "{", 1, 0),
+            Token::new(TokenKind::RBrace, "}", 1, 7),
+            0,
+            vec![kv],
+        );
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Dict(dict)]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            python_ver: Some(3413),
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        assert!(code.code_info().contains("BUILD_CONST_KEY_MAP      1"));
+        assert!(!code.consts.iter().any(|c| matches!(c, ValueObj::Dict(_))));
+    }
+
+    /// `<var> = <import> "<path>"`
+    fn import_def(import: &'static str, var: &'static str, path: &'static str) -> Expr {
+        let call = Call::new(
+            local(import, 5),
+            Args::new(vec![PosArg::new(str_(path, 14))], vec![], None),
+            Type::mono("Module"),
+        );
+        let body = DefBody::new(
+            Token::new(TokenKind::Equal, "=", 1, 3),
+            Block::new(vec![Expr::Call(call)]),
+            DefId(0),
+        );
+        let name = VarName::new(Token::new(TokenKind::Symbol, var, 1, 0));
+        Expr::Def(Def::new(
+            Signature::Var(VarSignature::new(
+                VarPattern::VarName(name),
+                Type::mono("Module"),
+            )),
+            body,
+        ))
+    }
+
+    /// `<var> = pyimport "<path>"; <var>.<attr>`
+    fn pyimport_code(
+        var: &'static str,
+        path: &'static str,
+        attr: &'static str,
+    ) -> erg_common::codeobj::CodeObj {
+        let def = import_def("pyimport", var, path);
+        let attr = Attribute::new(
+            local(var, 0),
+            Token::new(TokenKind::Symbol, attr, 2, 3),
+            Type::Obj,
+        );
+        let hir = HIR::new(
+            Str::ever("<module>"),
+            Module::new(vec![def, Expr::Accessor(Accessor::Attr(attr))]),
+        );
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            ..ErgConfig::default()
+        };
+        CodeGenerator::new(cfg).codegen(hir)
+    }
+
+    #[test]
+    fn test_pyimport() {
+        let code = pyimport_code("np", "numpy", "x");
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| Opcode::from(instr[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            &instrs[..6],
+            [
+                Opcode::LOAD_CONST,
+                Opcode::LOAD_CONST,
+                Opcode::IMPORT_NAME,
+                Opcode::STORE_NAME,
+                Opcode::LOAD_NAME,
+                Opcode::LOAD_ATTR,
+            ]
+        );
+        let arg = |i: usize| code.code[i * 2 + 1] as usize;
+        assert!(code.consts[arg(0)].marshal_eq(&ValueObj::Int(0)));
+        assert!(code.consts[arg(1)].marshal_eq(&ValueObj::None));
+        assert_eq!(&code.names[arg(2)][..], "numpy");
+        assert_eq!(&code.names[arg(3)][..], "np");
+        assert_eq!(&code.names[arg(4)][..], "np");
+        assert_eq!(&code.names[arg(5)][..], "x");
+        assert!(!code.names.contains(&Str::ever("__import__")));
+        // the submodule itself is bound, not the top-level package
+        let code = pyimport_code("path", "os.path", "sep");
+        let dis = code.code_info();
+        assert!(dis.contains("IMPORT_NAME              0 (os.path)"));
+        assert!(dis.contains("IMPORT_FROM              1 (path)"));
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+    }
+
+    #[test]
+    fn test_local_import() {
+        // `f = () -> m = import "os.path"`: the module is imported when `f` is called
+        let f = VarName::new(Token::new(TokenKind::Symbol, "f", 1, 0));
+        let def = Def::new(
+            Signature::Var(VarSignature::new(VarPattern::VarName(f), Type::Obj)),
+            DefBody::new(
+                Token::new(TokenKind::Equal, "=", 1, 2),
+                Block::new(vec![lambda_with(
+                    vec![],
+                    import_def("import", "m", "os.path"),
+                )]),
+                DefId(0),
+            ),
+        );
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![Expr::Def(def)]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        assert!(!code
+            .code
+            .chunks(2)
+            .any(|instr| Opcode::from(instr[0]) == Opcode::IMPORT_NAME));
+        assert!(!code.names.contains(&Str::ever("os.path")));
+        let lambda = inner_code(&code);
+        let dis = lambda.code_info();
+        assert!(dis.contains("IMPORT_NAME              0 (os.path)"));
+        assert!(dis.contains("IMPORT_FROM              1 (path)"));
+        assert!(!lambda.names.contains(&Str::ever("__import__")));
+        assert_eq!(
+            compute_stacksize(&lambda.code, &lambda.consts),
+            Ok(lambda.stacksize)
+        );
+    }
+
+    #[test]
+    fn test_set_literal() {
+        // `{1, 2, 3}`
+        let mut set = Set::new(
+            Token::new(TokenKind::LBrace, "{", 1, 0),
+            Token::new(TokenKind::RBrace, "}", 1, 8),
+            0,
+            Args::new(vec![], vec![], None),
+        );
+        for (n, col) in [(1, 1), (2, 4), (3, 7)] {
+            set.push(nat(n, col));
+        }
+        let code = module_code(Expr::Set(set));
+        // roundtrip through marshal
+        let mut bytes = code.into_bytes(3425);
+        bytes.remove(0); // type prefix of the code object
+        let code = CodeObj::from_bytes(&mut bytes, 3425).unwrap();
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1]))
+            .collect::<Vec<_>>();
+        // copied from a frozenset constant
+        assert_eq!(
+            instrs,
+            [
+                (Opcode::BUILD_SET, 0),
+                (Opcode::LOAD_CONST, 0),
+                (Opcode::SET_UPDATE, 1),
+                (Opcode::RETURN_VALUE, 0),
+            ]
+        );
+        // Nats are read back as Ints
+        let elems = [1, 2, 3].into_iter().map(ValueObj::Int).collect::<Vec<_>>();
+        assert!(code.consts[0].marshal_eq(&ValueObj::FrozenSet(elems.into())));
+        assert_eq!(compute_stacksize(&code.code, &code.consts), Ok(2));
+        // `{x, 1}`
+        let mut set = Set::new(
+            Token::new(TokenKind::LBrace, "{", 1, 0),
+            Token::new(TokenKind::RBrace, "}", 1, 5),
+            0,
+            Args::new(vec![], vec![], None),
+        );
+        set.push(local("x", 1));
+        set.push(nat(1, 4));
+        let code = module_code(Expr::Set(set));
+        let instrs = code.code.chunks(2).collect::<Vec<_>>();
+        assert_eq!(instrs[0][0], Opcode::LOAD_NAME as u8);
+        assert_eq!(instrs[1][0], Opcode::LOAD_CONST as u8);
+        assert_eq!(instrs[2], [Opcode::BUILD_SET as u8, 2]);
+        assert!(!code
+            .consts
+            .iter()
+            .any(|c| matches!(c, ValueObj::FrozenSet(_))));
+        assert_eq!(compute_stacksize(&code.code, &code.consts), Ok(2));
+    }
+
+    fn range(lhs: Expr, op: TokenKind, rhs: Expr) -> Expr {
+        Expr::BinOp(BinOp::new(
+            Token::new(op, "..", 1, 5),
+            lhs,
+            rhs,
+            Type::func2(Type::Nat, Type::Nat, Type::Obj),
+        ))
+    }
+
+    fn subscr_instrs(module: Vec<Expr>) -> Vec<(Opcode, String)> {
+        let hir = HIR::new(Str::ever("<module>"), Module::new(module));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        code.code
+            .chunks(2)
+            .map(|instr| {
+                let op = Opcode::from(instr[0]);
+                let arg = match op {
+                    Opcode::LOAD_NAME => code.names[instr[1] as usize].to_string(),
+                    Opcode::LOAD_CONST => code.consts[instr[1] as usize].to_string(),
+                    _ => instr[1].to_string(),
+                };
+                (op, arg)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_slice_assignment() {
+        // `arr[1..3] = xs`
+        let subscr = Subscript::new(
+            local("arr", 0),
+            range(nat(1, 4), TokenKind::Closed, nat(3, 7)),
+            Type::Obj,
+        );
+        let assign = SubscrAssign::new(
+            subscr,
+            Token::new(TokenKind::Equal, "=", 1, 10),
+            local("xs", 12),
+        );
+        let instrs = subscr_instrs(vec![Expr::SubscrAssign(assign)]);
+        let expected = [
+            (Opcode::LOAD_NAME, "xs"),
+            (Opcode::LOAD_NAME, "arr"),
+            (Opcode::LOAD_CONST, "1"),
+            (Opcode::LOAD_CONST, "4"),
+            (Opcode::BUILD_SLICE, "2"),
+            (Opcode::STORE_SUBSCR, "0"),
+            (Opcode::LOAD_CONST, "None"),
+            (Opcode::RETURN_VALUE, "0"),
+        ];
+        assert_eq!(instrs, expected.map(|(op, arg)| (op, arg.to_string())));
+        // `arr[i<..<j] = xs`: the start is shifted at runtime
+        let subscr = Subscript::new(
+            local("arr", 0),
+            range(local("i", 4), TokenKind::Open, local("j", 8)),
+            Type::Obj,
+        );
+        let assign = SubscrAssign::new(
+            subscr,
+            Token::new(TokenKind::Equal, "=", 1, 11),
+            local("xs", 13),
+        );
+        let instrs = subscr_instrs(vec![Expr::SubscrAssign(assign)]);
+        let ops = instrs.iter().map(|(op, _)| *op).collect::<Vec<_>>();
+        assert_eq!(
+            &ops[2..8],
+            [
+                Opcode::LOAD_NAME,
+                Opcode::LOAD_CONST,
+                Opcode::BINARY_ADD,
+                Opcode::LOAD_NAME,
+                Opcode::BUILD_SLICE,
+                Opcode::STORE_SUBSCR,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_range_operators() {
+        let range_call = |op: TokenKind, rhs: Expr| {
+            let instrs = subscr_instrs(vec![range(nat(1, 0), op, rhs)]);
+            instrs[..instrs.len() - 1]
+                .iter()
+                .map(|(op, arg)| format!("{op:?} {arg}"))
+                .collect::<Vec<_>>()
+        };
+        // `1..5` == `range(1, 6)`
+        assert_eq!(
+            range_call(TokenKind::Closed, nat(5, 3)),
+            [
+                "LOAD_NAME range",
+                "LOAD_CONST 1",
+                "LOAD_CONST 6",
+                "CALL_FUNCTION 2"
+            ]
+        );
+        // `1<..5` == `range(2, 6)`
+        assert_eq!(
+            range_call(TokenKind::LeftOpen, nat(5, 4)),
+            [
+                "LOAD_NAME range",
+                "LOAD_CONST 2",
+                "LOAD_CONST 6",
+                "CALL_FUNCTION 2"
+            ]
+        );
+        // `1<..<5` == `range(2, 5)`
+        assert_eq!(
+            range_call(TokenKind::Open, nat(5, 5)),
+            [
+                "LOAD_NAME range",
+                "LOAD_CONST 2",
+                "LOAD_CONST 5",
+                "CALL_FUNCTION 2"
+            ]
+        );
+        // `1..n` == `range(1, n + 1)`
+        assert_eq!(
+            range_call(TokenKind::Closed, local("n", 3)),
+            [
+                "LOAD_NAME range",
+                "LOAD_CONST 1",
+                "LOAD_NAME n",
+                "LOAD_CONST 1",
+                "BINARY_ADD 0",
+                "CALL_FUNCTION 2"
+            ]
+        );
+        // the shifted bound of `1..<max>` does not fit in the literal type
+        for bound in [
+            nat(u64::MAX, 3),
+            Expr::Lit(Literal::new(ValueObj::Int(i32::MAX), 1, 3)),
+        ] {
+            let hir = HIR::new(
+                Str::ever("<module>"),
+                Module::new(vec![range(nat(1, 0), TokenKind::Closed, bound)]),
+            );
+            let mut gen = CodeGenerator::new(ErgConfig::default());
+            gen.codegen(hir);
+            assert_eq!(gen.errs().iter().count(), 1);
+        }
+    }
+
+    fn logical(op: TokenKind, lhs: Expr, rhs: Expr) -> Expr {
+        let content = if op == TokenKind::AndOp { "and" } else { "or" };
+        Expr::BinOp(BinOp::new(
+            Token::new(op, content, 1, 2),
+            lhs,
+            rhs,
+            Type::func2(Type::Bool, Type::Bool, Type::Bool),
+        ))
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let instrs = |expr: Expr| {
+            let code = module_code(expr);
+            assert_eq!(stacksize(&code), Ok(1));
+            assert_eq!(&code.names[..], ["a", "b", "c"].map(Str::ever));
+            code.code
+                .chunks(2)
+                .map(|instr| (Opcode::from(instr[0]), instr[1]))
+                .collect::<Vec<_>>()
+        };
+        // (the jump args of Python 3.9 are byte offsets)
+        // `a and b or c`
+        let and = logical(TokenKind::AndOp, local("a", 0), local("b", 6));
+        assert_eq!(
+            instrs(logical(TokenKind::OrOp, and, local("c", 11))),
+            [
+                (Opcode::LOAD_NAME, 0),
+                (Opcode::JUMP_IF_FALSE_OR_POP, 6),
+                (Opcode::LOAD_NAME, 1),
+                (Opcode::JUMP_IF_TRUE_OR_POP, 10),
+                (Opcode::LOAD_NAME, 2),
+                (Opcode::RETURN_VALUE, 0),
+            ]
+        );
+        // `a or b and c`
+        let and = logical(TokenKind::AndOp, local("b", 5), local("c", 11));
+        assert_eq!(
+            instrs(logical(TokenKind::OrOp, local("a", 0), and)),
+            [
+                (Opcode::LOAD_NAME, 0),
+                (Opcode::JUMP_IF_TRUE_OR_POP, 10),
+                (Opcode::LOAD_NAME, 1),
+                (Opcode::JUMP_IF_FALSE_OR_POP, 10),
+                (Opcode::LOAD_NAME, 2),
+                (Opcode::RETURN_VALUE, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subscript_deletion() {
+        // `del! arr[0]`
+        let subscr = Subscript::new(local("arr", 5), nat(0, 9), Type::Obj);
+        let call = Call::new(
+            local("del!", 0),
+            Args::new(
+                vec![PosArg::new(Expr::Accessor(Accessor::Subscr(subscr)))],
+                vec![],
+                None,
+            ),
+            Type::NoneType,
+        );
+        let instrs = subscr_instrs(vec![Expr::Call(call)]);
+        let expected = [
+            (Opcode::LOAD_NAME, "arr"),
+            (Opcode::LOAD_CONST, "0"),
+            (Opcode::DELETE_SUBSCR, "0"),
+            (Opcode::LOAD_CONST, "None"),
+            (Opcode::RETURN_VALUE, "0"),
+        ];
+        assert_eq!(instrs, expected.map(|(op, arg)| (op, arg.to_string())));
+    }
+
+    #[test]
+    fn test_deletion_result() {
+        // `y = 1; x = del! y`
+        let def = |name: &'static str, body: Expr| {
+            let name = VarName::new(Token::new(TokenKind::Symbol, name, 1, 0));
+            Expr::Def(Def::new(
+                Signature::Var(VarSignature::new(VarPattern::VarName(name), Type::Obj)),
+                DefBody::new(
+                    Token::new(TokenKind::Equal, "=", 1, 2),
+                    Block::new(vec![body]),
+                    DefId(0),
+                ),
+            ))
+        };
+        let call = Call::new(
+            local("del!", 4),
+            Args::new(vec![PosArg::new(local("y", 9))], vec![], None),
+            Type::NoneType,
+        );
+        let instrs = subscr_instrs(vec![def("y", nat(1, 4)), def("x", Expr::Call(call))]);
+        let expected = [
+            (Opcode::LOAD_CONST, "1"),
+            (Opcode::STORE_NAME, "0"),
+            (Opcode::DELETE_NAME, "0"),
+            (Opcode::LOAD_CONST, "None"),
+            (Opcode::STORE_NAME, "1"),
+        ];
+        assert_eq!(instrs[..5], expected.map(|(op, arg)| (op, arg.to_string())));
+    }
+
+    /// `del! arr[<start>:<stop>(:<step>)]`
+    fn slice_deletion(start: Option<Expr>, stop: Option<Expr>, step: Option<Expr>) -> Expr {
+        let slice = Slice::new(Token::new(TokenKind::Colon, ":", 1, 10), start, stop, step);
+        let subscr = Subscript::new(local("arr", 5), Expr::Slice(slice), Type::Obj);
+        Expr::Call(Call::new(
+            local("del!", 0),
+            Args::new(
+                vec![PosArg::new(Expr::Accessor(Accessor::Subscr(subscr)))],
+                vec![],
+                None,
+            ),
+            Type::NoneType,
+        ))
+    }
+
+    #[test]
+    fn test_slice_bounds() {
+        let slice_instrs = |start, stop, step| {
+            let instrs = subscr_instrs(vec![slice_deletion(start, stop, step)]);
+            let build = instrs
+                .iter()
+                .position(|(op, _)| *op == Opcode::BUILD_SLICE)
+                .unwrap();
+            assert_eq!(instrs[build + 1].0, Opcode::DELETE_SUBSCR);
+            instrs[1..=build]
+                .iter()
+                .map(|(op, arg)| format!("{op:?} {arg}"))
+                .collect::<Vec<_>>()
+        };
+        let int = |i: i32| Expr::Lit(Literal::new(ValueObj::Int(i), 1, 12));
+        // `arr[1:]`
+        assert_eq!(
+            slice_instrs(Some(nat(1, 9)), None, None),
+            ["LOAD_CONST 1", "LOAD_CONST None", "BUILD_SLICE 2"]
+        );
+        // `arr[:2]`
+        assert_eq!(
+            slice_instrs(None, Some(nat(2, 11)), None),
+            ["LOAD_CONST None", "LOAD_CONST 2", "BUILD_SLICE 2"]
+        );
+        // `arr[::2]`
+        assert_eq!(
+            slice_instrs(None, None, Some(nat(2, 12))),
+            [
+                "LOAD_CONST None",
+                "LOAD_CONST None",
+                "LOAD_CONST 2",
+                "BUILD_SLICE 3"
+            ]
+        );
+        // `arr[::-1]`
+        assert_eq!(
+            slice_instrs(None, None, Some(int(-1))),
+            [
+                "LOAD_CONST None",
+                "LOAD_CONST None",
+                "LOAD_CONST -1",
+                "BUILD_SLICE 3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slice_load_and_store() {
+        let slice = |start, stop, step| {
+            let slice = Slice::new(Token::new(TokenKind::Colon, ":", 1, 4), start, stop, step);
+            Subscript::new(local("xs", 0), Expr::Slice(slice), Type::Obj)
+        };
+        // `xs[1:]`
+        let instrs = subscr_instrs(vec![Expr::Accessor(Accessor::Subscr(slice(
+            Some(nat(1, 3)),
+            None,
+            None,
+        )))]);
+        let expected = [
+            (Opcode::LOAD_NAME, "xs"),
+            (Opcode::LOAD_CONST, "1"),
+            (Opcode::LOAD_CONST, "None"),
+            (Opcode::BUILD_SLICE, "2"),
+            (Opcode::BINARY_SUBSCR, "0"),
+            (Opcode::RETURN_VALUE, "0"),
+        ];
+        assert_eq!(instrs, expected.map(|(op, arg)| (op, arg.to_string())));
+        // `xs[::2] = ys`
+        let assign = SubscrAssign::new(
+            slice(None, None, Some(nat(2, 5))),
+            Token::new(TokenKind::Equal, "=", 1, 8),
+            local("ys", 10),
+        );
+        let instrs = subscr_instrs(vec![Expr::SubscrAssign(assign)]);
+        let expected = [
+            (Opcode::LOAD_NAME, "ys"),
+            (Opcode::LOAD_NAME, "xs"),
+            (Opcode::LOAD_CONST, "None"),
+            (Opcode::LOAD_CONST, "None"),
+            (Opcode::LOAD_CONST, "2"),
+            (Opcode::BUILD_SLICE, "3"),
+            (Opcode::STORE_SUBSCR, "0"),
+            (Opcode::LOAD_CONST, "None"),
+            (Opcode::RETURN_VALUE, "0"),
+        ];
+        assert_eq!(instrs, expected.map(|(op, arg)| (op, arg.to_string())));
+    }
+
+    #[test]
+    fn test_record_literal() {
+        // `{y = 1; x = a}`
+        let attr = |name: &'static str, col: usize, value: Expr| {
+            RecordAttr::new(Token::new(TokenKind::Symbol, name, 1, col), value)
+        };
+        let record = Record::new(
+            Token::new(TokenKind::LBrace, "{", 1, 0),
+            Token::new(TokenKind::RBrace, "}", 1, 13),
+            vec![attr("y", 1, nat(1, 5)), attr("x", 8, local("a", 12))],
+        );
+        let instrs = subscr_instrs(vec![Expr::Record(record)]);
+        let expected = [
+            // the type is defined at the head of the module
+            (Opcode::LOAD_CONST, "0"),
+            (Opcode::LOAD_CONST, "None"),
+            (Opcode::IMPORT_NAME, "0"),
+            (Opcode::LOAD_ATTR, "1"),
+            (Opcode::DUP_TOP, "0"),
+            (Opcode::LOAD_CONST, "\"Record\""),
+            // the fields are in the order of definition
+            (Opcode::LOAD_CONST, "[\"y\", \"x\"]"),
+            (Opcode::CALL_FUNCTION, "2"),
+            (Opcode::STORE_NAME, "2"),
+            (Opcode::POP_TOP, "0"),
+            (Opcode::LOAD_NAME, "__erg_record(y, x)__"),
+            (Opcode::LOAD_CONST, "1"),
+            (Opcode::LOAD_NAME, "a"),
+            (Opcode::CALL_FUNCTION, "2"),
+            (Opcode::RETURN_VALUE, "0"),
+        ];
+        assert_eq!(instrs, expected.map(|(op, arg)| (op, arg.to_string())));
+    }
+
+    fn tuple(elems: Vec<Expr>) -> Expr {
+        let elems = elems.into_iter().map(PosArg::new).collect();
+        let paren = (
+            Token::new(TokenKind::LParen, "(", 1, 0),
+            Token::new(TokenKind::RParen, ")", 1, 10),
+        );
+        Expr::Tuple(Tuple::new(Args::new(elems, vec![], Some(paren))))
+    }
+
+    #[test]
+    fn test_tuple_literal() {
+        // `(1, "a", 2)` is loaded as a single constant
+        let code = module_code(tuple(vec![nat(1, 1), str_("a", 4), nat(2, 9)]));
+        let instrs = code.code.chunks(2).collect::<Vec<_>>();
+        assert_eq!(instrs.len(), 2);
+        assert_eq!(instrs[0][0], Opcode::LOAD_CONST as u8);
+        assert!(
+            code.consts[instrs[0][1] as usize].marshal_eq(&ValueObj::from(vec![
+                ValueObj::Nat(1),
+                ValueObj::Str(Str::ever("a")),
+                ValueObj::Nat(2),
+            ]))
+        );
+        assert!(!code.code_info().contains("BUILD_TUPLE"));
+        // `(1, x, 2)`
+        let code = module_code(tuple(vec![nat(1, 1), local("x", 4), nat(2, 7)]));
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            instrs,
+            [
+                (Opcode::LOAD_CONST, 0),
+                (Opcode::LOAD_NAME, 0),
+                (Opcode::LOAD_CONST, 1),
+                (Opcode::BUILD_TUPLE, 3),
+                (Opcode::RETURN_VALUE, 0),
+            ]
+        );
+        assert_eq!(compute_stacksize(&code.code, &code.consts), Ok(3));
+    }
+
+    /// `<name> += 1`
+    fn aug_assign(name: &'static str, col: usize) -> Expr {
+        let var = VarName::new(Token::new(TokenKind::Symbol, name, 1, col));
+        let body = DefBody::new(
+            Token::new(TokenKind::PlusEqual, "+=", 1, col + 2),
+            Block::new(vec![nat(1, col + 5)]),
+            DefId(0),
+        );
+        Expr::Def(Def::new(
+            Signature::Var(VarSignature::new(VarPattern::VarName(var), Type::Obj)),
+            body,
+        ))
+    }
+
+    /// `(<params>) -> <body>`
+    fn lambda_with(params: Vec<&'static str>, body: Expr) -> Expr {
+        let params = params
+            .into_iter()
+            .map(|name| {
+                let name = VarName::new(Token::new(TokenKind::Symbol, name, 1, 1));
+                ParamSignature::new(ParamPattern::VarName(name), None, None)
+            })
+            .collect();
+        let params = Params::new(
+            params,
+            vec![],
+            Some((
+                Token::new(TokenKind::LParen, "(", 1, 0),
+                Token::new(TokenKind::RParen, ")", 1, 2),
+            )),
+        );
+        Expr::Lambda(Lambda::new(
+            0,
+            params,
+            Token::new(TokenKind::FuncArrow, "->", 1, 4),
+            Block::new(vec![body]),
+            Type::Obj,
+        ))
+    }
+
+    #[test]
+    fn test_identical_lambdas_share_const() {
+        // `(() -> 1, () -> 1)`
+        let code = module_code(tuple(vec![
+            lambda_with(vec![], nat(1, 7)),
+            lambda_with(vec![], nat(1, 7)),
+        ]));
+        let codes = code
+            .consts
+            .iter()
+            .filter(|c| matches!(c, ValueObj::Code(_)))
+            .count();
+        assert_eq!(codes, 1);
+        let loads = code
+            .code
+            .chunks(2)
+            .take_while(|instr| instr[0] != Opcode::BUILD_TUPLE as u8)
+            .collect::<Vec<_>>();
+        // LOAD_CONST <code>, LOAD_CONST <qualname>, MAKE_FUNCTION (x2)
+        assert_eq!(loads.len(), 6);
+        assert_eq!(loads[0], loads[3]);
+        // `(() -> 0.0, () -> -0.0)`: the bodies are marshalled differently
+        let float = |f: f64| Expr::Lit(Literal::new(ValueObj::Float(f), 1, 7));
+        let code = module_code(tuple(vec![
+            lambda_with(vec![], float(0.0)),
+            lambda_with(vec![], float(-0.0)),
+        ]));
+        let codes = code
+            .consts
+            .iter()
+            .filter(|c| matches!(c, ValueObj::Code(_)))
+            .count();
+        assert_eq!(codes, 2);
+    }
+
+    fn inner_code(code: &CodeObj) -> &CodeObj {
+        code.consts
+            .iter()
+            .find_map(|c| match c {
+                ValueObj::Code(code) => Some(code.as_ref()),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    /// the instructions from the load of the target to the store back to it
+    fn aug_assign_instrs(code: &CodeObj) -> Vec<(Opcode, u8)> {
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1]))
+            .collect::<Vec<_>>();
+        let inplace = instrs
+            .iter()
+            .position(|(op, _)| *op == Opcode::INPLACE_ADD)
+            .unwrap();
+        instrs[inplace - 2..=inplace + 1].to_vec()
+    }
+
+    #[test]
+    fn test_augmented_assignment() {
+        // `x += 1` at the toplevel
+        let code = module_code(aug_assign("x", 0));
+        let instrs = aug_assign_instrs(&code);
+        let (load, store) = (instrs[0], instrs[3]);
+        assert_eq!(load.0, Opcode::LOAD_NAME);
+        assert_eq!(instrs[1].0, Opcode::LOAD_CONST);
+        assert_eq!(store, (Opcode::STORE_NAME, load.1));
+        assert_eq!(&code.names[..], [Str::ever("x")]);
+        // `(x) -> x += 1`
+        let code = module_code(lambda_with(vec!["x"], aug_assign("x", 7)));
+        let lambda = inner_code(&code);
+        let instrs = aug_assign_instrs(lambda);
+        assert_eq!(instrs[0], (Opcode::LOAD_FAST, 0));
+        assert_eq!(instrs[3], (Opcode::STORE_FAST, 0));
+        // `() -> x += 1` where `x` is defined at the toplevel
+        let hir = HIR::new(
+            Str::ever("<module>"),
+            Module::new(vec![
+                aug_assign("x", 0),
+                lambda_with(vec![], aug_assign("x", 7)),
+            ]),
+        );
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        let lambda = inner_code(&code);
+        let instrs = aug_assign_instrs(lambda);
+        assert_eq!(instrs[0].0, Opcode::LOAD_GLOBAL);
+        assert_eq!(instrs[3], (Opcode::STORE_GLOBAL, instrs[0].1));
+        assert_eq!(&lambda.names[..], [Str::ever("x")]);
+        // `(x) -> () -> x += 1`
+        let inner = lambda_with(vec![], aug_assign("x", 13));
+        let code = module_code(lambda_with(vec!["x"], inner));
+        let outer = inner_code(&code);
+        assert_eq!(&outer.cellvars[..], [Str::ever("x")]);
+        let lambda = inner_code(outer);
+        let instrs = aug_assign_instrs(lambda);
+        assert_eq!(instrs[0], (Opcode::LOAD_DEREF, 0));
+        assert_eq!(instrs[3], (Opcode::STORE_DEREF, 0));
+        assert_eq!(&lambda.freevars[..], [Str::ever("x")]);
+    }
+
+    #[test]
+    fn test_attr_augmented_assignment() {
+        // `obj.x += 1`
+        let obj = Local::new(
+            Token::new(TokenKind::Symbol, "obj", 1, 0),
+            None,
+            Type::mono("C"),
+        );
+        let attr = Attribute::new(
+            Expr::Accessor(Accessor::Local(obj)),
+            Token::new(TokenKind::Symbol, "x", 1, 4),
+            Type::Obj,
+        );
+        let assign = AttrAssign::new(
+            attr,
+            Token::new(TokenKind::PlusEqual, "+=", 1, 6),
+            nat(1, 9),
+        );
+        let instrs = subscr_instrs(vec![Expr::AttrAssign(assign)]);
+        let ops = instrs.iter().map(|(op, _)| *op).collect::<Vec<_>>();
+        assert_eq!(
+            &ops[..7],
+            [
+                Opcode::LOAD_NAME,
+                Opcode::DUP_TOP,
+                Opcode::LOAD_ATTR,
+                Opcode::LOAD_CONST,
+                Opcode::INPLACE_ADD,
+                Opcode::ROT_TWO,
+                Opcode::STORE_ATTR,
+            ]
+        );
+        assert_eq!(instrs[0].1, "obj");
+        assert_eq!(instrs[2].1, instrs[6].1);
+    }
+
+    /// `if c, () -> 1, () -> 2`
+    fn pure_if() -> Expr {
+        let args = Args::new(
+            vec![
+                PosArg::new(local("c", 3)),
+                PosArg::new(lambda_with(vec![], nat(1, 12))),
+                PosArg::new(lambda_with(vec![], nat(2, 21))),
+            ],
+            vec![],
+            None,
+        );
+        Expr::Call(Call::new(local("if", 0), args, Type::Obj))
+    }
+
+    #[test]
+    fn test_dead_code_elimination() {
+        let print_x = Call::new(
+            local("print!", 0),
+            Args::new(vec![PosArg::new(local("x", 7))], vec![], None),
+            Type::NoneType,
+        );
+        let discard = Call::new(
+            local("discard", 0),
+            Args::new(vec![PosArg::new(pure_if())], vec![], None),
+            Type::NoneType,
+        );
+        assert!(pure_if().is_pure() && !Expr::Call(print_x.clone()).is_pure());
+        let module = || {
+            Module::new(vec![
+                pure_if(),
+                Expr::Call(discard.clone()),
+                Expr::Call(print_x.clone()),
+            ])
+        };
+        let codegen = |opt_level: u8| {
+            let hir = HIR::new(Str::ever("<module>"), module());
+            let cfg = ErgConfig {
+                input: Input::Dummy,
+                opt_level: Some(opt_level),
+                ..ErgConfig::default()
+            };
+            CodeGenerator::new(cfg).codegen(hir)
+        };
+        let code = codegen(1);
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| Opcode::from(instr[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            instrs,
+            [
+                Opcode::LOAD_NAME,
+                Opcode::LOAD_NAME,
+                Opcode::CALL_FUNCTION,
+                Opcode::RETURN_VALUE,
+            ]
+        );
+        assert_eq!(&code.names[..], ["print", "x"].map(Str::ever));
+        // opt_level 0 keeps both of the conditionals
+        let code = codegen(0);
+        let dis = code.code_info();
+        assert_eq!(dis.matches("POP_JUMP_IF_FALSE").count(), 2);
+        assert_eq!(dis.matches("POP_TOP").count(), 2);
+    }
+
+    fn str_interp(segments: Vec<StrSegment>) -> Expr {
+        let left = Token::new(TokenKind::StrInterpLeft, "\"\\{", 1, 0);
+        let right = Token::new(TokenKind::StrInterpRight, "}\"", 1, 5);
+        Expr::StrInterp(StrInterp::new(left, right, segments))
+    }
+
+    fn embedded(
+        name: &'static str,
+        conversion: Option<FormatConversion>,
+        spec: Option<&'static str>,
+    ) -> StrSegment {
+        StrSegment::Expr {
+            expr: Box::new(local(name, 3)),
+            conversion,
+            spec: spec.map(Str::ever),
+        }
+    }
+
+    #[test]
+    fn test_str_interpolation() {
+        // `"x = \{x}"`
+        let interp = str_interp(vec![
+            StrSegment::Lit(Str::ever("x = ")),
+            embedded("x", None, None),
+        ]);
+        let instrs = subscr_instrs(vec![interp]);
+        let expected = [
+            (Opcode::LOAD_CONST, "\"x = \""),
+            (Opcode::LOAD_NAME, "x"),
+            (Opcode::FORMAT_VALUE, "0"),
+            (Opcode::BUILD_STRING, "2"),
+            (Opcode::RETURN_VALUE, "0"),
+        ];
+        assert_eq!(instrs, expected.map(|(op, arg)| (op, arg.to_string())));
+        // `"\{x:>5}"`: the spec is pushed and flagged with 0x04
+        let instrs = subscr_instrs(vec![str_interp(vec![embedded("x", None, Some(">5"))])]);
+        let expected = [
+            (Opcode::LOAD_NAME, "x"),
+            (Opcode::LOAD_CONST, "\">5\""),
+            (Opcode::FORMAT_VALUE, "4"),
+            (Opcode::RETURN_VALUE, "0"),
+        ];
+        assert_eq!(instrs, expected.map(|(op, arg)| (op, arg.to_string())));
+        // `"\{x!r:>5}\{y!s}"`
+        let code = module_code(str_interp(vec![
+            embedded("x", Some(FormatConversion::Repr), Some(">5")),
+            embedded("y", Some(FormatConversion::Str), None),
+        ]));
+        let formats = code
+            .code
+            .chunks(2)
+            .filter(|instr| instr[0] == Opcode::FORMAT_VALUE as u8)
+            .map(|instr| instr[1])
+            .collect::<Vec<_>>();
+        assert_eq!(formats, [0x02 | 0x04, 0x01]);
+        assert_eq!(compute_stacksize(&code.code, &code.consts), Ok(2));
+    }
+
+    /// `f x, y := 1 = x`
+    fn subr_def_with_default() -> Expr {
+        let param = |name: &'static str, col: usize, default: Option<&'static str>| {
+            let name = VarName::new(Token::new(TokenKind::Symbol, name, 1, col));
+            let default = default.map(|lit| {
+                let token = Token::new(TokenKind::NatLit, lit, 1, col + 5);
+                ConstExpr::Lit(erg_parser::ast::Literal::from(token))
+            });
+            ParamSignature::new(ParamPattern::VarName(name), None, default)
+        };
+        let params = Params::new(
+            vec![param("x", 2, None)],
+            vec![param("y", 5, Some("1"))],
+            None,
+        );
+        let sig = SubrSignature::new(
+            vec![],
+            VarName::new(Token::new(TokenKind::Symbol, "f", 1, 0)),
+            params,
+            Type::Obj,
+        );
+        let body = DefBody::new(
+            Token::new(TokenKind::Equal, "=", 1, 12),
+            Block::new(vec![local("x", 14)]),
+            DefId(0),
+        );
+        Expr::Def(Def::new(Signature::Subr(sig), body))
+    }
+
+    #[test]
+    fn test_param_defaults() {
+        let code = module_code(subr_def_with_default());
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1]))
+            .collect::<Vec<_>>();
+        // defaults, code, qualname
+        assert_eq!(instrs[0].0, Opcode::LOAD_CONST);
+        assert!(
+            code.consts[instrs[0].1 as usize].marshal_eq(&ValueObj::from(vec![ValueObj::Nat(1)]))
+        );
+        assert_eq!(instrs[1].0, Opcode::LOAD_CONST);
+        assert!(matches!(
+            code.consts[instrs[1].1 as usize],
+            ValueObj::Code(_)
+        ));
+        assert_eq!(instrs[3], (Opcode::MAKE_FUNCTION, 0x01));
+        assert_eq!(compute_stacksize(&code.code, &code.consts), Ok(3));
+        let f = inner_code(&code);
+        assert_eq!(f.argcount, 2);
+        assert_eq!(&f.varnames[..], ["x", "y"].map(Str::ever));
+    }
+
+    /// `<decorators> <name> <param> = <param>`
+    fn identity_def(decorators: Vec<Expr>, name: &'static str, param: &'static str) -> Expr {
+        let var = VarName::new(Token::new(TokenKind::Symbol, param, 1, 2));
+        let params = Params::new(
+            vec![ParamSignature::new(ParamPattern::VarName(var), None, None)],
+            vec![],
+            None,
+        );
+        let sig = SubrSignature::new(
+            decorators,
+            VarName::new(Token::new(TokenKind::Symbol, name, 1, 0)),
+            params,
+            Type::Obj,
+        );
+        let body = DefBody::new(
+            Token::new(TokenKind::Equal, "=", 1, 4),
+            Block::new(vec![local(param, 6)]),
+            DefId(0),
+        );
+        Expr::Def(Def::new(Signature::Subr(sig), body))
+    }
+
+    #[test]
+    fn test_decorators() {
+        // `wrap f = f; trace f = f; @wrap @trace g x = x`
+        let hir = HIR::new(
+            Str::ever("<module>"),
+            Module::new(vec![
+                identity_def(vec![], "wrap", "f"),
+                identity_def(vec![], "trace", "f"),
+                identity_def(vec![local("wrap", 1), local("trace", 7)], "g", "x"),
+            ]),
+        );
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1] as usize))
+            .collect::<Vec<_>>();
+        let name = |(_, arg): (Opcode, usize)| &code.names[arg][..];
+        let load_wrap = instrs
+            .iter()
+            .position(|(op, arg)| *op == Opcode::LOAD_NAME && &code.names[*arg][..] == "wrap")
+            .unwrap();
+        // the decorators are loaded before the function is created
+        assert_eq!(name(instrs[load_wrap + 1]), "trace");
+        assert_eq!(instrs[load_wrap + 1].0, Opcode::LOAD_NAME);
+        assert_eq!(instrs[load_wrap + 4].0, Opcode::MAKE_FUNCTION);
+        // `trace` (on top of the stack) is applied first, then `wrap`, and the result is bound to `g`
+        assert_eq!(instrs[load_wrap + 5], (Opcode::CALL_FUNCTION, 1));
+        assert_eq!(instrs[load_wrap + 6], (Opcode::CALL_FUNCTION, 1));
+        assert_eq!(instrs[load_wrap + 7].0, Opcode::STORE_NAME);
+        assert_eq!(name(instrs[load_wrap + 7]), "g");
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        assert_eq!(code.stacksize, 4);
+    }
+
+    /// `if <cond>, () -> <then>(, () -> <else>)`
+    fn if_call(name: &'static str, then: Expr, else_: Option<Expr>) -> Expr {
+        let mut args = vec![
+            PosArg::new(local("c", 3)),
+            PosArg::new(lambda_with(vec![], then)),
+        ];
+        args.extend(else_.map(|else_| PosArg::new(lambda_with(vec![], else_))));
+        Expr::Call(Call::new(
+            local(name, 0),
+            Args::new(args, vec![], None),
+            Type::Obj,
+        ))
+    }
+
+    /// `<name> = <value>` (at line `lineno`)
+    fn var_def(name: &str, lineno: usize, value: Expr) -> Expr {
+        let body = DefBody::new(
+            Token::new(TokenKind::Equal, "=", lineno, name.len() + 1),
+            Block::new(vec![value]),
+            DefId(0),
+        );
+        let name = VarName::new(Token::new(TokenKind::Symbol, Str::rc(name), lineno, 0));
+        Expr::Def(Def::new(
+            Signature::Var(VarSignature::new(VarPattern::VarName(name), Type::Obj)),
+            body,
+        ))
+    }
+
+    #[test]
+    fn test_if_as_value() {
+        // `y = if c, () -> 1, () -> 2`
+        let code = module_code(var_def("y", 1, if_call("if", nat(1, 12), Some(nat(2, 21)))));
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1] as usize))
+            .collect::<Vec<_>>();
+        let ops = instrs.iter().map(|(op, _)| *op).collect::<Vec<_>>();
+        assert_eq!(
+            ops[..6],
+            [
+                Opcode::LOAD_NAME,
+                Opcode::POP_JUMP_IF_FALSE,
+                Opcode::LOAD_CONST,
+                Opcode::JUMP_FORWARD,
+                Opcode::LOAD_CONST,
+                Opcode::STORE_NAME,
+            ]
+        );
+        // both branches leave their value for the same `STORE_NAME`
+        // (the jump args of Python 3.9 are byte offsets)
+        assert_eq!(instrs[1].1, 4 * 2);
+        assert_eq!(4 * 2 + instrs[3].1, 5 * 2);
+        assert_eq!(stacksize(&code), Ok(1));
+        // `y = f a, (if c, () -> 1)`: the missing else branch yields `None`
+        let call = Call::new(
+            local("f", 4),
+            Args::new(
+                vec![
+                    PosArg::new(local("a", 6)),
+                    PosArg::new(if_call("if", nat(1, 12), None)),
+                ],
+                vec![],
+                None,
+            ),
+            Type::Obj,
+        );
+        let code = module_code(var_def("y", 1, Expr::Call(call)));
+        assert!(code.code_info().contains("(None)"));
+        assert_eq!(stacksize(&code), Ok(code.stacksize));
+        assert_eq!(code.stacksize, 3);
+    }
+
+    #[test]
+    fn test_if_as_statement() {
+        // `if! c, () -> g(); x`
+        let g = Call::new(local("g", 12), Args::new(vec![], vec![], None), Type::Obj);
+        let hir = HIR::new(
+            Str::ever("<module>"),
+            Module::new(vec![if_call("if!", Expr::Call(g), None), local("x", 0)]),
+        );
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            python_ver: Some(3425),
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1] as usize))
+            .collect::<Vec<_>>();
+        // the result of `g()` is discarded inside the branch, and no `None` is pushed
+        assert_eq!(
+            instrs.iter().map(|(op, _)| *op).take(6).collect::<Vec<_>>(),
+            [
+                Opcode::LOAD_NAME,
+                Opcode::POP_JUMP_IF_FALSE,
+                Opcode::LOAD_NAME,
+                Opcode::CALL_FUNCTION,
+                Opcode::POP_TOP,
+                Opcode::LOAD_NAME,
+            ]
+        );
+        assert_eq!(instrs[1].1, 5 * 2);
+        assert_eq!(stacksize(&code), Ok(1));
+    }
+
+    fn compare(lhs: Expr, op: TokenKind, rhs: Expr) -> Expr {
+        Expr::BinOp(BinOp::new(
+            Token::new(op, "<", 1, 2),
+            lhs,
+            rhs,
+            Type::func2(Type::Int, Type::Int, Type::Bool),
+        ))
+    }
+
+    /// `<lhs> <op> <rhs>` where `lhs` is a comparison chained with this one
+    fn chained(lhs: Expr, op: TokenKind, rhs: Expr) -> Expr {
+        let mut bin = enum_unwrap!(compare(lhs, op, rhs), Expr::BinOp);
+        bin.chained = true;
+        Expr::BinOp(bin)
+    }
+
+    #[test]
+    fn test_chained_comparison() {
+        // `1 < x < 10`
+        let lt = compare(nat(1, 0), TokenKind::Less, local("x", 4));
+        let code = module_code(chained(lt, TokenKind::Less, nat(10, 8)));
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| (Opcode::from(instr[0]), instr[1]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            instrs,
+            [
+                (Opcode::LOAD_CONST, 0),
+                (Opcode::LOAD_NAME, 0),
+                (Opcode::DUP_TOP, 0),
+                (Opcode::ROT_THREE, 0),
+                (Opcode::COMPARE_OP, 0),
+                (Opcode::JUMP_IF_FALSE_OR_POP, 18),
+                (Opcode::LOAD_CONST, 1),
+                (Opcode::COMPARE_OP, 0),
+                (Opcode::JUMP_FORWARD, 4),
+                (Opcode::ROT_TWO, 0),
+                (Opcode::POP_TOP, 0),
+                (Opcode::RETURN_VALUE, 0),
+            ]
+        );
+        // `x` is evaluated only once
+        assert_eq!(&code.names[..], [Str::ever("x")]);
+        assert_eq!(stacksize(&code), Ok(3));
+        // `a < b <= c == d`
+        let lt = compare(local("a", 0), TokenKind::Less, local("b", 4));
+        let le = chained(lt, TokenKind::LessEq, local("c", 9));
+        let code = module_code(chained(le, TokenKind::DblEq, local("d", 14)));
+        let ops = code
+            .code
+            .chunks(2)
+            .map(|instr| Opcode::from(instr[0]))
+            .collect::<Vec<_>>();
+        let count = |op: Opcode| ops.iter().filter(|o| **o == op).count();
+        assert_eq!(count(Opcode::LOAD_NAME), 4);
+        assert_eq!(count(Opcode::COMPARE_OP), 3);
+        assert_eq!(count(Opcode::DUP_TOP), 2);
+        assert_eq!(count(Opcode::JUMP_IF_FALSE_OR_POP), 2);
+        assert_eq!(count(Opcode::POP_TOP), 1);
+        assert_eq!(&code.names[..], ["a", "b", "c", "d"].map(Str::ever));
+        assert_eq!(stacksize(&code), Ok(3));
+        assert_eq!(code.stacksize, 3);
+        // `(x > 0) == flag` is not chained: the result of `x > 0` is compared
+        let gt = compare(local("x", 1), TokenKind::Gre, nat(0, 5));
+        let code = module_code(compare(gt, TokenKind::DblEq, local("flag", 11)));
+        let instrs = code
+            .code
+            .chunks(2)
+            .map(|instr| Opcode::from(instr[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            instrs,
+            [
+                Opcode::LOAD_NAME,
+                Opcode::LOAD_CONST,
+                Opcode::COMPARE_OP,
+                Opcode::LOAD_NAME,
+                Opcode::COMPARE_OP,
+                Opcode::RETURN_VALUE,
+            ]
+        );
+    }
+
+    fn subscr(obj: Expr, index: Expr) -> Subscript {
+        Subscript::new(obj, index, Type::Obj)
+    }
+
+    #[test]
+    fn test_subscript() {
+        // `xs[0]`
+        let instrs = subscr_instrs(vec![Expr::Accessor(Accessor::Subscr(subscr(
+            local("xs", 0),
+            nat(0, 3),
+        )))]);
+        let expected = [
+            (Opcode::LOAD_NAME, "xs"),
+            (Opcode::LOAD_CONST, "0"),
+            (Opcode::BINARY_SUBSCR, "0"),
+            (Opcode::RETURN_VALUE, "0"),
+        ];
+        assert_eq!(instrs, expected.map(|(op, arg)| (op, arg.to_string())));
+        // `xs[0] = 1`: value, container, index
+        let assign = SubscrAssign::new(
+            subscr(local("xs", 0), nat(0, 3)),
+            Token::new(TokenKind::Equal, "=", 1, 6),
+            nat(1, 8),
+        );
+        let instrs = subscr_instrs(vec![Expr::SubscrAssign(assign)]);
+        let expected = [
+            (Opcode::LOAD_CONST, "1"),
+            (Opcode::LOAD_NAME, "xs"),
+            (Opcode::LOAD_CONST, "0"),
+            (Opcode::STORE_SUBSCR, "0"),
+            (Opcode::LOAD_CONST, "None"),
+            (Opcode::RETURN_VALUE, "0"),
+        ];
+        assert_eq!(instrs, expected.map(|(op, arg)| (op, arg.to_string())));
+        // `m[k][j]`
+        let m_k = Expr::Accessor(Accessor::Subscr(subscr(local("m", 0), local("k", 2))));
+        let m_k_j = Expr::Accessor(Accessor::Subscr(subscr(m_k.clone(), local("j", 5))));
+        let instrs = subscr_instrs(vec![m_k_j.clone()]);
+        let expected = [
+            (Opcode::LOAD_NAME, "m"),
+            (Opcode::LOAD_NAME, "k"),
+            (Opcode::BINARY_SUBSCR, "0"),
+            (Opcode::LOAD_NAME, "j"),
+            (Opcode::BINARY_SUBSCR, "0"),
+            (Opcode::RETURN_VALUE, "0"),
+        ];
+        assert_eq!(instrs, expected.map(|(op, arg)| (op, arg.to_string())));
+        let code = module_code(m_k_j);
+        assert_eq!(compute_stacksize(&code.code, &code.consts), Ok(2));
+        // `m[k][j] = v`: only the outermost subscription is a store
+        let assign = SubscrAssign::new(
+            subscr(m_k, local("j", 5)),
+            Token::new(TokenKind::Equal, "=", 1, 8),
+            local("v", 10),
+        );
+        let instrs = subscr_instrs(vec![Expr::SubscrAssign(assign)]);
+        let ops = instrs.iter().map(|(op, _)| *op).collect::<Vec<_>>();
+        assert_eq!(
+            ops[..6],
+            [
+                Opcode::LOAD_NAME,
+                Opcode::LOAD_NAME,
+                Opcode::LOAD_NAME,
+                Opcode::BINARY_SUBSCR,
+                Opcode::LOAD_NAME,
+                Opcode::STORE_SUBSCR,
+            ]
+        );
+        assert_eq!(instrs[0].1, "v");
+    }
+
+    /// (offset including `EXTENDED_ARG`s, opcode, arg)
+    fn decode(code: &CodeObj) -> Vec<(usize, Opcode, usize)> {
+        let mut instrs = vec![];
+        let (mut start, mut ext) = (0, 0);
+        for (i, instr) in code.code.chunks(2).enumerate() {
+            let arg = ext | instr[1] as usize;
+            if instr[0] == Opcode::EXTENDED_ARG as u8 {
+                ext = arg << 8;
+            } else {
+                instrs.push((start, Opcode::from(instr[0]), arg));
+                start = i * 2 + 2;
+                ext = 0;
+            }
+        }
+        instrs
+    }
+
+    #[test]
+    fn test_extended_arg() {
+        // `v0 = 0; v1 = 1; ...; v299 = 299`
+        let module = (0..300)
+            .map(|n| var_def(&format!("v{n}"), 1, nat(n, 5)))
+            .collect::<Vec<_>>();
+        let hir = HIR::new(Str::ever("<module>"), Module::new(module));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        let instrs = decode(&code);
+        let consts = instrs
+            .iter()
+            .filter(|(_, op, _)| *op == Opcode::LOAD_CONST)
+            .map(|(_, _, arg)| &code.consts[*arg])
+            .take(300)
+            .collect::<Vec<_>>();
+        assert!(consts
+            .iter()
+            .zip(0..300)
+            .all(|(obj, n)| obj.marshal_eq(&ValueObj::Nat(n))));
+        let names = instrs
+            .iter()
+            .filter(|(_, op, _)| *op == Opcode::STORE_NAME)
+            .map(|(_, _, arg)| code.names[*arg].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, (0..300).map(|n| format!("v{n}")).collect::<Vec<_>>());
+        let dis = code.code_info();
+        assert!(dis.contains("EXTENDED_ARG"));
+        assert!(dis.contains("(299)") && dis.contains("(v299)"));
+        assert_eq!(compute_stacksize(&code.code, &code.consts), Ok(1));
+    }
+
+    #[test]
+    fn test_long_jump() {
+        // `y = if c, () -> [a0, ..., a299], () -> 2` (the then branch is longer than 255 instructions)
+        let mut arr = Array::new(
+            Token::new(TokenKind::LSqBr, "[", 1, 12),
+            Token::new(TokenKind::RSqBr, "]", 1, 13),
+            0,
+            Args::new(vec![], vec![], None),
+            None,
+        );
+        for n in 0..300 {
+            arr.push(Expr::Accessor(Accessor::local(
+                Token::new(TokenKind::Symbol, format!("a{n}"), 1, 13),
+                Type::Obj,
+            )));
+        }
+        let module = Module::new(vec![var_def(
+            "y",
+            1,
+            if_call("if", Expr::Array(arr), Some(nat(2, 21))),
+        )]);
+        let hir = HIR::new(Str::ever("<module>"), module);
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            python_ver: Some(3425),
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        let instrs = decode(&code);
+        let at = |offset: usize| instrs.iter().find(|(o, _, _)| *o == offset).unwrap();
+        let pop_jump = instrs[1];
+        assert_eq!(pop_jump.1, Opcode::POP_JUMP_IF_FALSE);
+        assert_eq!(code.code[pop_jump.0], Opcode::EXTENDED_ARG as u8);
+        // the else branch
+        // (the jump args of Python 3.9 are byte offsets)
+        let (_, op, arg) = at(pop_jump.2);
+        assert_eq!(
+            (*op, &code.consts[*arg]),
+            (Opcode::LOAD_CONST, &ValueObj::Nat(2))
+        );
+        // the end of the then branch jumps to the store
+        let (jump_forward, _, delta) = instrs
+            .iter()
+            .find(|(_, op, _)| *op == Opcode::JUMP_FORWARD)
+            .unwrap();
+        let (_, op, arg) = at(jump_forward + 2 + delta);
+        assert_eq!((*op, &code.names[*arg][..]), (Opcode::STORE_NAME, "y"));
+        assert_eq!(stacksize(&code), Ok(code.stacksize));
+    }
+
+    #[test]
+    fn test_lnotab_line_order() {
+        let at = |name: &'static str, lineno: usize, col: usize| {
+            Expr::Accessor(Accessor::local(
+                Token::new(TokenKind::Symbol, name, lineno, col),
+                Type::Obj,
+            ))
+        };
+        // ```
+        // x = 1
+        // xs[0] =
+        //
+        //     v
+        // (395 empty lines)
+        // y = 2
+        // ```
+        let assign = SubscrAssign::new(
+            Subscript::new(
+                at("xs", 2, 0),
+                Expr::Lit(Literal::new(ValueObj::Nat(0), 2, 3)),
+                Type::Obj,
+            ),
+            Token::new(TokenKind::Equal, "=", 2, 6),
+            at("v", 4, 4),
+        );
+        let module = Module::new(vec![
+            var_def("x", 1, nat(1, 4)),
+            Expr::SubscrAssign(assign),
+            var_def("y", 400, Expr::Lit(Literal::new(ValueObj::Nat(2), 400, 4))),
+        ]);
+        let hir = HIR::new(Str::ever("<module>"), module);
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        // `v` is evaluated before `xs`, so the line goes back to 2
+        assert_eq!(code.line_starts(), vec![(0, 1), (4, 4), (6, 2), (12, 400)]);
+        assert_eq!(&code.lnotab[..4], [4, 3, 2, -2i8 as u8]);
+        // 398 = 127 + 127 + 127 + 17
+        assert_eq!(&code.lnotab[4..], [6, 127, 0, 127, 0, 127, 0, 17]);
+    }
+
+    fn call(name: &'static str, args: Vec<Expr>) -> Expr {
+        let args = args.into_iter().map(PosArg::new).collect();
+        Expr::Call(Call::new(
+            local(name, 0),
+            Args::new(args, vec![], None),
+            Type::Obj,
+        ))
+    }
+
+    #[test]
+    fn test_stacksize_matches_cpython() {
+        // the expected values are `co_stacksize` of the equivalent Python code (CPython 3.9)
+        // `a and b or c`
+        let and = logical(TokenKind::AndOp, local("a", 0), local("b", 6));
+        let code = module_code(logical(TokenKind::OrOp, and, local("c", 11)));
+        assert_eq!(code.stacksize, 1);
+        // `1 < x < 10`
+        let lt = compare(nat(1, 0), TokenKind::Less, local("x", 4));
+        let code = module_code(chained(lt, TokenKind::Less, nat(10, 8)));
+        assert_eq!(code.stacksize, 3);
+        // `xs[0] = 1`
+        let assign = SubscrAssign::new(
+            subscr(local("xs", 0), nat(0, 3)),
+            Token::new(TokenKind::Equal, "=", 1, 6),
+            nat(1, 8),
+        );
+        let code = module_code(Expr::SubscrAssign(assign));
+        assert_eq!(code.stacksize, 3);
+        // `y = f(a, 1 if c else 2)`
+        let if_ = if_call("if", nat(1, 12), Some(nat(2, 21)));
+        let code = module_code(var_def("y", 1, call("f", vec![local("a", 6), if_])));
+        assert_eq!(code.stacksize, 3);
+        // `{"a": 1, "b": x}`
+        let code = dict(vec![
+            (str_("a", 1), nat(1, 6)),
+            (str_("b", 9), local("x", 14)),
+        ]);
+        assert_eq!(code.stacksize, 3);
+        // `f(g(a, b), h(c, d, e))`
+        let g = call("g", vec![local("a", 0), local("b", 0)]);
+        let h = call("h", vec![local("c", 0), local("d", 0), local("e", 0)]);
+        let code = module_code(call("f", vec![g, h]));
+        assert_eq!(code.stacksize, 6);
+        // `[x + 1 for x in ...]`: the comprehension body
+        let code = module_code(array_comprehension(vec![]));
+        assert_eq!(inner_code(&code).stacksize, 4);
+    }
+
+    #[test]
+    fn test_peephole_opt_level() {
+        // `y = if True, () -> 1 + 2, () -> 4`
+        let add = Expr::BinOp(BinOp::new(
+            Token::new(TokenKind::Plus, "+", 1, 20),
+            nat(1, 18),
+            nat(2, 22),
+            Type::func2(Type::Nat, Type::Nat, Type::Nat),
+        ));
+        let args = Args::new(
+            vec![
+                PosArg::new(Expr::Lit(Literal::new(ValueObj::True, 1, 7))),
+                PosArg::new(lambda_with(vec![], add)),
+                PosArg::new(lambda_with(vec![], nat(4, 31))),
+            ],
+            vec![],
+            None,
+        );
+        let if_ = Expr::Call(Call::new(local("if", 4), args, Type::Obj));
+        let codegen = |opt_level: u8| {
+            let hir = HIR::new(
+                Str::ever("<module>"),
+                Module::new(vec![var_def("y", 1, if_.clone())]),
+            );
+            let cfg = ErgConfig {
+                input: Input::Dummy,
+                opt_level: Some(opt_level),
+                ..ErgConfig::default()
+            };
+            CodeGenerator::new(cfg).codegen(hir)
+        };
+        let ops = |code: &CodeObj| {
+            code.code
+                .chunks(2)
+                .map(|instr| Opcode::from(instr[0]))
+                .collect::<Vec<_>>()
+        };
+        // the default level does not run the peephole optimizer
+        let code = codegen(1);
+        assert!(ops(&code).contains(&Opcode::POP_JUMP_IF_FALSE));
+        assert!(ops(&code).contains(&Opcode::BINARY_ADD));
+        let code = codegen(2);
+        // the else branch is unreachable and removed
+        assert_eq!(
+            ops(&code)[..3],
+            [Opcode::LOAD_CONST, Opcode::JUMP_FORWARD, Opcode::STORE_NAME]
+        );
+        assert!(code.consts[code.code[1] as usize].marshal_eq(&ValueObj::Nat(3)));
+        assert_eq!(code.code[3], 0);
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+    }
+
+    /// `<pat> -> <body>` (a case of `match`)
+    fn case(pat: ParamPattern, body: Expr) -> PosArg {
+        let params = Params::new(vec![ParamSignature::new(pat, None, None)], vec![], None);
+        PosArg::new(Expr::Lambda(Lambda::new(
+            0,
+            params,
+            Token::new(TokenKind::FuncArrow, "->", 1, 12),
+            Block::new(vec![body]),
+            Type::Obj,
+        )))
+    }
+
+    #[test]
+    fn test_unreachable_branches() {
+        let codegen = |expr: Expr, opt_level: u8| {
+            let hir = HIR::new(Str::ever("<module>"), Module::new(vec![expr]));
+            let cfg = ErgConfig {
+                input: Input::Dummy,
+                python_ver: Some(3425),
+                opt_level: Some(opt_level),
+                ..ErgConfig::default()
+            };
+            let code = CodeGenerator::new(cfg).codegen(hir);
+            let instrs = code
+                .code
+                .chunks(2)
+                .map(|instr| {
+                    let op = Opcode::from(instr[0]);
+                    let arg = match op {
+                        Opcode::LOAD_CONST => code.consts[instr[1] as usize].to_string(),
+                        Opcode::LOAD_NAME | Opcode::STORE_NAME => {
+                            code.names[instr[1] as usize].to_string()
+                        }
+                        _ => instr[1].to_string(),
+                    };
+                    (op, arg)
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(stacksize(&code), Ok(code.stacksize));
+            instrs
+        };
+        let instr = |op: Opcode, arg: &str| (op, arg.to_string());
+        // `y = if True, () -> 1`: the `None` of the missing else branch is unreachable
+        let if_ = || {
+            let args = Args::new(
+                vec![
+                    PosArg::new(Expr::Lit(Literal::new(ValueObj::True, 1, 7))),
+                    PosArg::new(lambda_with(vec![], nat(1, 18))),
+                ],
+                vec![],
+                None,
+            );
+            var_def(
+                "y",
+                1,
+                Expr::Call(Call::new(local("if", 4), args, Type::Obj)),
+            )
+        };
+        assert_eq!(
+            codegen(if_(), 2),
+            [
+                instr(Opcode::LOAD_CONST, "1"),
+                instr(Opcode::JUMP_FORWARD, "0"),
+                instr(Opcode::STORE_NAME, "y"),
+                instr(Opcode::LOAD_CONST, "None"),
+                instr(Opcode::RETURN_VALUE, "0"),
+            ]
+        );
+        // the condition is not folded, so the else branch is reachable
+        let instrs = codegen(if_(), 1);
+        assert_eq!(instrs[4], instr(Opcode::LOAD_CONST, "None"));
+        assert_eq!(instrs[5], instr(Opcode::STORE_NAME, "y"));
+        // `match x, 1 -> (if True, () -> "a"), z -> "b"`
+        let match_ = || {
+            let args = Args::new(
+                vec![
+                    PosArg::new(local("x", 6)),
+                    case(
+                        ParamPattern::Lit(Token::new(TokenKind::NatLit, "1", 1, 10).into()),
+                        Expr::Call(Call::new(
+                            local("if", 14),
+                            Args::new(
+                                vec![
+                                    PosArg::new(Expr::Lit(Literal::new(ValueObj::True, 1, 17))),
+                                    PosArg::new(lambda_with(vec![], str_("a", 28))),
+                                ],
+                                vec![],
+                                None,
+                            ),
+                            Type::Obj,
+                        )),
+                    ),
+                    case(
+                        ParamPattern::VarName(VarName::new(Token::new(
+                            TokenKind::Symbol,
+                            "z",
+                            1,
+                            34,
+                        ))),
+                        str_("b", 40),
+                    ),
+                ],
+                vec![],
+                None,
+            );
+            Expr::Call(Call::new(local("match", 0), args, Type::Obj))
+        };
+        assert!(codegen(match_(), 1).contains(&instr(Opcode::LOAD_CONST, "None")));
+        // the jump to the end of `match` is kept because it is the target of `JUMP_FORWARD`
+        // (the jump args of Python 3.9 are byte offsets)
+        assert_eq!(
+            codegen(match_(), 2),
+            [
+                instr(Opcode::LOAD_NAME, "x"),
+                instr(Opcode::DUP_TOP, "0"),
+                instr(Opcode::LOAD_CONST, "1"),
+                instr(Opcode::COMPARE_OP, "2"),
+                instr(Opcode::POP_JUMP_IF_FALSE, "18"),
+                instr(Opcode::POP_TOP, "0"),
+                instr(Opcode::LOAD_CONST, "\"a\""),
+                instr(Opcode::JUMP_FORWARD, "0"),
+                instr(Opcode::JUMP_ABSOLUTE, "22"),
+                instr(Opcode::STORE_NAME, "z"),
+                instr(Opcode::LOAD_CONST, "\"b\""),
+                instr(Opcode::RETURN_VALUE, "0"),
+            ]
+        );
+    }
+
+    /// `f(<a>, *<xs>, **<kw>)`
+    fn spread_call_code(a: bool, xs: bool, kw: bool, python_ver: u32) -> Vec<(Opcode, usize)> {
+        let pos_args = if a {
+            vec![PosArg::new(local("a", 2))]
+        } else {
+            vec![]
+        };
+        let mut args = Args::new(pos_args, vec![], None);
+        if xs {
+            args.set_var_args(PosArg::new(local("xs", 6)));
+        }
+        if kw {
+            args.set_kw_var_args(PosArg::new(local("kw", 12)));
+        }
+        let call = Expr::Call(Call::new(local("f", 0), args, Type::Obj));
+        let hir = HIR::new(Str::ever("<module>"), Module::new(vec![call]));
+        let cfg = ErgConfig {
+            input: Input::Dummy,
+            python_ver: Some(python_ver),
+            ..ErgConfig::default()
+        };
+        let code = CodeGenerator::new(cfg).codegen(hir);
+        assert_eq!(
+            compute_stacksize(&code.code, &code.consts),
+            Ok(code.stacksize)
+        );
+        let mut instrs = decode(&code)
+            .into_iter()
+            .map(|(_, op, arg)| (op, arg))
+            .collect::<Vec<_>>();
+        // the result of the call is returned
+        assert_eq!(instrs.pop(), Some((Opcode::RETURN_VALUE, 0)));
+        instrs
+    }
+
+    #[test]
+    fn test_call_function_ex() {
+        use Opcode::*;
+        // `f(*xs)`
+        assert_eq!(
+            spread_call_code(false, true, false, 3425),
+            [(LOAD_NAME, 0), (LOAD_NAME, 1), (CALL_FUNCTION_EX, 0)]
+        );
+        // `f(**kw)`
+        assert_eq!(
+            spread_call_code(false, false, true, 3425),
+            [
+                (LOAD_NAME, 0),
+                (BUILD_TUPLE, 0),
+                (BUILD_MAP, 0),
+                (LOAD_NAME, 1),
+                (DICT_MERGE, 1),
+                (CALL_FUNCTION_EX, 1),
+            ]
+        );
+        // `f(a, *xs, **kw)`
+        assert_eq!(
+            spread_call_code(true, true, true, 3425),
+            [
+                (LOAD_NAME, 0),
+                (LOAD_NAME, 1),
+                (BUILD_LIST, 1),
+                (LOAD_NAME, 2),
+                (LIST_EXTEND, 1),
+                (LIST_TO_TUPLE, 0),
+                (BUILD_MAP, 0),
+                (LOAD_NAME, 3),
+                (DICT_MERGE, 1),
+                (CALL_FUNCTION_EX, 1),
+            ]
+        );
+        // Python 3.8
+        assert_eq!(
+            spread_call_code(false, false, true, 3413),
+            [
+                (LOAD_NAME, 0),
+                (BUILD_TUPLE, 0),
+                (LOAD_NAME, 1),
+                (CALL_FUNCTION_EX, 1),
+            ]
+        );
+        assert_eq!(
+            spread_call_code(true, true, true, 3413),
+            [
+                (LOAD_NAME, 0),
+                (LOAD_NAME, 1),
+                (BUILD_TUPLE, 1),
+                (LOAD_NAME, 2),
+                (BUILD_TUPLE_UNPACK_WITH_CALL, 2),
+                (LOAD_NAME, 3),
+                (CALL_FUNCTION_EX, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,844 @@
+extern crate erg_common;
+extern crate erg_compiler;
+
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::rc::Rc;
+
+    use erg_common::codeobj::CodeObj;
+    use erg_common::config::{ErgConfig, Input};
+    use erg_common::error::ErrorKind;
+    use erg_common::python_util::{find_python_executable_for, run_pyc, PythonVersion};
+    use erg_common::serialize::get_python_version;
+    use erg_common::traits::{Runnable, Stream};
+    use erg_common::value::ValueObj;
+
+    use erg_compiler::codegen::target_python_ver;
+    use erg_compiler::{disassemble_source, Compiler};
+
+    /// the instructions of a listing as `<offset> <opname> <arg> (<argrepr>)`
+    /// (line numbers and jump target markers are removed)
+    fn listing_instrs(listing: &str) -> Vec<String> {
+        listing
+            .lines()
+            .map(|l| {
+                let mut tokens = l
+                    .split_whitespace()
+                    .filter(|t| *t != ">>")
+                    .collect::<Vec<_>>();
+                // `<lineno> <offset> ...`
+                if tokens.len() >= 2 && tokens[..2].iter().all(|t| t.parse::<usize>().is_ok()) {
+                    tokens.remove(0);
+                }
+                tokens.join(" ")
+            })
+            .filter(|l| l.starts_with(|c: char| c.is_ascii_digit()))
+            .collect()
+    }
+
+    /// the listing of the module (excluding the nested code objects)
+    fn module_listing(dis: &str) -> &str {
+        dis.split("Disassembly of").next().unwrap()
+    }
+
+    #[test]
+    fn test_disassemble_source() {
+        let src = "x = 1
+f = () -> x
+";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        let lines = dis.lines().map(str::trim_end).collect::<Vec<_>>();
+        // the same layout as `dis.dis` of CPython
+        assert_eq!(
+            lines[..2],
+            [
+                "  1           0 LOAD_CONST               0 (1)",
+                "              2 STORE_NAME               0 (x)",
+            ]
+        );
+        assert!(lines[3]
+            .starts_with("  2           4 LOAD_CONST               1 (<code object <lambda>"));
+        assert_eq!(
+            lines[4],
+            "              6 LOAD_CONST               2 ('<lambda>')"
+        );
+        // nested code objects follow the module, indented
+        let (_, lambda) = dis
+            .split_once("Disassembly of <code object <lambda>")
+            .unwrap();
+        let lambda = lambda.lines().skip(1).collect::<Vec<_>>();
+        assert_eq!(
+            lambda,
+            [
+                "      2           0 LOAD_GLOBAL              0 (x)",
+                "                  2 RETURN_VALUE",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_names_are_interned() {
+        // `f!` is escaped into `f__erg_proc__` in every code object referring to it
+        let src = "f!() = print! 1\ng!() = f!()\nh!() = f!()\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let code = Compiler::new(cfg).compile(src.into(), "exec").unwrap();
+        let mut escaped = code
+            .names
+            .iter()
+            .chain(code.nested_codes().flat_map(|code| code.names.iter()))
+            .filter(|name| &name[..] == "f__erg_proc__")
+            .map(|name| name.clone().into_rc());
+        let first = escaped.next().unwrap();
+        let rest = escaped.collect::<Vec<_>>();
+        assert!(rest.len() >= 2, "{rest:?}");
+        assert!(rest.iter().all(|name| Rc::ptr_eq(name, &first)));
+    }
+
+    fn lambda_disassembly(src: &'static str) -> String {
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        let (_, lambda) = dis
+            .split_once("Disassembly of <code object <lambda>")
+            .unwrap();
+        lambda.to_string()
+    }
+
+    #[test]
+    fn test_lambda_trailing_def_returns_none() {
+        let lambda = lambda_disassembly("f = () =>\n    print! 1\n    y = 2\n");
+        assert!(lambda.contains("POP_TOP"));
+        let lines = lambda.lines().map(str::trim_end).collect::<Vec<_>>();
+        let ret = lines
+            .iter()
+            .position(|l| l.ends_with("RETURN_VALUE"))
+            .unwrap();
+        assert!(lines[ret - 1].ends_with("(None)"));
+        // the last statement leaves nothing, so the preceding POP_TOP must be kept
+        let lambda = lambda_disassembly("g = () -> 1\nf = () ->\n    discard g()\n");
+        assert!(lambda.contains("POP_TOP"));
+        assert!(lambda.contains("(None)"));
+    }
+
+    #[test]
+    fn test_coverage_markers() {
+        let src = "x = 1\n# comment\ny = 2\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            coverage: Some(true),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        // `<lineno> <offset> <opname>` at the start of each line
+        let line_starts = dis
+            .lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+            .filter(|ts| ts.len() >= 3 && ts[1].parse::<usize>().is_ok())
+            .map(|ts| (ts[0].to_string(), ts[2].to_string()))
+            .collect::<Vec<_>>();
+        // the comment line has a `NOP`
+        assert!(line_starts.contains(&("2".to_string(), "NOP".to_string())));
+        assert!(line_starts.iter().any(|(l, _)| l == "3"));
+    }
+
+    #[test]
+    fn test_literal_type_hint() {
+        let cfg = ErgConfig {
+            input: Input::Str("x: Float = 0".into()),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source("x: Float = 0", cfg).unwrap();
+        assert!(dis.contains("LOAD_CONST               0 (0.0)"));
+        // lossy conversions are rejected
+        let cfg = ErgConfig {
+            input: Input::Str("x: Int = 1.5".into()),
+            ..ErgConfig::default()
+        };
+        assert!(disassemble_source("x: Int = 1.5", cfg).is_err());
+    }
+
+    #[test]
+    fn test_main_guard() {
+        let src = "f = () -> 1\ndiscard f()\ng = () -> 3\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            main_guard: Some(true),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        let instrs = listing_instrs(module_listing(&dis));
+        let pos = |pat: &str| instrs.iter().position(|l| l.contains(pat)).unwrap();
+        let guard = pos("(__name__)");
+        assert!(instrs[guard + 1].ends_with("('__main__')"));
+        assert!(instrs[guard + 2].contains("COMPARE_OP"));
+        assert!(instrs[guard + 3].contains("POP_JUMP_IF_FALSE"));
+        // `f` is defined before the guard, `g` after the guarded statement
+        assert!(pos("(f)") < guard);
+        assert!(pos("CALL_FUNCTION") > guard);
+        assert!(pos("(g)") > pos("CALL_FUNCTION"));
+        // the jump target is the start of the definition of `g`
+        let g_begin = instrs[pos("(g)") - 3].split_whitespace().next().unwrap();
+        assert!(instrs[guard + 3].ends_with(&format!("(to {g_begin})")));
+    }
+
+    #[test]
+    fn test_discard_skips_literals() {
+        let src = "f = () -> 1\ndiscard 2, f()\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        let module = module_listing(&dis);
+        assert!(!module.contains("(2)"));
+        assert!(module.contains("CALL_FUNCTION"));
+        assert_eq!(module.matches("POP_TOP").count(), 1);
+        // opt_level 0 keeps the literal
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            opt_level: Some(0),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        let module = module_listing(&dis);
+        assert!(module.contains("(2)"));
+        assert_eq!(module.matches("POP_TOP").count(), 2);
+    }
+
+    #[test]
+    fn test_repeat_until() {
+        let src = "p! = () => print! 1\nrepeat_until! p!, () -> True\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        let lines = listing_instrs(module_listing(&dis));
+        let body = lines
+            .iter()
+            .position(|l| l.contains("LOAD_NAME") && l.contains("p__erg_proc__"))
+            .unwrap();
+        let call = lines
+            .iter()
+            .position(|l| l.contains("CALL_FUNCTION"))
+            .unwrap();
+        let cond = lines.iter().position(|l| l.contains("(True)")).unwrap();
+        let back_edge = lines
+            .iter()
+            .position(|l| l.contains("POP_JUMP_IF_FALSE"))
+            .unwrap();
+        assert!(body < call && call < cond && cond < back_edge);
+        // the back-edge targets the top of the body
+        let body_begin = lines[body].split_whitespace().next().unwrap();
+        assert!(lines[back_edge].ends_with(&format!("(to {body_begin})")));
+    }
+
+    #[test]
+    fn test_const_pool_distinguishes_int_and_bool() {
+        let src = "x = 1\ny = True\nz = 1\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let code = Compiler::new(cfg).compile(src.into(), "exec").unwrap();
+        let one = code
+            .consts
+            .iter()
+            .position(|c| c.marshal_eq(&ValueObj::Nat(1)))
+            .unwrap();
+        let true_ = code
+            .consts
+            .iter()
+            .position(|c| c.marshal_eq(&ValueObj::True))
+            .unwrap();
+        assert_ne!(one, true_);
+        assert_eq!(code.consts.len(), 3); // 1, True, None
+        assert!(!ValueObj::Nat(1).marshal_eq(&ValueObj::True));
+        assert!(!ValueObj::Float(0.0).marshal_eq(&ValueObj::Float(-0.0)));
+    }
+
+    fn module_instrs(src: &'static str) -> Vec<String> {
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            python_ver: Some(3425),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        listing_instrs(module_listing(&dis))
+            .iter()
+            .map(|l| l.split_once(' ').unwrap().1.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_log_prints_after_statement() {
+        // `print!` prints "a" as soon as it is called
+        let instrs = module_instrs("print! print!(\"a\")\nprint! \"b\"\n");
+        let calls = instrs
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.starts_with("CALL_FUNCTION"))
+            .map(|(n, _)| n)
+            .collect::<Vec<_>>();
+        assert_eq!(calls[1], calls[0] + 1); // inner, then outer
+                                            // `log` prints "a" after the outer `print!` is executed (and before "b")
+        let instrs = module_instrs("print! log(\"a\")\nprint! \"b\"\n");
+        let outer_call = instrs
+            .iter()
+            .position(|i| i.starts_with("CALL_FUNCTION"))
+            .unwrap();
+        let log_print = instrs
+            .iter()
+            .rposition(|i| i.starts_with("LOAD_NAME") && i.ends_with("(__erg_log_0__)"))
+            .unwrap();
+        let print_b = instrs.iter().position(|i| i.contains("('b')")).unwrap();
+        assert!(outer_call < log_print && log_print < print_b);
+        assert!(instrs[log_print - 1].ends_with("(print)"));
+        // the temporary is deleted as soon as it is loaded
+        let tmp_idx = instrs[log_print].split_whitespace().nth(1).unwrap();
+        assert_eq!(
+            instrs[log_print + 1],
+            format!("DELETE_NAME {tmp_idx} (__erg_log_0__)")
+        );
+        assert_eq!(instrs[log_print + 2], "CALL_FUNCTION 1");
+        let stores = instrs
+            .iter()
+            .filter(|i| i.starts_with("STORE_NAME"))
+            .count();
+        let deletes = instrs
+            .iter()
+            .filter(|i| i.starts_with("DELETE_NAME"))
+            .count();
+        assert_eq!((stores, deletes), (1, 1));
+    }
+
+    #[test]
+    fn test_named_expr() {
+        // the value is stored to `n` and also passed to `print!`
+        let instrs = module_instrs("print!((n := 1))\nprint! n\n");
+        let dup = instrs.iter().position(|i| i == "DUP_TOP").unwrap();
+        assert_eq!(
+            instrs[dup - 1..dup + 3],
+            [
+                "LOAD_CONST 0 (1)",
+                "DUP_TOP",
+                "STORE_NAME 1 (n)",
+                "CALL_FUNCTION 1"
+            ]
+        );
+        assert!(instrs.contains(&"LOAD_NAME 1 (n)".to_string()));
+        // in a subroutine, `n` is a local variable
+        let src = "f x = (n := x)\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        let (_, f) = dis.split_once("Disassembly of <code object f").unwrap();
+        assert!(f.contains("DUP_TOP"));
+        assert!(f.contains("STORE_FAST"));
+        assert!(!f.contains("STORE_GLOBAL"));
+    }
+
+    #[test]
+    fn test_tuple_from_source() {
+        let instrs = module_instrs("x = 1\nprint!((x, 2))\n");
+        let build = instrs.iter().position(|i| i == "BUILD_TUPLE 2").unwrap();
+        assert_eq!(
+            instrs[build - 2..build],
+            ["LOAD_NAME 0 (x)", "LOAD_CONST 1 (2)"]
+        );
+        // a constant tuple is loaded at once
+        let instrs = module_instrs("t = (1,)\ne = ()\n");
+        assert!(!instrs.iter().any(|i| i.starts_with("BUILD_TUPLE")));
+        let store_t = instrs.iter().position(|i| i == "STORE_NAME 0 (t)").unwrap();
+        assert!(instrs[store_t - 1].starts_with("LOAD_CONST 0"));
+        let store_e = instrs.iter().position(|i| i == "STORE_NAME 1 (e)").unwrap();
+        assert!(instrs[store_e - 1].starts_with("LOAD_CONST 1"));
+    }
+
+    #[test]
+    fn test_dict_from_source() {
+        // constant keys are passed as a tuple
+        let instrs = module_instrs("x = 1\nprint! {\"a\": x, \"b\": 2}\n");
+        let build = instrs
+            .iter()
+            .position(|i| i.starts_with("BUILD_CONST_KEY_MAP"))
+            .unwrap();
+        assert_eq!(instrs[build], "BUILD_CONST_KEY_MAP 2");
+        assert_eq!(
+            instrs[build - 3..build - 1],
+            ["LOAD_NAME 0 (x)", "LOAD_CONST 1 (2)"]
+        );
+        // non-constant keys are pushed alternately with the values
+        let instrs = module_instrs("k = \"a\"\nprint! {k: 1}\n");
+        let build = instrs.iter().position(|i| i == "BUILD_MAP 1").unwrap();
+        assert_eq!(
+            instrs[build - 2..build],
+            ["LOAD_NAME 0 (k)", "LOAD_CONST 1 (1)"]
+        );
+        let instrs = module_instrs("d = {:}\n");
+        assert!(instrs.contains(&"BUILD_MAP 0".to_string()));
+    }
+
+    #[test]
+    fn test_set_from_source() {
+        let instrs = module_instrs("x = 1\nprint! {x, 2}\n");
+        let build = instrs.iter().position(|i| i == "BUILD_SET 2").unwrap();
+        assert_eq!(
+            instrs[build - 2..build],
+            ["LOAD_NAME 0 (x)", "LOAD_CONST 1 (2)"]
+        );
+        // the element type is inferred from the elements
+        let instrs = module_instrs("print! {1}\n");
+        assert!(instrs.contains(&"SET_UPDATE 1".to_string()));
+    }
+
+    #[test]
+    fn test_slice_from_source() {
+        let instrs = module_instrs(
+            "arr = [1, 2, 3]\na = arr[1:]\nb = arr[:2]\nc = arr[::2]\nd = arr[::-1]\n",
+        );
+        // the three instructions before `BUILD_SLICE`, and `BUILD_SLICE` itself
+        let slices = instrs
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.starts_with("BUILD_SLICE"))
+            .map(|(n, _)| instrs[n - 3..=n].join("; "))
+            .collect::<Vec<_>>();
+        // omitted bounds are `None`
+        assert_eq!(
+            slices,
+            [
+                "LOAD_NAME 0 (arr); LOAD_CONST 0 (1); LOAD_CONST 3 (None); BUILD_SLICE 2",
+                "LOAD_NAME 0 (arr); LOAD_CONST 3 (None); LOAD_CONST 1 (2); BUILD_SLICE 2",
+                "LOAD_CONST 3 (None); LOAD_CONST 3 (None); LOAD_CONST 1 (2); BUILD_SLICE 3",
+                "LOAD_CONST 3 (None); LOAD_CONST 3 (None); LOAD_CONST 4 (-1); BUILD_SLICE 3",
+            ]
+        );
+        let subscrs = instrs.iter().filter(|i| *i == "BINARY_SUBSCR").count();
+        assert_eq!(subscrs, 4);
+        // only arrays and strings can be subscripted for now
+        let src = "x = 1\ny = x[1:]\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        assert!(disassemble_source(src, cfg).is_err());
+    }
+
+    #[test]
+    fn test_aug_assign_from_source() {
+        let instrs = module_instrs("x = 1\nx += 2\n");
+        let inplace = instrs.iter().position(|i| i == "INPLACE_ADD").unwrap();
+        assert_eq!(
+            instrs[inplace - 2..=inplace + 1],
+            [
+                "LOAD_NAME 0 (x)",
+                "LOAD_CONST 1 (2)",
+                "INPLACE_ADD",
+                "STORE_NAME 0 (x)"
+            ]
+        );
+        let instrs = module_instrs("x = 1.0\nx /= 2.0\n");
+        assert!(instrs.contains(&"INPLACE_TRUE_DIVIDE".to_string()));
+        // the target must be defined and keeps its type (`/=` makes a float)
+        for src in ["y += 1\n", "x = 1\nx += \"a\"\n", "x = 1\nx /= 2\n"] {
+            let cfg = ErgConfig {
+                input: Input::Str(src.into()),
+                ..ErgConfig::default()
+            };
+            assert!(disassemble_source(src, cfg).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn test_str_interp_from_source() {
+        let instrs = module_instrs("x = 1\ny = \"a\"\ns = \"x = \\{x!r:>5}, \\{y}\"\n");
+        let build = instrs.iter().position(|i| i == "BUILD_STRING 4").unwrap();
+        assert_eq!(
+            instrs[build - 7..=build + 1],
+            [
+                "LOAD_CONST 2 ('x = ')",
+                "LOAD_NAME 0 (x)",
+                "LOAD_CONST 3 ('>5')",
+                "FORMAT_VALUE 6 (repr, with format)",
+                "LOAD_CONST 4 (', ')",
+                "LOAD_NAME 1 (y)",
+                "FORMAT_VALUE 0",
+                "BUILD_STRING 4",
+                "STORE_NAME 2 (s)"
+            ]
+        );
+        // a single embedded expression needs no `BUILD_STRING`
+        let instrs = module_instrs("x = 1\ns = \"\\{x!s}\"\n");
+        let format = instrs
+            .iter()
+            .position(|i| i == "FORMAT_VALUE 1 (str)")
+            .unwrap();
+        assert_eq!(instrs[format + 1], "STORE_NAME 1 (s)");
+        for src in ["s = \"\\{z}\"\n", "s = \"\\{1\"\n"] {
+            let cfg = ErgConfig {
+                input: Input::Str(src.into()),
+                ..ErgConfig::default()
+            };
+            assert!(disassemble_source(src, cfg).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn test_array_comprehension_from_source() {
+        let src = "xs = [1, 2]\nflag = True\nys = [x | x <- xs, flag]\n";
+        let instrs = module_instrs(src);
+        let call = instrs.iter().position(|i| i == "CALL_FUNCTION 1").unwrap();
+        assert!(instrs[call - 5].starts_with("LOAD_CONST 3 (<code object <listcomp>"));
+        assert_eq!(
+            instrs[call - 4..=call],
+            [
+                "LOAD_CONST 4 ('<listcomp>')",
+                "MAKE_FUNCTION 0",
+                "LOAD_NAME 0 (xs)",
+                "GET_ITER",
+                "CALL_FUNCTION 1",
+            ]
+        );
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            python_ver: Some(3425),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        let (_, listcomp) = dis
+            .split_once("Disassembly of <code object <listcomp>")
+            .unwrap();
+        let listcomp = listing_instrs(listcomp)
+            .iter()
+            .map(|l| l.split_once(' ').unwrap().1.to_string())
+            .collect::<Vec<_>>();
+        let start = listcomp.iter().position(|i| i == "BUILD_LIST 0").unwrap();
+        // the loop variable is local to the comprehension
+        // (the jump args of Python 3.9 are byte offsets)
+        assert_eq!(
+            listcomp[start..],
+            [
+                "BUILD_LIST 0",
+                "LOAD_FAST 0 (.0)",
+                "FOR_ITER 12 (to 18)",
+                "STORE_FAST 1 (x)",
+                "LOAD_GLOBAL 0 (flag)",
+                "POP_JUMP_IF_FALSE 4 (to 4)",
+                "LOAD_FAST 1 (x)",
+                "LIST_APPEND 2",
+                "JUMP_ABSOLUTE 4 (to 4)",
+                "RETURN_VALUE",
+            ]
+        );
+        // guards must be `Bool`, and `x` is not visible outside
+        for src in [
+            "ys = [x | x <- [1], 1]\n",
+            "ys = [x | x <- 1]\n",
+            "ys = [x | y <- [1]]\n",
+            "ys = [x | x <- [1]]\nprint! x\n",
+        ] {
+            let cfg = ErgConfig {
+                input: Input::Str(src.into()),
+                ..ErgConfig::default()
+            };
+            assert!(disassemble_source(src, cfg).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn test_spread_args_from_source() {
+        let instrs = module_instrs("xs = [1, 2]\nprint!(*xs)\n");
+        let call = instrs
+            .iter()
+            .position(|i| i.starts_with("CALL_FUNCTION_EX"))
+            .unwrap();
+        assert_eq!(
+            instrs[call - 2..=call],
+            [
+                "LOAD_NAME 1 (print)",
+                "LOAD_NAME 0 (xs)",
+                "CALL_FUNCTION_EX 0"
+            ]
+        );
+        // the keyword arguments are merged into one dict
+        let instrs = module_instrs("kw = {\"a\": 1}\nquit(code: 1, **kw)\n");
+        let call = instrs
+            .iter()
+            .position(|i| i.starts_with("CALL_FUNCTION_EX"))
+            .unwrap();
+        assert_eq!(
+            instrs[call - 6..=call],
+            [
+                "BUILD_TUPLE 0",
+                "LOAD_CONST 1 ('code')",
+                "LOAD_CONST 2 (1)",
+                "BUILD_MAP 1",
+                "LOAD_NAME 0 (kw)",
+                "DICT_MERGE 1",
+                "CALL_FUNCTION_EX 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_optimize_asserts() {
+        let src = "x = True\nassert x\n";
+        // `LOAD_ASSERTION_ERROR` is available since Python 3.9
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            python_ver: Some(3425),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        assert!(dis.contains("LOAD_ASSERTION_ERROR"));
+        assert!(dis.contains("RAISE_VARARGS"));
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            optimize_asserts: Some(true),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        assert!(!dis.contains("LOAD_ASSERTION_ERROR"));
+        assert!(!dis.contains("RAISE_VARARGS"));
+        assert!(!dis.contains("POP_JUMP_IF_TRUE"));
+    }
+
+    #[test]
+    fn test_pipe_input_compiles_as_module() {
+        let src = "x = 1\nprint! x\ny = x\n";
+        let cfg = ErgConfig {
+            input: Input::Pipe(src.into()),
+            ..ErgConfig::default()
+        };
+        let code = Compiler::new(cfg).compile(src.into(), "exec").unwrap();
+        assert_eq!(&code.filename[..], "<stdin>");
+        let dis = code.code_info();
+        // no `print` wrapping the whole module (unlike the REPL), only `print! x` calls it
+        assert_eq!(dis.matches("(print)").count(), 1);
+        assert_eq!(dis.matches("CALL_FUNCTION").count(), 1);
+        let instrs = dis
+            .split_once("lnotab")
+            .unwrap()
+            .1
+            .lines()
+            .filter(|l| l.starts_with("  "))
+            .collect::<Vec<_>>();
+        assert!(instrs[0].contains("LOAD_CONST") && instrs[0].ends_with("(1)"));
+        assert!(instrs[instrs.len() - 2].ends_with("(None)"));
+    }
+
+    #[test]
+    fn test_logical_not() {
+        let instrs = module_instrs("a = True\nb = False\nc = not (a and b)\n");
+        assert_eq!(
+            instrs.iter().filter(|i| i.starts_with("UNARY_NOT")).count(),
+            1
+        );
+        let not = instrs
+            .iter()
+            .position(|i| i.starts_with("UNARY_NOT"))
+            .unwrap();
+        // the result of `a and b` is negated
+        assert!(instrs[not - 2].starts_with("JUMP_IF_FALSE_OR_POP"));
+        assert!(instrs[not - 1].ends_with("(b)"));
+        assert!(instrs[not + 1].ends_with("(c)"));
+        // `not` binds tighter than `and`: `(not a) and b`
+        let instrs = module_instrs("a = True\nb = False\nc = not a and b\n");
+        let not = instrs
+            .iter()
+            .position(|i| i.starts_with("UNARY_NOT"))
+            .unwrap();
+        assert!(instrs[not - 1].ends_with("(a)"));
+        assert!(instrs[not + 1].starts_with("JUMP_IF_FALSE_OR_POP"));
+    }
+
+    #[test]
+    fn test_record_from_source() {
+        let src = "r = {y = 1; x! = 2}\nf() = {y = 3; x! = 4}\ns = f()\nprint! r.x!, s.x!\n";
+        let instrs = module_instrs(src);
+        // the type is created once at the head of the module
+        assert_eq!(
+            instrs
+                .iter()
+                .filter(|i| i.ends_with("(namedtuple)"))
+                .count(),
+            1
+        );
+        let import = instrs
+            .iter()
+            .position(|i| i == "IMPORT_NAME 0 (collections)")
+            .unwrap();
+        assert_eq!(instrs[import + 4], "LOAD_CONST 3 (('y', 'x__erg_proc__'))");
+        assert_eq!(
+            instrs[import + 6],
+            "STORE_NAME 2 (__erg_record(y, x__erg_proc__)__)"
+        );
+        assert_eq!(
+            instrs[import + 8],
+            "LOAD_NAME 2 (__erg_record(y, x__erg_proc__)__)"
+        );
+        // the attribute names are escaped in the same way as the fields
+        // (and registered once)
+        assert_eq!(
+            instrs
+                .iter()
+                .filter(|i| *i == "LOAD_ATTR 7 (x__erg_proc__)")
+                .count(),
+            2
+        );
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg).unwrap();
+        // `f` loads the type defined in the module
+        let f = dis.split("Disassembly of").nth(1).unwrap();
+        assert!(f.contains("LOAD_GLOBAL              0 (__erg_record(y, x__erg_proc__)__)"));
+        assert!(!f.contains("namedtuple"));
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let target = get_python_version(target_python_ver(&cfg)).unwrap();
+        if find_python_executable_for(target).is_none() {
+            return;
+        }
+        let dir = env::temp_dir().join(format!("erg_test_record_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pyc = dir.join("record.pyc");
+        Compiler::new(cfg)
+            .compile_and_dump_as_pyc(src.into(), &pyc, "exec")
+            .unwrap();
+        assert!(run_pyc(&pyc, Vec::<&str>::new()).unwrap().success());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_jumps_for_python39() {
+        let src = "a = True\nb = False\nc = b and a\nassert not c\nxs = [1, 2]\nys = [x | x <- xs, a]\nprint! c, ys\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            python_ver: Some(3425),
+            ..ErgConfig::default()
+        };
+        let dis = disassemble_source(src, cfg.copy()).unwrap();
+        // Python 3.9 jumps to byte offsets (not to instruction offsets as 3.10+)
+        let instrs = listing_instrs(module_listing(&dis));
+        assert!(instrs.contains(&"10 JUMP_IF_FALSE_OR_POP 14 (to 14)".to_string()));
+        assert!(instrs.contains(&"14 STORE_NAME 2 (c)".to_string()));
+        if find_python_executable_for(PythonVersion::new(3, 9, 0)).is_none() {
+            return;
+        }
+        let dir = env::temp_dir().join(format!("erg_test_jumps_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pyc = dir.join("jumps.pyc");
+        Compiler::new(cfg)
+            .compile_and_dump_as_pyc(src.into(), &pyc, "exec")
+            .unwrap();
+        assert!(run_pyc(&pyc, Vec::<&str>::new()).unwrap().success());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_decorators_from_source() {
+        let instrs = module_instrs("wrap(f) = 1\ntrace(f) = 2\n@wrap\n@trace\ng() = 1\n");
+        let load_wrap = instrs
+            .iter()
+            .position(|i| i == "LOAD_NAME 0 (wrap)")
+            .unwrap();
+        // the decorators are loaded in the order of the source, then applied from the nearest one
+        assert_eq!(instrs[load_wrap + 1], "LOAD_NAME 1 (trace)");
+        assert!(instrs[load_wrap + 4].starts_with("MAKE_FUNCTION"));
+        assert_eq!(instrs[load_wrap + 5], "CALL_FUNCTION 1");
+        assert_eq!(instrs[load_wrap + 6], "CALL_FUNCTION 1");
+        assert_eq!(instrs[load_wrap + 7], "STORE_NAME 2 (g)");
+    }
+
+    #[test]
+    fn test_qualname() {
+        let src = "f() =\n    g() = 1\n    h = () -> 2\n    discard h()\n    g()\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let code = Compiler::new(cfg).compile(src.into(), "exec").unwrap();
+        let nested = |code: &CodeObj| {
+            code.consts
+                .iter()
+                .filter_map(|c| match c {
+                    ValueObj::Code(c) => Some(c.as_ref().clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(&code.qualname[..], "<module>");
+        let f = &nested(&code)[0];
+        assert_eq!(&f.qualname[..], "f");
+        let inner = nested(f);
+        let qualnames = inner.iter().map(|c| &c.qualname[..]).collect::<Vec<_>>();
+        assert_eq!(qualnames, ["f.<locals>.g", "f.<locals>.<lambda>"]);
+        // `MAKE_FUNCTION` takes the qualified name
+        assert!(f
+            .consts
+            .iter()
+            .any(|c| c.marshal_eq(&ValueObj::from("f.<locals>.g"))));
+    }
+
+    #[test]
+    fn test_run_pyc() {
+        let dir = env::temp_dir().join(format!("erg_test_run_pyc_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let src = "print! \"hi\"\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            ..ErgConfig::default()
+        };
+        let target = target_python_ver(&cfg);
+        let pyc = dir.join("a.pyc");
+        Compiler::new(cfg)
+            .compile_and_dump_as_pyc(src.into(), &pyc, "exec")
+            .unwrap();
+        // the .pyc file is stamped with the version the bytecode is generated for
+        let header = fs::read(&pyc).unwrap();
+        assert_eq!(
+            u32::from(u16::from_le_bytes([header[0], header[1]])),
+            target
+        );
+        let version = get_python_version(target).unwrap();
+        if find_python_executable_for(version).is_some() {
+            assert!(run_pyc(&pyc, ["arg"]).unwrap().success());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dump_as_pyc_error() {
+        let src = "print! \"hi\"\n";
+        let cfg = ErgConfig {
+            input: Input::Str(src.into()),
+            python_ver: Some(3425),
+            ..ErgConfig::default()
+        };
+        let pyc = env::temp_dir()
+            .join(format!("erg_test_no_such_dir_{}", std::process::id()))
+            .join("a.pyc");
+        let errs = Compiler::new(cfg)
+            .compile_and_dump_as_pyc(src.into(), &pyc, "exec")
+            .unwrap_err();
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs.first().unwrap().core.kind, ErrorKind::IoError);
+    }
+}
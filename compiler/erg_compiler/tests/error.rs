@@ -0,0 +1,18 @@
+extern crate erg_common;
+extern crate erg_compiler;
+
+mod tests {
+    use erg_common::config::Input;
+    use erg_common::error::Location;
+    use erg_common::traits::Stream;
+
+    use erg_compiler::error::{CompileError, CompileErrors};
+
+    #[test]
+    fn test_into_result() {
+        assert_eq!(CompileErrors::empty().into_result(1).ok(), Some(1));
+        let err = CompileError::compiler_bug(0, Input::Dummy, Location::Unknown, "test", 0);
+        let errs = CompileErrors::from(err).into_result(1).unwrap_err();
+        assert_eq!(errs.len(), 1);
+    }
+}
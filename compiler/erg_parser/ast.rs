@@ -3,7 +3,6 @@ use std::borrow::Borrow;
 use std::fmt;
 
 use erg_common::error::Location;
-use erg_common::set::Set;
 use erg_common::traits::{Locational, NestedDisplay, Stream};
 use erg_common::ty::SubrKind;
 use erg_common::value::ValueObj;
@@ -129,13 +128,26 @@ impl KwArg {
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Args {
     pos_args: Vec<PosArg>,
+    /// `*args`
+    var_args: Option<Box<PosArg>>,
     kw_args: Vec<KwArg>,
+    /// `**kwargs`
+    kw_var_args: Option<Box<PosArg>>,
     paren: Option<(Token, Token)>,
 }
 
 impl NestedDisplay for Args {
     fn fmt_nest(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
-        fmt_lines(self.pos_args.iter(), f, level)
+        fmt_lines(self.pos_args.iter(), f, level)?;
+        if let Some(var_args) = &self.var_args {
+            write!(f, "*")?;
+            var_args.fmt_nest(f, level)?;
+        }
+        if let Some(kw_var_args) = &self.kw_var_args {
+            write!(f, "**")?;
+            kw_var_args.fmt_nest(f, level)?;
+        }
+        Ok(())
     }
 }
 
@@ -161,7 +173,9 @@ impl Args {
     ) -> Self {
         Self {
             pos_args,
+            var_args: None,
             kw_args,
+            kw_var_args: None,
             paren,
         }
     }
@@ -170,7 +184,37 @@ impl Args {
         Self::new(vec![], vec![], None)
     }
 
-    // for replacing to hir::Args
+    pub fn set_var_args(&mut self, var_args: PosArg) {
+        self.var_args = Some(Box::new(var_args));
+    }
+
+    pub fn set_kw_var_args(&mut self, kw_var_args: PosArg) {
+        self.kw_var_args = Some(Box::new(kw_var_args));
+    }
+
+    pub fn var_args(&self) -> Option<&PosArg> {
+        self.var_args.as_deref()
+    }
+
+    pub fn kw_var_args(&self) -> Option<&PosArg> {
+        self.kw_var_args.as_deref()
+    }
+
+    pub fn take_var_args(&mut self) -> Option<PosArg> {
+        self.var_args.take().map(|arg| *arg)
+    }
+
+    pub fn take_kw_var_args(&mut self) -> Option<PosArg> {
+        self.kw_var_args.take().map(|arg| *arg)
+    }
+
+    /// whether `*args` or `**kwargs` is passed
+    #[inline]
+    pub fn has_spread(&self) -> bool {
+        self.var_args.is_some() || self.kw_var_args.is_some()
+    }
+
+    // for replacing to hir::Args (take `*args` and `**kwargs` first)
     pub fn deconstruct(self) -> (Vec<PosArg>, Vec<KwArg>, Option<(Token, Token)>) {
         (self.pos_args, self.kw_args, self.paren)
     }
@@ -299,8 +343,8 @@ impl TupleAttribute {
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Subscript {
-    obj: Box<Expr>,
-    index: Box<Expr>,
+    pub obj: Box<Expr>,
+    pub index: Box<Expr>,
 }
 
 impl fmt::Display for Subscript {
@@ -412,22 +456,253 @@ impl Array {
     }
 }
 
-/// DictはキーつきArrayとして実現される
+/// e.g. `[x | x <- xs, x > 0]`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ArrayComprehension {
+    pub l_sqbr: Token,
+    pub r_sqbr: Token,
+    pub elem: Box<Expr>,
+    /// the loop variable (`x` of `x <- xs`)
+    pub var: VarName,
+    pub iter: Box<Expr>,
+    pub guards: Vec<Expr>,
+    pub id: DefId,
+}
+
+impl NestedDisplay for ArrayComprehension {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "[{} | {} <- {}", self.elem, self.var, self.iter)?;
+        for guard in self.guards.iter() {
+            write!(f, ", {guard}")?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl_display_from_nested!(ArrayComprehension);
+impl_locational!(ArrayComprehension, l_sqbr, r_sqbr);
+
+impl ArrayComprehension {
+    pub fn new(
+        l_sqbr: Token,
+        r_sqbr: Token,
+        elem: Expr,
+        var: VarName,
+        iter: Expr,
+        guards: Vec<Expr>,
+        id: DefId,
+    ) -> Self {
+        Self {
+            l_sqbr,
+            r_sqbr,
+            elem: Box::new(elem),
+            var,
+            iter: Box::new(iter),
+            guards,
+            id,
+        }
+    }
+}
+
+/// e.g. `(1, 2)`, `(1,)`, `()`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Tuple {
+    /// the parentheses are kept in `elems`
+    pub elems: Args,
+}
+
+impl NestedDisplay for Tuple {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        if self.elems.pos_args().len() == 1 {
+            write!(f, "({},)", self.elems)
+        } else {
+            write!(f, "({})", self.elems)
+        }
+    }
+}
+
+impl_display_from_nested!(Tuple);
+impl_locational!(Tuple, elems, elems);
+
+impl Tuple {
+    pub const fn new(elems: Args) -> Self {
+        Self { elems }
+    }
+}
+
+/// The conversion of an embedded expression of an interpolated string (`!s`, `!r`, `!a` in Python)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FormatConversion {
+    Str = 1,
+    Repr = 2,
+    Ascii = 3,
+}
+
+impl FormatConversion {
+    pub const fn as_char(&self) -> char {
+        match self {
+            Self::Str => 's',
+            Self::Repr => 'r',
+            Self::Ascii => 'a',
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StrSegment {
+    Lit(Str),
+    /// `\{x}`, `\{x!r}`, `\{x:>5}`
+    Expr {
+        expr: Box<Expr>,
+        conversion: Option<FormatConversion>,
+        spec: Option<Str>,
+    },
+}
+
+impl fmt::Display for StrSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lit(s) => write!(f, "{s}"),
+            Self::Expr {
+                expr,
+                conversion,
+                spec,
+            } => {
+                write!(f, "\\{{{expr}")?;
+                if let Some(conv) = conversion {
+                    write!(f, "!{}", conv.as_char())?;
+                }
+                if let Some(spec) = spec {
+                    write!(f, ":{spec}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// e.g. `"x = \{x}"`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StrInterp {
+    /// `"x = \{`
+    pub left: Token,
+    /// `}"`
+    pub right: Token,
+    pub segments: Vec<StrSegment>,
+}
+
+impl NestedDisplay for StrInterp {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "\"")?;
+        for seg in self.segments.iter() {
+            write!(f, "{seg}")?;
+        }
+        write!(f, "\"")
+    }
+}
+
+impl_display_from_nested!(StrInterp);
+impl_locational!(StrInterp, left, right);
+
+impl StrInterp {
+    pub const fn new(left: Token, right: Token, segments: Vec<StrSegment>) -> Self {
+        Self {
+            left,
+            right,
+            segments,
+        }
+    }
+}
+
+/// a Python-style slice used as a subscript index (e.g. `1:`, `:2`, `::-1`)
+/// omitted bounds are `None`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Slice {
+    /// the first `:` (or `::`)
+    pub colon: Token,
+    pub start: Option<Box<Expr>>,
+    pub stop: Option<Box<Expr>>,
+    pub step: Option<Box<Expr>>,
+}
+
+impl NestedDisplay for Slice {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        if let Some(start) = &self.start {
+            write!(f, "{start}")?;
+        }
+        write!(f, ":")?;
+        if let Some(stop) = &self.stop {
+            write!(f, "{stop}")?;
+        }
+        if let Some(step) = &self.step {
+            write!(f, ":{step}")?;
+        }
+        Ok(())
+    }
+}
+
+impl_display_from_nested!(Slice);
+impl_locational!(Slice, colon, colon);
+
+impl Slice {
+    pub fn new(colon: Token, start: Option<Expr>, stop: Option<Expr>, step: Option<Expr>) -> Self {
+        Self {
+            colon,
+            start: start.map(Box::new),
+            stop: stop.map(Box::new),
+            step: step.map(Box::new),
+        }
+    }
+}
+
+/// e.g. `"a": 1` of `{"a": 1}`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyValue {
+    pub key: Expr,
+    pub value: Expr,
+}
+
+impl NestedDisplay for KeyValue {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.value)
+    }
+}
+
+impl_display_from_nested!(KeyValue);
+
+impl Locational for KeyValue {
+    fn loc(&self) -> Location {
+        Location::concat(&self.key, &self.value)
+    }
+}
+
+impl KeyValue {
+    pub const fn new(key: Expr, value: Expr) -> Self {
+        Self { key, value }
+    }
+}
+
+/// e.g. `{"a": 1, "b": 2}`, `{:}` (empty)
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Dict {
-    l_brace: Token,
-    r_brace: Token,
-    pub attrs: Args,
-    guard: Option<Box<Expr>>,
+    pub l_brace: Token,
+    pub r_brace: Token,
+    pub kvs: Vec<KeyValue>,
 }
 
 impl NestedDisplay for Dict {
     fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
-        if let Some(guard) = &self.guard {
-            write!(f, "{{{} | {guard}}}", self.attrs)
-        } else {
-            write!(f, "{{{}}}", self.attrs)
+        if self.kvs.is_empty() {
+            return write!(f, "{{:}}");
         }
+        write!(f, "{{")?;
+        for (i, kv) in self.kvs.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{kv}")?;
+        }
+        write!(f, "}}")
     }
 }
 
@@ -435,12 +710,73 @@ impl_display_from_nested!(Dict);
 impl_locational!(Dict, l_brace, r_brace);
 
 impl Dict {
-    pub fn new(l_brace: Token, r_brace: Token, attrs: Args, guard: Option<Expr>) -> Self {
+    pub const fn new(l_brace: Token, r_brace: Token, kvs: Vec<KeyValue>) -> Self {
+        Self {
+            l_brace,
+            r_brace,
+            kvs,
+        }
+    }
+}
+
+/// e.g. `{1, 2, 3}`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Set {
+    pub l_brace: Token,
+    pub r_brace: Token,
+    pub elems: Args,
+}
+
+impl NestedDisplay for Set {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "{{{}}}", self.elems)
+    }
+}
+
+impl_display_from_nested!(Set);
+impl_locational!(Set, l_brace, r_brace);
+
+impl Set {
+    pub const fn new(l_brace: Token, r_brace: Token, elems: Args) -> Self {
+        Self {
+            l_brace,
+            r_brace,
+            elems,
+        }
+    }
+}
+
+/// e.g. `{x = 1; y = 2}`
+/// The attributes are kept in the order of definition
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Record {
+    pub l_brace: Token,
+    pub r_brace: Token,
+    pub attrs: Vec<Def>,
+}
+
+impl NestedDisplay for Record {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, attr) in self.attrs.iter().enumerate() {
+            if i != 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{attr}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl_display_from_nested!(Record);
+impl_locational!(Record, l_brace, r_brace);
+
+impl Record {
+    pub const fn new(l_brace: Token, r_brace: Token, attrs: Vec<Def>) -> Self {
         Self {
             l_brace,
             r_brace,
             attrs,
-            guard: guard.map(Box::new),
         }
     }
 }
@@ -449,6 +785,8 @@ impl Dict {
 pub struct BinOp {
     pub op: Token,
     pub args: [Box<Expr>; 2],
+    /// the lhs is a comparison chained with this one in the source (`a < b < c`, not `(a < b) < c`)
+    pub chained: bool,
 }
 
 impl NestedDisplay for BinOp {
@@ -473,6 +811,7 @@ impl BinOp {
         Self {
             op,
             args: [Box::new(lhs), Box::new(rhs)],
+            chained: false,
         }
     }
 }
@@ -1307,6 +1646,14 @@ impl Decorator {
     pub const fn new(expr: Expr) -> Self {
         Self(expr)
     }
+
+    pub const fn expr(&self) -> &Expr {
+        &self.0
+    }
+
+    pub fn into_expr(self) -> Expr {
+        self.0
+    }
 }
 
 /// symbol as a left value
@@ -1499,6 +1846,8 @@ pub enum VarPattern {
     Discard(Token),
     VarName(VarName),
     SelfDot(VarName), // only self-attribute can assign once
+    /// e.g. `*xs` of `[x, *xs] = [1, 2, 3]`
+    VarArgsName(VarName),
     /// e.g. `[x, y, z]` of `[x, y, z] = [1, 2, 3]`
     Array(VarArrayPattern),
     /// e.g. `(x, y, z)` of `(x, y, z) = (1, 2, 3)`
@@ -1513,6 +1862,7 @@ impl NestedDisplay for VarPattern {
             Self::Discard(_) => write!(f, "_"),
             Self::VarName(n) => write!(f, "{}", n),
             Self::SelfDot(n) => write!(f, "self.{}", n),
+            Self::VarArgsName(n) => write!(f, "*{}", n),
             Self::Array(a) => write!(f, "{}", a),
             Self::Tuple(t) => write!(f, "{}", t),
             Self::Record(r) => write!(f, "{}", r),
@@ -1521,19 +1871,19 @@ impl NestedDisplay for VarPattern {
 }
 
 impl_display_from_nested!(VarPattern);
-impl_locational_for_enum!(VarPattern; Discard, VarName, SelfDot, Array, Tuple, Record);
+impl_locational_for_enum!(VarPattern; Discard, VarName, SelfDot, VarArgsName, Array, Tuple, Record);
 
 impl VarPattern {
     pub const fn inspect(&self) -> Option<&Str> {
         match self {
-            Self::VarName(n) | Self::SelfDot(n) => Some(n.inspect()),
+            Self::VarName(n) | Self::SelfDot(n) | Self::VarArgsName(n) => Some(n.inspect()),
             _ => None,
         }
     }
 
     pub fn inspects(&self) -> Vec<&Str> {
         match self {
-            Self::VarName(n) | Self::SelfDot(n) => vec![n.inspect()],
+            Self::VarName(n) | Self::SelfDot(n) | Self::VarArgsName(n) => vec![n.inspect()],
             Self::Array(VarArrayPattern { elems, .. })
             | Self::Tuple(VarTuplePattern { elems, .. })
             | Self::Record(VarRecordPattern { elems, .. }) => {
@@ -1655,10 +2005,43 @@ impl ParamArrayPattern {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ParamTuplePattern {
+    l_paren: Token,
+    pub elems: Params,
+    r_paren: Token,
+}
+
+impl NestedDisplay for ParamTuplePattern {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "({})", self.elems)
+    }
+}
+
+impl_display_from_nested!(ParamTuplePattern);
+impl_locational!(ParamTuplePattern, l_paren, r_paren);
+
+impl ParamTuplePattern {
+    pub const fn new(l_paren: Token, elems: Params, r_paren: Token) -> Self {
+        Self {
+            l_paren,
+            elems,
+            r_paren,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ParamRecordPattern {
     l_brace: Token,
-    pub(crate) elems: Params,
+    pub elems: Params,
     r_brace: Token,
 }
 
@@ -1692,11 +2075,12 @@ pub enum ParamPattern {
     VarArgsName(VarName),
     Lit(Literal),
     Array(ParamArrayPattern),
+    Tuple(ParamTuplePattern),
     Record(ParamRecordPattern),
 }
 
-impl_display_for_enum!(ParamPattern; Discard, VarName, VarArgsName, Lit, Array, Record);
-impl_locational_for_enum!(ParamPattern; Discard, VarName, VarArgsName, Lit, Array, Record);
+impl_display_for_enum!(ParamPattern; Discard, VarName, VarArgsName, Lit, Array, Tuple, Record);
+impl_locational_for_enum!(ParamPattern; Discard, VarName, VarArgsName, Lit, Array, Tuple, Record);
 
 impl ParamPattern {
     pub const fn inspect(&self) -> Option<&Str> {
@@ -1854,7 +2238,8 @@ impl Params {
 /// 引数を取るならTypeでもSubr扱い
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct SubrSignature {
-    pub decorators: Set<Decorator>,
+    /// in the order of the source (the last one is applied first)
+    pub decorators: Vec<Decorator>,
     pub name: VarName,
     pub params: Params,
     pub return_t_spec: Option<TypeSpec>,
@@ -1900,7 +2285,7 @@ impl Locational for SubrSignature {
 
 impl SubrSignature {
     pub const fn new(
-        decorators: Set<Decorator>,
+        decorators: Vec<Decorator>,
         name: VarName,
         params: Params,
         return_t: Option<TypeSpec>,
@@ -2105,26 +2490,63 @@ impl Def {
     }
 }
 
+/// An assignment expression (e.g. `(n := len xs)`)
+/// Unlike `Def`, this is evaluated to the assigned value
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NamedExpr {
+    pub name: VarName,
+    pub op: Token,
+    pub value: Box<Expr>,
+    pub id: DefId,
+}
+
+impl NestedDisplay for NamedExpr {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, level: usize) -> fmt::Result {
+        write!(f, "({} {} ", self.name, self.op.content)?;
+        self.value.fmt_nest(f, level)?;
+        write!(f, ")")
+    }
+}
+
+impl_display_from_nested!(NamedExpr);
+impl_locational!(NamedExpr, name, value);
+
+impl NamedExpr {
+    pub fn new(name: VarName, op: Token, value: Expr, id: DefId) -> Self {
+        Self {
+            name,
+            op,
+            value: Box::new(value),
+            id,
+        }
+    }
+}
+
 /// Expression(式)
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Expr {
     Lit(Literal),
     Accessor(Accessor),
     Array(Array),
-    // Dict(Dict),
-    // Set(Set),
+    ArrayComprehension(ArrayComprehension),
+    Tuple(Tuple),
     Dict(Dict),
+    Set(Set),
+    Record(Record),
     BinOp(BinOp),
     UnaryOp(UnaryOp),
     Call(Call),
     Lambda(Lambda),
     Decl(Decl),
     Def(Def),
+    NamedExpr(NamedExpr),
+    StrInterp(StrInterp),
+    Slice(Slice),
 }
 
-impl_nested_display_for_enum!(Expr; Lit, Accessor, Array, Dict, BinOp, UnaryOp, Call, Lambda, Decl, Def);
+impl_nested_display_for_enum!(Expr; Lit, Accessor, Array, ArrayComprehension, Tuple, Dict, Set, Record, BinOp, UnaryOp, Call, Lambda, Decl, Def, NamedExpr, StrInterp, Slice);
 impl_display_from_nested!(Expr);
-impl_locational_for_enum!(Expr; Lit, Accessor, Array, Dict, BinOp, UnaryOp, Call, Lambda, Decl, Def);
+impl_locational_for_enum!(Expr; Lit, Accessor, Array, ArrayComprehension, Tuple, Dict, Set, Record, BinOp, UnaryOp, Call, Lambda, Decl, Def, NamedExpr, StrInterp, Slice);
 
 impl Expr {
     pub fn is_match_call(&self) -> bool {
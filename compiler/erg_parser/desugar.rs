@@ -5,10 +5,10 @@
 //! 型チェックなどによる検証は行わない
 #![allow(dead_code)]
 
+use erg_common::enum_unwrap;
 use erg_common::set::Set;
 use erg_common::traits::{Locational, Stream};
 use erg_common::Str;
-use erg_common::{enum_unwrap, set};
 
 use crate::ast::{
     Accessor, Args, Block, Call, Def, DefBody, Expr, Lambda, LambdaSignature, Module, ParamPattern,
@@ -119,7 +119,7 @@ impl Desugarer {
                                 ParamSignature::new(ParamPattern::VarName(param), None, None);
                             let params = Params::new(vec![param], vec![], None);
                             let sig = Signature::Subr(SubrSignature::new(
-                                set! {},
+                                vec![],
                                 name,
                                 params,
                                 return_t_spec,
@@ -73,6 +73,8 @@ pub struct Lexer /*<'a>*/ {
     lineno_token_starts: usize,
     /// 0-origin, indicates the column number in which the token appears
     col_token_starts: usize,
+    /// the depth of the enclosures in each embedded expression (`\{...}`) of interpolated strings
+    interp_stack: Vec<usize>,
 }
 
 impl Lexer /*<'a>*/ {
@@ -86,6 +88,7 @@ impl Lexer /*<'a>*/ {
             prev_token: Token::new(TokenKind::BOF, "", 0, 0),
             lineno_token_starts: 0,
             col_token_starts: 0,
+            interp_stack: vec![],
         }
     }
 
@@ -99,6 +102,7 @@ impl Lexer /*<'a>*/ {
             prev_token: Token::new(TokenKind::BOF, "", 0, 0),
             lineno_token_starts: 0,
             col_token_starts: 0,
+            interp_stack: vec![],
         }
     }
 
@@ -456,8 +460,11 @@ impl Lexer /*<'a>*/ {
                 break;
             }
         }
+        // `\{x!r}`: `!r` is a conversion of the interpolated string, not a part of the name
         if let Some('!') = self.peek_cur_ch() {
-            cont.push(self.consume().unwrap());
+            if !self.at_interp_conversion(self.cursor) {
+                cont.push(self.consume().unwrap());
+            }
         }
         if cont.is_empty() {
             let token = self.emit_token(Illegal, &self.peek_cur_ch().unwrap().to_string());
@@ -472,6 +479,7 @@ impl Lexer /*<'a>*/ {
         // An alphabetical operator can also declare as a function, so checking is necessary
         // e.g. and(true, true, true) = true
         let kind = match &cont[..] {
+            "not" => PreNot,
             "and" => AndOp,
             "or" => OrOp,
             "in" => InOp,
@@ -493,11 +501,47 @@ impl Lexer /*<'a>*/ {
     }
 
     fn lex_str(&mut self) -> LexResult<Token> {
-        let mut s = "\"".to_string();
+        self.lex_quoted(StrLit, "\"".to_string())
+    }
+
+    /// e.g. `b"\x00"` (only ASCII characters are allowed, same as Python)
+    fn lex_bytes(&mut self) -> LexResult<Token> {
+        // `b` has been consumed
+        self.consume();
+        let token = self.lex_quoted(BytesLit, "b\"".to_string())?;
+        if token.content.is_ascii() {
+            Ok(token)
+        } else {
+            Err(LexError::syntax_error(
+                0,
+                token.loc(),
+                switch_lang!(
+                    "bytes can only contain ASCII literal characters",
+                    "バイト列にはASCII文字しか使用できません"
+                ),
+                None,
+            ))
+        }
+    }
+
+    /// `kind` is the kind of the token closed by `"`.
+    /// A string literal (`StrLit` or `StrInterpRight`) is split before an embedded expression (`\{`)
+    fn lex_quoted(&mut self, kind: TokenKind, mut s: String) -> LexResult<Token> {
+        let interp_kind = match kind {
+            StrLit => Some(StrInterpLeft),
+            StrInterpRight => Some(StrInterpMid),
+            _ => None,
+        };
         while let Some(c) = self.peek_cur_ch() {
             if c == '\"' && s.chars().last() != Some('\\') {
                 s.push(self.consume().unwrap());
-                let token = self.emit_token(StrLit, &s);
+                let token = self.emit_token(kind, &s);
+                return Ok(token);
+            } else if let Some(interp_kind) = interp_kind.filter(|_| c == '{' && s.ends_with('\\'))
+            {
+                s.push(self.consume().unwrap());
+                self.interp_stack.push(0);
+                let token = self.emit_token(interp_kind, &s);
                 return Ok(token);
             } else {
                 let c = self.consume().unwrap();
@@ -529,6 +573,57 @@ impl Lexer /*<'a>*/ {
     }
 }
 
+impl Lexer /*<'a>*/ {
+    #[inline]
+    fn at_interp_top(&self) -> bool {
+        self.interp_stack.last() == Some(&0)
+    }
+
+    /// `!s`, `!r` or `!a` (`bang` is the position of `!`) is at the top level of an embedded expression
+    fn at_interp_conversion(&self, bang: usize) -> bool {
+        self.at_interp_top()
+            && matches!(self.chars.get(bang + 1), Some('s' | 'r' | 'a'))
+            && matches!(self.chars.get(bang + 2), Some('}' | ':'))
+    }
+
+    fn enter_enclosure(&mut self) {
+        if let Some(depth) = self.interp_stack.last_mut() {
+            *depth += 1;
+        }
+    }
+
+    fn exit_enclosure(&mut self) {
+        if let Some(depth) = self.interp_stack.last_mut() {
+            *depth = depth.saturating_sub(1);
+        }
+    }
+
+    /// Lexes the rest of an interpolated string after an embedded expression.
+    /// The conversion and the format spec are kept in the token (e.g. `!r:>5}b"`)
+    fn lex_interp_rest(&mut self, first_ch: char) -> LexResult<Token> {
+        self.interp_stack.pop();
+        let mut s = first_ch.to_string();
+        while !s.ends_with('}') {
+            match self.peek_cur_ch() {
+                Some(c) if c != '\"' && c != '\n' && c != '{' => s.push(self.consume().unwrap()),
+                _ => {
+                    let token = self.emit_token(Illegal, &s);
+                    return Err(LexError::syntax_error(
+                        0,
+                        token.loc(),
+                        switch_lang!(
+                            "the embedded expression is not closed by }",
+                            "埋め込み式が}によって閉じられていません"
+                        ),
+                        None,
+                    ));
+                }
+            }
+        }
+        self.lex_quoted(StrInterpRight, s)
+    }
+}
+
 impl Iterator for Lexer /*<'a>*/ {
     type Item = LexResult<Token>;
 
@@ -546,12 +641,32 @@ impl Iterator for Lexer /*<'a>*/ {
             }
         }
         match self.consume() {
-            Some('(') => self.accept(LParen, "("),
-            Some(')') => self.accept(RParen, ")"),
-            Some('[') => self.accept(LSqBr, "["),
-            Some(']') => self.accept(RSqBr, "]"),
-            Some('{') => self.accept(LBrace, "{"),
-            Some('}') => self.accept(RBrace, "}"),
+            Some('(') => {
+                self.enter_enclosure();
+                self.accept(LParen, "(")
+            }
+            Some(')') => {
+                self.exit_enclosure();
+                self.accept(RParen, ")")
+            }
+            Some('[') => {
+                self.enter_enclosure();
+                self.accept(LSqBr, "[")
+            }
+            Some(']') => {
+                self.exit_enclosure();
+                self.accept(RSqBr, "]")
+            }
+            Some('{') => {
+                self.enter_enclosure();
+                self.accept(LBrace, "{")
+            }
+            // the end of an embedded expression (`\{x}`)
+            Some('}') if self.at_interp_top() => Some(self.lex_interp_rest('}')),
+            Some('}') => {
+                self.exit_enclosure();
+                self.accept(RBrace, "}")
+            }
             Some('<') => match self.peek_cur_ch() {
                 Some('.') => {
                     self.consume();
@@ -582,6 +697,10 @@ impl Iterator for Lexer /*<'a>*/ {
                     self.consume();
                     self.accept(Shl, "<<")
                 }
+                Some('-') => {
+                    self.consume();
+                    self.accept(Inclusion, "<-")
+                }
                 _ => self.accept(Less, "<"),
             },
             Some('>') => match self.peek_cur_ch() {
@@ -614,6 +733,8 @@ impl Iterator for Lexer /*<'a>*/ {
                 _ => self.accept(Dot, "."),
             },
             Some(',') => self.accept(Comma, ","),
+            // the format spec of an embedded expression (`\{x:>5}`)
+            Some(':') if self.at_interp_top() => Some(self.lex_interp_rest(':')),
             Some(':') => match self.peek_cur_ch() {
                 Some(':') => {
                     self.consume();
@@ -623,6 +744,10 @@ impl Iterator for Lexer /*<'a>*/ {
                     self.consume();
                     self.accept(SupertypeOf, ":>")
                 }
+                Some('=') => {
+                    self.consume();
+                    self.accept(Walrus, ":=")
+                }
                 _ => self.accept(Colon, ":"),
             },
             Some(';') => self.accept(Semi, ";"),
@@ -669,6 +794,9 @@ impl Iterator for Lexer /*<'a>*/ {
                 }
                 _ => self.accept(Equal, "="),
             },
+            Some('!') if self.at_interp_conversion(self.cursor - 1) => {
+                Some(self.lex_interp_rest('!'))
+            }
             Some('!') => {
                 if let Some('=') = self.peek_cur_ch() {
                     self.consume();
@@ -678,19 +806,29 @@ impl Iterator for Lexer /*<'a>*/ {
                 }
             }
             Some('?') => self.accept(Try, "?"),
-            Some('+') => {
-                let kind = if self.is_bin_position().unwrap() {
-                    Plus
-                } else {
-                    PrePlus
-                };
-                self.accept(kind, "+")
-            }
+            Some('+') => match self.peek_cur_ch() {
+                Some('=') => {
+                    self.consume();
+                    self.accept(PlusEqual, "+=")
+                }
+                _ => {
+                    let kind = if self.is_bin_position().unwrap() {
+                        Plus
+                    } else {
+                        PrePlus
+                    };
+                    self.accept(kind, "+")
+                }
+            },
             Some('-') => match self.peek_cur_ch() {
                 Some('>') => {
                     self.consume();
                     self.accept(FuncArrow, "->")
                 }
+                Some('=') => {
+                    self.consume();
+                    self.accept(MinusEqual, "-=")
+                }
                 _ => {
                     if self.is_bin_position().unwrap() {
                         self.accept(Minus, "-")
@@ -713,6 +851,10 @@ impl Iterator for Lexer /*<'a>*/ {
                     self.consume();
                     self.accept(Pow, "**")
                 }
+                Some('=') => {
+                    self.consume();
+                    self.accept(StarEqual, "*=")
+                }
                 _ => {
                     let kind = if self.is_bin_position().unwrap() {
                         Star
@@ -725,11 +867,27 @@ impl Iterator for Lexer /*<'a>*/ {
             Some('/') => match self.peek_cur_ch() {
                 Some('/') => {
                     self.consume();
-                    self.accept(FloorDiv, "//")
+                    match self.peek_cur_ch() {
+                        Some('=') => {
+                            self.consume();
+                            self.accept(FloorDivEqual, "//=")
+                        }
+                        _ => self.accept(FloorDiv, "//"),
+                    }
+                }
+                Some('=') => {
+                    self.consume();
+                    self.accept(SlashEqual, "/=")
                 }
                 _ => self.accept(Slash, "/"),
             },
-            Some('%') => self.accept(Mod, "%"),
+            Some('%') => match self.peek_cur_ch() {
+                Some('=') => {
+                    self.consume();
+                    self.accept(ModEqual, "%=")
+                }
+                _ => self.accept(Mod, "%"),
+            },
             // Newline
             // 改行記号はLexer新規生成時に全て\nにreplaceしてある
             Some('\n') => {
@@ -789,6 +947,8 @@ impl Iterator for Lexer /*<'a>*/ {
             }
             // IntLit or RatioLit
             Some(n) if n.is_ascii_digit() => Some(self.lex_num(n)),
+            // BytesLit
+            Some('b') if self.peek_cur_ch() == Some('\"') => Some(self.lex_bytes()),
             // Symbol (includes '_')
             Some(c) if Self::is_valid_symbol_ch(c) => Some(self.lex_symbol(c)),
             // Invalid character (e.g. space-like character)
@@ -9,12 +9,11 @@ use erg_common::color::{GREEN, RED, RESET};
 use erg_common::config::ErgConfig;
 use erg_common::config::{Input, BUILD_INFO, SEMVER};
 use erg_common::error::Location;
-use erg_common::set::Set;
 use erg_common::traits::Runnable;
 use erg_common::traits::{Locational, Stream};
 use erg_common::Str;
 use erg_common::{
-    caused_by, debug_power_assert, enum_unwrap, fn_name, log, set, switch_lang, switch_unreachable,
+    caused_by, debug_power_assert, enum_unwrap, fn_name, log, switch_lang, switch_unreachable,
 };
 
 use crate::ast::*;
@@ -26,6 +25,9 @@ use crate::token::{Token, TokenCategory, TokenKind, TokenStream};
 use TokenCategory as TC;
 use TokenKind::*;
 
+/// comparison operators have the same precedence, and a sequence of them is chained (`a < b < c`)
+const COMPARISON_PREC: Option<usize> = Less.precedence();
+
 /// Display the name of the called function for debugging the parser
 /// I thought about displaying the nesting level, but due to `?` operator there is not a single exit point for the function
 /// So it is not possible to lower the level
@@ -40,8 +42,12 @@ macro_rules! debug_call_info {
 }
 
 enum ExprOrOp {
-    Expr(Expr),
-    Op(Token),
+    Expr(Box<Expr>),
+    /// `chained`: a comparison operator following another one (e.g. the second `<` of `a < b < c`)
+    Op {
+        op: Token,
+        chained: bool,
+    },
 }
 
 enum PosOrKwArg {
@@ -133,6 +139,25 @@ impl Parser {
     /// `(Rhs) , (LhsLambda) ->`
     /// `(Rhs) (LhsLambda) -> (Rhs);`
     fn cur_side(&self) -> Side {
+        // `@deco` precedes a definition
+        if self.cur_is(AtSign) {
+            return Side::LhsAssign;
+        }
+        // `{x = 1; y = 2}` is a record, `{x; y} = r` is a pattern
+        if self.cur_is(LBrace) {
+            let mut depth = 0;
+            let r_brace = self.tokens.iter().position(|t| {
+                match t.kind {
+                    LBrace => depth += 1,
+                    RBrace => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            });
+            if !r_brace.is_some_and(|idx| self.nth_is(idx + 1, Equal)) {
+                return Side::Rhs;
+            }
+        }
         // 以降に=, ->などがないならすべて右辺値
         let opt_equal_pos = self.tokens.iter().skip(1).position(|t| t.is(Equal));
         let opt_arrow_pos = self
@@ -253,6 +278,13 @@ impl Parser {
         }
     }
 
+    /// skips line breaks and indentation in an enclosure (e.g. `{` ... `}`)
+    fn skip_newlines(&mut self) {
+        while self.cur_is(Newline) || self.cur_is(Indent) || self.cur_is(Dedent) {
+            self.skip();
+        }
+    }
+
     fn skip_and_throw_syntax_err(&mut self, caused_by: &str) -> ParseError {
         let loc = self.peek().unwrap().loc();
         log!("{RED}[DEBUG] error caused by: {caused_by}{GREEN}");
@@ -305,7 +337,7 @@ impl Runnable for ParserRunner {
 impl ParserRunner {
     pub fn parse(&mut self, ts: TokenStream) -> Result<AST, ParserRunnerErrors> {
         Parser::new(ts)
-            .parse(Str::ever(self.cfg.module))
+            .parse(Str::ever(self.cfg.module()))
             .map_err(|errs| ParserRunnerErrors::convert(self.input(), errs))
     }
 
@@ -452,12 +484,15 @@ impl Parser {
     }
 
     #[inline]
-    fn opt_reduce_decorators(&mut self) -> ParseResult<Set<Decorator>> {
-        let mut decs = set![];
+    fn opt_reduce_decorators(&mut self) -> ParseResult<Vec<Decorator>> {
+        let mut decs = vec![];
         loop {
             match self.opt_reduce_decorator()? {
                 Some(deco) => {
-                    decs.insert(deco);
+                    decs.push(deco);
+                    while self.cur_is(Newline) {
+                        self.skip();
+                    }
                 }
                 None => {
                     break;
@@ -568,12 +603,20 @@ impl Parser {
     #[inline]
     fn try_reduce_non_default_param_sig(&mut self) -> ParseResult<ParamSignature> {
         debug_call_info!(self);
-        let pat = self.try_reduce_param_pattern()?;
+        let (pat, paren_t_spec) = if self.cur_is(LParen) {
+            self.try_reduce_paren_param_pattern()?
+        } else {
+            (self.try_reduce_param_pattern()?, None)
+        };
         let t_spec = if self.cur_is(Colon) {
+            // `(a: Int): Int`
+            if paren_t_spec.is_some() {
+                return Err(self.skip_and_throw_syntax_err(caused_by!()));
+            }
             self.skip();
             Some(self.try_reduce_type_spec()?)
         } else {
-            None
+            paren_t_spec
         };
         Ok(ParamSignature::new(pat, t_spec, None))
     }
@@ -618,19 +661,14 @@ impl Parser {
                 }
                 Some(t) if t.is(LSqBr) => {
                     self.skip();
-                    let index = self.try_reduce_expr()?;
+                    let index = self.try_reduce_subscr_index()?;
                     if self.cur_is(RSqBr) {
                         self.skip();
-                    } else {
-                        return Err(self.skip_and_throw_syntax_err(caused_by!()));
-                    }
-                    acc = Accessor::subscr(Expr::Accessor(acc), index);
-                    if self.cur_is(RSqBr) {
-                        self.lpop();
                     } else {
                         // TODO: error report: RSqBr not found
                         return Err(self.skip_and_throw_syntax_err(caused_by!()));
                     }
+                    acc = Accessor::subscr(Expr::Accessor(acc), index);
                 }
                 _ => {
                     break;
@@ -640,6 +678,41 @@ impl Parser {
         Ok(acc)
     }
 
+    /// `i`, `1:`, `:2`, `1:5:2` or `::-1` of `arr[...]`
+    fn try_reduce_subscr_index(&mut self) -> ParseResult<Expr> {
+        debug_call_info!(self);
+        let start = if self.cur_is(Colon) || self.cur_is(DblColon) {
+            None
+        } else {
+            Some(self.try_reduce_expr()?)
+        };
+        let (colon, stop) = match self.peek() {
+            Some(t) if t.is(Colon) => {
+                let colon = self.lpop();
+                let stop = if self.cur_is(Colon) || self.cur_is(RSqBr) {
+                    None
+                } else {
+                    Some(self.try_reduce_expr()?)
+                };
+                if self.cur_is(Colon) {
+                    self.skip();
+                } else {
+                    return Ok(Expr::Slice(Slice::new(colon, start, stop, None)));
+                }
+                (colon, stop)
+            }
+            // `::` is lexed as one token (the stop is omitted)
+            Some(t) if t.is(DblColon) => (self.lpop(), None),
+            _ => return Ok(start.unwrap()),
+        };
+        let step = if self.cur_is(RSqBr) {
+            None
+        } else {
+            Some(self.try_reduce_expr()?)
+        };
+        Ok(Expr::Slice(Slice::new(colon, start, stop, step)))
+    }
+
     fn try_reduce_elems(&mut self) -> ParseResult<Vars> {
         debug_call_info!(self);
         let mut elems = Vars::empty();
@@ -806,6 +879,10 @@ impl Parser {
         match self.peek() {
             Some(t) if t.is(Symbol) => Ok(VarPattern::VarName(self.try_reduce_name()?)),
             Some(t) if t.is(UBar) => Ok(VarPattern::Discard(self.lpop())),
+            Some(t) if t.is(PreStar) => {
+                self.skip();
+                Ok(VarPattern::VarArgsName(self.try_reduce_name()?))
+            }
             Some(t) if t.is(LSqBr) => {
                 let l_sqbr = self.lpop();
                 let elems = self.try_reduce_elems()?;
@@ -860,19 +937,53 @@ impl Parser {
                     Err(self.skip_and_throw_syntax_err(caused_by!()))
                 }
             }
-            Some(t) if t.is(LParen) => {
+            Some(t) if t.is(LParen) => Ok(self.try_reduce_paren_param_pattern()?.0),
+            _ => Err(self.skip_and_throw_syntax_err(caused_by!())),
+        }
+    }
+
+    /// `(a)` and `(a: Int)` are just parenthesized (the type spec is returned),
+    /// `()`, `(a,)` and `(a, b)` are tuple patterns (same as Python)
+    fn try_reduce_paren_param_pattern(&mut self) -> ParseResult<(ParamPattern, Option<TypeSpec>)> {
+        debug_call_info!(self);
+        let l_paren = self.lpop();
+        let mut non_default_params = vec![];
+        let mut default_params = vec![];
+        let mut trailing_comma = false;
+        while !self.cur_is(RParen) {
+            let param = self.try_reduce_param_sig()?;
+            if param.has_default() {
+                default_params.push(param);
+            } else if default_params.is_empty() {
+                non_default_params.push(param);
+            } else {
+                return Err(ParseError::syntax_error(
+                    0,
+                    param.loc(),
+                    "non-default argument follows default argument",
+                    None,
+                ));
+            }
+            trailing_comma = self.cur_is(Comma);
+            if trailing_comma {
                 self.skip();
-                let pat = self.try_reduce_param_pattern()?;
-                if self.cur_is(RParen) {
-                    self.skip();
-                    Ok(pat)
-                } else {
-                    // TODO: error report: RParen not found
-                    Err(self.skip_and_throw_syntax_err(caused_by!()))
-                }
+            } else if !self.cur_is(RParen) {
+                // TODO: error report: RParen not found
+                return Err(self.skip_and_throw_syntax_err(caused_by!()));
             }
-            _ => Err(self.skip_and_throw_syntax_err(caused_by!())),
         }
+        let r_paren = self.lpop();
+        if non_default_params.len() == 1 && default_params.is_empty() && !trailing_comma {
+            let param = non_default_params.remove(0);
+            return Ok((param.pat, param.t_spec));
+        }
+        let elems = Params::new(
+            non_default_params,
+            default_params,
+            Some((l_paren.clone(), r_paren.clone())),
+        );
+        let pat = ParamPattern::Tuple(ParamTuplePattern::new(l_paren, elems, r_paren));
+        Ok((pat, None))
     }
 
     // TODO: set type
@@ -1076,7 +1187,9 @@ impl Parser {
                     || t.is(LParen)
                     || t.is(LSqBr)
                     || t.is(LBrace)
-                    || t.is(Colon) =>
+                    || t.is(StrInterpLeft)
+                    // `f:` + a block (a colon not followed by a line break is a dict separator)
+                    || (t.is(Colon) && self.nth_is(1, Newline)) =>
             {
                 Some(self.try_reduce_args())
             }
@@ -1106,10 +1219,8 @@ impl Parser {
         } else if self.cur_category_is(TC::REnclosure) {
             return Ok(Args::new(vec![], vec![], None));
         }
-        let mut args = match self.try_reduce_arg()? {
-            PosOrKwArg::Pos(arg) => Args::new(vec![arg], vec![], None),
-            PosOrKwArg::Kw(arg) => Args::new(vec![], vec![arg], None),
-        };
+        let mut args = Args::empty();
+        self.push_arg(&mut args)?;
         let mut colon_style = false;
         loop {
             match self.peek() {
@@ -1117,7 +1228,7 @@ impl Parser {
                     self.skip();
                     return Err(self.skip_and_throw_syntax_err(caused_by!()));
                 }
-                Some(t) if t.is(Colon) => {
+                Some(t) if t.is(Colon) && self.nth_is(1, Newline) => {
                     self.skip();
                     colon_style = true;
                     while self.cur_is(Newline) {
@@ -1125,36 +1236,14 @@ impl Parser {
                     }
                     debug_power_assert!(self.cur_is(Indent));
                     self.skip();
-                    if !args.kw_is_empty() {
-                        args.push_kw(self.try_reduce_kw_arg()?);
-                    } else {
-                        match self.try_reduce_arg()? {
-                            PosOrKwArg::Pos(arg) => {
-                                args.push_pos(arg);
-                            }
-                            PosOrKwArg::Kw(arg) => {
-                                args.push_kw(arg);
-                            }
-                        }
-                    }
+                    self.push_arg(&mut args)?;
                 }
                 Some(t) if t.is(Comma) => {
                     self.skip();
                     if colon_style || self.cur_is(Comma) {
                         return Err(self.skip_and_throw_syntax_err(caused_by!()));
                     }
-                    if !args.kw_is_empty() {
-                        args.push_kw(self.try_reduce_kw_arg()?);
-                    } else {
-                        match self.try_reduce_arg()? {
-                            PosOrKwArg::Pos(arg) => {
-                                args.push_pos(arg);
-                            }
-                            PosOrKwArg::Kw(arg) => {
-                                args.push_kw(arg);
-                            }
-                        }
-                    }
+                    self.push_arg(&mut args)?;
                 }
                 Some(t) if t.is(Newline) && colon_style => {
                     while self.cur_is(Newline) {
@@ -1164,23 +1253,20 @@ impl Parser {
                         self.skip();
                         break;
                     }
-                    if !args.kw_is_empty() {
-                        args.push_kw(self.try_reduce_kw_arg()?);
-                    } else {
-                        match self.try_reduce_arg()? {
-                            PosOrKwArg::Pos(arg) => {
-                                args.push_pos(arg);
-                            }
-                            PosOrKwArg::Kw(arg) => {
-                                args.push_kw(arg);
-                            }
-                        }
-                    }
+                    self.push_arg(&mut args)?;
                 }
                 Some(t) if t.is(RParen) => {
                     rp = Some(self.lpop());
+                    let var_args = args.take_var_args();
+                    let kw_var_args = args.take_kw_var_args();
                     let (pos_args, kw_args, _) = args.deconstruct();
                     args = Args::new(pos_args, kw_args, Some((lp.unwrap(), rp.unwrap())));
+                    if let Some(var_args) = var_args {
+                        args.set_var_args(var_args);
+                    }
+                    if let Some(kw_var_args) = kw_var_args {
+                        args.set_kw_var_args(kw_var_args);
+                    }
                     break;
                 }
                 _ => {
@@ -1191,6 +1277,43 @@ impl Parser {
         Ok(args)
     }
 
+    /// The order of the arguments is: positional, `*args`, keyword, `**kwargs` (same as Python)
+    fn push_arg(&mut self, args: &mut Args) -> ParseResult<()> {
+        debug_call_info!(self);
+        if args.kw_var_args().is_some() {
+            return Err(self.skip_and_throw_syntax_err(caused_by!()));
+        }
+        match self.peek() {
+            Some(t) if t.is(PreStar) => {
+                if args.var_args().is_some() || !args.kw_is_empty() {
+                    return Err(self.skip_and_throw_syntax_err(caused_by!()));
+                }
+                self.skip();
+                args.set_var_args(PosArg::new(self.try_reduce_expr()?));
+            }
+            // `**` is lexed as `Pow`
+            Some(t) if t.is(Pow) => {
+                self.skip();
+                args.set_kw_var_args(PosArg::new(self.try_reduce_expr()?));
+            }
+            _ if !args.kw_is_empty() => {
+                args.push_kw(self.try_reduce_kw_arg()?);
+            }
+            _ => match self.try_reduce_arg()? {
+                PosOrKwArg::Pos(arg) if args.var_args().is_some() => {
+                    return Err(ParseError::simple_syntax_error(0, arg.loc()));
+                }
+                PosOrKwArg::Pos(arg) => {
+                    args.push_pos(arg);
+                }
+                PosOrKwArg::Kw(arg) => {
+                    args.push_kw(arg);
+                }
+            },
+        }
+        Ok(())
+    }
+
     fn try_reduce_arg(&mut self) -> ParseResult<PosOrKwArg> {
         debug_call_info!(self);
         match self.peek() {
@@ -1252,7 +1375,6 @@ impl Parser {
 
     fn try_reduce_expr(&mut self) -> ParseResult<Expr> {
         debug_call_info!(self);
-        let mut stack = Vec::<ExprOrOp>::new();
         match self.cur_side() {
             Side::LhsAssign => {
                 let sig = self.try_reduce_decl()?;
@@ -1298,97 +1420,151 @@ impl Parser {
                 }
             }
             Side::Rhs => {
-                stack.push(ExprOrOp::Expr(self.try_reduce_lhs()?));
-                loop {
-                    match self.peek() {
-                        Some(op) if op.category_is(TC::BinOp) => {
-                            let op_prec = op.kind.precedence();
-                            if stack.len() >= 2 {
-                                while let Some(ExprOrOp::Op(prev_op)) = stack.get(stack.len() - 2) {
-                                    if prev_op.category_is(TC::BinOp)
-                                        && prev_op.kind.precedence() >= op_prec
-                                    {
-                                        let rhs =
-                                            enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
-                                        let prev_op =
-                                            enum_unwrap!(stack.pop(), Some:(ExprOrOp::Op:(_)));
-                                        let lhs =
-                                            enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
-                                        let bin = BinOp::new(prev_op, lhs, rhs);
-                                        stack.push(ExprOrOp::Expr(Expr::BinOp(bin)));
-                                    } else {
-                                        break;
-                                    }
-                                    if stack.len() <= 1 {
-                                        break;
-                                    }
-                                }
-                            }
-                            stack.push(ExprOrOp::Op(self.lpop()));
-                            stack.push(ExprOrOp::Expr(self.try_reduce_lhs()?));
-                        }
-                        Some(t) if t.category_is(TC::DefOp) => {
-                            switch_unreachable!()
-                        }
-                        Some(t) if t.is(Dot) => {
-                            self.skip();
-                            match self.lpop() {
-                                symbol if symbol.is(Symbol) => {
-                                    let obj = if let Some(ExprOrOp::Expr(expr)) = stack.pop() {
-                                        expr
-                                    } else {
-                                        return Err(self.skip_and_throw_syntax_err(caused_by!()));
-                                    };
-                                    let acc = Accessor::attr(obj, Local::new(symbol));
-                                    if let Ok(args) = self.try_reduce_args() {
-                                        let call = Call::new(Expr::Accessor(acc), args);
-                                        stack.push(ExprOrOp::Expr(Expr::Call(call)));
-                                    } else {
-                                        stack.push(ExprOrOp::Expr(Expr::Accessor(acc)));
-                                    }
-                                }
-                                other => {
-                                    self.restore(other);
-                                    return Err(self.skip_and_throw_syntax_err(caused_by!()));
-                                }
+                let expr = self.try_reduce_bin_expr(None)?;
+                match self.peek() {
+                    Some(t)
+                        if t.is(PlusEqual)
+                            || t.is(MinusEqual)
+                            || t.is(StarEqual)
+                            || t.is(SlashEqual)
+                            || t.is(FloorDivEqual)
+                            || t.is(ModEqual) =>
+                    {
+                        Ok(Expr::Def(self.try_reduce_aug_assign(expr)?))
+                    }
+                    _ => Ok(expr),
+                }
+            }
+        }
+    }
+
+    /// `x += 1` is reduced to a `Def` whose operator is `+=`
+    fn try_reduce_aug_assign(&mut self, target: Expr) -> ParseResult<Def> {
+        debug_call_info!(self);
+        let name = match target {
+            Expr::Accessor(Accessor::Local(local)) => VarName::new(local.symbol),
+            other => {
+                self.next_expr();
+                return Err(ParseError::feature_error(
+                    0,
+                    other.loc(),
+                    "augmented assignment to anything but a variable",
+                ));
+            }
+        };
+        let sig = Signature::Var(VarSignature::new(VarPattern::VarName(name), None));
+        let op = self.lpop();
+        self.counter.inc();
+        let body = DefBody::new(op, self.try_reduce_block()?, self.counter);
+        Ok(Def::new(sig, body))
+    }
+
+    /// Reduces a sequence of operands and binary operators.
+    /// Stops before a binary operator whose precedence is lower than `min_prec`
+    /// (e.g. the operand of `not` is `a == b` in `not a == b`, but `a` in `not a and b`).
+    fn try_reduce_bin_expr(&mut self, min_prec: Option<usize>) -> ParseResult<Expr> {
+        debug_call_info!(self);
+        let mut stack = Vec::<ExprOrOp>::new();
+        stack.push(ExprOrOp::Expr(Box::new(self.try_reduce_lhs()?)));
+        loop {
+            match self.peek() {
+                Some(op) if op.category_is(TC::BinOp) && op.kind.precedence() >= min_prec => {
+                    let op_prec = op.kind.precedence();
+                    let is_comparison = op_prec == COMPARISON_PREC;
+                    // `a < b < c`: `a < b` is reduced here (`(a < b) < c` is not chained)
+                    let mut chained = false;
+                    if stack.len() >= 2 {
+                        while let Some(ExprOrOp::Op { op: prev_op, .. }) =
+                            stack.get(stack.len() - 2)
+                        {
+                            if prev_op.category_is(TC::BinOp)
+                                && prev_op.kind.precedence() >= op_prec
+                            {
+                                let rhs = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
+                                let (prev_op, prev_chained) = enum_unwrap!(
+                                    stack.pop().unwrap(),
+                                    ExprOrOp::Op { op, chained }
+                                );
+                                let lhs = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
+                                chained =
+                                    is_comparison && prev_op.kind.precedence() == COMPARISON_PREC;
+                                let mut bin = BinOp::new(prev_op, *lhs, *rhs);
+                                bin.chained = prev_chained;
+                                stack.push(ExprOrOp::Expr(Box::new(Expr::BinOp(bin))));
+                            } else {
+                                break;
                             }
-                        }
-                        _ => {
                             if stack.len() <= 1 {
                                 break;
                             }
-                            // else if stack.len() == 2 { switch_unreachable!() }
-                            else {
-                                while stack.len() >= 3 {
-                                    let rhs = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
-                                    let op = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Op:(_)));
-                                    let lhs = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
-                                    let bin = BinOp::new(op, lhs, rhs);
-                                    stack.push(ExprOrOp::Expr(Expr::BinOp(bin)));
-                                }
+                        }
+                    }
+                    let op = self.lpop();
+                    stack.push(ExprOrOp::Op { op, chained });
+                    stack.push(ExprOrOp::Expr(Box::new(self.try_reduce_lhs()?)));
+                }
+                Some(t) if t.category_is(TC::DefOp) => {
+                    switch_unreachable!()
+                }
+                Some(t) if t.is(Dot) => {
+                    self.skip();
+                    match self.lpop() {
+                        symbol if symbol.is(Symbol) => {
+                            let obj = if let Some(ExprOrOp::Expr(expr)) = stack.pop() {
+                                *expr
+                            } else {
+                                return Err(self.skip_and_throw_syntax_err(caused_by!()));
+                            };
+                            let acc = Accessor::attr(obj, Local::new(symbol));
+                            if let Ok(args) = self.try_reduce_args() {
+                                let call = Call::new(Expr::Accessor(acc), args);
+                                stack.push(ExprOrOp::Expr(Box::new(Expr::Call(call))));
+                            } else {
+                                stack.push(ExprOrOp::Expr(Box::new(Expr::Accessor(acc))));
                             }
                         }
+                        other => {
+                            self.restore(other);
+                            return Err(self.skip_and_throw_syntax_err(caused_by!()));
+                        }
                     }
                 }
-                match stack.pop() {
-                    Some(ExprOrOp::Expr(expr)) if stack.is_empty() => Ok(expr),
-                    Some(ExprOrOp::Expr(expr)) => {
-                        let extra = stack.pop().unwrap();
-                        let loc = match extra {
-                            ExprOrOp::Expr(expr) => expr.loc(),
-                            ExprOrOp::Op(op) => op.loc(),
-                        };
-                        self.warns
-                            .push(ParseError::compiler_bug(0, loc, fn_name!(), line!()));
-                        Ok(expr)
+                _ => {
+                    if stack.len() <= 1 {
+                        break;
                     }
-                    Some(ExprOrOp::Op(op)) => {
-                        Err(ParseError::compiler_bug(0, op.loc(), fn_name!(), line!()))
+                    // else if stack.len() == 2 { switch_unreachable!() }
+                    else {
+                        while stack.len() >= 3 {
+                            let rhs = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
+                            let (op, chained) =
+                                enum_unwrap!(stack.pop().unwrap(), ExprOrOp::Op { op, chained });
+                            let lhs = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
+                            let mut bin = BinOp::new(op, *lhs, *rhs);
+                            bin.chained = chained;
+                            stack.push(ExprOrOp::Expr(Box::new(Expr::BinOp(bin))));
+                        }
                     }
-                    _ => switch_unreachable!(),
                 }
             }
         }
+        match stack.pop() {
+            Some(ExprOrOp::Expr(expr)) if stack.is_empty() => Ok(*expr),
+            Some(ExprOrOp::Expr(expr)) => {
+                let extra = stack.pop().unwrap();
+                let loc = match extra {
+                    ExprOrOp::Expr(expr) => expr.loc(),
+                    ExprOrOp::Op { op, .. } => op.loc(),
+                };
+                self.warns
+                    .push(ParseError::compiler_bug(0, loc, fn_name!(), line!()));
+                Ok(*expr)
+            }
+            Some(ExprOrOp::Op { op, .. }) => {
+                Err(ParseError::compiler_bug(0, op.loc(), fn_name!(), line!()))
+            }
+            _ => switch_unreachable!(),
+        }
     }
 
     /// "LHS" is the smallest unit that can be the left-hand side of an BinOp.
@@ -1400,6 +1576,7 @@ impl Parser {
                 // TODO: 10.times ...などメソッド呼び出しもある
                 Ok(Expr::Lit(self.try_reduce_lit()?))
             }
+            Some(t) if t.is(StrInterpLeft) => Ok(Expr::StrInterp(self.try_reduce_str_interp()?)),
             Some(t) if t.is(Symbol) || t.is(Dot) => Ok(self.try_reduce_call_or_acc()?),
             Some(t) if t.category_is(TC::UnaryOp) => Ok(Expr::UnaryOp(self.try_reduce_unary()?)),
             Some(t) if t.category_is(TC::Caret) => {
@@ -1407,8 +1584,21 @@ impl Parser {
                 Ok(Expr::Lambda(lambda))
             }
             Some(t) if t.is(LParen) => {
-                self.skip();
-                let expr = self.try_reduce_expr()?;
+                let lp = self.lpop();
+                // `()`
+                if self.cur_is(RParen) {
+                    let rp = self.lpop();
+                    let elems = Args::new(vec![], vec![], Some((lp, rp)));
+                    return Ok(Expr::Tuple(Tuple::new(elems)));
+                }
+                let expr = if self.cur_is(Symbol) && self.nth_is(1, Walrus) {
+                    Expr::NamedExpr(self.try_reduce_named_expr()?)
+                } else {
+                    self.try_reduce_expr()?
+                };
+                if self.cur_is(Comma) {
+                    return Ok(Expr::Tuple(self.try_reduce_tuple(lp, expr)?));
+                }
                 if self.cur_is(RParen) {
                     self.skip();
                 } else {
@@ -1416,10 +1606,8 @@ impl Parser {
                 }
                 Ok(expr)
             }
-            Some(t) if t.is(LSqBr) => Ok(Expr::Array(self.try_reduce_array()?)),
-            Some(t) if t.is(LBrace) => {
-                todo!()
-            }
+            Some(t) if t.is(LSqBr) => self.try_reduce_array(),
+            Some(t) if t.is(LBrace) => self.try_reduce_brace_container(),
             Some(t) if t.is(UBar) => {
                 let token = self.lpop();
                 Err(ParseError::feature_error(0, token.loc(), "discard pattern"))
@@ -1458,25 +1646,289 @@ impl Parser {
         Ok(Lambda::new(sig, op, body, self.counter))
     }
 
+    /// `"a\{x}b\{y!r:>5}c"`
+    fn try_reduce_str_interp(&mut self) -> ParseResult<StrInterp> {
+        debug_call_info!(self);
+        let left = self.lpop();
+        // `"a\{` -> `a`
+        let lit = &left.content[1..left.content.len() - 2];
+        let mut segments = vec![];
+        // empty literals are not emitted (as CPython does)
+        if !lit.is_empty() {
+            segments.push(StrSegment::Lit(Str::rc(lit)));
+        }
+        loop {
+            let expr = self.try_reduce_expr()?;
+            let closing = self.lpop();
+            if !closing.is(StrInterpMid) && !closing.is(StrInterpRight) {
+                return Err(ParseError::simple_syntax_error(0, closing.loc()));
+            }
+            // `!r:>5}b\{` -> (`!r`, `:>5`, `b\{`)
+            let (head, rest) = closing.content.split_once('}').unwrap();
+            let (conversion, spec) = match head.split_once(':') {
+                Some((conversion, spec)) => (conversion, Some(Str::rc(spec))),
+                None => (head, None),
+            };
+            let conversion = match conversion {
+                "" => None,
+                "!s" => Some(FormatConversion::Str),
+                "!r" => Some(FormatConversion::Repr),
+                "!a" => Some(FormatConversion::Ascii),
+                _ => return Err(ParseError::simple_syntax_error(0, closing.loc())),
+            };
+            segments.push(StrSegment::Expr {
+                expr: Box::new(expr),
+                conversion,
+                spec,
+            });
+            // `b"` -> `b`, `b\{` -> `b`
+            let lit = if closing.is(StrInterpRight) {
+                &rest[..rest.len() - 1]
+            } else {
+                &rest[..rest.len() - 2]
+            };
+            if !lit.is_empty() {
+                segments.push(StrSegment::Lit(Str::rc(lit)));
+            }
+            if closing.is(StrInterpRight) {
+                return Ok(StrInterp::new(left, closing, segments));
+            }
+        }
+    }
+
+    /// `n := x` (in parentheses)
+    #[inline]
+    fn try_reduce_named_expr(&mut self) -> ParseResult<NamedExpr> {
+        debug_call_info!(self);
+        let name = self.try_reduce_name()?;
+        let op = self.lpop();
+        let value = self.try_reduce_expr()?;
+        self.counter.inc();
+        Ok(NamedExpr::new(name, op, value, self.counter))
+    }
+
     #[inline]
     fn try_reduce_unary(&mut self) -> ParseResult<UnaryOp> {
         debug_call_info!(self);
         let op = self.lpop();
-        let expr = self.try_reduce_expr()?;
+        let expr = if op.is(PreNot) {
+            self.try_reduce_bin_expr(op.kind.precedence())?
+        } else {
+            self.try_reduce_expr()?
+        };
         Ok(UnaryOp::new(op, expr))
     }
 
     #[inline]
-    fn try_reduce_array(&mut self) -> ParseResult<Array> {
+    fn try_reduce_array(&mut self) -> ParseResult<Expr> {
         debug_call_info!(self);
         let l_sqbr = self.lpop();
         let elems = self.try_reduce_args()?;
+        if self.cur_is(VBar) {
+            let comp = self.try_reduce_array_comprehension(l_sqbr, elems)?;
+            return Ok(Expr::ArrayComprehension(comp));
+        }
         let r_sqbr = self.lpop();
         if !r_sqbr.is(RSqBr) {
             return Err(ParseError::simple_syntax_error(0, r_sqbr.loc()));
         }
+        if elems.has_spread() {
+            return Err(ParseError::feature_error(
+                0,
+                l_sqbr.loc(),
+                "unpacking in arrays",
+            ));
+        }
         let arr = Array::new(l_sqbr, r_sqbr, elems, None);
-        Ok(arr)
+        Ok(Expr::Array(arr))
+    }
+
+    /// `[elem | x <- iter, guard, ...]`
+    /// `elems` is what was parsed before `|` and must be a single element
+    fn try_reduce_array_comprehension(
+        &mut self,
+        l_sqbr: Token,
+        elems: Args,
+    ) -> ParseResult<ArrayComprehension> {
+        debug_call_info!(self);
+        if elems.has_spread() || !elems.kw_args().is_empty() || elems.pos_args().len() != 1 {
+            return Err(self.skip_and_throw_syntax_err(caused_by!()));
+        }
+        let elem = elems.into_iters().0.into_iter().next().unwrap().expr;
+        self.skip();
+        let var = self.try_reduce_name()?;
+        if !self.cur_is(Inclusion) {
+            return Err(self.skip_and_throw_syntax_err(caused_by!()));
+        }
+        self.skip();
+        let iter = self.try_reduce_expr()?;
+        let mut guards = vec![];
+        loop {
+            match self.peek() {
+                Some(t) if t.is(Comma) => {
+                    self.skip();
+                    guards.push(self.try_reduce_expr()?);
+                }
+                Some(t) if t.is(RSqBr) => {
+                    let r_sqbr = self.lpop();
+                    self.counter.inc();
+                    return Ok(ArrayComprehension::new(
+                        l_sqbr,
+                        r_sqbr,
+                        elem,
+                        var,
+                        iter,
+                        guards,
+                        self.counter,
+                    ));
+                }
+                _ => return Err(self.skip_and_throw_syntax_err(caused_by!())),
+            }
+        }
+    }
+
+    /// `first` is the first element (`1` of `(1, 2)`)
+    fn try_reduce_tuple(&mut self, lp: Token, first: Expr) -> ParseResult<Tuple> {
+        debug_call_info!(self);
+        let mut pos_args = vec![PosArg::new(first)];
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                Some(t) if t.is(Comma) => {
+                    self.skip();
+                    self.skip_newlines();
+                    // `(1,)` is a 1-tuple
+                    if self.cur_is(RParen) {
+                        let rp = self.lpop();
+                        return Ok(Tuple::new(Args::new(pos_args, vec![], Some((lp, rp)))));
+                    }
+                    pos_args.push(PosArg::new(self.try_reduce_expr()?));
+                }
+                Some(t) if t.is(RParen) => {
+                    let rp = self.lpop();
+                    return Ok(Tuple::new(Args::new(pos_args, vec![], Some((lp, rp)))));
+                }
+                _ => return Err(self.skip_and_throw_syntax_err(caused_by!())),
+            }
+        }
+    }
+
+    /// `{x = 1; y = 2}` (record), `{"a": 1, "b": 2}` (dict) or `{1, 2}` (set)
+    fn try_reduce_brace_container(&mut self) -> ParseResult<Expr> {
+        debug_call_info!(self);
+        let l_brace = self.lpop();
+        self.skip_newlines();
+        match self.peek() {
+            Some(t) if t.is(RBrace) => {
+                let r_brace = self.lpop();
+                Ok(Expr::Record(Record::new(l_brace, r_brace, vec![])))
+            }
+            // `{:}`
+            Some(t) if t.is(Colon) && self.nth_is(1, RBrace) => {
+                self.skip();
+                let r_brace = self.lpop();
+                Ok(Expr::Dict(Dict::new(l_brace, r_brace, vec![])))
+            }
+            Some(t) if t.is(EOF) => Err(self.skip_and_throw_syntax_err(caused_by!())),
+            Some(_) => match self.try_reduce_expr()? {
+                // only `name = expr` is allowed
+                Expr::Def(def) if !def.is_subr() && def.sig.name().is_some() => {
+                    Ok(Expr::Record(self.try_reduce_record(l_brace, def)?))
+                }
+                key if self.cur_is(Colon) => Ok(Expr::Dict(self.try_reduce_dict(l_brace, key)?)),
+                Expr::Def(def) => Err(ParseError::simple_syntax_error(0, def.loc())),
+                elem => Ok(Expr::Set(self.try_reduce_set(l_brace, elem)?)),
+            },
+            None => switch_unreachable!(),
+        }
+    }
+
+    /// `first` is the first attribute (`x = 1` of `{x = 1; y = 2}`)
+    fn try_reduce_record(&mut self, l_brace: Token, first: Def) -> ParseResult<Record> {
+        debug_call_info!(self);
+        let mut attrs = vec![first];
+        loop {
+            match self.peek() {
+                Some(t) if t.is(RBrace) => {
+                    let r_brace = self.lpop();
+                    return Ok(Record::new(l_brace, r_brace, attrs));
+                }
+                Some(t) if t.category_is(TC::Separator) || t.is(Indent) || t.is(Dedent) => {
+                    self.skip();
+                }
+                Some(t) if t.is(EOF) => {
+                    return Err(self.skip_and_throw_syntax_err(caused_by!()));
+                }
+                Some(_) => match self.try_reduce_expr()? {
+                    Expr::Def(def) if !def.is_subr() && def.sig.name().is_some() => {
+                        attrs.push(def);
+                    }
+                    other => {
+                        return Err(ParseError::simple_syntax_error(0, other.loc()));
+                    }
+                },
+                None => switch_unreachable!(),
+            }
+        }
+    }
+
+    /// `first_key` is the first key (`"a"` of `{"a": 1, "b": 2}`)
+    fn try_reduce_dict(&mut self, l_brace: Token, first_key: Expr) -> ParseResult<Dict> {
+        debug_call_info!(self);
+        let mut kvs = vec![];
+        let mut key = first_key;
+        loop {
+            if !self.cur_is(Colon) {
+                return Err(self.skip_and_throw_syntax_err(caused_by!()));
+            }
+            self.skip();
+            let value = self.try_reduce_expr()?;
+            kvs.push(KeyValue::new(key, value));
+            self.skip_newlines();
+            match self.peek() {
+                Some(t) if t.is(Comma) => {
+                    self.skip();
+                    self.skip_newlines();
+                    // a trailing comma is allowed
+                    if self.cur_is(RBrace) {
+                        let r_brace = self.lpop();
+                        return Ok(Dict::new(l_brace, r_brace, kvs));
+                    }
+                    key = self.try_reduce_expr()?;
+                }
+                Some(t) if t.is(RBrace) => {
+                    let r_brace = self.lpop();
+                    return Ok(Dict::new(l_brace, r_brace, kvs));
+                }
+                _ => return Err(self.skip_and_throw_syntax_err(caused_by!())),
+            }
+        }
+    }
+
+    /// `first` is the first element (`1` of `{1, 2}`)
+    fn try_reduce_set(&mut self, l_brace: Token, first: Expr) -> ParseResult<Set> {
+        debug_call_info!(self);
+        let mut elems = Args::new(vec![PosArg::new(first)], vec![], None);
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                Some(t) if t.is(Comma) => {
+                    self.skip();
+                    self.skip_newlines();
+                    // a trailing comma is allowed
+                    if self.cur_is(RBrace) {
+                        let r_brace = self.lpop();
+                        return Ok(Set::new(l_brace, r_brace, elems));
+                    }
+                    elems.push_pos(PosArg::new(self.try_reduce_expr()?));
+                }
+                Some(t) if t.is(RBrace) => {
+                    let r_brace = self.lpop();
+                    return Ok(Set::new(l_brace, r_brace, elems));
+                }
+                _ => return Err(self.skip_and_throw_syntax_err(caused_by!())),
+            }
+        }
     }
 
     #[inline]
@@ -6,10 +6,12 @@ mod tests {
 
     use erg_common::config::{ErgConfig, Input};
     use erg_common::error::MultiErrorDisplay;
-    use erg_common::traits::Runnable;
+    use erg_common::traits::{Runnable, Stream};
+    use erg_common::{enum_unwrap, Str};
 
     // use erg_compiler::parser;
 
+    use erg_parser::ast::{Accessor, Expr, FormatConversion, ParamPattern, Signature, StrSegment};
     use erg_parser::error::*;
     use erg_parser::lex::Lexer;
     use erg_parser::token::*;
@@ -117,6 +119,52 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_lex_bytes() -> ParseResult<()> {
+        let mut lexer = Lexer::from_str("b\"\" b\"\\x00\\n\" b\n".into());
+        let token_array = [
+            (BytesLit, "b\"\""),
+            (BytesLit, "b\"\\x00\\n\""),
+            // not followed by `"`
+            (Symbol, "b"),
+        ];
+        for (kind, cont) in token_array.into_iter() {
+            let tok = lexer.next().unwrap()?;
+            assert_eq!(tok, Token::from_str(kind, cont));
+        }
+        assert!(Lexer::from_str("b\"あ\"".into()).lex().is_err());
+        Ok(())
+    }
+
+    /// the top-level expressions of `src`
+    fn parse_exprs(src: &str) -> Vec<Expr> {
+        let mut parser = ParserRunner::new(ErgConfig::default());
+        let ast = parser.parse_from_str(Str::rc(src)).unwrap();
+        ast.module.into_iter().collect()
+    }
+
+    #[test]
+    fn test_chained_comparison() {
+        // `1 < x < 10` == `(1 < x) < 10` (chained)
+        let exprs = parse_exprs("1 < x < 10\n");
+        let bin = enum_unwrap!(&exprs[0], Expr::BinOp);
+        assert!(bin.chained);
+        assert!(!enum_unwrap!(bin.args[0].as_ref(), Expr::BinOp).chained);
+        // `a < b + c <= d`
+        let exprs = parse_exprs("a < b + c <= d\n");
+        assert!(enum_unwrap!(&exprs[0], Expr::BinOp).chained);
+        // parenthesized: not chained
+        let exprs = parse_exprs("(x > 0) == flag\n");
+        let bin = enum_unwrap!(&exprs[0], Expr::BinOp);
+        assert_eq!(bin.op.kind, DblEq);
+        assert!(!bin.chained);
+        let exprs = parse_exprs("x == (1 < y)\n");
+        assert!(!enum_unwrap!(&exprs[0], Expr::BinOp).chained);
+        // `and` is not a comparison
+        let exprs = parse_exprs("a < b and c < d\n");
+        assert!(!enum_unwrap!(&exprs[0], Expr::BinOp).chained);
+    }
+
     #[test]
     fn tesop_te_prec() {
         assert_eq!(Mod.precedence(), Some(160));
@@ -145,4 +193,312 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_not_precedence() {
+        // `not a == b` == `not (a == b)`
+        let exprs = parse_exprs("not a == b\n");
+        let unary = enum_unwrap!(&exprs[0], Expr::UnaryOp);
+        assert!(unary.op.is(PreNot));
+        let bin = enum_unwrap!(unary.args[0].as_ref(), Expr::BinOp);
+        assert!(bin.op.is(DblEq));
+        // `not a and b` == `(not a) and b`
+        let exprs = parse_exprs("not a and not b or c\n");
+        let or = enum_unwrap!(&exprs[0], Expr::BinOp);
+        assert!(or.op.is(OrOp));
+        let and = enum_unwrap!(or.args[0].as_ref(), Expr::BinOp);
+        assert!(and.op.is(AndOp));
+        for operand in and.args.iter() {
+            let unary = enum_unwrap!(operand.as_ref(), Expr::UnaryOp);
+            assert!(unary.op.is(PreNot));
+            assert!(matches!(unary.args[0].as_ref(), Expr::Accessor(_)));
+        }
+    }
+
+    #[test]
+    fn test_paren_param_pattern() {
+        let param = |src: &str| {
+            let exprs = parse_exprs(src);
+            let def = enum_unwrap!(&exprs[0], Expr::Def);
+            let sig = enum_unwrap!(&def.sig, Signature::Subr);
+            assert_eq!(sig.params.len(), 1, "{src}");
+            sig.params.non_defaults[0].clone()
+        };
+        // `(a: Int)` is a parenthesized binding with a type, not a 1-tuple
+        let sig = param("f((a: Int)) = a\n");
+        assert!(matches!(sig.pat, ParamPattern::VarName(_)));
+        assert_eq!(sig.t_spec.unwrap().to_string(), "Int");
+        let sig = param("f((a)) = a\n");
+        assert!(matches!(sig.pat, ParamPattern::VarName(_)));
+        assert!(sig.t_spec.is_none());
+        // a trailing comma makes a tuple (same as Python)
+        for (src, len) in [
+            ("f((a: Int,)) = a\n", 1),
+            ("f((a,)) = a\n", 1),
+            ("f((a, b)) = a\n", 2),
+            ("f((a, b: Int,)) = a\n", 2),
+            ("f(()) = 1\n", 0),
+        ] {
+            let sig = param(src);
+            let tuple = enum_unwrap!(sig.pat, ParamPattern::Tuple);
+            assert_eq!(tuple.len(), len, "{src}");
+            assert!(sig.t_spec.is_none());
+        }
+        let tuple = enum_unwrap!(param("f((a: Int,)) = a\n").pat, ParamPattern::Tuple);
+        assert_eq!(
+            tuple.elems.non_defaults[0]
+                .t_spec
+                .as_ref()
+                .unwrap()
+                .to_string(),
+            "Int"
+        );
+        let mut parser = ParserRunner::new(ErgConfig::default());
+        assert!(parser
+            .parse_from_str(Str::ever("f((a: Int): Int) = a\n"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_record_literal() {
+        let exprs = parse_exprs("r = {y = 1; x = a}\n");
+        let def = enum_unwrap!(&exprs[0], Expr::Def);
+        let record = enum_unwrap!(&def.body.block[0], Expr::Record);
+        let names = record
+            .attrs
+            .iter()
+            .map(|attr| &attr.sig.name().unwrap().inspect()[..])
+            .collect::<Vec<_>>();
+        // in the order of definition
+        assert_eq!(names, ["y", "x"]);
+        // only `name = expr` can be an attribute
+        let mut parser = ParserRunner::new(ErgConfig::default());
+        assert!(parser.parse_from_str(Str::ever("{f x = x}\n")).is_err());
+    }
+
+    #[test]
+    fn test_tuple_literal() {
+        for (src, len) in [
+            ("(1, x, f(x))\n", 3),
+            ("(1, 2,)\n", 2),
+            ("(x,)\n", 1),
+            ("()\n", 0),
+        ] {
+            let exprs = parse_exprs(src);
+            let tuple = enum_unwrap!(&exprs[0], Expr::Tuple);
+            assert_eq!(tuple.elems.pos_args().len(), len, "{src}");
+        }
+        // not a tuple
+        let exprs = parse_exprs("(x)\n");
+        assert!(matches!(exprs[0], Expr::Accessor(_)));
+        let mut parser = ParserRunner::new(ErgConfig::default());
+        assert!(parser.parse_from_str(Str::ever("(1, 2\n")).is_err());
+    }
+
+    #[test]
+    fn test_dict_literal() {
+        let exprs = parse_exprs("d = {\"a\": 1, k: f(x)}\n");
+        let def = enum_unwrap!(&exprs[0], Expr::Def);
+        let dict = enum_unwrap!(&def.body.block[0], Expr::Dict);
+        assert_eq!(dict.kvs.len(), 2);
+        assert!(matches!(dict.kvs[0].key, Expr::Lit(_)));
+        assert!(matches!(dict.kvs[1].key, Expr::Accessor(_)));
+        assert!(matches!(dict.kvs[1].value, Expr::Call(_)));
+        // a trailing comma and line breaks are allowed
+        let exprs = parse_exprs("{\n    \"a\": 1,\n    \"b\": 2,\n}\n");
+        assert_eq!(enum_unwrap!(&exprs[0], Expr::Dict).kvs.len(), 2);
+        let exprs = parse_exprs("{:}\n");
+        assert!(enum_unwrap!(&exprs[0], Expr::Dict).kvs.is_empty());
+        // `{}` is an empty record
+        let exprs = parse_exprs("{}\n");
+        assert!(matches!(exprs[0], Expr::Record(_)));
+        let mut parser = ParserRunner::new(ErgConfig::default());
+        assert!(parser
+            .parse_from_str(Str::ever("{\"a\": 1 \"b\"}\n"))
+            .is_err());
+        assert!(parser
+            .parse_from_str(Str::ever("{\"a\": 1, x = 1}\n"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_set_literal() {
+        let exprs = parse_exprs("{1, x, f(x),}\n");
+        let set = enum_unwrap!(&exprs[0], Expr::Set);
+        assert_eq!(set.elems.pos_args().len(), 3);
+        let exprs = parse_exprs("{x}\n");
+        assert_eq!(enum_unwrap!(&exprs[0], Expr::Set).elems.pos_args().len(), 1);
+        // an element cannot be followed by a key-value pair and vice versa
+        let mut parser = ParserRunner::new(ErgConfig::default());
+        assert!(parser.parse_from_str(Str::ever("{1, \"a\": 1}\n")).is_err());
+        assert!(parser.parse_from_str(Str::ever("{\"a\": 1, 1}\n")).is_err());
+    }
+
+    #[test]
+    fn test_subscr_slice() {
+        let index = |src: &str| {
+            let exprs = parse_exprs(src);
+            let acc = enum_unwrap!(&exprs[0], Expr::Accessor);
+            let subscr = enum_unwrap!(acc, Accessor::Subscr);
+            subscr.index.as_ref().clone()
+        };
+        assert!(matches!(index("arr[i]\n"), Expr::Accessor(_)));
+        // (start, stop, step)
+        for (src, bounds) in [
+            ("arr[1:]\n", (true, false, false)),
+            ("arr[:2]\n", (false, true, false)),
+            ("arr[1:2]\n", (true, true, false)),
+            ("arr[::2]\n", (false, false, true)),
+            ("arr[::-1]\n", (false, false, true)),
+            ("arr[1::2]\n", (true, false, true)),
+            ("arr[:2:]\n", (false, true, false)),
+            ("arr[1:5:2]\n", (true, true, true)),
+        ] {
+            let slice = enum_unwrap!(index(src), Expr::Slice);
+            let found = (
+                slice.start.is_some(),
+                slice.stop.is_some(),
+                slice.step.is_some(),
+            );
+            assert_eq!(found, bounds, "{src}");
+        }
+    }
+
+    #[test]
+    fn test_aug_assign() {
+        let exprs = parse_exprs("x += 1\nx -= 1\nx *= 1\nx /= 1\nx //= 1\nx %= 1\n");
+        let ops = exprs
+            .iter()
+            .map(|expr| {
+                let def = enum_unwrap!(expr, Expr::Def);
+                assert_eq!(&def.sig.name_as_str()[..], "x");
+                def.body.op.kind
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            [
+                PlusEqual,
+                MinusEqual,
+                StarEqual,
+                SlashEqual,
+                FloorDivEqual,
+                ModEqual
+            ]
+        );
+        let mut parser = ParserRunner::new(ErgConfig::default());
+        assert!(parser.parse_from_str(Str::ever("x.y += 1\n")).is_err());
+    }
+
+    #[test]
+    fn test_array_comprehension() {
+        let exprs = parse_exprs("[x | x <- xs, p, q]\n");
+        let comp = enum_unwrap!(&exprs[0], Expr::ArrayComprehension);
+        assert_eq!(comp.elem.to_string(), "x");
+        assert_eq!(comp.var.to_string(), "x");
+        assert_eq!(comp.iter.to_string(), "xs");
+        assert_eq!(comp.guards.len(), 2);
+        let exprs = parse_exprs("[f(y) | y <- [1, 2]]\n");
+        let comp = enum_unwrap!(&exprs[0], Expr::ArrayComprehension);
+        assert!(comp.guards.is_empty());
+        let mut parser = ParserRunner::new(ErgConfig::default());
+        for src in ["[x, y | x <- xs]\n", "[x | x xs]\n", "[x | x <- xs\n"] {
+            assert!(parser.parse_from_str(Str::ever(src)).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn test_spread_args() {
+        let exprs = parse_exprs("f(1, *xs, a: 1, **kw)\n");
+        let call = enum_unwrap!(&exprs[0], Expr::Call);
+        assert_eq!(call.args.pos_args().len(), 1);
+        assert_eq!(call.args.kw_args().len(), 1);
+        assert_eq!(call.args.var_args().unwrap().to_string(), "xs");
+        assert_eq!(call.args.kw_var_args().unwrap().to_string(), "kw");
+        let exprs = parse_exprs("f(**kw)\n");
+        let call = enum_unwrap!(&exprs[0], Expr::Call);
+        assert!(call.args.var_args().is_none() && call.args.kw_var_args().is_some());
+        // positional, `*args`, keyword, `**kwargs` (same as Python)
+        let mut parser = ParserRunner::new(ErgConfig::default());
+        for src in [
+            "f(*xs, 1)\n",
+            "f(*xs, *ys)\n",
+            "f(a: 1, *xs)\n",
+            "f(**kw, a: 1)\n",
+            "f(**kw, *xs)\n",
+            "[*xs]\n",
+        ] {
+            assert!(parser.parse_from_str(Str::ever(src)).is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn test_named_expr() -> ParseResult<()> {
+        let exprs = parse_exprs("print!((n := f(x)))\n");
+        let call = enum_unwrap!(&exprs[0], Expr::Call);
+        let named = enum_unwrap!(&call.args.pos_args()[0].expr, Expr::NamedExpr);
+        assert_eq!(&named.name.inspect()[..], "n");
+        assert_eq!(named.op.kind, Walrus);
+        assert!(matches!(named.value.as_ref(), Expr::Call(_)));
+        // `:` followed by a space is a type ascription
+        let mut lexer = Lexer::from_str("n : Int\n".into());
+        assert_eq!(lexer.nth(1).unwrap()?.kind, Colon);
+        Ok(())
+    }
+
+    #[test]
+    fn test_str_interp() -> ParseResult<()> {
+        let kinds = Lexer::from_str("\"a\\{x}b\\{y}c\"\n".into())
+            .map(|t| t.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            kinds[..],
+            [
+                StrInterpLeft,
+                Symbol,
+                StrInterpMid,
+                Symbol,
+                StrInterpRight,
+                Newline,
+                EOF
+            ]
+        );
+        let exprs = parse_exprs("print!(\"x = \\{x!r:>5}, \\{f(y)}\")\n");
+        let call = enum_unwrap!(&exprs[0], Expr::Call);
+        let interp = enum_unwrap!(&call.args.pos_args()[0].expr, Expr::StrInterp);
+        // the trailing empty literal is omitted
+        assert_eq!(interp.segments.len(), 4);
+        assert!(matches!(&interp.segments[0], StrSegment::Lit(s) if &s[..] == "x = "));
+        let (expr, conversion, spec) = enum_unwrap!(
+            &interp.segments[1],
+            StrSegment::Expr {
+                expr,
+                conversion,
+                spec
+            }
+        );
+        assert_eq!(expr.to_string(), "x");
+        assert_eq!(*conversion, Some(FormatConversion::Repr));
+        assert_eq!(spec.as_ref().map(|s| &s[..]), Some(">5"));
+        assert!(
+            matches!(&interp.segments[3], StrSegment::Expr { expr, .. } if matches!(expr.as_ref(), Expr::Call(_)))
+        );
+        // nested
+        let exprs = parse_exprs("\"\\{\"\\{x}\"}\"\n");
+        let interp = enum_unwrap!(&exprs[0], Expr::StrInterp);
+        let (expr, _, _) = enum_unwrap!(
+            &interp.segments[0],
+            StrSegment::Expr {
+                expr,
+                conversion,
+                spec
+            }
+        );
+        assert!(matches!(expr.as_ref(), Expr::StrInterp(_)));
+        let mut parser = ParserRunner::new(ErgConfig::default());
+        for src in ["\"\\{x\"\n", "\"\\{x\n", "\"\\{x:{}}\"\n", "\"\\{}\"\n"] {
+            assert!(parser.parse_from_str(Str::ever(src)).is_err(), "{src}");
+        }
+        Ok(())
+    }
 }
@@ -24,6 +24,14 @@ pub enum TokenKind {
     RatioLit,
     BoolLit,
     StrLit,
+    /// `"a\{` of `"a\{x}b\{y}c"`
+    StrInterpLeft,
+    /// `}b\{` of `"a\{x}b\{y}c"`
+    StrInterpMid,
+    /// `}c"` of `"a\{x}b\{y}c"`
+    StrInterpRight,
+    /// e.g. b"\x00"
+    BytesLit,
     NoneLit,
     NoImplLit,
     EllipsisLit,
@@ -36,6 +44,8 @@ pub enum TokenKind {
     PreStar,
     /// ~ (unary)
     PreBitNot,
+    /// `not` (unary)
+    PreNot,
     // PreAmp,    // & (unary)
     // PreAt,     // @ (unary)
     /// ! (unary)
@@ -108,10 +118,26 @@ pub enum TokenKind {
     Equal,
     /// |=
     OrEqual,
+    /// :=
+    Walrus,
+    /// +=
+    PlusEqual,
+    /// -=
+    MinusEqual,
+    /// *=
+    StarEqual,
+    /// /=
+    SlashEqual,
+    /// //=
+    FloorDivEqual,
+    /// %=
+    ModEqual,
     /// ->
     FuncArrow,
     /// =>
     ProcArrow,
+    /// <-
+    Inclusion,
     /// (
     LParen,
     /// )
@@ -172,6 +198,7 @@ impl From<TokenKind> for Type {
             IntLit => Type::Int,
             RatioLit => Type::Ratio,
             StrLit => Type::Str,
+            BytesLit => Type::mono("Bytes"),
             BoolLit => Type::Bool,
             NoneLit => Type::NoneType,
             NoImplLit => Type::NotImplemented,
@@ -189,6 +216,7 @@ impl From<&ValueObj> for TokenKind {
             ValueObj::Nat(_) => TokenKind::NatLit,
             ValueObj::Float(_) => TokenKind::RatioLit,
             ValueObj::Str(_) => TokenKind::StrLit,
+            ValueObj::Bytes(_) => TokenKind::BytesLit,
             ValueObj::True => TokenKind::BoolLit,
             ValueObj::False => TokenKind::BoolLit,
             ValueObj::None => TokenKind::NoneLit,
@@ -243,11 +271,17 @@ impl TokenKind {
     pub const fn category(&self) -> TokenCategory {
         match self {
             Symbol => TokenCategory::Symbol,
-            NatLit | IntLit | RatioLit | StrLit | BoolLit | NoneLit | EllipsisLit | NoImplLit
-            | InfLit => TokenCategory::Literal,
-            PrePlus | PreMinus | PreStar | PreBitNot | Mutate => TokenCategory::UnaryOp,
+            NatLit | IntLit | RatioLit | StrLit | BytesLit | BoolLit | NoneLit | EllipsisLit
+            | NoImplLit | InfLit => TokenCategory::Literal,
+            StrInterpLeft => TokenCategory::LEnclosure,
+            // separates two embedded expressions
+            StrInterpMid => TokenCategory::SpecialBinOp,
+            StrInterpRight => TokenCategory::REnclosure,
+            PrePlus | PreMinus | PreStar | PreBitNot | PreNot | Mutate => TokenCategory::UnaryOp,
             Try => TokenCategory::PostfixOp,
-            Comma | Colon | DblColon | SupertypeOf | SubtypeOf | Dot | Pipe | OrEqual => {
+            Comma | Colon | DblColon | SupertypeOf | SubtypeOf | Dot | Pipe | OrEqual | Walrus
+            | Inclusion => TokenCategory::SpecialBinOp,
+            PlusEqual | MinusEqual | StarEqual | SlashEqual | FloorDivEqual | ModEqual => {
                 TokenCategory::SpecialBinOp
             }
             Equal => TokenCategory::DefOp,
@@ -279,12 +313,14 @@ impl TokenKind {
             BitOr => 120,                                           // ||
             Closed | LeftOpen | RightOpen | Open => 100,            // range operators
             Less | Gre | LessEq | GreEq | DblEq | NotEq | InOp | NotInOp | IsOp | IsNotOp => 90, // < > <= >= == != in notin is isnot
+            PreNot => 85,                          // not
             AndOp => 80,                           // and
             OrOp => 70,                            // or
             FuncArrow | ProcArrow => 60,           // -> =>
             Colon | SupertypeOf | SubtypeOf => 50, // : :> <:
             Comma => 40,                           // ,
-            Equal | OrEqual => 20,                 // = |=
+            Equal | OrEqual | Walrus => 20,        // = |= :=
+            PlusEqual | MinusEqual | StarEqual | SlashEqual | FloorDivEqual | ModEqual => 20, // += -= *= /= //= %=
             Newline | Semi => 10,                  // \n ;
             LParen | LBrace | LSqBr | Indent => 0, // ( { [ Indent
             _ => return None,
@@ -17,7 +17,7 @@ use erg::dummy::DummyVM;
 
 fn main() {
     let cfg = ErgConfig::parse();
-    match cfg.mode {
+    match cfg.mode() {
         "lex" => {
             LexerRunner::run(cfg);
         }
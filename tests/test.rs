@@ -1,6 +1,43 @@
 extern crate erg;
 
 mod tests {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn test_non_utf8_stdin() {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_erg"))
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"print! \"\xff\"\n")
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        // reported as an I/O error, not a panic
+        assert_eq!(output.status.code(), Some(1));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("cannot read '<stdin>'"), "{stdout}");
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("panicked"));
+    }
+
+    #[test]
+    fn test_unsupported_python_version() {
+        let output = Command::new(env!("CARGO_BIN_EXE_erg"))
+            .args(["--py-ver", "3531", "-c", "print! 1"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout, "unsupported Python version (magic number: 3531)\n");
+    }
+
     /*
     use erg_common::config::{ErgConfig, Input};
     use erg_common::error::MultiErrorFmt;